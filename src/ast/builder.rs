@@ -0,0 +1,638 @@
+//! Builders for syntax trees of generated code.
+//!
+//! The builders assemble the tree with the same constructors the parser uses and never fail on
+//! their own; [`PackageBuilder::build`] then checks the whole tree against the grammar, so what
+//! it returns can be printed by `MexLangTransformer` and parsed back into the same tree.
+//!
+//! ```
+//! use mex_lang::ast::{EnumBuilder, PackageBuilder, RecordBuilder, TypeBuilder};
+//!
+//! let package = PackageBuilder::new("shop")
+//!     .with_scalar("Int")
+//!     .with_model(RecordBuilder::new("Item")
+//!         .with_field("id", "Int")
+//!         .with_field("tags", TypeBuilder::new("List").with_arg("Int").optional()))
+//!     .with_model(EnumBuilder::new("Status").with_variant("Active").with_variant("Blocked"))
+//!     .build()
+//!     .unwrap();
+//! ```
+
+use crate::ast::*;
+use crate::transform::TransformError;
+
+/// Type reference by name, with generic arguments and metadata.
+pub struct TypeBuilder<'a> {
+    id: Id<'a>,
+    generics: Vec<ModelParam<'a>>,
+    metadata: Vec<ModelParam<'a>>,
+    optional: bool,
+}
+
+impl<'a> TypeBuilder<'a> {
+    pub fn new(name: &'a str) -> Self {
+        TypeBuilder {
+            id: Id::Name(name),
+            generics: vec![],
+            metadata: vec![],
+            optional: false,
+        }
+    }
+
+    pub fn with_arg(mut self, item_type: impl Into<ItemType<'a>>) -> Self {
+        self.generics.push(ModelParam::new_generic(item_type.into()));
+        self
+    }
+
+    pub fn with_metadata(mut self, name: &'a str, value: Literal<'a>) -> Self {
+        self.metadata.push(ModelParam::new_metadata(Id::Name(name), value));
+        self
+    }
+
+    /// Marks the type optional, which only fields and tuple items can be.
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    pub fn build(mut self) -> ItemType<'a> {
+        self.generics.append(&mut self.metadata);
+        let item_type = ItemType::new_name(self.id, Some(self.generics));
+        match self.optional {
+            true => ItemType::new_optional(item_type),
+            false => item_type,
+        }
+    }
+}
+
+impl<'a> From<TypeBuilder<'a>> for ItemType<'a> {
+    fn from(value: TypeBuilder<'a>) -> Self {
+        value.build()
+    }
+}
+
+impl<'a> From<&'a str> for ItemType<'a> {
+    fn from(value: &'a str) -> Self {
+        TypeBuilder::new(value).build()
+    }
+}
+
+/// Generic and metadata parameters of a model, kept apart because generics come first.
+#[derive(Default)]
+struct Params<'a> {
+    generics: Vec<ModelParamDefinition<'a>>,
+    metadata: Vec<ModelParamDefinition<'a>>,
+}
+
+impl<'a> Params<'a> {
+    fn build(mut self) -> Option<Vec<ModelParamDefinition<'a>>> {
+        self.generics.append(&mut self.metadata);
+        Some(self.generics)
+    }
+}
+
+/// Record or fragment with named fields and spreads.
+pub struct RecordBuilder<'a> {
+    id: Id<'a>,
+    fragment: bool,
+    items: Vec<RecordItem<'a>>,
+    params: Params<'a>,
+}
+
+impl<'a> RecordBuilder<'a> {
+    pub fn new(name: &'a str) -> Self {
+        RecordBuilder {
+            id: Id::Name(name),
+            fragment: false,
+            items: vec![],
+            params: Params::default(),
+        }
+    }
+
+    pub fn fragment(name: &'a str) -> Self {
+        RecordBuilder { fragment: true, ..Self::new(name) }
+    }
+
+    /// Record without a name, used as a field type or an enum variant.
+    pub fn inline() -> Self {
+        RecordBuilder { id: Id::Inline, ..Self::new("") }
+    }
+
+    pub fn with_generic(mut self, name: &'a str, constraint_type: Option<ItemType<'a>>) -> Self {
+        self.params.generics.push(ModelParamDefinition::new_generic(Id::Name(name), constraint_type));
+        self
+    }
+
+    pub fn with_metadata(mut self, name: &'a str, item_type: impl Into<ItemType<'a>>, def_value: Option<Literal<'a>>) -> Self {
+        self.params.metadata.push(ModelParamDefinition::new_metadata(Id::Name(name), item_type.into(), def_value));
+        self
+    }
+
+    pub fn with_field(mut self, name: &'a str, item_type: impl Into<ItemType<'a>>) -> Self {
+        self.items.push(RecordItem::new_item(Id::Name(name), item_type.into()));
+        self
+    }
+
+    pub fn with_spread(mut self, item_type: impl Into<ItemType<'a>>) -> Self {
+        self.items.push(RecordItem::new_spread(item_type.into()));
+        self
+    }
+
+    pub fn build(self) -> ModelDefinition<'a> {
+        match self.fragment {
+            true => ModelDefinition::new_fragment(self.id, Some(self.items), self.params.build()),
+            false => ModelDefinition::new_record(self.id, Some(self.items), self.params.build()),
+        }
+    }
+}
+
+/// Tuple with positional or named items.
+pub struct TupleBuilder<'a> {
+    id: Id<'a>,
+    items: Vec<TupleItem<'a>>,
+    params: Params<'a>,
+}
+
+impl<'a> TupleBuilder<'a> {
+    pub fn new(name: &'a str) -> Self {
+        TupleBuilder {
+            id: Id::Name(name),
+            items: vec![],
+            params: Params::default(),
+        }
+    }
+
+    /// Tuple without a name, used as an item type or an enum variant.
+    pub fn inline() -> Self {
+        TupleBuilder { id: Id::Inline, ..Self::new("") }
+    }
+
+    pub fn with_generic(mut self, name: &'a str, constraint_type: Option<ItemType<'a>>) -> Self {
+        self.params.generics.push(ModelParamDefinition::new_generic(Id::Name(name), constraint_type));
+        self
+    }
+
+    pub fn with_metadata(mut self, name: &'a str, item_type: impl Into<ItemType<'a>>, def_value: Option<Literal<'a>>) -> Self {
+        self.params.metadata.push(ModelParamDefinition::new_metadata(Id::Name(name), item_type.into(), def_value));
+        self
+    }
+
+    pub fn with_item(mut self, item_type: impl Into<ItemType<'a>>) -> Self {
+        self.items.push(TupleItem::new_item(item_type.into()));
+        self
+    }
+
+    pub fn with_named_item(mut self, name: &'a str, item_type: impl Into<ItemType<'a>>) -> Self {
+        self.items.push(TupleItem::new_named_item(Id::Name(name), item_type.into()));
+        self
+    }
+
+    pub fn build(self) -> ModelDefinition<'a> {
+        ModelDefinition::new_tuple(self.id, Some(self.items), self.params.build())
+    }
+}
+
+/// Enum with unit, record, tuple and nested enum variants.
+pub struct EnumBuilder<'a> {
+    id: Id<'a>,
+    items: Vec<EnumItem<'a>>,
+    params: Params<'a>,
+}
+
+impl<'a> EnumBuilder<'a> {
+    pub fn new(name: &'a str) -> Self {
+        EnumBuilder {
+            id: Id::Name(name),
+            items: vec![],
+            params: Params::default(),
+        }
+    }
+
+    /// Enum without a name, used as a field type.
+    pub fn inline() -> Self {
+        EnumBuilder { id: Id::Inline, ..Self::new("") }
+    }
+
+    pub fn with_generic(mut self, name: &'a str, constraint_type: Option<ItemType<'a>>) -> Self {
+        self.params.generics.push(ModelParamDefinition::new_generic(Id::Name(name), constraint_type));
+        self
+    }
+
+    pub fn with_metadata(mut self, name: &'a str, item_type: impl Into<ItemType<'a>>, def_value: Option<Literal<'a>>) -> Self {
+        self.params.metadata.push(ModelParamDefinition::new_metadata(Id::Name(name), item_type.into(), def_value));
+        self
+    }
+
+    pub fn with_variant(mut self, name: &'a str) -> Self {
+        self.items.push(EnumItem::new_item(Id::Name(name)));
+        self
+    }
+
+    /// Variant with the fields of the record, whose name and parameters are ignored.
+    pub fn with_record_variant(mut self, name: &'a str, record: RecordBuilder<'a>) -> Self {
+        self.items.push(EnumItem::new_record(Id::Name(name), record.items));
+        self
+    }
+
+    /// Variant with the items of the tuple, whose name and parameters are ignored.
+    pub fn with_tuple_variant(mut self, name: &'a str, tuple: TupleBuilder<'a>) -> Self {
+        self.items.push(EnumItem::new_tuple(Id::Name(name), tuple.items));
+        self
+    }
+
+    /// Variant with the variants of the enum, whose name and parameters are ignored.
+    pub fn with_enum_variant(mut self, name: &'a str, variants: EnumBuilder<'a>) -> Self {
+        self.items.push(EnumItem::new_enum(Id::Name(name), variants.items));
+        self
+    }
+
+    pub fn build(self) -> ModelDefinition<'a> {
+        ModelDefinition::new_enum(self.id, self.items, self.params.build())
+    }
+}
+
+macro_rules! model_conversions {
+    ($($builder:ident),*) => {$(
+        impl<'a> From<$builder<'a>> for ModelDefinition<'a> {
+            fn from(value: $builder<'a>) -> Self {
+                value.build()
+            }
+        }
+
+        impl<'a> From<$builder<'a>> for ItemType<'a> {
+            fn from(value: $builder<'a>) -> Self {
+                ItemType::new_inline(value.build())
+            }
+        }
+    )*};
+}
+
+model_conversions!(RecordBuilder, TupleBuilder, EnumBuilder);
+
+/// Package, or the global scope, with models and nested packages.
+pub struct PackageBuilder<'a> {
+    id: Option<Id<'a>>,
+    items: Vec<RefScope<'a>>,
+}
+
+impl<'a> PackageBuilder<'a> {
+    pub fn new(name: &'a str) -> Self {
+        PackageBuilder {
+            id: Some(Id::Name(name)),
+            items: vec![],
+        }
+    }
+
+    /// Global scope, whose models are not in any package.
+    pub fn global() -> Self {
+        PackageBuilder {
+            id: None,
+            items: vec![],
+        }
+    }
+
+    pub fn with_package(mut self, package: PackageBuilder<'a>) -> Self {
+        let scope = match package.id {
+            Some(id) => Scope::new_package(id, Some(package.items)),
+            None => Scope::Global(package.items).into(),
+        };
+        self.items.push(scope);
+        self
+    }
+
+    pub fn with_model(mut self, def: impl Into<ModelDefinition<'a>>) -> Self {
+        self.items.push(Scope::new_model(def.into()));
+        self
+    }
+
+    pub fn with_scalar(self, name: &'a str) -> Self {
+        self.with_model(ModelDefinition::new_scalar(Id::Name(name)))
+    }
+
+    pub fn with_alias(self, name: &'a str, item_type: impl Into<ItemType<'a>>) -> Self {
+        self.with_model(ModelDefinition::new_alias(Id::Name(name), None, item_type.into()))
+    }
+
+    /// The scope, or every place where the tree cannot be written in Mex.
+    pub fn build(self) -> crate::Result<'a, RefScope<'a>> {
+        let scope = match self.id {
+            Some(id) => Scope::new_package(id, Some(self.items)),
+            None => Scope::Global(self.items).into(),
+        };
+
+        let mut check = Check::default();
+        check.scope(&scope, true);
+        match check.errors.is_empty() {
+            true => Ok(scope),
+            false => Err(check.errors.into()),
+        }
+    }
+}
+
+const KEYWORDS: [&str; 5] = ["package", "model", "enum", "fragment", "scalar"];
+
+/// Whether the grammar reads `name` as a name: not a keyword, and made of ASCII letters, digits
+/// and underscores, starting with no digit.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        && !KEYWORDS.contains(&name)
+}
+
+/// Where a type is written, as the grammar allows different types in each place.
+#[derive(Clone, Copy, PartialEq)]
+enum Place {
+    Field,
+    TupleItem,
+    Other,
+}
+
+/// Collects the parts of a tree the grammar cannot express.
+#[derive(Default)]
+struct Check {
+    errors: Vec<TransformError>,
+}
+
+impl Check {
+    fn error(&mut self, message: String) {
+        self.errors.push(TransformError::InvalidAst(message));
+    }
+
+    fn name(&mut self, id: &Id) {
+        match id {
+            Id::Name(name) => {
+                if !is_valid_name(name) {
+                    self.error(format!("`{}` is not a valid name", name));
+                }
+            },
+            Id::Index(_) | Id::Branch(..) => self.error(format!("`{:?}` has no syntax", id)),
+            Id::Inline => self.error("a name is missing".to_string()),
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::String(value) => self.name(&Id::Name(value)),
+            Literal::Number(value) => {
+                let mut chars = value.chars();
+                let valid = chars.next().is_some_and(|c| ('1'..='9').contains(&c)) && chars.all(|c| c.is_ascii_digit());
+                if !valid {
+                    self.error(format!("`{}` is not a valid number", value));
+                }
+            },
+        }
+    }
+
+    fn scope(&mut self, scope: &RefScope, is_root: bool) {
+        match **scope.borrow() {
+            Scope::Global(ref items) if is_root => items.iter().for_each(|item| self.scope(item, false)),
+            Scope::Global(_) => self.error("the global scope is nested in a package".to_string()),
+            Scope::Package(ref id, ref items) => {
+                self.name(id);
+                items.iter().for_each(|item| self.scope(item, false));
+            },
+            Scope::Model(ref def) => self.model(def, true),
+            Scope::Error(_) => self.error("the tree has a syntax error".to_string()),
+        }
+    }
+
+    fn model(&mut self, def: &ModelDefinition, is_top: bool) {
+        let (id, params) = match def {
+            ModelDefinition::Fragment(id, _, params) | ModelDefinition::Record(id, _, params) |
+            ModelDefinition::Tuple(id, _, params) | ModelDefinition::Enum(id, _, params) |
+            ModelDefinition::Alias(id, params, _) => (id, params.as_slice()),
+            ModelDefinition::Scalar(id) => (id, [].as_slice()),
+        };
+
+        match (id, is_top) {
+            (_, true) => self.name(id),
+            (Id::Inline, false) => {},
+            (id, false) => self.name(id),
+        }
+        if !is_top && !params.is_empty() {
+            self.error(format!("inline model `{}` has parameters", id.as_name().unwrap_or_default()));
+        }
+        if !is_top && matches!(def, ModelDefinition::Fragment(..) | ModelDefinition::Alias(..) | ModelDefinition::Scalar(_)) {
+            self.error("only records, tuples and enums can be inline".to_string());
+        }
+        self.params_def(params);
+
+        match def {
+            ModelDefinition::Fragment(_, items, _) | ModelDefinition::Record(_, items, _) => self.record_items(items),
+            ModelDefinition::Tuple(_, items, _) => self.tuple_items(items),
+            ModelDefinition::Enum(id, items, _) => self.enum_items(id, items),
+            ModelDefinition::Alias(_, _, item_type) => self.item_type(item_type, Place::Other),
+            ModelDefinition::Scalar(_) => {},
+        }
+    }
+
+    fn params_def(&mut self, params: &[ModelParamDefinition]) {
+        let mut metadata = false;
+        for param in params {
+            match param {
+                ModelParamDefinition::Generic { id, constraint_type } => {
+                    self.name(id);
+                    if metadata {
+                        self.error(format!("generic `{}` follows metadata", id.as_name().unwrap_or_default()));
+                    }
+                    if let Some(item_type) = constraint_type {
+                        self.item_type(item_type, Place::Other);
+                    }
+                },
+                ModelParamDefinition::Metadata { id, type_id, def_value } => {
+                    metadata = true;
+                    self.name(id);
+                    self.item_type(type_id, Place::Other);
+                    if let Some(value) = def_value {
+                        self.literal(value);
+                    }
+                },
+                ModelParamDefinition::Constraint { id, .. } => {
+                    self.error(format!("constraint `{}` has no syntax", id.as_name().unwrap_or_default()));
+                },
+            }
+        }
+    }
+
+    fn record_items(&mut self, items: &[RecordItem]) {
+        for item in items {
+            match item {
+                RecordItem::Item(id, item_type) => {
+                    self.name(id);
+                    self.item_type(item_type, Place::Field);
+                },
+                RecordItem::Spread(item_type) => self.item_type(item_type, Place::Other),
+            }
+        }
+    }
+
+    fn tuple_items(&mut self, items: &[TupleItem]) {
+        for item in items {
+            match item {
+                TupleItem::Item(item_type) => self.item_type(item_type, Place::TupleItem),
+                TupleItem::NamedItem(id, item_type) => {
+                    self.name(id);
+                    self.item_type(item_type, Place::TupleItem);
+                },
+            }
+        }
+    }
+
+    fn enum_items(&mut self, id: &Id, items: &[EnumItem]) {
+        if items.is_empty() {
+            self.error(format!("enum `{}` has no variants", id.as_name().unwrap_or_default()));
+        }
+
+        for item in items {
+            match item {
+                EnumItem::Item(id) => self.name(id),
+                EnumItem::Record(id, ItemType::Inline(ModelDefinition::Record(_, items, _))) => {
+                    self.name(id);
+                    if items.is_empty() {
+                        self.error(format!("record variant `{}` has no fields", id.as_name().unwrap_or_default()));
+                    }
+                    self.record_items(items);
+                },
+                EnumItem::Tuple(id, ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => {
+                    self.name(id);
+                    self.tuple_items(items);
+                },
+                EnumItem::Enum(id, ItemType::Inline(ModelDefinition::Enum(_, items, _))) => {
+                    self.name(id);
+                    self.enum_items(id, items);
+                },
+                EnumItem::Record(id, _) | EnumItem::Tuple(id, _) | EnumItem::Enum(id, _) => {
+                    self.error(format!("variant `{}` does not match its kind", id.as_name().unwrap_or_default()));
+                },
+            }
+        }
+    }
+
+    fn item_type(&mut self, item_type: &ItemType, place: Place) {
+        match item_type {
+            ItemType::Model(id, params) => {
+                self.name(id);
+                let mut metadata = false;
+                for param in params {
+                    match param {
+                        ModelParam::Generic(item_type) => {
+                            if metadata {
+                                self.error(format!("argument of `{}` follows metadata", id.as_name().unwrap_or_default()));
+                            }
+                            self.item_type(item_type, Place::Other);
+                        },
+                        ModelParam::Metadata(id, value) => {
+                            metadata = true;
+                            self.name(id);
+                            self.literal(value);
+                        },
+                    }
+                }
+            },
+            ItemType::Inline(def) => {
+                if place == Place::TupleItem && !matches!(def, ModelDefinition::Tuple(Id::Inline, _, _)) {
+                    self.error("tuple items can only be named types or unnamed tuples".to_string());
+                }
+                self.model(def, false);
+            },
+            ItemType::Optional(item_type) => {
+                if place == Place::Other || matches!(**item_type, ItemType::Optional(_)) {
+                    self.error("only fields and tuple items can be optional".to_string());
+                }
+                self.item_type(item_type, place);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::ast::owned;
+    use crate::transform::{MexLangTransformer, StringRender, TransformError};
+
+    fn print(scope: &RefScope) -> String {
+        let render = StringRender::new();
+        MexLangTransformer::new().apply(scope, &render).unwrap();
+        render.as_string(4)
+    }
+
+    #[test]
+    fn check() {
+        let scope = PackageBuilder::new("shop")
+            .with_scalar("Int")
+            .with_model(RecordBuilder::fragment("Audit").with_field("created", "Int"))
+            .with_model(RecordBuilder::new("Page")
+                .with_generic("T", None)
+                .with_metadata("size", "Int", Some(Literal::Number("20")))
+                .with_spread("Audit")
+                .with_field("items", TypeBuilder::new("List").with_arg("T"))
+                .with_field("next", TypeBuilder::new("Int").optional())
+                .with_field("point", TupleBuilder::inline().with_item("Int").with_item("Int")))
+            .with_model(TupleBuilder::new("Range").with_named_item("from", "Int").with_named_item("to", "Int"))
+            .with_model(EnumBuilder::new("Status")
+                .with_variant("Active")
+                .with_tuple_variant("Blocked", TupleBuilder::inline().with_item("Int"))
+                .with_record_variant("Moved", RecordBuilder::inline().with_field("to", "Int")))
+            .with_alias("Ids", TypeBuilder::new("List").with_arg("Int").with_metadata("max", Literal::Number("4")))
+            .with_package(PackageBuilder::new("orders").with_model(RecordBuilder::new("Order").with_field("id", "Int")))
+            .build()
+            .unwrap();
+
+        let code = print(&scope);
+        assert_eq!(code, "package shop;
+
+scalar Int;
+fragment Audit {
+    created: Int
+}
+
+model Page<T>[size: Int = 20] {
+    ... Audit
+    items: List<T>
+    next: Int?
+    point: (Int, Int)
+}
+
+model Range(from: Int, to: Int)
+model Status enum {
+    Active
+    Blocked(Int)
+    Moved {
+        to: Int
+    }
+}
+
+model Ids = List<Int>[max=4]
+package orders {
+    model Order {
+        id: Int
+    }
+}");
+        assert_eq!(owned::Scope::parse(&code).unwrap(), owned::Scope::from_ast(&scope));
+    }
+
+    #[test_case(PackageBuilder::new("1st"), "`1st` is not a valid name"; "invalid package name")]
+    #[test_case(PackageBuilder::global().with_scalar("model"), "`model` is not a valid name"; "keyword")]
+    #[test_case(PackageBuilder::global().with_model(RecordBuilder::inline()), "a name is missing"; "inline at package level")]
+    #[test_case(PackageBuilder::global().with_model(EnumBuilder::new("E")), "enum `E` has no variants"; "empty enum")]
+    #[test_case(
+        PackageBuilder::global().with_model(TupleBuilder::new("T").with_item(RecordBuilder::inline())),
+        "tuple items can only be named types or unnamed tuples"; "record in tuple"
+    )]
+    #[test_case(
+        PackageBuilder::global().with_alias("A", TypeBuilder::new("Int").optional()),
+        "only fields and tuple items can be optional"; "optional alias"
+    )]
+    #[test_case(
+        PackageBuilder::global().with_alias("A", TypeBuilder::new("Hash").with_metadata("len", Literal::Number("08"))),
+        "`08` is not a valid number"; "invalid number"
+    )]
+    fn check_errors(builder: PackageBuilder, expected: &str) {
+        match builder.build() {
+            Err(Error::Transform(errors)) => assert_eq!(errors, vec![TransformError::InvalidAst(expected.to_string())]),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+/// `///` doc comments of a source, keyed by the dotted path of the documented item
+/// including its packages, e.g. `Shop.Item` for a model and `Shop.Item.id` for one of
+/// its fields or variants.
+///
+/// The lexer skips comments, so they are collected by a separate pass over the text.
+#[derive(Debug, Default)]
+pub struct DocComments {
+    docs: HashMap<String, String>,
+}
+
+impl DocComments {
+    pub fn parse(code: &str) -> Self {
+        let mut docs = HashMap::new();
+        let mut pending: Vec<&str> = vec![];
+        // Package declared for the whole file with `package Name;`.
+        let mut root: Option<String> = None;
+        // Open blocks: package or model name and the depth they were opened at.
+        let mut stack: Vec<(String, usize)> = vec![];
+        let mut depth = 0;
+
+        for line in code.lines() {
+            let line = line.trim();
+
+            if let Some(doc) = line.strip_prefix("///") {
+                pending.push(doc.strip_prefix(' ').unwrap_or(doc));
+                continue;
+            }
+
+            let code = line.split("//").next().unwrap_or_default().trim();
+            if code.is_empty() {
+                if line.is_empty() {
+                    pending.clear();
+                }
+                continue;
+            }
+
+            let mut words = code.split(|c: char| !(c.is_alphanumeric() || c == '_')).filter(|w| !w.is_empty());
+            let keyword = words.next();
+            let name = match keyword {
+                Some("package" | "model" | "scalar" | "fragment") => words.next().map(String::from),
+                Some(item) if !stack.is_empty() && !code.starts_with("...") => Some(item.to_string()),
+                _ => None,
+            };
+
+            let opens = code.matches('{').count();
+            let closes = code.matches('}').count();
+
+            if keyword == Some("package") {
+                if opens == 0 && stack.is_empty() {
+                    root = name.clone();
+                }
+            } else if let Some(name) = &name {
+                if !pending.is_empty() {
+                    let path = root.iter().chain(stack.iter().map(|(n, _)| n)).chain([name]);
+                    docs.insert(path.map(String::as_str).collect::<Vec<_>>().join("."), pending.join("\n"));
+                }
+            }
+            pending.clear();
+
+            if opens > closes {
+                stack.push((name.unwrap_or_default(), depth));
+            }
+
+            depth = (depth + opens).saturating_sub(closes);
+            while stack.last().is_some_and(|(_, open)| *open >= depth) {
+                stack.pop();
+            }
+        }
+
+        DocComments { docs }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.docs.get(path).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check() {
+        let code = "/// Shop models\npackage Shop {\n    /// An item\n    /// for sale.\n    model Item {\n        /// Identifier\n        id: Int\n\
+            \n        meta: {\n            /// Total count\n            total: Int\n        }\n        // plain comment\n        name: String\n    }\n}\n\
+            /// Status\nmodel Status enum {\n    /// Not published yet\n    Draft\n}";
+        let docs = DocComments::parse(code);
+
+        assert_eq!(docs.get("Shop.Item"), Some("An item\nfor sale."));
+        assert_eq!(docs.get("Shop.Item.id"), Some("Identifier"));
+        assert_eq!(docs.get("Shop.Item.meta.total"), Some("Total count"));
+        assert_eq!(docs.get("Shop.Item.name"), None);
+        assert_eq!(docs.get("Item"), None);
+        assert_eq!(docs.get("Status"), Some("Status"));
+        assert_eq!(docs.get("Status.Draft"), Some("Not published yet"));
+    }
+
+    #[test]
+    fn check_packages() {
+        let code = "package shop;\n\n/// Shop name\nmodel Name(String)\n\npackage billing {\n    /// Billing name\n    model Name(String)\n}";
+        let docs = DocComments::parse(code);
+
+        assert_eq!(docs.get("shop.Name"), Some("Shop name"));
+        assert_eq!(docs.get("shop.billing.Name"), Some("Billing name"));
+        assert_eq!(docs.get("Name"), None);
+    }
+}
@@ -1,10 +1,13 @@
+use std::ops::Range;
 use lalrpop_util::ParseError;
 use crate::lexer::{LexicalError, Token};
+use crate::transform::TransformError;
 
 #[derive(Debug)]
 pub enum Error<'input> {
     Io(std::io::Error),
     Parsing(ParseError<usize, Token<'input>, LexicalError>),
+    Transform(Vec<TransformError>),
 }
 
 impl<'input> From<std::io::Error> for Error<'input> {
@@ -17,4 +20,38 @@ impl<'input> From<ParseError<usize, Token<'input>, LexicalError>> for Error<'inp
     fn from(value: ParseError<usize, Token<'input>, LexicalError>) -> Self {
         Error::Parsing(value)
     }
-}
\ No newline at end of file
+}
+
+impl<'input> From<Vec<TransformError>> for Error<'input> {
+    fn from(value: Vec<TransformError>) -> Self {
+        Error::Transform(value)
+    }
+}
+
+/// Span and readable message of a syntax error in `code`.
+pub fn syntax_message(error: &ParseError<usize, Token, LexicalError>, code: &str) -> (Range<usize>, String) {
+    let expected = |expected: &[String]| match expected.is_empty() {
+        true => String::new(),
+        false => format!(", expected {}", expected.iter()
+            .map(|e| format!("`{}`", e.trim_matches('"')))
+            .collect::<Vec<_>>()
+            .join(", ")),
+    };
+
+    match error {
+        ParseError::InvalidToken { location } => (*location..*location + 1, "invalid token".to_string()),
+        ParseError::UnrecognizedEof { location, expected: e } => {
+            (*location..*location, format!("unexpected end of file{}", expected(e)))
+        },
+        // `enum Name { ... }` reads as an item of its own, but enums are models.
+        ParseError::UnrecognizedToken { token: (start, Token::KeywordEnum, end), expected: e } if e.iter().any(|e| e == "\"model\"") => {
+            (*start..*end, "unexpected `enum`, declare enums as `model Name enum { ... }`".to_string())
+        },
+        ParseError::UnrecognizedToken { token: (start, _, end), expected: e } => {
+            (*start..*end, format!("unexpected `{}`{}", &code[*start..*end], expected(e)))
+        },
+        ParseError::ExtraToken { token: (start, _, end) } => (*start..*end, format!("unexpected `{}`", &code[*start..*end])),
+        ParseError::User { error: LexicalError::Unsupported(span, reason) } => (span.clone(), reason.clone()),
+        ParseError::User { error } => (0..0, error.to_string()),
+    }
+}
@@ -2,11 +2,21 @@ mod source;
 mod error;
 mod scope;
 mod model;
+mod doc_comments;
+mod symbol;
+mod builder;
+mod tree;
+pub mod owned;
+pub mod visit;
 
 pub use source::*;
 pub use error::*;
 pub use scope::*;
 pub use model::*;
+pub use doc_comments::*;
+pub use symbol::*;
+pub use builder::*;
+pub use tree::*;
 pub use model::record_item::*;
 pub use model::tuple_item::*;
 pub use model::enum_item::*;
@@ -25,6 +35,16 @@ pub enum Id<'input> {
     Inline,
 }
 
+impl<'input> Id<'input> {
+    pub fn as_name(&self) -> Option<&'input str> {
+        match self {
+            Id::Name(name) => Some(name),
+            Id::Branch(name, _) => Some(name),
+            _ => None,
+        }
+    }
+}
+
 impl<'input> From<Option<Id<'input>>> for Id<'input> {
     fn from(value: Option<Id<'input>>) -> Self {
         match value {
@@ -24,7 +24,7 @@ impl<'a> EnumItem<'a> {
     }
 
     pub fn new_enum(id: Id<'a>, items: Vec<EnumItem<'a>>) -> Self {
-        let def = ModelDefinition::new_enum(Id::Inline, items.into(), vec![].into());
+        let def = ModelDefinition::new_enum(Id::Inline, items, vec![].into());
         Self::Enum(id, ItemType::new_inline(def))
     }
 }
\ No newline at end of file
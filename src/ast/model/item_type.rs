@@ -4,6 +4,7 @@ use crate::ast::{Id, Literal, ModelDefinition, TupleItem};
 pub enum ItemType<'input> {
     Model(Id<'input>, Vec<ModelParam<'input>>),
     Inline(ModelDefinition<'input>),
+    Optional(Box<ItemType<'input>>),
 }
 
 impl<'input> ItemType<'input> {
@@ -15,6 +16,10 @@ impl<'input> ItemType<'input> {
         ItemType::Inline(inline)
     }
 
+    pub fn new_optional(item_type: ItemType<'input>) -> Self {
+        ItemType::Optional(Box::new(item_type))
+    }
+
     pub fn new_inline_tuple(items: Vec<TupleItem<'input>>) -> Self {
         ItemType::Inline(
             ModelDefinition::new_tuple(Id::Inline, items.into(), vec![].into())
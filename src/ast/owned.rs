@@ -0,0 +1,527 @@
+//! Owned form of the AST, free of the source lifetime.
+//!
+//! Names are shared [`Symbol`]s and children are plain vectors, so a tree can be stored,
+//! cloned, cached and sent between threads after the [`Compiler`] and its source are gone.
+//! [`Scope::from_ast`] converts a parsed tree, and [`Scope::to_ast`] gives the borrowed form
+//! back for the transformers.
+//!
+//! A tree is serialized as Mex source by [`Scope::to_source`] and read back by [`Scope::parse`].
+//! [`Scope::to_json`] writes the JSON document of `json::AstJson`, which is not read back.
+
+use lalrpop_util::{ErrorRecovery, ParseError};
+use crate::ast::{self, Interner, RefScope, Source, Symbol};
+use crate::json::AstJson;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{MexLangTransformer, StringRender};
+use crate::{parse_error, syntax_error, Compiler};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Scope {
+    Global(Vec<Scope>),
+    Package(Id, Vec<Scope>),
+    Model(ModelDefinition),
+    /// Part of the source the parser recovered from, with the error it reported.
+    Error(SyntaxError),
+}
+
+/// Error the parser recovered from, with the locations and the expected tokens it reported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxError {
+    pub error: ParseError<usize, SyntaxToken, LexicalError>,
+    /// Tokens skipped to recover, with their byte ranges.
+    pub dropped_tokens: Vec<(usize, SyntaxToken, usize)>,
+}
+
+/// Token of a syntax error, with the text of a name or a number.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxToken {
+    /// The token, without its text.
+    pub kind: Token<'static>,
+    pub text: Option<Symbol>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Branch {
+    Version(Symbol),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Id {
+    Name(Symbol),
+    Index(i32),
+    Branch(Symbol, Branch),
+    Inline,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Literal {
+    String(Symbol),
+    Number(Symbol),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModelDefinition {
+    Fragment(Id, Vec<RecordItem>, Vec<ModelParamDefinition>),
+    Record(Id, Vec<RecordItem>, Vec<ModelParamDefinition>),
+    Tuple(Id, Vec<TupleItem>, Vec<ModelParamDefinition>),
+    Enum(Id, Vec<EnumItem>, Vec<ModelParamDefinition>),
+    Alias(Id, Vec<ModelParamDefinition>, Box<ItemType>),
+    Scalar(Id),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordItem {
+    Item(Id, ItemType),
+    Spread(ItemType),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TupleItem {
+    Item(ItemType),
+    NamedItem(Id, ItemType),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnumItem {
+    Item(Id),
+    Record(Id, ItemType),
+    Tuple(Id, ItemType),
+    Enum(Id, ItemType),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ItemType {
+    Model(Id, Vec<ModelParam>),
+    Inline(ModelDefinition),
+    Optional(Box<ItemType>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GenericConstraintDefinition {
+    Contains(ItemType),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModelParamDefinition {
+    Generic {
+        id: Id,
+        constraint_type: Option<ItemType>,
+    },
+    Metadata {
+        id: Id,
+        type_id: ItemType,
+        def_value: Option<Literal>,
+    },
+    Constraint {
+        id: Id,
+        constraint: GenericConstraintDefinition,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModelParam {
+    Generic(ItemType),
+    Metadata(Id, Literal),
+}
+
+impl Scope {
+    /// Parses the code into an owned tree. Recovered syntax errors are kept as `Error` scopes.
+    pub fn parse(code: &str) -> crate::Result<'static, Scope> {
+        let global = ast::Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).map_err(|e| syntax_error(format!("{:?}", e)))?;
+        let ast = compiler.make_ast().map_err(|e| parse_error(e, code))?;
+        Ok(Scope::from_ast(&ast))
+    }
+
+    /// Owned tree of a parsed one, whose equal names share their string.
+    pub fn from_ast(scope: &RefScope) -> Scope {
+        Interner::session(|| Scope::convert(scope))
+    }
+
+    fn convert(scope: &RefScope) -> Scope {
+        match **scope.borrow() {
+            ast::Scope::Global(ref items) => Scope::Global(items.iter().map(Scope::convert).collect()),
+            ast::Scope::Package(ref id, ref items) => Scope::Package(id.into(), items.iter().map(Scope::convert).collect()),
+            ast::Scope::Model(ref def) => Scope::Model(def.into()),
+            ast::Scope::Error(ref error) => Scope::Error(error.into()),
+        }
+    }
+
+    /// Borrowed form of the tree, whose strings live as long as the owned one.
+    pub fn to_ast(&self) -> RefScope<'_> {
+        match self {
+            Scope::Global(items) => ast::Scope::Global(items.iter().map(Scope::to_ast).collect()).into(),
+            Scope::Package(id, items) => ast::Scope::Package(id.to_ast(), items.iter().map(Scope::to_ast).collect()).into(),
+            Scope::Model(def) => ast::Scope::Model(def.to_ast()).into(),
+            Scope::Error(error) => ast::Scope::Error(error.to_ast()).into(),
+        }
+    }
+
+    /// JSON document of the tree, as [`AstJson`] writes it without spans.
+    pub fn to_json(&self, json: &AstJson) -> String {
+        json.scope_to_json(&self.to_ast())
+    }
+}
+
+impl Scope {
+    /// Mex source of the tree in the default style. Fails on ids the grammar has no syntax for,
+    /// which the owned tree can hold as a value.
+    pub fn to_source(&self) -> crate::Result<'static, String> {
+        let render = StringRender::new();
+        MexLangTransformer::new().apply(&self.to_ast(), &render)?;
+        Ok(render.as_string(4))
+    }
+}
+
+impl SyntaxError {
+    pub fn to_ast(&self) -> ErrorRecovery<usize, Token<'_>, LexicalError> {
+        ErrorRecovery {
+            error: map_tokens(&self.error, SyntaxToken::to_ast),
+            dropped_tokens: self.dropped_tokens.iter().map(|(start, token, end)| (*start, token.to_ast(), *end)).collect(),
+        }
+    }
+}
+
+impl From<&ErrorRecovery<usize, Token<'_>, LexicalError>> for SyntaxError {
+    fn from(value: &ErrorRecovery<usize, Token, LexicalError>) -> Self {
+        SyntaxError {
+            error: map_tokens(&value.error, SyntaxToken::from),
+            dropped_tokens: value.dropped_tokens.iter().map(|(start, token, end)| (*start, token.into(), *end)).collect(),
+        }
+    }
+}
+
+impl SyntaxToken {
+    pub fn to_ast(&self) -> Token<'_> {
+        self.kind.with_text(self.text.as_ref().map_or("", Symbol::as_str))
+    }
+}
+
+impl From<&Token<'_>> for SyntaxToken {
+    fn from(value: &Token) -> Self {
+        SyntaxToken { kind: value.with_text(""), text: value.text().map(Symbol::intern) }
+    }
+}
+
+impl Id {
+    pub fn as_name(&self) -> Option<Symbol> {
+        match self {
+            Id::Name(name) => Some(name.clone()),
+            Id::Branch(name, _) => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn to_ast(&self) -> ast::Id<'_> {
+        match self {
+            Id::Name(name) => ast::Id::Name(name.as_str()),
+            Id::Index(index) => ast::Id::Index(*index),
+            Id::Branch(name, Branch::Version(version)) => ast::Id::Branch(name.as_str(), ast::Branch::Version(version.as_str())),
+            Id::Inline => ast::Id::Inline,
+        }
+    }
+}
+
+impl From<&ast::Id<'_>> for Id {
+    fn from(value: &ast::Id) -> Self {
+        match value {
+            ast::Id::Name(name) => Id::Name(Symbol::intern(name)),
+            ast::Id::Index(index) => Id::Index(*index),
+            ast::Id::Branch(name, ast::Branch::Version(version)) => Id::Branch(Symbol::intern(name), Branch::Version(Symbol::intern(version))),
+            ast::Id::Inline => Id::Inline,
+        }
+    }
+}
+
+impl Literal {
+    pub fn to_ast(&self) -> ast::Literal<'_> {
+        match self {
+            Literal::String(value) => ast::Literal::String(value.as_str()),
+            Literal::Number(value) => ast::Literal::Number(value.as_str()),
+        }
+    }
+}
+
+impl From<&ast::Literal<'_>> for Literal {
+    fn from(value: &ast::Literal) -> Self {
+        match value {
+            ast::Literal::String(value) => Literal::String(Symbol::intern(value)),
+            ast::Literal::Number(value) => Literal::Number(Symbol::intern(value)),
+        }
+    }
+}
+
+impl ModelDefinition {
+    pub fn id(&self) -> &Id {
+        match self {
+            ModelDefinition::Fragment(id, ..) | ModelDefinition::Record(id, ..) | ModelDefinition::Tuple(id, ..) |
+            ModelDefinition::Enum(id, ..) | ModelDefinition::Alias(id, ..) | ModelDefinition::Scalar(id) => id,
+        }
+    }
+
+    pub fn to_ast(&self) -> ast::ModelDefinition<'_> {
+        match self {
+            ModelDefinition::Fragment(id, items, params) => ast::ModelDefinition::Fragment(id.to_ast(), to_ast(items, RecordItem::to_ast), to_ast(params, ModelParamDefinition::to_ast)),
+            ModelDefinition::Record(id, items, params) => ast::ModelDefinition::Record(id.to_ast(), to_ast(items, RecordItem::to_ast), to_ast(params, ModelParamDefinition::to_ast)),
+            ModelDefinition::Tuple(id, items, params) => ast::ModelDefinition::Tuple(id.to_ast(), to_ast(items, TupleItem::to_ast), to_ast(params, ModelParamDefinition::to_ast)),
+            ModelDefinition::Enum(id, items, params) => ast::ModelDefinition::Enum(id.to_ast(), to_ast(items, EnumItem::to_ast), to_ast(params, ModelParamDefinition::to_ast)),
+            ModelDefinition::Alias(id, params, item_type) => ast::ModelDefinition::Alias(id.to_ast(), to_ast(params, ModelParamDefinition::to_ast), Box::new(item_type.to_ast())),
+            ModelDefinition::Scalar(id) => ast::ModelDefinition::Scalar(id.to_ast()),
+        }
+    }
+}
+
+impl From<&ast::ModelDefinition<'_>> for ModelDefinition {
+    fn from(value: &ast::ModelDefinition) -> Self {
+        match value {
+            ast::ModelDefinition::Fragment(id, items, params) => ModelDefinition::Fragment(id.into(), from_ast(items), from_ast(params)),
+            ast::ModelDefinition::Record(id, items, params) => ModelDefinition::Record(id.into(), from_ast(items), from_ast(params)),
+            ast::ModelDefinition::Tuple(id, items, params) => ModelDefinition::Tuple(id.into(), from_ast(items), from_ast(params)),
+            ast::ModelDefinition::Enum(id, items, params) => ModelDefinition::Enum(id.into(), from_ast(items), from_ast(params)),
+            ast::ModelDefinition::Alias(id, params, item_type) => ModelDefinition::Alias(id.into(), from_ast(params), Box::new(item_type.as_ref().into())),
+            ast::ModelDefinition::Scalar(id) => ModelDefinition::Scalar(id.into()),
+        }
+    }
+}
+
+impl RecordItem {
+    pub fn to_ast(&self) -> ast::RecordItem<'_> {
+        match self {
+            RecordItem::Item(id, item_type) => ast::RecordItem::Item(id.to_ast(), item_type.to_ast()),
+            RecordItem::Spread(item_type) => ast::RecordItem::Spread(item_type.to_ast()),
+        }
+    }
+}
+
+impl From<&ast::RecordItem<'_>> for RecordItem {
+    fn from(value: &ast::RecordItem) -> Self {
+        match value {
+            ast::RecordItem::Item(id, item_type) => RecordItem::Item(id.into(), item_type.into()),
+            ast::RecordItem::Spread(item_type) => RecordItem::Spread(item_type.into()),
+        }
+    }
+}
+
+impl TupleItem {
+    pub fn to_ast(&self) -> ast::TupleItem<'_> {
+        match self {
+            TupleItem::Item(item_type) => ast::TupleItem::Item(item_type.to_ast()),
+            TupleItem::NamedItem(id, item_type) => ast::TupleItem::NamedItem(id.to_ast(), item_type.to_ast()),
+        }
+    }
+}
+
+impl From<&ast::TupleItem<'_>> for TupleItem {
+    fn from(value: &ast::TupleItem) -> Self {
+        match value {
+            ast::TupleItem::Item(item_type) => TupleItem::Item(item_type.into()),
+            ast::TupleItem::NamedItem(id, item_type) => TupleItem::NamedItem(id.into(), item_type.into()),
+        }
+    }
+}
+
+impl EnumItem {
+    pub fn to_ast(&self) -> ast::EnumItem<'_> {
+        match self {
+            EnumItem::Item(id) => ast::EnumItem::Item(id.to_ast()),
+            EnumItem::Record(id, item_type) => ast::EnumItem::Record(id.to_ast(), item_type.to_ast()),
+            EnumItem::Tuple(id, item_type) => ast::EnumItem::Tuple(id.to_ast(), item_type.to_ast()),
+            EnumItem::Enum(id, item_type) => ast::EnumItem::Enum(id.to_ast(), item_type.to_ast()),
+        }
+    }
+}
+
+impl From<&ast::EnumItem<'_>> for EnumItem {
+    fn from(value: &ast::EnumItem) -> Self {
+        match value {
+            ast::EnumItem::Item(id) => EnumItem::Item(id.into()),
+            ast::EnumItem::Record(id, item_type) => EnumItem::Record(id.into(), item_type.into()),
+            ast::EnumItem::Tuple(id, item_type) => EnumItem::Tuple(id.into(), item_type.into()),
+            ast::EnumItem::Enum(id, item_type) => EnumItem::Enum(id.into(), item_type.into()),
+        }
+    }
+}
+
+impl ItemType {
+    pub fn to_ast(&self) -> ast::ItemType<'_> {
+        match self {
+            ItemType::Model(id, params) => ast::ItemType::Model(id.to_ast(), to_ast(params, ModelParam::to_ast)),
+            ItemType::Inline(def) => ast::ItemType::Inline(def.to_ast()),
+            ItemType::Optional(item_type) => ast::ItemType::Optional(Box::new(item_type.to_ast())),
+        }
+    }
+}
+
+impl From<&ast::ItemType<'_>> for ItemType {
+    fn from(value: &ast::ItemType) -> Self {
+        match value {
+            ast::ItemType::Model(id, params) => ItemType::Model(id.into(), from_ast(params)),
+            ast::ItemType::Inline(def) => ItemType::Inline(def.into()),
+            ast::ItemType::Optional(item_type) => ItemType::Optional(Box::new(item_type.as_ref().into())),
+        }
+    }
+}
+
+impl ModelParamDefinition {
+    pub fn to_ast(&self) -> ast::ModelParamDefinition<'_> {
+        match self {
+            ModelParamDefinition::Generic { id, constraint_type } => ast::ModelParamDefinition::Generic {
+                id: id.to_ast(),
+                constraint_type: constraint_type.as_ref().map(ItemType::to_ast),
+            },
+            ModelParamDefinition::Metadata { id, type_id, def_value } => ast::ModelParamDefinition::Metadata {
+                id: id.to_ast(),
+                type_id: type_id.to_ast(),
+                def_value: def_value.as_ref().map(Literal::to_ast),
+            },
+            ModelParamDefinition::Constraint { id, constraint: GenericConstraintDefinition::Contains(item_type) } => ast::ModelParamDefinition::Constraint {
+                id: id.to_ast(),
+                constraint: ast::GenericConstraintDefinition::Contains(item_type.to_ast()),
+            },
+        }
+    }
+}
+
+impl From<&ast::ModelParamDefinition<'_>> for ModelParamDefinition {
+    fn from(value: &ast::ModelParamDefinition) -> Self {
+        match value {
+            ast::ModelParamDefinition::Generic { id, constraint_type } => ModelParamDefinition::Generic {
+                id: id.into(),
+                constraint_type: constraint_type.as_ref().map(ItemType::from),
+            },
+            ast::ModelParamDefinition::Metadata { id, type_id, def_value } => ModelParamDefinition::Metadata {
+                id: id.into(),
+                type_id: type_id.into(),
+                def_value: def_value.as_ref().map(Literal::from),
+            },
+            ast::ModelParamDefinition::Constraint { id, constraint: ast::GenericConstraintDefinition::Contains(item_type) } => ModelParamDefinition::Constraint {
+                id: id.into(),
+                constraint: GenericConstraintDefinition::Contains(item_type.into()),
+            },
+        }
+    }
+}
+
+impl ModelParam {
+    pub fn to_ast(&self) -> ast::ModelParam<'_> {
+        match self {
+            ModelParam::Generic(item_type) => ast::ModelParam::Generic(item_type.to_ast()),
+            ModelParam::Metadata(id, value) => ast::ModelParam::Metadata(id.to_ast(), value.to_ast()),
+        }
+    }
+}
+
+impl From<&ast::ModelParam<'_>> for ModelParam {
+    fn from(value: &ast::ModelParam) -> Self {
+        match value {
+            ast::ModelParam::Generic(item_type) => ModelParam::Generic(item_type.into()),
+            ast::ModelParam::Metadata(id, value) => ModelParam::Metadata(id.into(), value.into()),
+        }
+    }
+}
+
+/// The error with its tokens converted by reference.
+fn map_tokens<'a, T, U>(error: &'a ParseError<usize, T, LexicalError>, f: impl Fn(&'a T) -> U) -> ParseError<usize, U, LexicalError> {
+    match error {
+        ParseError::InvalidToken { location } => ParseError::InvalidToken { location: *location },
+        ParseError::UnrecognizedEof { location, expected } => ParseError::UnrecognizedEof { location: *location, expected: expected.clone() },
+        ParseError::UnrecognizedToken { token: (start, token, end), expected } => {
+            ParseError::UnrecognizedToken { token: (*start, f(token), *end), expected: expected.clone() }
+        },
+        ParseError::ExtraToken { token: (start, token, end) } => ParseError::ExtraToken { token: (*start, f(token), *end) },
+        ParseError::User { error } => ParseError::User { error: error.clone() },
+    }
+}
+
+fn from_ast<'a, A: 'a, T: From<&'a A>>(items: &'a [A]) -> Vec<T> {
+    items.iter().map(T::from).collect()
+}
+
+fn to_ast<'a, T, A>(items: &'a [T], f: impl Fn(&'a T) -> A) -> Vec<A> {
+    items.iter().map(f).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("model Point(x: Int, y: Int)"; "tuple")]
+    #[test_case("package shop;\n\nmodel Item {\n    id: Int\n    name: String?\n}"; "root package")]
+    #[test_case("model Page<T> {\n    items: List<T>\n    ... Paging\n}"; "generics and spreads")]
+    #[test_case("model Hash[len: Int = 32] {\n    data: Bytes\n}\n\nmodel User {\n    hash: Hash[len=64]\n}"; "metadata")]
+    #[test_case("model Status enum {\n    Active\n    Blocked(Int)\n    Sub enum {\n        Test\n    }\n}"; "enum")]
+    #[test_case("package a {\n    model A = (Int, Int)\n}"; "nested package")]
+    fn check(code: &str) {
+        let global = ast::Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let borrowed = compiler.make_ast().unwrap();
+
+        let owned = Scope::from_ast(&borrowed);
+        // The borrowed tree is invariant over its lifetime, so it is compared by its debug form.
+        assert_eq!(format!("{:?}", owned.to_ast()), format!("{:?}", borrowed));
+        assert_eq!(Scope::parse(&owned.to_source().unwrap()).unwrap(), owned);
+    }
+
+    #[test]
+    fn check_syntax_error() {
+        let code = "model A {\n    x: Int\n}\n\nmodel B {\n    y Int\n}\n";
+        let global = ast::Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let borrowed = compiler.make_ast().unwrap();
+
+        let owned = Scope::from_ast(&borrowed);
+        assert_eq!(format!("{:?}", owned.to_ast()), format!("{:?}", borrowed));
+
+        let Scope::Global(items) = &owned else { panic!("{:?}", owned) };
+        let Some(Scope::Error(error)) = items.iter().find(|item| matches!(item, Scope::Error(_))) else { panic!("{:?}", owned) };
+        let (span, message) = ast::syntax_message(&error.to_ast().error, code);
+        assert_eq!((&code[span], message.as_str()), ("Int", "unexpected `Int`, expected `:`"));
+    }
+
+    #[test]
+    fn check_no_syntax() {
+        let scope = Scope::Model(ModelDefinition::Tuple(Id::Name("Pair".into()), vec![TupleItem::NamedItem(Id::Index(1), ItemType::Model(Id::Name("Int".into()), vec![]))], vec![]));
+        assert!(scope.to_source().is_err());
+    }
+
+    #[test]
+    fn check_send() {
+        let scope = Scope::parse("model Point(x: Int, y: Int)").unwrap();
+        let cloned = scope.clone();
+
+        let printed = std::thread::spawn(move || cloned.to_source().unwrap()).join().unwrap();
+        assert_eq!(printed, "model Point(x: Int, y: Int)");
+        assert_eq!(scope, Scope::parse(&printed).unwrap());
+    }
+
+    #[test]
+    fn check_json() {
+        let scope = Scope::parse("scalar Int;").unwrap();
+        assert_eq!(
+            scope.to_json(&AstJson::new().with_compact()),
+            r#"{"version":1,"root":{"kind":"global","items":[{"kind":"scalar","name":"Int","params":[]}]}}"#,
+        );
+    }
+
+    #[test]
+    fn check_symbols() {
+        assert_eq!(Symbol::intern("Point"), Symbol::from("Point"));
+        assert_ne!(Symbol::intern("Point"), Symbol::intern("Line"));
+        assert_eq!(Symbol::intern("Point").as_str(), "Point");
+        assert!(Symbol::intern("b") > Symbol::intern("a"));
+    }
+
+    #[test]
+    fn check_sessions() {
+        let (a, b) = Interner::session(|| (Symbol::intern("Point"), Symbol::intern("Point")));
+        let c = Symbol::intern("Point");
+
+        assert!(std::ptr::eq(a.as_str(), b.as_str()));
+        assert!(!std::ptr::eq(a.as_str(), c.as_str()));
+        assert_eq!(a, c);
+    }
+}
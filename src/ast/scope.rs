@@ -33,8 +33,8 @@ impl<'input> Scope<'input> {
 
 pub type RefScope<'input> = Rc<RefCell<Box<Scope<'input>>>>;
 
-impl<'input> Into<RefScope<'input>> for Scope<'input> {
-    fn into(self) -> RefScope<'input> {
-        Rc::new(RefCell::new(Box::new(self)))
+impl<'input> From<Scope<'input>> for RefScope<'input> {
+    fn from(value: Scope<'input>) -> Self {
+        Rc::new(RefCell::new(Box::new(value)))
     }
 }
\ No newline at end of file
@@ -1,4 +1,7 @@
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
 use std::path::Path;
 
 pub enum Source {
@@ -11,21 +14,43 @@ impl Source {
         Source::File(path.as_ref().into())
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(text: &str) -> Source {
         Source::String(String::from(text))
     }
 
-    pub fn get_name(&self) -> Cow<str> {
+    pub fn get_name(&self) -> Cow<'_, str> {
         match self {
             Source::File(path) => Cow::Borrowed(path.file_stem().unwrap().to_str().unwrap()),
             _ => Cow::Owned(String::default()),
         }
     }
 
-    pub fn read(&self) -> crate::Result<Cow<str>> {
+    pub fn read(&self) -> crate::Result<'_, Cow<'_, str>> {
         match self {
             Source::File(path) => Ok(Cow::Owned(std::fs::read_to_string(path)?)),
             Source::String(s) => Ok(Cow::Borrowed(s)),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Byte ranges of the names in a parsed tree, as the parser located them.
+///
+/// A name is looked up by the slice of the code the tree holds, so two names with the same
+/// text each find their own range.
+#[derive(Default)]
+pub struct NameSpans<'input>(RefCell<HashMap<*const str, Range<usize>>>, std::marker::PhantomData<&'input str>);
+
+impl<'input> NameSpans<'input> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, name: &'input str, range: Range<usize>) {
+        self.0.borrow_mut().insert(name, range);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Range<usize>> {
+        self.0.borrow().get(&(name as *const str)).cloned()
+    }
+}
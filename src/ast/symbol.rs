@@ -0,0 +1,111 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Name of the owned AST: a shared string, cloned by reference count.
+///
+/// Symbols made within one [`Interner::session`], such as a whole tree converted by
+/// `owned::Scope::from_ast`, share one allocation per distinct string and compare by pointer
+/// first. The table is dropped with the session and a string is freed with its last symbol, so
+/// a long-running process does not keep the names of trees it no longer holds.
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+/// Strings of the current session on this thread.
+#[derive(Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+thread_local! {
+    static SESSION: RefCell<Option<Interner>> = const { RefCell::new(None) };
+}
+
+impl Interner {
+    /// Runs `f` with symbols of equal strings sharing their allocation. A nested session has
+    /// a table of its own, and the outer one is restored when it ends, even by a panic.
+    pub fn session<T>(f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<Interner>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let outer = self.0.take();
+                SESSION.with(|session| *session.borrow_mut() = outer);
+            }
+        }
+
+        let _restore = Restore(SESSION.with(|session| session.borrow_mut().replace(Interner::default())));
+        f()
+    }
+
+    fn intern(&mut self, text: &str) -> Symbol {
+        match self.strings.get(text) {
+            Some(text) => Symbol(text.clone()),
+            None => {
+                let text: Arc<str> = text.into();
+                self.strings.insert(text.clone());
+                Symbol(text)
+            },
+        }
+    }
+}
+
+impl Symbol {
+    pub fn intern(text: &str) -> Self {
+        SESSION.with(|session| match session.borrow_mut().as_mut() {
+            Some(interner) => interner.intern(text),
+            None => Symbol(text.into()),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(value: &str) -> Self {
+        Symbol::intern(value)
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders by the text.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
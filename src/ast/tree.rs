@@ -0,0 +1,333 @@
+//! Arena form of the scope tree, built from the owned AST.
+//!
+//! Scopes are stored in a flat vector and addressed by [`ScopeId`], so a node knows its parent,
+//! a child is found by name in constant time, and the whole tree is `Send` and `Sync`.
+//!
+//! The tree sits next to [`RefScope`] rather than replacing it: the parser still produces a
+//! `RefScope`, and the transformers, `ModelIndex` and the language server still walk it, as
+//! they hold names borrowed from the source. Passes on owned trees, such as `query`, build on
+//! this one. Names resolve by [`resolve_name`] in both forms.
+
+use std::collections::HashMap;
+use std::ops::Index;
+use crate::ast::owned::{Id, ModelDefinition, Scope, SyntaxError};
+use crate::ast::{RefScope, Symbol};
+
+/// The rule every name in a tree resolves by, shared by [`ScopeTree::resolve`],
+/// `ModelIndex::find` and the language server.
+///
+/// Among the declarations of a name, each given with the names of the packages it is declared
+/// in, a reference from inside the packages `path` sees the one of the innermost enclosing
+/// package, then one of any other package. The first declaration wins a tie.
+pub fn resolve_name<'p, T, P: PartialEq + 'p>(path: &[P], declarations: impl IntoIterator<Item = (T, &'p [P])>) -> Option<T> {
+    let mut found: Option<(isize, T)> = None;
+    for (declaration, declared_in) in declarations {
+        let rank = match path.starts_with(declared_in) {
+            true => declared_in.len() as isize,
+            false => -1,
+        };
+        if found.as_ref().is_none_or(|(best, _)| rank > *best) {
+            found = Some((rank, declaration));
+        }
+    }
+    found.map(|(_, declaration)| declaration)
+}
+
+/// Index of a scope in its [`ScopeTree`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScopeId(u32);
+
+impl ScopeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScopeKind {
+    Global,
+    Package(Id),
+    Model(ModelDefinition),
+    /// Part of the source the parser recovered from, with the error it reported.
+    Error(SyntaxError),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeNode {
+    pub kind: ScopeKind,
+    parent: Option<ScopeId>,
+    children: Vec<ScopeId>,
+    /// Innermost package or global scope holding the node, the node itself if it is one.
+    package: ScopeId,
+    /// Names of the enclosing packages and of the node itself.
+    path: Vec<Symbol>,
+}
+
+impl ScopeNode {
+    pub fn parent(&self) -> Option<ScopeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[ScopeId] {
+        &self.children
+    }
+
+    pub fn package(&self) -> ScopeId {
+        self.package
+    }
+
+    pub fn path(&self) -> &[Symbol] {
+        &self.path
+    }
+
+    /// Name of a package or of a named model.
+    pub fn name(&self) -> Option<Symbol> {
+        match &self.kind {
+            ScopeKind::Package(id) => id.as_name(),
+            ScopeKind::Model(def) => def.id().as_name(),
+            ScopeKind::Global | ScopeKind::Error(_) => None,
+        }
+    }
+
+    pub fn as_model(&self) -> Option<&ModelDefinition> {
+        match &self.kind {
+            ScopeKind::Model(def) => Some(def),
+            _ => None,
+        }
+    }
+}
+
+/// Scope tree stored in an arena. The root is always a `Global` scope.
+///
+/// When a scope declares the same name twice, lookups find the first declaration.
+#[derive(Clone, Debug)]
+pub struct ScopeTree {
+    nodes: Vec<ScopeNode>,
+    children: HashMap<(ScopeId, Symbol), ScopeId>,
+    /// Named models by name, in declaration order.
+    models: HashMap<Symbol, Vec<ScopeId>>,
+}
+
+impl Default for ScopeTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScopeTree {
+    /// Tree with an empty global scope.
+    pub fn new() -> Self {
+        ScopeTree {
+            nodes: vec![ScopeNode { kind: ScopeKind::Global, parent: None, children: vec![], package: ScopeId(0), path: vec![] }],
+            children: HashMap::new(),
+            models: HashMap::new(),
+        }
+    }
+
+    /// Parses the code into a tree. Recovered syntax errors are kept as `Error` scopes.
+    pub fn parse(code: &str) -> crate::Result<'static, Self> {
+        Scope::parse(code).map(|scope| ScopeTree::from(&scope))
+    }
+
+    pub fn from_ast(scope: &RefScope) -> Self {
+        ScopeTree::from(&Scope::from_ast(scope))
+    }
+
+    pub fn root(&self) -> ScopeId {
+        ScopeId(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    pub fn get(&self, id: ScopeId) -> Option<&ScopeNode> {
+        self.nodes.get(id.index())
+    }
+
+    /// Appends a scope as the last child of `parent`.
+    pub fn add(&mut self, parent: ScopeId, kind: ScopeKind) -> ScopeId {
+        let id = ScopeId(self.nodes.len() as u32);
+        let package = match kind {
+            ScopeKind::Package(_) => id,
+            _ => self[parent].package,
+        };
+        let mut path = self[parent].path.clone();
+        let mut node = ScopeNode { kind, parent: Some(parent), children: vec![], package, path: vec![] };
+        path.extend(node.name());
+        node.path = path;
+
+        let name = node.name();
+        let is_model = node.as_model().is_some();
+        self.nodes.push(node);
+        self.nodes[parent.index()].children.push(id);
+
+        if let Some(name) = name {
+            self.children.entry((parent, name.clone())).or_insert(id);
+            if is_model {
+                self.models.entry(name).or_default().push(id);
+            }
+        }
+        id
+    }
+
+    pub fn parent(&self, id: ScopeId) -> Option<ScopeId> {
+        self[id].parent
+    }
+
+    pub fn children(&self, id: ScopeId) -> &[ScopeId] {
+        &self[id].children
+    }
+
+    /// The scope itself, then its parents up to the root.
+    pub fn ancestors(&self, id: ScopeId) -> impl Iterator<Item = ScopeId> + '_ {
+        std::iter::successors(Some(id), |id| self.parent(*id))
+    }
+
+    /// All scopes in declaration order, parents before their children.
+    pub fn iter(&self) -> impl Iterator<Item = (ScopeId, &ScopeNode)> {
+        self.nodes.iter().enumerate().map(|(i, node)| (ScopeId(i as u32), node))
+    }
+
+    /// Models of the tree in declaration order.
+    pub fn models(&self) -> impl Iterator<Item = (ScopeId, &ModelDefinition)> {
+        self.iter().filter_map(|(id, node)| node.as_model().map(|def| (id, def)))
+    }
+
+    /// Names of the enclosing packages and of the scope itself.
+    pub fn path(&self, id: ScopeId) -> &[Symbol] {
+        &self[id].path
+    }
+
+    /// Child of `scope` declared with `name`.
+    pub fn lookup(&self, scope: ScopeId, name: &Symbol) -> Option<ScopeId> {
+        self.children.get(&(scope, name.clone())).copied()
+    }
+
+    /// Scope at the dotted `path` from the root.
+    pub fn find(&self, path: &[Symbol]) -> Option<ScopeId> {
+        path.iter().try_fold(self.root(), |scope, name| self.lookup(scope, name))
+    }
+
+    /// Model `name` refers to when used inside `from`, see [`resolve_name`]. Packages are not
+    /// types, so a name never resolves to one.
+    pub fn resolve(&self, from: ScopeId, name: &Symbol) -> Option<ScopeId> {
+        let declarations = self.models.get(name)?.iter().map(|id| (*id, self.path(self[*id].package)));
+        resolve_name(self.path(self[from].package), declarations)
+    }
+
+    /// Owned tree of the scope and its children.
+    pub fn to_scope(&self, id: ScopeId) -> Scope {
+        let children = || self.children(id).iter().map(|child| self.to_scope(*child)).collect();
+        match &self[id].kind {
+            ScopeKind::Global => Scope::Global(children()),
+            ScopeKind::Package(name) => Scope::Package(name.clone(), children()),
+            ScopeKind::Model(def) => Scope::Model(def.clone()),
+            ScopeKind::Error(error) => Scope::Error(error.clone()),
+        }
+    }
+
+    fn add_scope(&mut self, parent: ScopeId, scope: &Scope) {
+        let (kind, items) = match scope {
+            Scope::Global(items) => {
+                items.iter().for_each(|item| self.add_scope(parent, item));
+                return;
+            },
+            Scope::Package(name, items) => (ScopeKind::Package(name.clone()), items.as_slice()),
+            Scope::Model(def) => (ScopeKind::Model(def.clone()), [].as_slice()),
+            Scope::Error(error) => (ScopeKind::Error(error.clone()), [].as_slice()),
+        };
+
+        let id = self.add(parent, kind);
+        items.iter().for_each(|item| self.add_scope(id, item));
+    }
+}
+
+/// Builds the tree of a global scope, or of any other scope placed under a new global one.
+impl From<&Scope> for ScopeTree {
+    fn from(value: &Scope) -> Self {
+        let mut tree = ScopeTree::new();
+        tree.add_scope(tree.root(), value);
+        tree
+    }
+}
+
+impl Index<ScopeId> for ScopeTree {
+    type Output = ScopeNode;
+
+    fn index(&self, index: ScopeId) -> &Self::Output {
+        &self.nodes[index.index()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const CODE: &str = "package shop;
+
+model Item {
+    id: Int
+}
+
+package orders {
+    model Order {
+        item: Item
+    }
+
+    model Item(Int)
+}";
+
+    fn path(names: &str) -> Vec<Symbol> {
+        names.split('.').filter(|name| !name.is_empty()).map(Symbol::intern).collect()
+    }
+
+    #[test]
+    fn check_structure() {
+        let tree = ScopeTree::parse(CODE).unwrap();
+        let order = tree.find(&path("shop.orders.Order")).unwrap();
+        let orders = tree.parent(order).unwrap();
+
+        assert_eq!(tree[orders].name(), Some(Symbol::intern("orders")));
+        assert_eq!(tree.path(order), path("shop.orders.Order"));
+        assert_eq!(tree[order].package(), orders);
+        assert_eq!(tree[orders].package(), orders);
+        assert_eq!(tree.ancestors(order).count(), 4);
+        assert_eq!(tree.children(orders).len(), 2);
+        assert_eq!(tree.models().count(), 3);
+        // The parser returns the root package itself, which the tree places under a global scope.
+        assert_eq!(tree.to_scope(tree.find(&path("shop")).unwrap()), Scope::parse(CODE).unwrap());
+    }
+
+    #[test_case("shop.orders.Order", "Item", "shop.orders.Item"; "innermost first")]
+    #[test_case("shop.Item", "Order", "shop.orders.Order"; "any other package")]
+    #[test_case("shop", "Item", "shop.Item"; "from package")]
+    #[test_case("shop.Item", "orders", ""; "not a package")]
+    fn check_resolve(from: &str, name: &str, expected: &str) {
+        let tree = ScopeTree::parse(CODE).unwrap();
+        let from = tree.find(&path(from)).unwrap();
+        let found = tree.resolve(from, &Symbol::intern(name));
+        assert_eq!(found.map(|found| tree.path(found)).unwrap_or_default(), path(expected));
+    }
+
+    #[test_case(&["a", "b"], &[("x", &[]), ("y", &["a"]), ("z", &["c"])], Some("y"); "innermost enclosing")]
+    #[test_case(&["a"], &[("x", &["c"]), ("y", &["d"])], Some("x"); "first of other packages")]
+    #[test_case(&["a"], &[("x", &["a"]), ("y", &["a"])], Some("x"); "first on a tie")]
+    #[test_case(&["a"], &[], None; "undeclared")]
+    fn check_resolve_name(path: &[&str], declarations: &[(&str, &[&str])], expected: Option<&str>) {
+        let found = resolve_name(path, declarations.iter().map(|(name, declared_in)| (*name, *declared_in)));
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn check_send() {
+        let tree = ScopeTree::parse(CODE).unwrap();
+        let count = std::thread::spawn(move || tree.models().count()).join().unwrap();
+        assert_eq!(count, 3);
+    }
+}
@@ -0,0 +1,571 @@
+//! Traversal of the owned AST.
+//!
+//! [`Visitor`] and [`VisitorMut`] have a method per node kind whose default walks into the
+//! children, so a pass only overrides the nodes it cares about and calls the matching `walk_*`
+//! function to keep descending. [`Fold`] rebuilds the tree by value, for rewriting passes that
+//! replace nodes, such as expanding spreads or resolving aliases.
+
+use crate::ast::owned::*;
+
+pub trait Visitor<'ast> {
+    fn visit_scope(&mut self, scope: &'ast Scope) {
+        walk_scope(self, scope)
+    }
+
+    fn visit_model(&mut self, def: &'ast ModelDefinition) {
+        walk_model(self, def)
+    }
+
+    fn visit_record_item(&mut self, item: &'ast RecordItem) {
+        walk_record_item(self, item)
+    }
+
+    fn visit_tuple_item(&mut self, item: &'ast TupleItem) {
+        walk_tuple_item(self, item)
+    }
+
+    fn visit_enum_item(&mut self, item: &'ast EnumItem) {
+        walk_enum_item(self, item)
+    }
+
+    fn visit_item_type(&mut self, item_type: &'ast ItemType) {
+        walk_item_type(self, item_type)
+    }
+
+    fn visit_model_param(&mut self, param: &'ast ModelParam) {
+        walk_model_param(self, param)
+    }
+
+    fn visit_model_param_def(&mut self, param: &'ast ModelParamDefinition) {
+        walk_model_param_def(self, param)
+    }
+
+    fn visit_id(&mut self, _id: &'ast Id) {}
+
+    fn visit_literal(&mut self, _literal: &'ast Literal) {}
+}
+
+pub fn walk_scope<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, scope: &'ast Scope) {
+    match scope {
+        Scope::Global(items) => items.iter().for_each(|item| visitor.visit_scope(item)),
+        Scope::Package(id, items) => {
+            visitor.visit_id(id);
+            items.iter().for_each(|item| visitor.visit_scope(item));
+        },
+        Scope::Model(def) => visitor.visit_model(def),
+        Scope::Error(_) => {},
+    }
+}
+
+pub fn walk_model<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, def: &'ast ModelDefinition) {
+    visitor.visit_id(def.id());
+    match def {
+        ModelDefinition::Fragment(_, items, params) | ModelDefinition::Record(_, items, params) => {
+            params.iter().for_each(|param| visitor.visit_model_param_def(param));
+            items.iter().for_each(|item| visitor.visit_record_item(item));
+        },
+        ModelDefinition::Tuple(_, items, params) => {
+            params.iter().for_each(|param| visitor.visit_model_param_def(param));
+            items.iter().for_each(|item| visitor.visit_tuple_item(item));
+        },
+        ModelDefinition::Enum(_, items, params) => {
+            params.iter().for_each(|param| visitor.visit_model_param_def(param));
+            items.iter().for_each(|item| visitor.visit_enum_item(item));
+        },
+        ModelDefinition::Alias(_, params, item_type) => {
+            params.iter().for_each(|param| visitor.visit_model_param_def(param));
+            visitor.visit_item_type(item_type);
+        },
+        ModelDefinition::Scalar(_) => {},
+    }
+}
+
+pub fn walk_record_item<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, item: &'ast RecordItem) {
+    match item {
+        RecordItem::Item(id, item_type) => {
+            visitor.visit_id(id);
+            visitor.visit_item_type(item_type);
+        },
+        RecordItem::Spread(item_type) => visitor.visit_item_type(item_type),
+    }
+}
+
+pub fn walk_tuple_item<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, item: &'ast TupleItem) {
+    match item {
+        TupleItem::Item(item_type) => visitor.visit_item_type(item_type),
+        TupleItem::NamedItem(id, item_type) => {
+            visitor.visit_id(id);
+            visitor.visit_item_type(item_type);
+        },
+    }
+}
+
+pub fn walk_enum_item<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, item: &'ast EnumItem) {
+    match item {
+        EnumItem::Item(id) => visitor.visit_id(id),
+        EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+            visitor.visit_id(id);
+            visitor.visit_item_type(item_type);
+        },
+    }
+}
+
+pub fn walk_item_type<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, item_type: &'ast ItemType) {
+    match item_type {
+        ItemType::Model(id, params) => {
+            visitor.visit_id(id);
+            params.iter().for_each(|param| visitor.visit_model_param(param));
+        },
+        ItemType::Inline(def) => visitor.visit_model(def),
+        ItemType::Optional(item_type) => visitor.visit_item_type(item_type),
+    }
+}
+
+pub fn walk_model_param<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, param: &'ast ModelParam) {
+    match param {
+        ModelParam::Generic(item_type) => visitor.visit_item_type(item_type),
+        ModelParam::Metadata(id, value) => {
+            visitor.visit_id(id);
+            visitor.visit_literal(value);
+        },
+    }
+}
+
+pub fn walk_model_param_def<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, param: &'ast ModelParamDefinition) {
+    match param {
+        ModelParamDefinition::Generic { id, constraint_type } => {
+            visitor.visit_id(id);
+            if let Some(item_type) = constraint_type {
+                visitor.visit_item_type(item_type);
+            }
+        },
+        ModelParamDefinition::Metadata { id, type_id, def_value } => {
+            visitor.visit_id(id);
+            visitor.visit_item_type(type_id);
+            if let Some(value) = def_value {
+                visitor.visit_literal(value);
+            }
+        },
+        ModelParamDefinition::Constraint { id, constraint: GenericConstraintDefinition::Contains(item_type) } => {
+            visitor.visit_id(id);
+            visitor.visit_item_type(item_type);
+        },
+    }
+}
+
+/// [`Visitor`] over a mutable tree, for passes that edit nodes in place.
+pub trait VisitorMut {
+    fn visit_scope_mut(&mut self, scope: &mut Scope) {
+        walk_scope_mut(self, scope)
+    }
+
+    fn visit_model_mut(&mut self, def: &mut ModelDefinition) {
+        walk_model_mut(self, def)
+    }
+
+    fn visit_record_item_mut(&mut self, item: &mut RecordItem) {
+        walk_record_item_mut(self, item)
+    }
+
+    fn visit_tuple_item_mut(&mut self, item: &mut TupleItem) {
+        walk_tuple_item_mut(self, item)
+    }
+
+    fn visit_enum_item_mut(&mut self, item: &mut EnumItem) {
+        walk_enum_item_mut(self, item)
+    }
+
+    fn visit_item_type_mut(&mut self, item_type: &mut ItemType) {
+        walk_item_type_mut(self, item_type)
+    }
+
+    fn visit_model_param_mut(&mut self, param: &mut ModelParam) {
+        walk_model_param_mut(self, param)
+    }
+
+    fn visit_model_param_def_mut(&mut self, param: &mut ModelParamDefinition) {
+        walk_model_param_def_mut(self, param)
+    }
+
+    fn visit_id_mut(&mut self, _id: &mut Id) {}
+
+    fn visit_literal_mut(&mut self, _literal: &mut Literal) {}
+}
+
+pub fn walk_scope_mut<V: VisitorMut + ?Sized>(visitor: &mut V, scope: &mut Scope) {
+    match scope {
+        Scope::Global(items) => items.iter_mut().for_each(|item| visitor.visit_scope_mut(item)),
+        Scope::Package(id, items) => {
+            visitor.visit_id_mut(id);
+            items.iter_mut().for_each(|item| visitor.visit_scope_mut(item));
+        },
+        Scope::Model(def) => visitor.visit_model_mut(def),
+        Scope::Error(_) => {},
+    }
+}
+
+pub fn walk_model_mut<V: VisitorMut + ?Sized>(visitor: &mut V, def: &mut ModelDefinition) {
+    match def {
+        ModelDefinition::Fragment(id, items, params) | ModelDefinition::Record(id, items, params) => {
+            visitor.visit_id_mut(id);
+            params.iter_mut().for_each(|param| visitor.visit_model_param_def_mut(param));
+            items.iter_mut().for_each(|item| visitor.visit_record_item_mut(item));
+        },
+        ModelDefinition::Tuple(id, items, params) => {
+            visitor.visit_id_mut(id);
+            params.iter_mut().for_each(|param| visitor.visit_model_param_def_mut(param));
+            items.iter_mut().for_each(|item| visitor.visit_tuple_item_mut(item));
+        },
+        ModelDefinition::Enum(id, items, params) => {
+            visitor.visit_id_mut(id);
+            params.iter_mut().for_each(|param| visitor.visit_model_param_def_mut(param));
+            items.iter_mut().for_each(|item| visitor.visit_enum_item_mut(item));
+        },
+        ModelDefinition::Alias(id, params, item_type) => {
+            visitor.visit_id_mut(id);
+            params.iter_mut().for_each(|param| visitor.visit_model_param_def_mut(param));
+            visitor.visit_item_type_mut(item_type);
+        },
+        ModelDefinition::Scalar(id) => visitor.visit_id_mut(id),
+    }
+}
+
+pub fn walk_record_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut RecordItem) {
+    match item {
+        RecordItem::Item(id, item_type) => {
+            visitor.visit_id_mut(id);
+            visitor.visit_item_type_mut(item_type);
+        },
+        RecordItem::Spread(item_type) => visitor.visit_item_type_mut(item_type),
+    }
+}
+
+pub fn walk_tuple_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut TupleItem) {
+    match item {
+        TupleItem::Item(item_type) => visitor.visit_item_type_mut(item_type),
+        TupleItem::NamedItem(id, item_type) => {
+            visitor.visit_id_mut(id);
+            visitor.visit_item_type_mut(item_type);
+        },
+    }
+}
+
+pub fn walk_enum_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut EnumItem) {
+    match item {
+        EnumItem::Item(id) => visitor.visit_id_mut(id),
+        EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+            visitor.visit_id_mut(id);
+            visitor.visit_item_type_mut(item_type);
+        },
+    }
+}
+
+pub fn walk_item_type_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item_type: &mut ItemType) {
+    match item_type {
+        ItemType::Model(id, params) => {
+            visitor.visit_id_mut(id);
+            params.iter_mut().for_each(|param| visitor.visit_model_param_mut(param));
+        },
+        ItemType::Inline(def) => visitor.visit_model_mut(def),
+        ItemType::Optional(item_type) => visitor.visit_item_type_mut(item_type),
+    }
+}
+
+pub fn walk_model_param_mut<V: VisitorMut + ?Sized>(visitor: &mut V, param: &mut ModelParam) {
+    match param {
+        ModelParam::Generic(item_type) => visitor.visit_item_type_mut(item_type),
+        ModelParam::Metadata(id, value) => {
+            visitor.visit_id_mut(id);
+            visitor.visit_literal_mut(value);
+        },
+    }
+}
+
+pub fn walk_model_param_def_mut<V: VisitorMut + ?Sized>(visitor: &mut V, param: &mut ModelParamDefinition) {
+    match param {
+        ModelParamDefinition::Generic { id, constraint_type } => {
+            visitor.visit_id_mut(id);
+            if let Some(item_type) = constraint_type {
+                visitor.visit_item_type_mut(item_type);
+            }
+        },
+        ModelParamDefinition::Metadata { id, type_id, def_value } => {
+            visitor.visit_id_mut(id);
+            visitor.visit_item_type_mut(type_id);
+            if let Some(value) = def_value {
+                visitor.visit_literal_mut(value);
+            }
+        },
+        ModelParamDefinition::Constraint { id, constraint: GenericConstraintDefinition::Contains(item_type) } => {
+            visitor.visit_id_mut(id);
+            visitor.visit_item_type_mut(item_type);
+        },
+    }
+}
+
+/// Rewrites the tree by value: every method takes a node and returns its replacement.
+///
+/// The defaults rebuild the node from its folded children. A pass that drops or adds
+/// record fields overrides `fold_record_items`, which sees the whole list at once.
+pub trait Fold {
+    fn fold_scope(&mut self, scope: Scope) -> Scope {
+        fold_scope(self, scope)
+    }
+
+    fn fold_model(&mut self, def: ModelDefinition) -> ModelDefinition {
+        fold_model(self, def)
+    }
+
+    fn fold_record_items(&mut self, items: Vec<RecordItem>) -> Vec<RecordItem> {
+        items.into_iter().map(|item| self.fold_record_item(item)).collect()
+    }
+
+    fn fold_record_item(&mut self, item: RecordItem) -> RecordItem {
+        fold_record_item(self, item)
+    }
+
+    fn fold_tuple_item(&mut self, item: TupleItem) -> TupleItem {
+        fold_tuple_item(self, item)
+    }
+
+    fn fold_enum_item(&mut self, item: EnumItem) -> EnumItem {
+        fold_enum_item(self, item)
+    }
+
+    fn fold_item_type(&mut self, item_type: ItemType) -> ItemType {
+        fold_item_type(self, item_type)
+    }
+
+    fn fold_model_param(&mut self, param: ModelParam) -> ModelParam {
+        fold_model_param(self, param)
+    }
+
+    fn fold_model_param_def(&mut self, param: ModelParamDefinition) -> ModelParamDefinition {
+        fold_model_param_def(self, param)
+    }
+
+    fn fold_id(&mut self, id: Id) -> Id {
+        id
+    }
+
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        literal
+    }
+}
+
+fn fold_all<T>(items: Vec<T>, mut f: impl FnMut(T) -> T) -> Vec<T> {
+    items.into_iter().map(&mut f).collect()
+}
+
+pub fn fold_scope<F: Fold + ?Sized>(folder: &mut F, scope: Scope) -> Scope {
+    match scope {
+        Scope::Global(items) => Scope::Global(fold_all(items, |item| folder.fold_scope(item))),
+        Scope::Package(id, items) => {
+            let id = folder.fold_id(id);
+            Scope::Package(id, fold_all(items, |item| folder.fold_scope(item)))
+        },
+        Scope::Model(def) => Scope::Model(folder.fold_model(def)),
+        Scope::Error(error) => Scope::Error(error),
+    }
+}
+
+pub fn fold_model<F: Fold + ?Sized>(folder: &mut F, def: ModelDefinition) -> ModelDefinition {
+    match def {
+        ModelDefinition::Fragment(id, items, params) => {
+            let id = folder.fold_id(id);
+            let params = fold_all(params, |param| folder.fold_model_param_def(param));
+            ModelDefinition::Fragment(id, folder.fold_record_items(items), params)
+        },
+        ModelDefinition::Record(id, items, params) => {
+            let id = folder.fold_id(id);
+            let params = fold_all(params, |param| folder.fold_model_param_def(param));
+            ModelDefinition::Record(id, folder.fold_record_items(items), params)
+        },
+        ModelDefinition::Tuple(id, items, params) => {
+            let id = folder.fold_id(id);
+            let params = fold_all(params, |param| folder.fold_model_param_def(param));
+            ModelDefinition::Tuple(id, fold_all(items, |item| folder.fold_tuple_item(item)), params)
+        },
+        ModelDefinition::Enum(id, items, params) => {
+            let id = folder.fold_id(id);
+            let params = fold_all(params, |param| folder.fold_model_param_def(param));
+            ModelDefinition::Enum(id, fold_all(items, |item| folder.fold_enum_item(item)), params)
+        },
+        ModelDefinition::Alias(id, params, item_type) => {
+            let id = folder.fold_id(id);
+            let params = fold_all(params, |param| folder.fold_model_param_def(param));
+            ModelDefinition::Alias(id, params, Box::new(folder.fold_item_type(*item_type)))
+        },
+        ModelDefinition::Scalar(id) => ModelDefinition::Scalar(folder.fold_id(id)),
+    }
+}
+
+pub fn fold_record_item<F: Fold + ?Sized>(folder: &mut F, item: RecordItem) -> RecordItem {
+    match item {
+        RecordItem::Item(id, item_type) => RecordItem::Item(folder.fold_id(id), folder.fold_item_type(item_type)),
+        RecordItem::Spread(item_type) => RecordItem::Spread(folder.fold_item_type(item_type)),
+    }
+}
+
+pub fn fold_tuple_item<F: Fold + ?Sized>(folder: &mut F, item: TupleItem) -> TupleItem {
+    match item {
+        TupleItem::Item(item_type) => TupleItem::Item(folder.fold_item_type(item_type)),
+        TupleItem::NamedItem(id, item_type) => TupleItem::NamedItem(folder.fold_id(id), folder.fold_item_type(item_type)),
+    }
+}
+
+pub fn fold_enum_item<F: Fold + ?Sized>(folder: &mut F, item: EnumItem) -> EnumItem {
+    match item {
+        EnumItem::Item(id) => EnumItem::Item(folder.fold_id(id)),
+        EnumItem::Record(id, item_type) => EnumItem::Record(folder.fold_id(id), folder.fold_item_type(item_type)),
+        EnumItem::Tuple(id, item_type) => EnumItem::Tuple(folder.fold_id(id), folder.fold_item_type(item_type)),
+        EnumItem::Enum(id, item_type) => EnumItem::Enum(folder.fold_id(id), folder.fold_item_type(item_type)),
+    }
+}
+
+pub fn fold_item_type<F: Fold + ?Sized>(folder: &mut F, item_type: ItemType) -> ItemType {
+    match item_type {
+        ItemType::Model(id, params) => {
+            let id = folder.fold_id(id);
+            ItemType::Model(id, fold_all(params, |param| folder.fold_model_param(param)))
+        },
+        ItemType::Inline(def) => ItemType::Inline(folder.fold_model(def)),
+        ItemType::Optional(item_type) => ItemType::Optional(Box::new(folder.fold_item_type(*item_type))),
+    }
+}
+
+pub fn fold_model_param<F: Fold + ?Sized>(folder: &mut F, param: ModelParam) -> ModelParam {
+    match param {
+        ModelParam::Generic(item_type) => ModelParam::Generic(folder.fold_item_type(item_type)),
+        ModelParam::Metadata(id, value) => ModelParam::Metadata(folder.fold_id(id), folder.fold_literal(value)),
+    }
+}
+
+pub fn fold_model_param_def<F: Fold + ?Sized>(folder: &mut F, param: ModelParamDefinition) -> ModelParamDefinition {
+    match param {
+        ModelParamDefinition::Generic { id, constraint_type } => ModelParamDefinition::Generic {
+            id: folder.fold_id(id),
+            constraint_type: constraint_type.map(|item_type| folder.fold_item_type(item_type)),
+        },
+        ModelParamDefinition::Metadata { id, type_id, def_value } => ModelParamDefinition::Metadata {
+            id: folder.fold_id(id),
+            type_id: folder.fold_item_type(type_id),
+            def_value: def_value.map(|value| folder.fold_literal(value)),
+        },
+        ModelParamDefinition::Constraint { id, constraint: GenericConstraintDefinition::Contains(item_type) } => ModelParamDefinition::Constraint {
+            id: folder.fold_id(id),
+            constraint: GenericConstraintDefinition::Contains(folder.fold_item_type(item_type)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+    use super::*;
+    use crate::ast::Symbol;
+
+    const CODE: &str = "scalar Int
+scalar Uuid
+
+model Id = Uuid
+
+model Base {
+    id: Id
+}
+
+model User {
+    ... Base
+    age: Int?
+    tags: List<Id>
+}";
+
+    /// Declared scalars and every referenced type name, to find the referenced scalars.
+    #[derive(Default)]
+    struct Scalars {
+        declared: BTreeSet<Symbol>,
+        used: BTreeSet<Symbol>,
+    }
+
+    impl<'ast> Visitor<'ast> for Scalars {
+        fn visit_model(&mut self, def: &'ast ModelDefinition) {
+            if let ModelDefinition::Scalar(Id::Name(name)) = def {
+                self.declared.insert(name.clone());
+            }
+            walk_model(self, def)
+        }
+
+        fn visit_item_type(&mut self, item_type: &'ast ItemType) {
+            if let ItemType::Model(Id::Name(name), _) = item_type {
+                self.used.insert(name.clone());
+            }
+            walk_item_type(self, item_type)
+        }
+    }
+
+    struct Rename(Symbol, Symbol);
+
+    impl VisitorMut for Rename {
+        fn visit_id_mut(&mut self, id: &mut Id) {
+            if *id == Id::Name(self.0.clone()) {
+                *id = Id::Name(self.1.clone());
+            }
+        }
+    }
+
+    /// Replaces references to aliases with their target and drops the aliases.
+    struct Aliases(HashMap<Symbol, ItemType>);
+
+    impl Fold for Aliases {
+        fn fold_scope(&mut self, scope: Scope) -> Scope {
+            match fold_scope(self, scope) {
+                Scope::Global(items) => Scope::Global(items.into_iter()
+                    .filter(|item| !matches!(item, Scope::Model(ModelDefinition::Alias(..))))
+                    .collect()),
+                scope => scope,
+            }
+        }
+
+        fn fold_item_type(&mut self, item_type: ItemType) -> ItemType {
+            match item_type {
+                ItemType::Model(Id::Name(name), params) if params.is_empty() && self.0.contains_key(&name) => {
+                    self.0[&name].clone()
+                },
+                item_type => fold_item_type(self, item_type),
+            }
+        }
+    }
+
+    fn names(names: &[&str]) -> BTreeSet<Symbol> {
+        names.iter().map(|name| Symbol::intern(name)).collect()
+    }
+
+    #[test]
+    fn check_visitor() {
+        let scope = Scope::parse(CODE).unwrap();
+        let mut scalars = Scalars::default();
+        scalars.visit_scope(&scope);
+
+        assert_eq!(scalars.declared, names(&["Int", "Uuid"]));
+        assert_eq!(&scalars.used & &scalars.declared, names(&["Int", "Uuid"]));
+        assert_eq!(scalars.used, names(&["Base", "Id", "Int", "List", "Uuid"]));
+    }
+
+    #[test]
+    fn check_visitor_mut() {
+        let mut scope = Scope::parse(CODE).unwrap();
+        Rename(Symbol::intern("Id"), Symbol::intern("UserId")).visit_scope_mut(&mut scope);
+
+        assert_eq!(scope, Scope::parse(&CODE.replace("Id", "UserId")).unwrap());
+    }
+
+    #[test]
+    fn check_fold() {
+        let scope = Scope::parse(CODE).unwrap();
+        let uuid = ItemType::Model(Id::Name(Symbol::intern("Uuid")), vec![]);
+        let scope = Aliases(HashMap::from([(Symbol::intern("Id"), uuid)])).fold_scope(scope);
+
+        let expected = "scalar Int\nscalar Uuid\n\nmodel Base {\n    id: Uuid\n}\n\nmodel User {\n    ... Base\n    age: Int?\n    tags: List<Uuid>\n}";
+        assert_eq!(scope, Scope::parse(expected).unwrap());
+    }
+}
@@ -0,0 +1,40 @@
+use std::io::{BufReader, Write};
+use std::process::ExitCode;
+use mex_lang::lsp::{read_message, write_message, Server, Value};
+
+/// Language server for Mex sources over stdio.
+fn main() -> ExitCode {
+    let mut input = BufReader::new(std::io::stdin().lock());
+    let mut output = std::io::stdout().lock();
+    let mut server = Server::new();
+
+    loop {
+        let body = match read_message(&mut input) {
+            Ok(Some(body)) => body,
+            Ok(None) => break,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                return ExitCode::FAILURE;
+            },
+        };
+        let message = match Value::parse(&body) {
+            Ok(message) => message,
+            Err(error) => {
+                eprintln!("error: invalid message: {}", error);
+                continue;
+            },
+        };
+
+        for reply in server.handle(&message) {
+            if let Err(error) = write_message(&mut output, &reply) {
+                eprintln!("error: {}", error);
+                return ExitCode::FAILURE;
+            }
+        }
+        if let Some(code) = server.exit_code() {
+            let _ = output.flush();
+            return ExitCode::from(code as u8);
+        }
+    }
+    ExitCode::FAILURE
+}
@@ -1,16 +1,358 @@
-use mex_lang::ast::{Scope, Source};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use mex_lang::Compiler;
-use mex_lang::transform::{ConsoleRender, MexLangTransformer};
+use mex_lang::ast::{Error, Scope, Source};
+use mex_lang::formatter::Formatter;
+use mex_lang::json::AstJson;
+use mex_lang::manifest::Manifest;
+use mex_lang::transform::{FilesRender, StringRender, Style, Target, TemplateTransformer, TextToken, TransformError};
+use mex_lang::transform::template::Templates;
 
-fn main() {
+const USAGE: &str = "usage: mexc fmt [--check] [--width <n>] [<path>...]
+       mexc ast [--format json] [--spans] [--references] [--spreads] [--compact] [<file>]
+       mexc gen --templates <dir> [--file-name <template>] [--out <dir>] [<file>]
+
+`fmt` formats Mex sources in place. Directories are searched for `.mex` files;
+without paths the code is read from stdin and written to stdout. The style
+is read from the `[format]` section of the nearest `mex.toml`.
+
+options:
+    --check        do not write, list unformatted files and exit with 1
+    --width <n>    line width long tuples are wrapped at, overrides `mex.toml`
+
+`ast` prints the syntax tree of a file, or of stdin without a file.
+
+options:
+    --format json  output format, JSON is the only one so far
+    --spans        include the source span of every node
+    --references   include the model every type reference resolves to
+    --spreads      include the fields of records with spreads expanded
+    --compact      print the JSON on a single line
+
+`gen` renders the templates of a directory over the models of a file, or of
+stdin without a file. Each template is named after its file without the
+extension, see `TemplateTransformer` for the `package` and `model` templates.
+
+options:
+    --templates <dir>        directory of the templates
+    --file-name <template>   template of the file each package is written to,
+                             e.g. `{{ package.path | join(\"/\") }}/models.ts`;
+                             without it the output is printed
+    --out <dir>              directory the files are written to, `.` by default";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    match args.split_first() {
+        Some((command, args)) if command == "fmt" => fmt(args),
+        Some((command, args)) if command == "ast" => ast(args),
+        Some((command, args)) if command == "gen" => gen(args),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn fmt(args: &[String]) -> ExitCode {
+    let mut check = false;
+    let mut width = None;
+    let mut paths = vec![];
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--width" => match args.next().and_then(|width| width.parse().ok()) {
+                Some(value) => width = Some(value),
+                None => {
+                    eprintln!("`--width` expects a number\n\n{}", USAGE);
+                    return ExitCode::from(2);
+                },
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("unknown option `{}`\n\n{}", arg, USAGE);
+                return ExitCode::from(2);
+            },
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let mut styles = Styles { width, manifests: HashMap::new() };
+
+    if paths.is_empty() {
+        let formatter = match styles.formatter(Path::new(".")) {
+            Ok(formatter) => formatter,
+            Err(error) => {
+                eprintln!("error: {}", describe(&error));
+                return ExitCode::FAILURE;
+            },
+        };
+        let mut code = String::new();
+        if let Err(error) = std::io::stdin().read_to_string(&mut code) {
+            eprintln!("error: stdin: {}", error);
+            return ExitCode::FAILURE;
+        }
+        return match formatter.format(&code) {
+            Ok(formatted) if check => match formatted == code {
+                true => ExitCode::SUCCESS,
+                false => {
+                    println!("<stdin>");
+                    ExitCode::FAILURE
+                }
+            },
+            Ok(formatted) => {
+                print!("{}", formatted);
+                ExitCode::SUCCESS
+            },
+            Err(error) => {
+                eprintln!("error: <stdin>: {}", describe(&error));
+                ExitCode::FAILURE
+            },
+        };
+    }
+
+    let mut files = vec![];
+    for path in &paths {
+        if let Err(error) = collect(path, &mut files) {
+            eprintln!("error: {}: {}", path.display(), error);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let mut failed = false;
+    for file in &files {
+        let dir = file.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let result = styles.formatter(dir).and_then(|formatter| {
+            let code = std::fs::read_to_string(file)?;
+            formatter.format(&code).map(|formatted| (code, formatted))
+        });
+
+        match result {
+            Ok((code, formatted)) if code == formatted => {},
+            Ok(_) if check => {
+                println!("{}", file.display());
+                failed = true;
+            },
+            Ok((_, formatted)) => {
+                if let Err(error) = std::fs::write(file, formatted) {
+                    eprintln!("error: {}: {}", file.display(), error);
+                    failed = true;
+                }
+            },
+            Err(error) => {
+                eprintln!("error: {}: {}", file.display(), describe(&error));
+                failed = true;
+            },
+        }
+    }
+
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+fn ast(args: &[String]) -> ExitCode {
+    let mut json = AstJson::new();
+    let mut file = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("json") => {},
+                _ => {
+                    eprintln!("`--format` expects `json`\n\n{}", USAGE);
+                    return ExitCode::from(2);
+                },
+            },
+            "--spans" => json = json.with_spans(),
+            "--references" => json = json.with_references(),
+            "--spreads" => json = json.with_spreads(),
+            "--compact" => json = json.with_compact(),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            },
+            _ if arg.starts_with('-') || file.is_some() => {
+                eprintln!("unexpected argument `{}`\n\n{}", arg, USAGE);
+                return ExitCode::from(2);
+            },
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let (name, code) = match &file {
+        Some(file) => (file.display().to_string(), std::fs::read_to_string(file)),
+        None => {
+            let mut code = String::new();
+            ("<stdin>".to_string(), std::io::stdin().read_to_string(&mut code).map(|_| code))
+        },
+    };
+
+    match code.map_err(Error::Io).and_then(|code| json.to_json(&code)) {
+        Ok(json) => {
+            println!("{}", json);
+            ExitCode::SUCCESS
+        },
+        Err(error) => {
+            eprintln!("error: {}: {}", name, describe(&error));
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn gen(args: &[String]) -> ExitCode {
+    let mut templates = None;
+    let mut file_name = None;
+    let mut out = PathBuf::from(".");
+    let mut file = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--templates" | "--file-name" | "--out" => match args.next() {
+                Some(value) if arg == "--templates" => templates = Some(PathBuf::from(value)),
+                Some(value) if arg == "--file-name" => file_name = Some(value.clone()),
+                Some(value) => out = PathBuf::from(value),
+                None => {
+                    eprintln!("`{}` expects a value\n\n{}", arg, USAGE);
+                    return ExitCode::from(2);
+                },
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            },
+            _ if arg.starts_with('-') || file.is_some() => {
+                eprintln!("unexpected argument `{}`\n\n{}", arg, USAGE);
+                return ExitCode::from(2);
+            },
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let Some(templates) = templates else {
+        eprintln!("`--templates` is required\n\n{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let templates = match Templates::load(&templates) {
+        Ok(templates) => templates,
+        Err(error) => {
+            eprintln!("error: {}: {}", templates.display(), describe(&error));
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let (name, code) = match &file {
+        Some(file) => (file.display().to_string(), std::fs::read_to_string(file)),
+        None => {
+            let mut code = String::new();
+            ("<stdin>".to_string(), std::io::stdin().read_to_string(&mut code).map(|_| code))
+        },
+    };
+    let code = match code {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("error: {}: {}", name, error);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let result = match &file_name {
+        Some(file_name) => {
+            let render = FilesRender::new();
+            generate(&code, templates, Some(file_name), &render).and_then(|_| Ok(render.write_to(&out, 4)?))
+        },
+        None => {
+            let render = StringRender::new();
+            generate(&code, templates, None, &render).map(|_| println!("{}", render.as_string(4)))
+        },
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}: {}", name, describe(&error));
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn generate<R: Target<TextToken>>(code: &str, templates: Templates, file_name: Option<&str>, render: &R) -> Result<(), Error<'static>> {
     let global = Scope::Global(vec![]).into();
-    let source = Source::from_file("examples/empty_package.mex");
-    let compiler = Compiler::new(&source, &global).unwrap();
-    let ast = compiler.make_ast().unwrap();
+    let source = Source::from_str(code);
+    let compiler = Compiler::new(&source, &global)
+        .map_err(|error| Error::Transform(vec![TransformError::Parsing(describe(&error))]))?;
+    let ast = compiler.make_checked_ast()?;
+
+    let transformer = TemplateTransformer::new(templates);
+    match file_name {
+        Some(file_name) => transformer.with_file_name(file_name).apply(&ast, render),
+        None => transformer.apply(&ast, render),
+    }
+}
+
+/// Formatting styles of the manifests found so far.
+struct Styles {
+    width: Option<usize>,
+    manifests: HashMap<PathBuf, Style>,
+}
+
+impl Styles {
+    fn formatter(&mut self, dir: &Path) -> Result<Formatter, Error<'static>> {
+        let dir = dir.canonicalize()?;
+        let mut style = match Manifest::find(&dir) {
+            Some(path) => match self.manifests.get(&path) {
+                Some(style) => style.clone(),
+                None => {
+                    let manifest = Manifest::load(&path)?;
+                    let style = Style::from_manifest(&manifest).map_err(|error| Error::Transform(vec![error]))?;
+                    self.manifests.insert(path, style.clone());
+                    style
+                },
+            },
+            None => Style::default(),
+        };
+
+        if let Some(width) = self.width {
+            style.width = width;
+        }
+        Ok(Formatter::new().with_style(style))
+    }
+}
+
+/// Adds the file, or the `.mex` files under the directory in a stable order.
+fn collect(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
 
-    println!("global: {:?}", global);
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "mex") {
+            collect(&entry, files)?;
+        }
+    }
+    Ok(())
+}
 
-    let render = ConsoleRender::new(4);
-    let transformer = MexLangTransformer::new();
-    transformer.apply(&ast, &render);
-}
\ No newline at end of file
+fn describe(error: &Error) -> String {
+    match error {
+        Error::Io(error) => error.to_string(),
+        Error::Parsing(error) => format!("syntax error: {:?}", error),
+        Error::Transform(errors) => errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "),
+    }
+}
@@ -0,0 +1,443 @@
+use std::cell::RefCell;
+use std::ops::Range;
+use logos::Logos;
+use crate::ast::{Scope, Source};
+use crate::lexer::Token;
+use crate::transform::{Indent, Layout, MexLangTransformer, Style, Target, TextToken};
+use crate::{syntax_error, Compiler};
+
+/// Canonical Mex formatter.
+///
+/// The code is reprinted by `MexLangTransformer`, then the `//` comments and blank lines of the
+/// source are put back. The lexer skips both, so they are taken from the gaps between the source
+/// tokens and anchored to the token that follows (or, on the same line, precedes) them. Separators
+/// (`,` and `;`) are optional in the grammar and are left to the printer and its `Style`.
+/// A tuple with comments between its items is always wrapped, one item per line.
+///
+/// Formatting is idempotent: formatting the output again yields the same text.
+pub struct Formatter {
+    style: Style,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Formatter {
+    pub fn new() -> Self {
+        Formatter {
+            style: Style::default(),
+        }
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_indent(mut self, indent: usize) -> Self {
+        self.style.indent = Indent::Spaces(indent);
+        self
+    }
+
+    /// Line width long tuples are wrapped at.
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.style.width = width;
+        self
+    }
+
+    pub fn format(&self, code: &str) -> crate::Result<'static, String> {
+        let source = tokens(code)?;
+        let mut gaps = vec![];
+        let mut start = 0;
+        for (_, span) in &source {
+            gaps.push(Gap::parse(&code[start..span.start], start > 0));
+            start = span.end;
+        }
+        gaps.push(Gap::parse(&code[start..], start > 0));
+
+        let printed = self.print(code, &gaps)?;
+        let target = tokens(&printed)?;
+        if source.len() != target.len() || source.iter().zip(&target).any(|((a, _), (b, _))| a != b) {
+            return Err(syntax_error("printed code does not match the source"));
+        }
+
+        let unit = match self.style.indent {
+            Indent::Spaces(count) => " ".repeat(count),
+            Indent::Tabs => "\t".to_string(),
+        };
+        let mut output = Output {
+            max_blank: self.style.blank_lines.max(1),
+            ..Output::default()
+        };
+        let mut token = 0;
+        let mut offset = 0;
+        let mut blank = 0;
+
+        for line in printed.split('\n') {
+            let end = offset + line.len();
+            let text = line.trim_start();
+            offset = end + 1;
+
+            if text.is_empty() {
+                blank += 1;
+                continue;
+            }
+
+            output.blank(blank);
+            blank = 0;
+
+            let indent = &line[..line.len() - text.len()];
+            let indent = match text.starts_with(['}', ')']) {
+                true => format!("{}{}", indent, unit),
+                false => indent.to_string(),
+            };
+
+            let first = token;
+            let mut trailing = None;
+            while token < target.len() && target[token].1.start < end {
+                if let Some(comment) = trailing.take() {
+                    output.comment(&indent, comment);
+                }
+                for trivia in &gaps[token].leading {
+                    match trivia {
+                        Trivia::Blank(count) if token == first => output.blank(*count),
+                        Trivia::Blank(_) => {},
+                        Trivia::Comment(comment) => output.comment(&indent, comment),
+                    }
+                }
+                trailing = gaps[token + 1].trailing;
+                token += 1;
+            }
+
+            output.code(line, trailing);
+        }
+
+        for trivia in &gaps[source.len()].leading {
+            match trivia {
+                Trivia::Blank(count) => output.blank(*count),
+                Trivia::Comment(comment) => output.comment("", comment),
+            }
+        }
+
+        Ok(output.lines.join("\n") + "\n")
+    }
+
+    /// Prints the code, breaking every group that has comments between its tokens so that
+    /// each comment stays on the line of its item.
+    fn print(&self, code: &str, gaps: &[Gap]) -> crate::Result<'static, String> {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).map_err(|e| syntax_error(format!("{:?}", e)))?;
+        let ast = compiler.make_checked_ast()?;
+
+        let render = Tokens::default();
+        MexLangTransformer::new().with_style(self.style.clone()).apply(&ast, &render)?;
+        let printed = render.0.take();
+
+        // Groups are dropped by their position, along with the source tokens they hold.
+        let mut broken = vec![];
+        let mut groups = vec![];
+        let mut count = 0;
+        for (i, token) in printed.iter().enumerate() {
+            match token {
+                TextToken::Text(text) => count += tokens(text).map(|tokens| tokens.len()).unwrap_or(0),
+                TextToken::BeginGroup => groups.push((i, count)),
+                TextToken::EndGroup => {
+                    let Some((begin, first)) = groups.pop() else { continue };
+                    let commented = gaps.get(first..count).unwrap_or_default().iter()
+                        .any(|gap| gap.trailing.is_some() || gap.leading.iter().any(|t| matches!(t, Trivia::Comment(_))));
+                    if commented {
+                        broken.extend([begin, i]);
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        let printed = printed.into_iter().enumerate()
+            .filter(|(i, _)| !broken.contains(i))
+            .map(|(_, token)| token)
+            .collect::<Vec<_>>();
+        let lines = Layout::new(self.style.indent_count(), self.style.width)
+            .with_tabs(self.style.indent == Indent::Tabs)
+            .lines(&printed);
+        Ok(lines.join("\n"))
+    }
+}
+
+/// Printed tokens, before they are laid out.
+#[derive(Default)]
+struct Tokens(RefCell<Vec<TextToken>>);
+
+impl Target<TextToken> for Tokens {
+    fn render(&self, token: TextToken) {
+        self.0.borrow_mut().push(token);
+    }
+}
+
+/// Significant tokens of the code with their spans; separators are skipped.
+fn tokens(code: &str) -> crate::Result<'static, Vec<(Token<'_>, Range<usize>)>> {
+    let mut tokens = vec![];
+    for (token, span) in Token::lexer(code).spanned() {
+        match token {
+            Ok(Token::Comma | Token::Semicolon) => {},
+            Ok(token) => tokens.push((token, span)),
+            Err(error) => return Err(syntax_error(format!("{:?} at {}", error, span.start))),
+        }
+    }
+    Ok(tokens)
+}
+
+enum Trivia<'a> {
+    /// Consecutive blank lines.
+    Blank(usize),
+    Comment(&'a str),
+}
+
+/// Comments and blank lines between two tokens.
+struct Gap<'a> {
+    /// Comment on the line of the previous token.
+    trailing: Option<&'a str>,
+    /// Comments and blank lines on their own lines before the next token.
+    leading: Vec<Trivia<'a>>,
+}
+
+impl<'a> Gap<'a> {
+    fn parse(text: &'a str, after_token: bool) -> Self {
+        let mut trailing = None;
+        let mut leading = vec![];
+        let lines = text.split('\n').collect::<Vec<_>>();
+
+        for (i, line) in lines.iter().enumerate() {
+            let comment = line.find("//").map(|start| line[start..].trim_end());
+            let is_last = i + 1 == lines.len();
+
+            match comment {
+                Some(comment) if i == 0 && after_token => trailing = Some(comment),
+                Some(comment) => leading.push(Trivia::Comment(comment)),
+                None if i > 0 && !is_last && line.trim().is_empty() => match leading.last_mut() {
+                    Some(Trivia::Blank(count)) => *count += 1,
+                    _ => leading.push(Trivia::Blank(1)),
+                },
+                None => {},
+            }
+        }
+
+        Gap { trailing, leading }
+    }
+}
+
+#[derive(Default)]
+struct Output {
+    lines: Vec<String>,
+    /// Blank lines before the next line, from the printer or the source, whichever has more.
+    blank: usize,
+    max_blank: usize,
+    opened: bool,
+}
+
+impl Output {
+    fn blank(&mut self, count: usize) {
+        if !self.lines.is_empty() && !self.opened {
+            self.blank = self.blank.max(count.min(self.max_blank));
+        }
+    }
+
+    fn comment(&mut self, indent: &str, comment: &str) {
+        self.push(format!("{}{}", indent, comment));
+        self.opened = false;
+    }
+
+    fn code(&mut self, line: &str, comment: Option<&str>) {
+        if line.trim_start().starts_with(['}', ')']) {
+            self.blank = 0;
+        }
+        match comment {
+            Some(comment) => self.push(format!("{} {}", line, comment)),
+            None => self.push(line.to_string()),
+        }
+        self.opened = line.ends_with(['{', '(']);
+    }
+
+    fn push(&mut self, line: String) {
+        for _ in 0..self.blank {
+            self.lines.push(String::new());
+        }
+        self.blank = 0;
+        self.lines.push(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::ast::Error;
+    use crate::transform::{Semicolons, Separator};
+
+    #[test_case("model Point(Int,Int);", "model Point(Int, Int)\n"; "canonical spacing")]
+    #[test_case("scalar Int\nscalar Long;", "scalar Int;\nscalar Long;\n"; "scalars")]
+    #[test_case("model A {\n  x: Int,\n  y: Int,\n}", "model A {\n    x: Int\n    y: Int\n}\n"; "record separators")]
+    #[test_case("model A {}\nmodel B {}", "model A {\n}\n\nmodel B {\n}\n"; "blank line after block")]
+    #[test_case("scalar A;\n\n\n\nscalar B;", "scalar A;\n\nscalar B;\n"; "blank lines collapsed")]
+    #[test_case("model A {\n\n    x: Int\n\n    y: Int\n\n}", "model A {\n    x: Int\n\n    y: Int\n}\n"; "blank lines inside block")]
+    #[test_case("// header\n\n// about A\nscalar A; // trailing\n", "// header\n\n// about A\nscalar A; // trailing\n"; "comments")]
+    #[test_case("model A {\n    x: Int // x\n    // end\n}", "model A {\n    x: Int // x\n    // end\n}\n"; "comment before brace")]
+    #[test_case("model A(\n    x: Int, // x\n    y: Int\n)", "model A(\n    x: Int, // x\n    y: Int\n)\n"; "comment keeps tuple wrapped")]
+    #[test_case("model HashedPassword(value: String[len=32], salt: String[len=5], pepper: String[len=16], rounds: Int)", "model HashedPassword(\n    value: String[len=32],\n    salt: String[len=5],\n    pepper: String[len=16],\n    rounds: Int\n)\n"; "long tuple wrapped")]
+    #[test_case("model A(\n    // x\n    x: Int, // first\n\n    y: (Int, Int) // y\n)", "model A(\n    // x\n    x: Int, // first\n\n    y: (Int, Int) // y\n)\n"; "comments keep short tuple wrapped")]
+    #[test_case("model A(\n    x: Int,\n    y: Int\n) // end", "model A(x: Int, y: Int) // end\n"; "short tuple joined")]
+    #[test_case("package a {\n    /// Doc\n    model A(Int)\n}\n// end", "package a {\n    /// Doc\n    model A(Int)\n}\n// end\n"; "nested package")]
+    fn check(code: &str, expected: &str) {
+        let formatter = Formatter::new();
+        let result = formatter.format(code).unwrap();
+        assert_eq!(result, expected);
+
+        let again = formatter.format(&result).unwrap();
+        assert_eq!(again, result, "formatting is not idempotent");
+    }
+
+    #[test_case("separator = \"comma\"\ntrailing_commas = true", "model A {\n    x: Int\n    y: Int\n}", "model A {\n    x: Int,\n    y: Int,\n}\n"; "trailing commas")]
+    #[test_case("separator = \"comma\"", "model E enum {\n    A\n    B { x: Int }\n    C(Int)\n}", "model E enum {\n    A,\n    B {\n        x: Int\n    },\n    C(Int)\n}\n"; "comma separators")]
+    #[test_case("indent = \"tab\"", "model A {\n  x: Int // x\n}", "model A {\n\tx: Int // x\n}\n"; "tabs")]
+    #[test_case("semicolons = \"always\"", "model P(Int)\nscalar S", "model P(Int);\nscalar S;\n"; "semicolons always")]
+    #[test_case("semicolons = \"never\"", "model P(Int);\nscalar S;", "model P(Int)\nscalar S\n"; "semicolons never")]
+    #[test_case("blank_lines = 2", "model A {}\n\n\n\n// B\nmodel B {}", "model A {\n}\n\n\n// B\nmodel B {\n}\n"; "two blank lines")]
+    #[test_case("blank_lines = 0", "model A {}\nmodel B {}", "model A {\n}\nmodel B {\n}\n"; "no blank lines")]
+    fn check_style(format: &str, code: &str, expected: &str) {
+        let manifest = format!("[format]\n{}", format).parse().unwrap();
+        let formatter = Formatter::new().with_style(Style::from_manifest(&manifest).unwrap());
+        let result = formatter.format(code).unwrap();
+        assert_eq!(result, expected);
+
+        let again = formatter.format(&result).unwrap();
+        assert_eq!(again, result, "formatting is not idempotent");
+    }
+
+    #[test_case("indent = \"wide\""; "invalid indent")]
+    #[test_case("trailing_commas = yes"; "invalid flag")]
+    #[test_case("line_width = 80"; "unknown option")]
+    fn check_style_errors(format: &str) {
+        let manifest = format!("[format]\n{}", format).parse().unwrap();
+        assert!(Style::from_manifest(&manifest).is_err());
+    }
+
+    #[test_case("model A {"; "unclosed block")]
+    #[test_case("model A # B"; "invalid token")]
+    fn check_errors(code: &str) {
+        assert!(Formatter::new().format(code).is_err());
+    }
+
+    /// Styles the corpus is printed in.
+    fn styles() -> Vec<Style> {
+        let commas = Style {
+            separator: Separator::Comma,
+            trailing_commas: true,
+            semicolons: Semicolons::Always,
+            ..Style::default()
+        };
+        let compact = Style {
+            indent: Indent::Tabs,
+            width: 20,
+            semicolons: Semicolons::Never,
+            blank_lines: 0,
+            ..Style::default()
+        };
+        vec![Style::default(), commas, compact]
+    }
+
+    /// Models over the type forms of the grammar in fields, tuple items, variants and aliases.
+    fn generated() -> Vec<String> {
+        let tuple_types = [
+            "Int", "Int?", "List<Int>", "Map<String, List<Int>>", "String[len=32]",
+            "Dic<Int, String>[min=1, max=2]", "()", "(Int, x: Int?)", "((Int, Int), Int)?",
+        ];
+        let field_types = tuple_types.iter().copied().chain([
+            "{ x: Int, ... Base }", "Point { x: Int }?", "Pair(Int, Int)",
+            "enum { A, B(Int), C { x: Int }, D enum { E } }", "Kind enum { A, B() }",
+        ]);
+
+        let mut cases = vec![];
+        for item in field_types {
+            cases.push(format!("model M {{ f: {}, ... Base<Int>, g: Int }}", item));
+            cases.push(format!("fragment F<T, K: Int> {{ ... Base, f: {} }}", item));
+            if !item.ends_with('?') {
+                cases.push(format!("model A<T>[n: Int = 1] = {}", item));
+            }
+        }
+        for item in tuple_types {
+            cases.push(format!("model T[n: Int = 1](a: {}, {})", item, item));
+            cases.push(format!("package p {{ model E enum {{ V({}), R {{ f: {} }} W }} }}", item, item));
+        }
+        cases
+    }
+
+    fn check_round_trip(name: &str, code: &str) {
+        for style in styles() {
+            let formatter = Formatter::new().with_style(style.clone());
+            let formatted = formatter.format(code)
+                .unwrap_or_else(|error| panic!("{} in {:?}: {:?}", name, style, error));
+
+            let global = Scope::Global(vec![]).into();
+            let source = Source::from_str(code);
+            let compiler = Compiler::new(&source, &global).unwrap();
+            let expected = compiler.make_ast().unwrap();
+
+            let global = Scope::Global(vec![]).into();
+            let source = Source::from_str(&formatted);
+            let compiler = Compiler::new(&source, &global).unwrap();
+            let result = compiler.make_ast().unwrap();
+
+            assert_eq!(result, expected, "{} in {:?}:\n{}", name, style, formatted);
+            assert_eq!(formatter.format(&formatted).unwrap(), formatted, "{} in {:?} is not idempotent", name, style);
+        }
+    }
+
+    /// Examples sketching syntax the grammar does not support, see the README. They are left out
+    /// of the round trip and must be rejected with a message pointing at that syntax.
+    const UNSUPPORTED_EXAMPLES: [(&str, &str); 2] = [
+        ("common.mex", "23:43: tuple item `salt` needs a `:` before its type"),
+        ("empty_package.mex", "9:1: unexpected `enum`, declare enums as `model Name enum { ... }`"),
+    ];
+
+    #[test]
+    fn check_examples() {
+        let dir = format!("{}/examples", env!("CARGO_MANIFEST_DIR"));
+        let mut files = std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+        files.sort();
+
+        let mut round_tripped = 0;
+        for path in files.iter().filter(|path| path.extension().is_some_and(|ext| ext == "mex")) {
+            let file = path.file_name().unwrap().to_string_lossy();
+            let code = std::fs::read_to_string(path).unwrap();
+
+            match UNSUPPORTED_EXAMPLES.iter().find(|(name, _)| *name == file) {
+                Some((_, expected)) => for style in styles() {
+                    match Formatter::new().with_style(style).format(&code) {
+                        Err(Error::Transform(errors)) => assert_eq!(errors[0].to_string(), format!("syntax error: {}", expected)),
+                        result => panic!("{} is not rejected: {:?}", file, result),
+                    }
+                },
+                None => {
+                    check_round_trip(&file, &code);
+                    round_tripped += 1;
+                },
+            }
+        }
+
+        for (name, _) in UNSUPPORTED_EXAMPLES {
+            assert!(files.iter().any(|path| path.ends_with(name)), "{} is not in examples/", name);
+        }
+        assert!(round_tripped > 0, "no example is round-tripped");
+    }
+
+    #[test]
+    fn check_generated() {
+        for code in generated() {
+            check_round_trip(&code, &code);
+        }
+    }
+}
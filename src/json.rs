@@ -0,0 +1,598 @@
+use std::cell::RefCell;
+use std::ops::Range;
+use logos::Logos;
+use crate::ast::*;
+use crate::lexer::Token;
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex};
+use crate::{syntax_error, Compiler};
+
+/// Version of the JSON representation, bumped on every incompatible change.
+pub const AST_JSON_VERSION: u32 = 1;
+
+/// JSON representation of a parsed Mex source, for tools outside Rust.
+///
+/// The document is `{"version": 1, "root": <scope>}`. Every node is an object with a `kind`:
+///
+/// - scopes: `global` (`items`), `package` (`name`, `items`);
+/// - models: `record` and `fragment` (`fields`), `tuple` (`items`), `enum` (`variants`),
+///   `alias` (`target`) and `scalar`, all with `name` (null when inline) and `params`;
+/// - fields: `field` (`name`, `type`) and `spread` (`type`); tuple items have `name` and `type`;
+/// - variants: `unit`, `record` (`fields`), `tuple` (`items`) and `enum` (`variants`), with `name`;
+/// - params: `generic` (`name`, `constraint`) and `metadata` (`name`, `type`, `default`);
+/// - types: `ref` (`name`, `args`, `metadata`, and `generic: true` for a generic parameter),
+///   `optional` (`type`) and `inline` (`model`);
+/// - literals: `string` and `number`, both with a string `value`.
+///
+/// Optionally nodes carry a `span` (`start` and `end` byte offsets, 1-based `line` and `column`),
+/// `ref` types the dotted path of the referenced model in `resolves` (null when unknown), and
+/// records and fragments the `expanded` fields with spreads inlined and generic arguments bound
+/// (null when a spread cannot be expanded).
+pub struct AstJson {
+    spans: bool,
+    references: bool,
+    spreads: bool,
+    compact: bool,
+}
+
+impl Default for AstJson {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AstJson {
+    pub fn new() -> Self {
+        AstJson {
+            spans: false,
+            references: false,
+            spreads: false,
+            compact: false,
+        }
+    }
+
+    pub fn with_spans(mut self) -> Self {
+        self.spans = true;
+        self
+    }
+
+    pub fn with_references(mut self) -> Self {
+        self.references = true;
+        self
+    }
+
+    pub fn with_spreads(mut self) -> Self {
+        self.spreads = true;
+        self
+    }
+
+    /// Writes the document on a single line.
+    pub fn with_compact(mut self) -> Self {
+        self.compact = true;
+        self
+    }
+
+    pub fn to_json(&self, code: &str) -> crate::Result<'static, String> {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).map_err(|e| syntax_error(format!("{:?}", e)))?;
+        let ast = compiler.make_checked_ast()?;
+
+        Ok(self.write(&ast, Cursor::new(code)))
+    }
+
+    /// Document of a tree that was not parsed from source, such as an owned one, without spans.
+    pub fn scope_to_json(&self, ast: &RefScope) -> String {
+        let mut cursor = Cursor::new("");
+        cursor.enabled = false;
+        self.write(ast, cursor)
+    }
+
+    fn write(&self, ast: &RefScope, cursor: Cursor) -> String {
+        let builder = Builder {
+            options: self,
+            index: ModelIndex::new(ast),
+            cursor: RefCell::new(cursor),
+            path: RefCell::new(vec![]),
+            generics: RefCell::new(vec![]),
+        };
+
+        let json = Json::Object(vec![
+            ("version", Json::Number(AST_JSON_VERSION as usize)),
+            ("root", builder.scope(ast, true)),
+        ]);
+
+        let mut out = String::new();
+        json.write(&mut out, if self.compact { None } else { Some(0) });
+        out
+    }
+}
+
+enum Json {
+    Null,
+    Bool(bool),
+    Number(usize),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn str<S: Into<String>>(value: S) -> Self {
+        Json::Str(value.into())
+    }
+
+    fn name(id: &Id) -> Self {
+        id.as_name().map(Json::str).unwrap_or(Json::Null)
+    }
+
+    fn literal(literal: &Literal) -> Self {
+        let (kind, value) = match literal {
+            Literal::String(value) => ("string", value),
+            Literal::Number(value) => ("number", value),
+        };
+        Json::Object(vec![("kind", Json::str(kind)), ("value", Json::str(*value))])
+    }
+
+    /// Writes the value pretty-printed at `indent` levels, or on one line without an indent.
+    fn write(&self, out: &mut String, indent: Option<usize>) {
+        let (items, open, close) = match self {
+            Json::Null => return out.push_str("null"),
+            Json::Bool(value) => return out.push_str(&value.to_string()),
+            Json::Number(value) => return out.push_str(&value.to_string()),
+            Json::Str(value) => return Self::write_str(out, value),
+            Json::Array(items) => (items.iter().map(|item| (None, item)).collect::<Vec<_>>(), '[', ']'),
+            Json::Object(entries) => (entries.iter().map(|(key, value)| (Some(*key), value)).collect(), '{', '}'),
+        };
+
+        out.push(open);
+        for (i, (key, value)) in items.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            if let Some(indent) = indent {
+                out.push('\n');
+                out.push_str(&"  ".repeat(indent + 1));
+            }
+            if let Some(key) = key {
+                Self::write_str(out, key);
+                out.push_str(if indent.is_some() { ": " } else { ":" });
+            }
+            value.write(out, indent.map(|indent| indent + 1));
+        }
+        if let (Some(indent), false) = (indent, items.is_empty()) {
+            out.push('\n');
+            out.push_str(&"  ".repeat(indent));
+        }
+        out.push(close);
+    }
+
+    fn write_str(out: &mut String, value: &str) {
+        out.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+/// Walks the source tokens along the tree to find the span of every node.
+///
+/// Separators are optional in the grammar and never start or end a node, so they are skipped.
+struct Cursor {
+    tokens: Vec<Range<usize>>,
+    lines: Vec<usize>,
+    pos: usize,
+    end: usize,
+    /// Off while walking nodes that are not in the source, such as expanded spreads.
+    enabled: bool,
+}
+
+impl Cursor {
+    fn new(code: &str) -> Self {
+        let tokens = Token::lexer(code).spanned()
+            .filter(|(token, _)| !matches!(token, Ok(Token::Comma | Token::Semicolon)))
+            .map(|(_, span)| span)
+            .collect();
+        let lines = std::iter::once(0)
+            .chain(code.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Cursor { tokens, lines, pos: 0, end: 0, enabled: true }
+    }
+
+    fn start(&self) -> usize {
+        self.tokens.get(self.pos).map(|span| span.start).unwrap_or(self.end)
+    }
+
+    /// Consumes `count` tokens.
+    fn next(&mut self, count: usize) {
+        if !self.enabled {
+            return;
+        }
+        for _ in 0..count {
+            if let Some(span) = self.tokens.get(self.pos) {
+                self.end = span.end;
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn span(&self, start: usize) -> Json {
+        let line = self.lines.partition_point(|&offset| offset <= start);
+        let column = start - self.lines[line - 1] + 1;
+        Json::Object(vec![
+            ("start", Json::Number(start)),
+            ("end", Json::Number(self.end)),
+            ("line", Json::Number(line)),
+            ("column", Json::Number(column)),
+        ])
+    }
+}
+
+struct Builder<'a, 'input> {
+    options: &'a AstJson,
+    index: ModelIndex<'input>,
+    cursor: RefCell<Cursor>,
+    path: RefCell<Vec<String>>,
+    generics: RefCell<Vec<Vec<String>>>,
+}
+
+impl Builder<'_, '_> {
+
+    fn start(&self) -> usize {
+        self.cursor.borrow().start()
+    }
+
+    fn next(&self, count: usize) {
+        self.cursor.borrow_mut().next(count);
+    }
+
+    /// Object of a node, with its span from `start` to the last consumed token.
+    fn node(&self, kind: &str, start: usize, mut entries: Vec<(&'static str, Json)>) -> Json {
+        entries.insert(0, ("kind", Json::str(kind)));
+        if self.options.spans && self.cursor.borrow().enabled {
+            entries.push(("span", self.cursor.borrow().span(start)));
+        }
+        Json::Object(entries)
+    }
+
+    fn scope(&self, scope: &RefScope, is_root: bool) -> Json {
+        let start = self.start();
+
+        match **scope.borrow() {
+            Scope::Global(ref items) => {
+                let items = items.iter().map(|item| self.scope(item, false)).collect();
+                Json::Object(vec![("kind", Json::str("global")), ("items", Json::Array(items))])
+            },
+            Scope::Package(ref id, ref items) => {
+                // A root package declares only its header, a nested one spans its block.
+                self.next(2);
+                let header = self.cursor.borrow().span(start);
+
+                self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+                if !is_root {
+                    self.next(1);
+                }
+                let items = items.iter().map(|item| self.scope(item, false)).collect();
+                if !is_root {
+                    self.next(1);
+                }
+                self.path.borrow_mut().pop();
+
+                let mut entries = vec![
+                    ("kind", Json::str("package")),
+                    ("name", Json::name(id)),
+                    ("items", Json::Array(items)),
+                ];
+                if self.options.spans {
+                    entries.push(("span", if is_root { header } else { self.cursor.borrow().span(start) }));
+                }
+                Json::Object(entries)
+            },
+            Scope::Model(ref def) => self.model(def, true),
+            Scope::Error(_) => Json::Null,
+        }
+    }
+
+    fn model(&self, def: &ModelDefinition, keyword: bool) -> Json {
+        let start = self.start();
+        if keyword {
+            self.next(1);
+        }
+
+        let (kind, id) = match def {
+            ModelDefinition::Scalar(id) => ("scalar", id),
+            ModelDefinition::Record(id, _, _) => ("record", id),
+            ModelDefinition::Fragment(id, _, _) => ("fragment", id),
+            ModelDefinition::Tuple(id, _, _) => ("tuple", id),
+            ModelDefinition::Enum(id, _, _) => ("enum", id),
+            ModelDefinition::Alias(id, _, _) => ("alias", id),
+        };
+        if id.as_name().is_some() {
+            self.next(1);
+        }
+
+        let params = model_params_def(def);
+        let mut entries = vec![("name", Json::name(id)), ("params", self.params_def(params))];
+
+        self.generics.borrow_mut().push(generic_names(params));
+        match def {
+            ModelDefinition::Scalar(_) => {},
+            ModelDefinition::Record(_, items, _) | ModelDefinition::Fragment(_, items, _) => {
+                entries.push(("fields", self.fields(items)));
+                if self.options.spreads {
+                    entries.push(("expanded", self.expanded(items)));
+                }
+            },
+            ModelDefinition::Tuple(_, items, _) => entries.push(("items", self.tuple_items(items))),
+            ModelDefinition::Enum(_, items, _) => {
+                self.next(1);
+                entries.push(("variants", self.variants(items)));
+            },
+            ModelDefinition::Alias(_, _, item_type) => {
+                self.next(1);
+                entries.push(("target", self.item_type(item_type, &Bindings::root())));
+            },
+        }
+        self.generics.borrow_mut().pop();
+
+        self.node(kind, start, entries)
+    }
+
+    fn params_def(&self, params: &[ModelParamDefinition]) -> Json {
+        let generics = params.iter().filter(|p| matches!(p, ModelParamDefinition::Generic { .. })).count();
+        let metadata = params.iter().filter(|p| matches!(p, ModelParamDefinition::Metadata { .. })).count();
+        let mut items = vec![];
+
+        for (i, param) in params.iter().enumerate() {
+            // Opening `<` or `[` before the first parameter of a list.
+            if i == 0 || (i == generics && metadata > 0) {
+                self.next(1);
+            }
+
+            let start = self.start();
+            let item = match param {
+                ModelParamDefinition::Generic { id, constraint_type } => {
+                    self.next(1);
+                    let constraint = match constraint_type {
+                        Some(item_type) => {
+                            self.next(1);
+                            self.item_type(item_type, &Bindings::root())
+                        },
+                        None => Json::Null,
+                    };
+                    self.node("generic", start, vec![("name", Json::name(id)), ("constraint", constraint)])
+                },
+                ModelParamDefinition::Metadata { id, type_id, def_value } => {
+                    self.next(2);
+                    let item_type = self.item_type(type_id, &Bindings::root());
+                    let default = match def_value {
+                        Some(value) => {
+                            self.next(2);
+                            Json::literal(value)
+                        },
+                        None => Json::Null,
+                    };
+                    self.node("metadata", start, vec![("name", Json::name(id)), ("type", item_type), ("default", default)])
+                },
+                ModelParamDefinition::Constraint { id, .. } => {
+                    self.node("constraint", start, vec![("name", Json::name(id))])
+                },
+            };
+            items.push(item);
+
+            // Closing `>` or `]` after the last parameter of a list.
+            if i + 1 == generics || i + 1 == params.len() {
+                self.next(1);
+            }
+        }
+
+        Json::Array(items)
+    }
+
+    /// Record fields between braces.
+    fn fields(&self, items: &[RecordItem]) -> Json {
+        self.next(1);
+        let fields = items.iter()
+            .map(|item| {
+                let start = self.start();
+                match item {
+                    RecordItem::Item(id, item_type) => {
+                        self.next(2);
+                        let item_type = self.item_type(item_type, &Bindings::root());
+                        self.node("field", start, vec![("name", Json::name(id)), ("type", item_type)])
+                    },
+                    RecordItem::Spread(item_type) => {
+                        self.next(1);
+                        let item_type = self.item_type(item_type, &Bindings::root());
+                        self.node("spread", start, vec![("type", item_type)])
+                    },
+                }
+            })
+            .collect();
+        self.next(1);
+
+        Json::Array(fields)
+    }
+
+    /// Fields with spreads inlined, or null when a spread cannot be expanded.
+    fn expanded(&self, items: &[RecordItem]) -> Json {
+        let path = self.path.borrow().clone();
+        let mut fields = vec![];
+
+        let enabled = std::mem::replace(&mut self.cursor.borrow_mut().enabled, false);
+        let result = self.index.walk_record(items, &Bindings::root(), &path, &mut |id, item_type, bindings| {
+            fields.push(Json::Object(vec![
+                ("name", Json::name(id)),
+                ("type", self.item_type(item_type, bindings)),
+            ]));
+        });
+        self.cursor.borrow_mut().enabled = enabled;
+
+        match result {
+            Ok(()) => Json::Array(fields),
+            Err(_) => Json::Null,
+        }
+    }
+
+    /// Tuple items between parentheses.
+    fn tuple_items(&self, items: &[TupleItem]) -> Json {
+        self.next(1);
+        let items = items.iter()
+            .map(|item| {
+                let start = self.start();
+                let (id, item_type) = match item {
+                    TupleItem::Item(item_type) => (None, item_type),
+                    TupleItem::NamedItem(id, item_type) => {
+                        self.next(2);
+                        (Some(id), item_type)
+                    },
+                };
+                let item_type = self.item_type(item_type, &Bindings::root());
+                self.node("item", start, vec![("name", id.map(Json::name).unwrap_or(Json::Null)), ("type", item_type)])
+            })
+            .collect();
+        self.next(1);
+
+        Json::Array(items)
+    }
+
+    /// Enum variants between braces.
+    fn variants(&self, items: &[EnumItem]) -> Json {
+        self.next(1);
+        let variants = items.iter()
+            .map(|item| {
+                let start = self.start();
+                self.next(1);
+                match item {
+                    EnumItem::Item(id) => self.node("unit", start, vec![("name", Json::name(id))]),
+                    EnumItem::Record(id, ItemType::Inline(ModelDefinition::Record(_, items, _))) => {
+                        self.node("record", start, vec![("name", Json::name(id)), ("fields", self.fields(items))])
+                    },
+                    EnumItem::Tuple(id, ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => {
+                        self.node("tuple", start, vec![("name", Json::name(id)), ("items", self.tuple_items(items))])
+                    },
+                    EnumItem::Enum(id, ItemType::Inline(ModelDefinition::Enum(_, items, _))) => {
+                        self.next(1);
+                        self.node("enum", start, vec![("name", Json::name(id)), ("variants", self.variants(items))])
+                    },
+                    EnumItem::Record(id, _) | EnumItem::Tuple(id, _) | EnumItem::Enum(id, _) => {
+                        self.node("unit", start, vec![("name", Json::name(id))])
+                    },
+                }
+            })
+            .collect();
+        self.next(1);
+
+        Json::Array(variants)
+    }
+
+    fn item_type<'b, 'i>(&self, item_type: &'b ItemType<'i>, bindings: &'b Bindings<'b, 'i>) -> Json {
+        let start = self.start();
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                self.next(1);
+                let name = id.as_name().unwrap_or_default();
+
+                let generics = params.iter().filter(|p| matches!(p, ModelParam::Generic(_))).count();
+                let mut args = vec![];
+                let mut metadata = vec![];
+                for (i, param) in params.iter().enumerate() {
+                    if i == 0 || i == generics {
+                        self.next(1);
+                    }
+                    match param {
+                        ModelParam::Generic(item_type) => args.push(self.item_type(item_type, bindings)),
+                        ModelParam::Metadata(id, value) => {
+                            let start = self.start();
+                            self.next(3);
+                            metadata.push(self.node("metadata", start, vec![("name", Json::name(id)), ("value", Json::literal(value))]));
+                        },
+                    }
+                    if i + 1 == generics || i + 1 == params.len() {
+                        self.next(1);
+                    }
+                }
+
+                let mut entries = vec![
+                    ("name", Json::str(name)),
+                    ("args", Json::Array(args)),
+                    ("metadata", Json::Array(metadata)),
+                ];
+                let is_generic = params.is_empty() && self.generics.borrow().iter().any(|names| names.iter().any(|n| n == name));
+                if is_generic {
+                    entries.push(("generic", Json::Bool(true)));
+                } else if self.options.references {
+                    let resolved = self.index.find(name, &self.path.borrow())
+                        .map(|entry| Json::str(entry.path.iter().chain([&entry.name]).cloned().collect::<Vec<_>>().join(".")))
+                        .unwrap_or(Json::Null);
+                    entries.push(("resolves", resolved));
+                }
+                self.node("ref", start, entries)
+            },
+            ItemType::Optional(item_type) => {
+                let item_type = self.item_type(item_type, bindings);
+                self.next(1);
+                self.node("optional", start, vec![("type", item_type)])
+            },
+            ItemType::Inline(def) => {
+                let model = self.model(def, false);
+                self.node("inline", start, vec![("model", model)])
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("scalar Int;", r#"{"version":1,"root":{"kind":"global","items":[{"kind":"scalar","name":"Int","params":[]}]}}"#; "scalar")]
+    #[test_case("package shop;\nmodel Id(Int?)", r#"{"version":1,"root":{"kind":"package","name":"shop","items":[{"kind":"tuple","name":"Id","params":[],"items":[{"kind":"item","name":null,"type":{"kind":"optional","type":{"kind":"ref","name":"Int","args":[],"metadata":[]}}}]}]}}"#; "root package")]
+    #[test_case("model E<T> enum { A, B { x: T } }", r#"{"version":1,"root":{"kind":"global","items":[{"kind":"enum","name":"E","params":[{"kind":"generic","name":"T","constraint":null}],"variants":[{"kind":"unit","name":"A"},{"kind":"record","name":"B","fields":[{"kind":"field","name":"x","type":{"kind":"ref","name":"T","args":[],"metadata":[],"generic":true}}]}]}]}}"#; "enum")]
+    fn check(code: &str, expected: &str) {
+        let result = AstJson::new().with_compact().to_json(code).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn check_spans() {
+        let code = "package shop {\n  model P[n: Int = 1](List<Int>[len=2], x: (Int, y: Int))\n  model R { ... P, r: Int? }\n}";
+        let result = AstJson::new().with_spans().with_compact().to_json(code).unwrap();
+
+        for (text, line, column) in [
+            (code, 1, 1),
+            ("model P[n: Int = 1](List<Int>[len=2], x: (Int, y: Int))", 2, 3),
+            ("n: Int = 1", 2, 11),
+            ("List<Int>[len=2]", 2, 23),
+            ("len=2", 2, 33),
+            ("x: (Int, y: Int)", 2, 41),
+            ("(Int, y: Int)", 2, 44),
+            ("... P", 3, 13),
+            ("r: Int?", 3, 20),
+        ] {
+            let start = code.find(text).unwrap();
+            let span = format!(r#""span":{{"start":{},"end":{},"line":{},"column":{}}}"#, start, start + text.len(), line, column);
+            assert!(result.contains(&span), "no span of `{}` in {}", text, result);
+        }
+    }
+
+    #[test]
+    fn check_references() {
+        let code = "package a { fragment Base<T> { id: T } }\npackage b { model User { ... Base<Long>, name: Name } }\nscalar Long;";
+        let result = AstJson::new().with_references().with_spreads().with_compact().to_json(code).unwrap();
+
+        assert!(result.contains(r#""kind":"ref","name":"Base","args":[{"kind":"ref","name":"Long","args":[],"metadata":[],"resolves":"Long"}],"metadata":[],"resolves":"a.Base""#), "{}", result);
+        assert!(result.contains(r#""name":"Name","args":[],"metadata":[],"resolves":null"#), "{}", result);
+        assert!(result.contains(r#""expanded":[{"name":"id","type":{"kind":"ref","name":"Long","args":[],"metadata":[],"resolves":"Long"}},{"name":"name""#), "{}", result);
+    }
+}
@@ -1,14 +1,35 @@
+use std::fmt;
 use std::num::ParseIntError;
+use std::ops::Range;
+use lalrpop_util::ParseError;
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub enum LexicalError {
     #[default]
     InvalidToken,
     InvalidInteger(ParseIntError),
+    /// Syntax the grammar recognizes only to reject it, with the span and the reason.
+    Unsupported(Range<usize>, String),
+}
+
+impl LexicalError {
+    pub fn unsupported<T>(span: Range<usize>, reason: String) -> ParseError<usize, T, LexicalError> {
+        ParseError::User { error: LexicalError::Unsupported(span, reason) }
+    }
 }
 
 impl From<ParseIntError> for LexicalError {
     fn from(err: ParseIntError) -> Self {
         LexicalError::InvalidInteger(err)
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for LexicalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexicalError::InvalidToken => write!(f, "invalid token"),
+            LexicalError::InvalidInteger(err) => write!(f, "invalid integer: {}", err),
+            LexicalError::Unsupported(span, reason) => write!(f, "{} at {}", reason, span.start),
+        }
+    }
+}
@@ -66,6 +66,47 @@ pub enum Token<'input> {
     Error(LexicalError),
 }
 
+impl<'input> Token<'input> {
+    /// Text of a name or a number.
+    pub fn text(&self) -> Option<&'input str> {
+        match self {
+            Token::Identifier(text) | Token::Number(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The same token with `text` as the text of a name or a number.
+    pub fn with_text<'a>(&self, text: &'a str) -> Token<'a> {
+        match self {
+            Token::KeywordPackage => Token::KeywordPackage,
+            Token::KeywordModel => Token::KeywordModel,
+            Token::KeywordEnum => Token::KeywordEnum,
+            Token::KeywordFragment => Token::KeywordFragment,
+            Token::KeywordScalar => Token::KeywordScalar,
+            Token::Identifier(_) => Token::Identifier(text),
+            Token::Number(_) => Token::Number(text),
+            Token::LParen => Token::LParen,
+            Token::RParen => Token::RParen,
+            Token::LBracket => Token::LBracket,
+            Token::RBracket => Token::RBracket,
+            Token::Lees => Token::Lees,
+            Token::Greater => Token::Greater,
+            Token::LSquare => Token::LSquare,
+            Token::RSquare => Token::RSquare,
+            Token::Question => Token::Question,
+            Token::Assign => Token::Assign,
+            Token::Semicolon => Token::Semicolon,
+            Token::Colon => Token::Colon,
+            Token::Comma => Token::Comma,
+            Token::Spread => Token::Spread,
+            Token::Comment => Token::Comment,
+            Token::Newline => Token::Newline,
+            Token::Whitespace => Token::Whitespace,
+            Token::Error(error) => Token::Error(error.clone()),
+        }
+    }
+}
+
 impl<'input> fmt::Display for Token<'input> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -1,22 +1,33 @@
 use std::borrow::Cow;
 use lalrpop_util::lalrpop_mod;
-use crate::ast::{Error, RefScope, Source};
+use crate::ast::{syntax_message, Error, NameSpans, RefScope, Scope, Source};
+use crate::transform::TransformError;
 use crate::lexer::Lexer;
 use crate::mex::PackageParser;
 
 pub mod ast;
+pub mod formatter;
+pub mod json;
 pub mod lexer;
+pub mod lsp;
+pub mod manifest;
+pub mod query;
 pub mod transform;
 
 lalrpop_mod!(pub mex);
 
-pub struct Compiler<'a>(&'a Source, Cow<'a, str>, &'a RefScope<'a>);
+pub struct Compiler<'a>(&'a Source, Cow<'a, str>, &'a RefScope<'a>, NameSpans<'a>);
 
 impl<'a> Compiler<'a> {
 
     pub fn new(source: &'a Source, global: &'a RefScope<'a>) -> Result<'a, Self> {
         let code = source.read()?;
-        Ok(Self(source, code, global))
+        Ok(Self(source, code, global, NameSpans::new()))
+    }
+
+    /// Ranges of the names parsed by `make_ast`.
+    pub fn spans(&'a self) -> &'a NameSpans<'a> {
+        &self.3
     }
 
     fn make_lexer(&'a self) -> Lexer<'a> {
@@ -30,13 +41,51 @@ impl<'a> Compiler<'a> {
     pub fn make_ast(&'a self) -> Result<'a, RefScope<'a>> {
         let lexer = self.make_lexer();
         let parser = self.make_parser();
-        let ast = parser.parse(self.0, self.2, lexer)?;
+        let ast = parser.parse(self.0, self.2, &self.3, lexer)?;
         Ok(ast)
     }
+
+    /// Parses the code, failing on the first syntax error whether the parser recovered from it
+    /// or not, with a readable message.
+    pub fn make_checked_ast(&'a self) -> Result<'static, RefScope<'a>> {
+        match self.make_ast() {
+            Ok(ast) => find_errors(&ast, &self.1).map(|_| ast),
+            // What was parsed before the parser gave up is in the global scope.
+            Err(error) => find_errors(self.2, &self.1).and(Err(parse_error(error, &self.1))),
+        }
+    }
 }
 
 pub type Result<'a, T> = core::result::Result<T, Error<'a>>;
 
+/// Syntax error detached from the source, for code parsed from a borrowed string.
+pub(crate) fn syntax_error(error: impl Into<String>) -> Error<'static> {
+    Error::Transform(vec![TransformError::Parsing(error.into())])
+}
+
+/// Syntax error of the parser with its message and the line and column it starts at.
+pub(crate) fn parse_error(error: Error, code: &str) -> Error<'static> {
+    match error {
+        Error::Parsing(error) => {
+            let (span, message) = syntax_message(&error, code);
+            let before = &code[..span.start.min(code.len())];
+            let line = before.matches('\n').count() + 1;
+            let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+            syntax_error(format!("{}:{}: {}", line, column, message))
+        },
+        error => syntax_error(format!("{:?}", error)),
+    }
+}
+
+/// Fails on the first part of the tree the parser had to recover from.
+pub(crate) fn find_errors(scope: &RefScope, code: &str) -> Result<'static, ()> {
+    match **scope.borrow() {
+        Scope::Global(ref items) | Scope::Package(_, ref items) => items.iter().try_for_each(|item| find_errors(item, code)),
+        Scope::Model(_) => Ok(()),
+        Scope::Error(ref error) => Err(parse_error(Error::Parsing(error.error.clone()), code)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -62,6 +111,8 @@ mod tests {
 
     #[test_case("model Test {\n    x: Int\n    y: Int\n    ... Test\n}"; "named record")]
     #[test_case("model T = {\n    ... Test\n    x: Int\n}"; "inline record")]
+    #[test_case("model Test {\n    x: Int?\n    y: (Int, Int)?\n}"; "optional fields")]
+    #[test_case("model Test(Int, y: Int?)"; "optional tuple items")]
 
     #[test_case("model Test enum {\n    Int\n}"; "named enum")]
     #[test_case("model T = enum {\n    Int\n}"; "inline enum")]
@@ -78,9 +129,26 @@ mod tests {
 
         let render = StringRender::new();
         let transformer = MexLangTransformer::new();
-        transformer.apply(&ast, &render);
+        transformer.apply(&ast, &render).unwrap();
         let result = render.as_string(4);
 
         assert_eq!(&result, code)
     }
+
+    #[test_case("model A(salt String)", "1:9: tuple item `salt` needs a `:` before its type"; "tuple item without colon")]
+    #[test_case("model File([Byte])", "1:12: write lists as `List<T>`, `[T]` is not supported"; "list shorthand")]
+    #[test_case("scalar String[len: Int];", "1:14: scalar `String` cannot declare parameters"; "scalar parameters")]
+    #[test_case("model A {\n    f: enum Test {\n        B\n    }\n}", "2:8: name inline enums as `Test enum { ... }`"; "inline enum named after keyword")]
+    #[test_case("package a;\n\npackage b\npackage c {\n}", "3:1: package `b` needs a block, `package b { ... }`"; "package without block")]
+    #[test_case("package a {\n    model Name\n}", "2:11: model `Name` needs a body, or can be declared as `scalar Name`"; "model without body")]
+    #[test_case("model A(Int)\nenum B {\n    C\n}", "2:1: unexpected `enum`, declare enums as `model Name enum { ... }`"; "enum keyword")]
+    fn check_unsupported(code: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        match compiler.make_checked_ast() {
+            Err(Error::Transform(errors)) => assert_eq!(errors[0].to_string(), format!("syntax error: {}", expected)),
+            _ => panic!("{} is accepted", code),
+        }
+    }
 }
@@ -0,0 +1,293 @@
+use crate::lexer::{Lexer, Token};
+use crate::lsp::document::DeclKind;
+use crate::lsp::workspace::Workspace;
+
+/// Keywords starting a declaration in a package.
+const DECLARATION_KEYWORDS: [&str; 4] = ["package", "model", "fragment", "scalar"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompletionKind {
+    Keyword,
+    Decl(DeclKind),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+/// What may be written at a position, as far as the tokens before it tell.
+///
+/// The tokens are read rather than the syntax tree, as the text being typed rarely parses.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Context {
+    pub expected: Expected,
+    /// Start of the name being typed, up to the position.
+    pub prefix: String,
+    /// Names of the enclosing packages.
+    pub path: Vec<String>,
+    /// Generic parameters of the enclosing model.
+    pub generics: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Expected {
+    #[default]
+    Nothing,
+    Keywords(&'static [&'static str]),
+    Type { spread: bool },
+    /// Metadata key of the type with the name, with the keys already given.
+    Metadata { type_name: String, given: Vec<String> },
+}
+
+/// Bracket opened before the position.
+struct Open<'t> {
+    token: Token<'t>,
+    /// Package the `{` is the body of.
+    package: Option<&'t str>,
+    /// Whether the `<` or `[` declares parameters rather than passing them.
+    declaring: bool,
+    /// Type the `<` or `[` passes parameters to.
+    type_name: Option<&'t str>,
+    /// Metadata keys given in the `[`.
+    keys: Vec<&'t str>,
+}
+
+impl Context {
+    pub fn new(text: &str, offset: usize) -> Self {
+        let text = &text[..offset.min(text.len())];
+        let mut tokens = Lexer::new(text)
+            .filter_map(|token| token.ok())
+            .filter(|(_, token, _)| !matches!(token, Token::Error(_)))
+            .collect::<Vec<_>>();
+
+        let mut context = Context::default();
+        let is_word = |token: &Token| matches!(token, Token::Identifier(_) | Token::KeywordPackage | Token::KeywordModel |
+            Token::KeywordEnum | Token::KeywordFragment | Token::KeywordScalar);
+        if let Some((start, token, end)) = tokens.last() {
+            if *end == text.len() && is_word(token) {
+                context.prefix = text[*start..].to_string();
+                tokens.pop();
+            }
+        }
+
+        let mut root = vec![];
+        let mut stack: Vec<Open> = vec![];
+        // Depth of the model being declared, with its generic parameters.
+        let mut model: Option<(usize, Vec<&str>)> = None;
+        let mut closed: Option<Open> = None;
+
+        for i in 0..tokens.len() {
+            let token = &tokens[i].1;
+            let prev = i.checked_sub(1).map(|i| &tokens[i].1);
+            let prev2 = i.checked_sub(2).map(|i| &tokens[i].1);
+            let name = match prev {
+                Some(Token::Identifier(name)) => Some(*name),
+                _ => None,
+            };
+            let declares = name.is_some() && matches!(prev2, Some(Token::KeywordModel | Token::KeywordFragment));
+
+            match token {
+                Token::KeywordModel | Token::KeywordFragment | Token::KeywordScalar | Token::KeywordPackage => {
+                    if model.as_ref().is_some_and(|(depth, _)| *depth >= stack.len()) {
+                        model = None;
+                    }
+                    if matches!(token, Token::KeywordModel | Token::KeywordFragment) {
+                        model = Some((stack.len(), vec![]));
+                    }
+                },
+                Token::Semicolon if matches!(prev2, Some(Token::KeywordPackage)) && stack.is_empty() => {
+                    root.extend(name);
+                },
+                Token::Identifier(name) => {
+                    let open = stack.last_mut();
+                    let listed = matches!(prev, Some(Token::Lees | Token::LSquare | Token::Comma));
+                    match open {
+                        Some(open) if open.token == Token::Lees && open.declaring && listed => {
+                            if let Some((_, generics)) = model.as_mut() {
+                                generics.push(name);
+                            }
+                        },
+                        Some(open) if open.token == Token::LSquare && !open.declaring && listed => open.keys.push(name),
+                        _ => {},
+                    }
+                },
+                Token::LBracket | Token::LParen | Token::Lees | Token::LSquare => {
+                    let after_params = matches!(prev, Some(Token::Greater)) && closed.as_ref().is_some_and(|open| open.declaring);
+                    let type_name = match prev {
+                        Some(Token::Greater) => closed.as_ref().and_then(|open| open.type_name),
+                        _ => name,
+                    };
+                    stack.push(Open {
+                        token: token.clone(),
+                        package: name.filter(|_| matches!(prev2, Some(Token::KeywordPackage))),
+                        declaring: declares || (after_params && *token == Token::LSquare),
+                        type_name,
+                        keys: vec![],
+                    });
+                },
+                Token::RBracket | Token::RParen | Token::Greater | Token::RSquare => {
+                    closed = stack.pop();
+                    if model.as_ref().is_some_and(|(depth, _)| *depth > stack.len()) {
+                        model = None;
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        context.path = root.iter().chain(stack.iter().filter_map(|open| open.package.as_ref()))
+            .map(|name| name.to_string())
+            .collect();
+        context.generics = model.map(|(_, generics)| generics.iter().map(|name| name.to_string()).collect()).unwrap_or_default();
+
+        let prev = tokens.last().map(|(_, token, _)| token);
+        let prev2 = tokens.len().checked_sub(2).map(|i| &tokens[i].1);
+        let open = stack.last();
+        context.expected = match (open.map(|open| (&open.token, open.declaring)), prev) {
+            (_, Some(Token::Spread)) => Expected::Type { spread: true },
+            (Some((Token::LSquare, false)), Some(Token::LSquare | Token::Comma)) => Expected::Metadata {
+                type_name: open.and_then(|open| open.type_name).unwrap_or_default().to_string(),
+                given: open.map(|open| open.keys.iter().map(|key| key.to_string()).collect()).unwrap_or_default(),
+            },
+            (Some((Token::LSquare | Token::Lees, true)), Some(Token::Colon)) => Expected::Type { spread: false },
+            (Some((Token::LSquare | Token::Lees, true)), _) => Expected::Nothing,
+            (Some((Token::LSquare, false)), _) => Expected::Nothing,
+            (Some((Token::Lees | Token::LParen, _)), Some(Token::Lees | Token::LParen | Token::Comma)) => Expected::Type { spread: false },
+            (_, Some(Token::Colon | Token::Assign)) => Expected::Type { spread: false },
+            (None, _) | (Some((Token::LBracket, _)), _) if open.is_none_or(|open| open.package.is_some()) => match (prev, prev2) {
+                (Some(Token::Identifier(_)), Some(Token::KeywordModel)) => Expected::Keywords(&["enum"]),
+                (Some(Token::KeywordPackage | Token::KeywordModel | Token::KeywordFragment | Token::KeywordScalar), _) => Expected::Nothing,
+                _ => Expected::Keywords(&DECLARATION_KEYWORDS),
+            },
+            _ => Expected::Nothing,
+        };
+        context
+    }
+}
+
+impl Workspace {
+    /// Names that may be written at the offset of the document and start with what is typed.
+    pub fn completions(&self, uri: &str, offset: usize) -> Vec<Completion> {
+        let Some(document) = self.get(uri) else {
+            return vec![];
+        };
+        let context = Context::new(&document.text, offset);
+        let mut completions = vec![];
+
+        match &context.expected {
+            Expected::Nothing => {},
+            Expected::Keywords(keywords) => {
+                completions.extend(keywords.iter().map(|keyword| Completion {
+                    label: keyword.to_string(),
+                    kind: CompletionKind::Keyword,
+                    detail: String::new(),
+                }));
+            },
+            Expected::Type { spread } => {
+                if !spread {
+                    completions.extend(context.generics.iter().map(|name| Completion {
+                        label: name.clone(),
+                        kind: CompletionKind::Decl(DeclKind::Generic),
+                        detail: "generic parameter".to_string(),
+                    }));
+                }
+
+                let mut models = vec![];
+                for (_, document) in self.documents() {
+                    for decl in &document.decls {
+                        if !decl.kind.is_model() || models.iter().any(|(name, _)| *name == decl.name) {
+                            continue;
+                        }
+                        // The model the name resolves to here, which may be declared elsewhere.
+                        if let Some(target) = self.find_model(&decl.name, &context.path, uri) {
+                            models.push((decl.name.clone(), target));
+                        }
+                    }
+                }
+                models.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+                for (name, target) in models {
+                    let kind = self.decl(&target).unwrap().kind;
+                    if *spread && !matches!(kind, DeclKind::Record | DeclKind::Fragment) {
+                        continue;
+                    }
+                    let detail = self.qualified_name(&target);
+                    completions.push(Completion { label: name, kind: CompletionKind::Decl(kind), detail });
+                }
+            },
+            Expected::Metadata { type_name, given } => {
+                if let Some(model) = self.find_model(type_name, &context.path, uri) {
+                    let document = self.get(&model.uri).unwrap();
+                    completions.extend(document.children(Some(model.decl))
+                        .filter(|(_, decl)| decl.kind == DeclKind::Metadata && !given.contains(&decl.name))
+                        .map(|(_, decl)| Completion {
+                            label: decl.name.clone(),
+                            kind: CompletionKind::Decl(DeclKind::Metadata),
+                            detail: decl.detail.clone(),
+                        }));
+                }
+            },
+        }
+
+        completions.retain(|completion| completion.label.starts_with(&context.prefix));
+        completions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const COMMON: &str = "package common;
+
+fragment Audit {
+    created: DateTime
+}
+
+model Hash[len: Int = 32, hex: Bool] = Bytes
+
+model Status enum {
+    Active
+}
+
+scalar String
+scalar Int
+scalar Uuid";
+
+    /// Labels completed at the `|` of the code, in a workspace with `COMMON`.
+    #[test_case("package shop;\n\n|", "package, model, fragment, scalar"; "top level")]
+    #[test_case("package shop;\n\nmo|", "model"; "keyword prefix")]
+    #[test_case("package shop;\n\nmodel Order |", "enum"; "after model name")]
+    #[test_case("package shop;\n\nmodel Order {\n    |\n}", ""; "field name")]
+    #[test_case("package shop;\n\nmodel Order {\n    id: Uu|\n}", "Uuid"; "declared scalar")]
+    #[test_case("package shop;\n\nmodel Order {\n    status: St|\n}", "Status, String"; "model and scalar")]
+    #[test_case("package shop;\n\nmodel Order {\n    ... |\n}", "Audit"; "spread")]
+    #[test_case("package shop;\n\nmodel Page<T, Item> {\n    items: List<I|>\n}", "Item, Int"; "generic")]
+    #[test_case("package shop;\n\nmodel Page<T> {\n    items: List<|", "T, Audit, Hash, Int, Status, String, Uuid"; "unparsed")]
+    #[test_case("package shop;\n\nmodel Order {\n    hash: Hash[len=8, |]\n}", "hex"; "metadata")]
+    #[test_case("package shop;\n\nmodel Key[|] = Bytes", ""; "metadata declaration")]
+    #[test_case("package shop;\n\nmodel Order {\n    hash: Hash[len=|]\n}", ""; "metadata value")]
+    fn check(code: &str, expected: &str) {
+        let offset = code.find('|').unwrap();
+        let mut workspace = Workspace::new();
+        workspace.insert("file:///common.mex", COMMON.to_string());
+        workspace.insert("file:///shop.mex", code.replace('|', ""));
+
+        let labels = workspace.completions("file:///shop.mex", offset).into_iter().map(|c| c.label).collect::<Vec<_>>();
+        assert_eq!(labels.join(", "), expected);
+    }
+
+    #[test]
+    fn check_context() {
+        let code = "package shop;\n\npackage orders {\n    model Line<T>(T, In";
+        let context = Context::new(code, code.len());
+        assert_eq!(context.expected, Expected::Type { spread: false });
+        assert_eq!(context.prefix, "In");
+        assert_eq!(context.path, ["shop", "orders"]);
+        assert_eq!(context.generics, ["T"]);
+    }
+}
@@ -0,0 +1,562 @@
+use std::ops::Range;
+use lalrpop_util::ParseError;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::Compiler;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeclKind {
+    Package,
+    Scalar,
+    Record,
+    Fragment,
+    Tuple,
+    Enum,
+    Alias,
+    Field,
+    /// Named tuple item.
+    Item,
+    Variant,
+    Generic,
+    Metadata,
+}
+
+impl DeclKind {
+    fn of(def: &ModelDefinition) -> Self {
+        match def {
+            ModelDefinition::Scalar(_) => DeclKind::Scalar,
+            ModelDefinition::Record(..) => DeclKind::Record,
+            ModelDefinition::Fragment(..) => DeclKind::Fragment,
+            ModelDefinition::Tuple(..) => DeclKind::Tuple,
+            ModelDefinition::Enum(..) => DeclKind::Enum,
+            ModelDefinition::Alias(..) => DeclKind::Alias,
+        }
+    }
+
+    pub fn is_model(self) -> bool {
+        matches!(self, DeclKind::Scalar | DeclKind::Record | DeclKind::Fragment | DeclKind::Tuple | DeclKind::Enum | DeclKind::Alias)
+    }
+}
+
+/// Declared name: a package, a model, or a part of a model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decl {
+    pub name: String,
+    pub kind: DeclKind,
+    /// Byte range of the name.
+    pub range: Range<usize>,
+    /// Enclosing declaration, none at the top level.
+    pub parent: Option<usize>,
+    /// Names of the enclosing packages.
+    pub path: Vec<String>,
+    /// Source of a model, or the signature of a part, for hovers.
+    pub detail: String,
+    /// Dotted path the doc comments of the declaration are keyed by, see `DocComments`.
+    pub doc_path: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RefKind {
+    Type,
+    Spread,
+    /// Metadata key of the type reference with the index.
+    Metadata(usize),
+}
+
+/// Name used as a type, a spread or a metadata key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub kind: RefKind,
+    pub range: Range<usize>,
+    /// Names of the enclosing packages.
+    pub path: Vec<String>,
+    /// Generic parameters in scope.
+    pub generics: Vec<usize>,
+    /// Innermost declaration the reference is part of.
+    pub owner: Option<usize>,
+    /// Top-level model the reference is part of.
+    pub model: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error = 1,
+    Warning = 2,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub range: Range<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Source of an editor document with its declarations and references.
+pub struct Document {
+    pub text: String,
+    pub decls: Vec<Decl>,
+    pub refs: Vec<Reference>,
+    /// Syntax errors. Semantic ones depend on other documents, see `Workspace::diagnostics`.
+    pub errors: Vec<Diagnostic>,
+    pub docs: DocComments,
+    lines: Vec<usize>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let global: RefScope = Scope::Global(vec![]).into();
+        let source = Source::from_str(&text);
+        let compiler = Compiler::new(&source, &global).expect("a string source is always read");
+        let mut walker = Walker::new(&text, compiler.spans());
+
+        // Parsed packages are added to the global scope as they are reduced, so it holds what
+        // was parsed even when the parser gives up.
+        if let Err(Error::Parsing(error)) = compiler.make_ast() {
+            walker.error(&error);
+        }
+        walker.scope(&global);
+
+        let Walker { decls, refs, errors, .. } = walker;
+        let lines = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        Document {
+            docs: DocComments::parse(&text),
+            text,
+            decls,
+            refs,
+            errors,
+            lines,
+        }
+    }
+
+    /// Line and UTF-16 column of a byte offset.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.lines.partition_point(|&start| start <= offset) - 1;
+        let start = self.lines[line];
+        let column = self.text[start..offset.min(self.text.len())].encode_utf16().count();
+        (line, column)
+    }
+
+    /// Byte offset of a line and UTF-16 column, clamped to the line.
+    pub fn offset(&self, line: usize, column: usize) -> usize {
+        let Some(&start) = self.lines.get(line) else {
+            return self.text.len();
+        };
+        let end = self.lines.get(line + 1).map(|end| end - 1).unwrap_or(self.text.len());
+
+        let mut units = 0;
+        for (i, c) in self.text[start..end].char_indices() {
+            if units >= column {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        end
+    }
+
+    pub fn decl_at(&self, offset: usize) -> Option<usize> {
+        self.decls.iter().position(|decl| decl.range.start <= offset && offset <= decl.range.end)
+    }
+
+    pub fn ref_at(&self, offset: usize) -> Option<usize> {
+        self.refs.iter().position(|r| r.range.start <= offset && offset <= r.range.end)
+    }
+
+    /// Declarations directly inside `parent`, or at the top level.
+    pub fn children(&self, parent: Option<usize>) -> impl Iterator<Item = (usize, &Decl)> {
+        self.decls.iter().enumerate().filter(move |(_, decl)| decl.parent == parent)
+    }
+}
+
+struct Walker<'t> {
+    text: &'t str,
+    spans: &'t NameSpans<'t>,
+    decls: Vec<Decl>,
+    refs: Vec<Reference>,
+    errors: Vec<Diagnostic>,
+    path: Vec<String>,
+    parents: Vec<usize>,
+    generics: Vec<usize>,
+    model: Option<usize>,
+}
+
+impl<'t> Walker<'t> {
+    fn new(text: &'t str, spans: &'t NameSpans<'t>) -> Self {
+        Walker { text, spans, decls: vec![], refs: vec![], errors: vec![], path: vec![], parents: vec![], generics: vec![], model: None }
+    }
+
+    fn decl(&mut self, id: &Id, kind: DeclKind, detail: String) -> Option<usize> {
+        let name = id.as_name()?;
+        let range = self.spans.get(name)?;
+        let parent = self.parents.last().copied();
+        let doc_path = match (kind, parent) {
+            (DeclKind::Package, _) => String::new(),
+            (kind, _) if kind.is_model() && self.model.is_none() => {
+                self.path.iter().map(String::as_str).chain([name]).collect::<Vec<_>>().join(".")
+            },
+            (_, Some(parent)) if !self.decls[parent].doc_path.is_empty() => format!("{}.{}", self.decls[parent].doc_path, name),
+            _ => name.to_string(),
+        };
+
+        self.decls.push(Decl {
+            name: name.to_string(),
+            kind,
+            range,
+            parent,
+            path: self.path.clone(),
+            detail,
+            doc_path,
+        });
+        Some(self.decls.len() - 1)
+    }
+
+    fn reference(&mut self, id: &Id, kind: RefKind) -> Option<usize> {
+        let name = id.as_name()?;
+        let range = self.spans.get(name)?;
+        self.refs.push(Reference {
+            name: name.to_string(),
+            kind,
+            range,
+            path: self.path.clone(),
+            generics: self.generics.clone(),
+            owner: self.parents.last().copied(),
+            model: self.model,
+        });
+        Some(self.refs.len() - 1)
+    }
+
+    /// Walks `f` with the declaration as the innermost parent.
+    fn within(&mut self, decl: Option<usize>, f: impl FnOnce(&mut Self)) {
+        if let Some(decl) = decl {
+            self.parents.push(decl);
+        }
+        f(self);
+        if decl.is_some() {
+            self.parents.pop();
+        }
+    }
+
+    fn error(&mut self, error: &ParseError<usize, Token, LexicalError>) {
+        let (range, message) = syntax_message(error, self.text);
+        self.errors.push(Diagnostic { range, severity: Severity::Error, message });
+    }
+
+    fn scope(&mut self, scope: &RefScope) {
+        match **scope.borrow() {
+            Scope::Global(ref items) => items.iter().for_each(|item| self.scope(item)),
+            Scope::Package(ref id, ref items) => {
+                let decl = self.decl(id, DeclKind::Package, format!("package {}", id.as_name().unwrap_or_default()));
+                self.path.push(id.as_name().unwrap_or_default().to_string());
+                self.within(decl, |walker| items.iter().for_each(|item| walker.scope(item)));
+                self.path.pop();
+            },
+            Scope::Model(ref def) => self.model(def),
+            Scope::Error(ref error) => self.error(&error.error),
+        }
+    }
+
+    fn model(&mut self, def: &ModelDefinition) {
+        let detail = owned::Scope::Model(def.into()).to_source().unwrap_or_default();
+        let decl = self.decl(model_id(def), DeclKind::of(def), detail);
+        self.model = decl;
+        self.within(decl, |walker| walker.model_items(def));
+        self.model = None;
+    }
+
+    fn model_items(&mut self, def: &ModelDefinition) {
+        let generics = self.generics.len();
+        match def {
+            ModelDefinition::Fragment(_, items, params) | ModelDefinition::Record(_, items, params) => {
+                self.params_def(params);
+                self.record_items(items);
+            },
+            ModelDefinition::Tuple(_, items, params) => {
+                self.params_def(params);
+                self.tuple_items(items);
+            },
+            ModelDefinition::Enum(_, items, params) => {
+                self.params_def(params);
+                self.enum_items(items);
+            },
+            ModelDefinition::Alias(_, params, item_type) => {
+                self.params_def(params);
+                self.item_type(item_type, false);
+            },
+            ModelDefinition::Scalar(_) => {},
+        }
+        self.generics.truncate(generics);
+    }
+
+    fn params_def(&mut self, params: &[ModelParamDefinition]) {
+        for param in params {
+            match param {
+                ModelParamDefinition::Generic { id, constraint_type } => {
+                    let detail = match constraint_type {
+                        Some(item_type) => format!("{}: {}", id.as_name().unwrap_or_default(), type_text(item_type)),
+                        None => id.as_name().unwrap_or_default().to_string(),
+                    };
+                    let decl = self.decl(id, DeclKind::Generic, detail);
+                    self.generics.extend(decl);
+                    if let Some(item_type) = constraint_type {
+                        self.within(decl, |walker| walker.item_type(item_type, false));
+                    }
+                },
+                ModelParamDefinition::Metadata { id, type_id, def_value } => {
+                    let mut detail = format!("{}: {}", id.as_name().unwrap_or_default(), type_text(type_id));
+                    if let Some(value) = def_value {
+                        detail = format!("{} = {}", detail, literal_text(value));
+                    }
+                    let decl = self.decl(id, DeclKind::Metadata, detail);
+                    self.within(decl, |walker| walker.item_type(type_id, false));
+                },
+                ModelParamDefinition::Constraint { constraint: GenericConstraintDefinition::Contains(item_type), .. } => {
+                    self.item_type(item_type, false);
+                },
+            }
+        }
+    }
+
+    fn record_items(&mut self, items: &[RecordItem]) {
+        for item in items {
+            match item {
+                RecordItem::Item(id, item_type) => {
+                    let detail = format!("{}: {}", id.as_name().unwrap_or_default(), type_text(item_type));
+                    let decl = self.decl(id, DeclKind::Field, detail);
+                    self.within(decl, |walker| walker.item_type(item_type, false));
+                },
+                RecordItem::Spread(item_type) => self.item_type(item_type, true),
+            }
+        }
+    }
+
+    fn tuple_items(&mut self, items: &[TupleItem]) {
+        for item in items {
+            match item {
+                TupleItem::Item(item_type) => self.item_type(item_type, false),
+                TupleItem::NamedItem(id, item_type) => {
+                    let detail = format!("{}: {}", id.as_name().unwrap_or_default(), type_text(item_type));
+                    let decl = self.decl(id, DeclKind::Item, detail);
+                    self.within(decl, |walker| walker.item_type(item_type, false));
+                },
+            }
+        }
+    }
+
+    fn enum_items(&mut self, items: &[EnumItem]) {
+        for item in items {
+            let (id, payload) = match item {
+                EnumItem::Item(id) => (id, None),
+                EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => (id, Some(item_type)),
+            };
+
+            let detail = match payload {
+                Some(item_type @ ItemType::Inline(ModelDefinition::Tuple(..))) => format!("{}{}", id.as_name().unwrap_or_default(), type_text(item_type)),
+                Some(item_type) => format!("{} {}", id.as_name().unwrap_or_default(), type_text(item_type)),
+                None => id.as_name().unwrap_or_default().to_string(),
+            };
+            let decl = self.decl(id, DeclKind::Variant, detail);
+            if let Some(ItemType::Inline(def)) = payload {
+                self.within(decl, |walker| walker.model_items(def));
+            }
+        }
+    }
+
+    fn item_type(&mut self, item_type: &ItemType, spread: bool) {
+        match item_type {
+            ItemType::Model(id, params) => {
+                let reference = self.reference(id, if spread { RefKind::Spread } else { RefKind::Type });
+                for param in params {
+                    match param {
+                        ModelParam::Generic(item_type) => self.item_type(item_type, false),
+                        ModelParam::Metadata(id, _) => {
+                            if let Some(reference) = reference {
+                                self.reference(id, RefKind::Metadata(reference));
+                            }
+                        },
+                    }
+                }
+            },
+            ItemType::Inline(def) => {
+                // Named inline models are listed, though other models cannot refer to them.
+                let decl = match model_id(def) {
+                    Id::Inline => None,
+                    id => self.decl(id, DeclKind::of(def), type_text(item_type)),
+                };
+                self.within(decl, |walker| walker.model_items(def));
+            },
+            ItemType::Optional(item_type) => self.item_type(item_type, spread),
+        }
+    }
+}
+
+fn model_id<'a, 'b>(def: &'a ModelDefinition<'b>) -> &'a Id<'b> {
+    match def {
+        ModelDefinition::Fragment(id, ..) | ModelDefinition::Record(id, ..) | ModelDefinition::Tuple(id, ..) |
+        ModelDefinition::Enum(id, ..) | ModelDefinition::Alias(id, ..) | ModelDefinition::Scalar(id) => id,
+    }
+}
+
+fn literal_text(literal: &Literal) -> String {
+    match literal {
+        Literal::String(value) | Literal::Number(value) => value.to_string(),
+    }
+}
+
+/// Single line text of a type, with inline models spelled out.
+pub(crate) fn type_text(item_type: &ItemType) -> String {
+    let join = |items: Vec<String>| items.join(", ");
+    match item_type {
+        ItemType::Model(id, params) => {
+            let mut text = id.as_name().unwrap_or_default().to_string();
+            let generics = params.iter().filter_map(|param| match param {
+                ModelParam::Generic(item_type) => Some(type_text(item_type)),
+                ModelParam::Metadata(..) => None,
+            }).collect::<Vec<_>>();
+            let metadata = params.iter().filter_map(|param| match param {
+                ModelParam::Metadata(id, value) => Some(format!("{}={}", id.as_name().unwrap_or_default(), literal_text(value))),
+                ModelParam::Generic(_) => None,
+            }).collect::<Vec<_>>();
+
+            if !generics.is_empty() {
+                text = format!("{}<{}>", text, join(generics));
+            }
+            if !metadata.is_empty() {
+                text = format!("{}[{}]", text, join(metadata));
+            }
+            text
+        },
+        ItemType::Inline(def) => {
+            let name = match model_id(def).as_name() {
+                Some(name) => format!("{} ", name),
+                None => String::new(),
+            };
+            match def {
+                ModelDefinition::Record(_, items, _) | ModelDefinition::Fragment(_, items, _) => {
+                    let items = items.iter().map(|item| match item {
+                        RecordItem::Item(id, item_type) => format!("{}: {}", id.as_name().unwrap_or_default(), type_text(item_type)),
+                        RecordItem::Spread(item_type) => format!("... {}", type_text(item_type)),
+                    }).collect::<Vec<_>>();
+                    format!("{}{{ {} }}", name, join(items))
+                },
+                ModelDefinition::Tuple(_, items, _) => {
+                    let items = items.iter().map(|item| match item {
+                        TupleItem::Item(item_type) => type_text(item_type),
+                        TupleItem::NamedItem(id, item_type) => format!("{}: {}", id.as_name().unwrap_or_default(), type_text(item_type)),
+                    }).collect::<Vec<_>>();
+                    format!("{}({})", name.trim_end(), join(items))
+                },
+                ModelDefinition::Enum(_, items, _) => {
+                    let items = items.iter().map(|item| match item {
+                        EnumItem::Item(id) => id.as_name().unwrap_or_default().to_string(),
+                        EnumItem::Tuple(id, item_type) => format!("{}{}", id.as_name().unwrap_or_default(), type_text(item_type)),
+                        EnumItem::Record(id, item_type) | EnumItem::Enum(id, item_type) => {
+                            format!("{} {}", id.as_name().unwrap_or_default(), type_text(item_type))
+                        },
+                    }).collect::<Vec<_>>();
+                    format!("{}enum {{ {} }}", name, join(items))
+                },
+                ModelDefinition::Alias(_, _, item_type) => type_text(item_type),
+                ModelDefinition::Scalar(id) => id.as_name().unwrap_or_default().to_string(),
+            }
+        },
+        ItemType::Optional(item_type) => format!("{}?", type_text(item_type)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "package shop;
+
+/// Price in cents.
+scalar Money
+
+model Item<T>[currency: String = EUR] {
+    /// Unit price.
+    price: Money
+    tags: List<T>?
+    ... Audit
+}
+
+model Status enum {
+    Active
+    Moved(to: Int)
+}";
+
+    fn decl<'a>(document: &'a Document, name: &str) -> &'a Decl {
+        document.decls.iter().find(|decl| decl.name == name).unwrap()
+    }
+
+    #[test]
+    fn check_decls() {
+        let document = Document::new(CODE.to_string());
+        let names = document.decls.iter().map(|decl| (decl.name.as_str(), decl.kind)).collect::<Vec<_>>();
+        assert_eq!(names, [
+            ("shop", DeclKind::Package),
+            ("Money", DeclKind::Scalar),
+            ("Item", DeclKind::Record),
+            ("T", DeclKind::Generic),
+            ("currency", DeclKind::Metadata),
+            ("price", DeclKind::Field),
+            ("tags", DeclKind::Field),
+            ("Status", DeclKind::Enum),
+            ("Active", DeclKind::Variant),
+            ("Moved", DeclKind::Variant),
+            ("to", DeclKind::Item),
+        ]);
+
+        let price = decl(&document, "price");
+        assert_eq!(&CODE[price.range.clone()], "price");
+        assert_eq!(document.position(price.range.start), (7, 4));
+        assert_eq!(price.detail, "price: Money");
+        assert_eq!(document.docs.get(&price.doc_path), Some("Unit price."));
+        assert_eq!(decl(&document, "tags").detail, "tags: List<T>?");
+        assert_eq!(decl(&document, "Moved").detail, "Moved(to: Int)");
+        assert!(document.errors.is_empty());
+    }
+
+    #[test]
+    fn check_refs() {
+        let document = Document::new(CODE.to_string());
+        let refs = document.refs.iter().map(|r| (r.name.as_str(), r.kind)).collect::<Vec<_>>();
+        assert_eq!(refs, [
+            ("String", RefKind::Type),
+            ("Money", RefKind::Type),
+            ("List", RefKind::Type),
+            ("T", RefKind::Type),
+            ("Audit", RefKind::Spread),
+            ("Int", RefKind::Type),
+        ]);
+
+        // Each name has the range the parser located it at, though both read `Money`.
+        let money = &document.refs[1];
+        assert_eq!(&CODE[money.range.clone()], "Money");
+        assert_eq!(document.position(money.range.start), (7, 11));
+        assert_ne!(money.range, decl(&document, "Money").range);
+
+        let t = &document.refs[3];
+        assert_eq!(t.generics, [document.decls.iter().position(|d| d.name == "T").unwrap()]);
+        assert_eq!(t.path, ["shop"]);
+    }
+
+    #[test]
+    fn check_errors() {
+        let document = Document::new("model A {\n    x: Int\n}\n\nmodel B {\n    y Int\n}\n".to_string());
+        assert_eq!(document.errors.len(), 1);
+        assert_eq!(document.errors[0].message, "unexpected `Int`, expected `:`");
+        assert_eq!(document.position(document.errors[0].range.start), (5, 6));
+        assert_eq!(decl(&document, "x").name, "x");
+    }
+
+    #[test]
+    fn check_positions() {
+        let document = Document::new("// é😀\nmodel A()".to_string());
+        assert_eq!(document.position(9), (0, 6));
+        assert_eq!(document.position(16), (1, 6));
+        assert_eq!(document.offset(0, 4), 5);
+        assert_eq!(document.offset(1, 6), 16);
+        assert_eq!(document.offset(1, 99), 19);
+        assert_eq!(document.offset(9, 0), 19);
+    }
+}
@@ -0,0 +1,305 @@
+use std::fmt::{Display, Formatter, Write};
+
+/// JSON value of a protocol message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn object(entries: Vec<(&str, Value)>) -> Self {
+        Value::Object(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    pub fn parse(text: &str) -> Result<Value, String> {
+        let mut parser = Parser { chars: text.char_indices().peekable(), text };
+        let value = parser.value()?;
+        parser.whitespace();
+        match parser.chars.next() {
+            None => Ok(value),
+            Some((i, _)) => Err(format!("unexpected text at {}", i)),
+        }
+    }
+
+    /// Member of an object, or `Null` when there is none.
+    pub fn get(&self, key: &str) -> &Value {
+        match self {
+            Value::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v).unwrap_or(&Value::Null),
+            _ => &Value::Null,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Value::Number(value) if *value >= 0.0 && value.fract() == 0.0 => Some(*value as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Value] {
+        match self {
+            Value::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::Number(value as f64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Value::Array(value)
+    }
+}
+
+/// Writes the value on a single line.
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) if value.fract() == 0.0 && value.abs() < 1e15 => write!(f, "{}", *value as i64),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write_str(f, value),
+            Value::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            },
+            Value::Object(entries) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_str(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            },
+        }
+    }
+}
+
+fn write_str(f: &mut Formatter<'_>, value: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    text: &'a str,
+}
+
+impl Parser<'_> {
+    fn whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((i, c)) => Err(format!("expected `{}` at {}, found `{}`", expected, i, c)),
+            None => Err(format!("expected `{}` at the end", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.whitespace();
+        match self.chars.peek().copied() {
+            Some((_, '{')) => self.object(),
+            Some((_, '[')) => self.array(),
+            Some((_, '"')) => self.string().map(Value::String),
+            Some((_, '-' | '0'..='9')) => self.number(),
+            Some((i, _)) => {
+                let rest = &self.text[i..];
+                let (word, value) = [("null", Value::Null), ("true", Value::Bool(true)), ("false", Value::Bool(false))]
+                    .into_iter()
+                    .find(|(word, _)| rest.starts_with(word))
+                    .ok_or_else(|| format!("unexpected text at {}", i))?;
+                word.chars().for_each(|_| { self.chars.next(); });
+                Ok(value)
+            },
+            None => Err("unexpected end".to_string()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut entries = vec![];
+        self.whitespace();
+        if self.chars.next_if(|(_, c)| *c == '}').is_some() {
+            return Ok(Value::Object(entries));
+        }
+
+        loop {
+            self.whitespace();
+            let key = self.string()?;
+            self.whitespace();
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+            self.whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, '}')) => return Ok(Value::Object(entries)),
+                _ => return Err("expected `,` or `}`".to_string()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = vec![];
+        self.whitespace();
+        if self.chars.next_if(|(_, c)| *c == ']').is_some() {
+            return Ok(Value::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+            self.whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, ']')) => return Ok(Value::Array(items)),
+                _ => return Err("expected `,` or `]`".to_string()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(value),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, 'b')) => value.push('\u{8}'),
+                    Some((_, 'f')) => value.push('\u{c}'),
+                    Some((_, 'u')) => {
+                        let high = self.hex()?;
+                        let code = match high {
+                            0xd800..=0xdbff => {
+                                self.expect('\\')?;
+                                self.expect('u')?;
+                                0x10000 + ((high - 0xd800) << 10) + (self.hex()? - 0xdc00)
+                            },
+                            code => code,
+                        };
+                        value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    },
+                    Some((_, c)) => value.push(c),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some((_, c)) => value.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits = (0..4).filter_map(|_| self.chars.next().map(|(_, c)| c)).collect::<String>();
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid escape `\\u{}`", digits))
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.chars.peek().map(|(i, _)| *i).unwrap_or_default();
+        let mut end = start;
+        while let Some((i, c)) = self.chars.next_if(|(_, c)| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            end = i + c.len_utf8();
+        }
+        self.text[start..end].parse().map(Value::Number).map_err(|_| format!("invalid number at {}", start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(r#"{"a":[1,true,null,"x"],"b":{}}"#; "nested")]
+    #[test_case(r#""quote \" and \\ and \n""#; "escapes")]
+    #[test_case(r#"[-1.5,0,[]]"#; "numbers")]
+    fn check(text: &str) {
+        assert_eq!(Value::parse(text).unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn check_parse() {
+        let value = Value::parse(r#" { "id" : 7, "name": "é😀", "ok": false } "#).unwrap();
+        assert_eq!(value.get("id").as_usize(), Some(7));
+        assert_eq!(value.get("name").as_str(), Some("é😀"));
+        assert_eq!(value.get("ok").as_bool(), Some(false));
+        assert!(value.get("missing").is_null());
+    }
+
+    #[test_case("{"; "unterminated object")]
+    #[test_case("[1 2]"; "missing comma")]
+    #[test_case("nul"; "unknown word")]
+    fn check_errors(text: &str) {
+        assert!(Value::parse(text).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Language server for Mex sources, speaking the Language Server Protocol.
+//!
+//! [`Server`] turns each client message into responses and notifications; the `mex-lsp`
+//! binary only moves messages between it and stdio with [`read_message`] and [`write_message`].
+
+use std::io::{BufRead, Write};
+
+mod json;
+mod document;
+mod workspace;
+mod completion;
+mod server;
+
+pub use json::Value;
+pub use document::{Decl, DeclKind, Diagnostic, Document, RefKind, Reference, Severity};
+pub use workspace::{Target, Workspace};
+pub use completion::{Completion, CompletionKind, Context, Expected};
+pub use server::Server;
+
+/// Reads the body of the next message, or none at the end of the input.
+pub fn read_message(reader: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "missing `Content-Length` header"));
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_messages() {
+        let mut output = vec![];
+        write_message(&mut output, &Value::object(vec![("id", 1.into())])).unwrap();
+        write_message(&mut output, &Value::object(vec![("name", "é".into())])).unwrap();
+        assert_eq!(String::from_utf8(output.clone()).unwrap(), "Content-Length: 8\r\n\r\n{\"id\":1}Content-Length: 13\r\n\r\n{\"name\":\"é\"}");
+
+        let mut input = output.as_slice();
+        assert_eq!(read_message(&mut input).unwrap().as_deref(), Some("{\"id\":1}"));
+        assert_eq!(read_message(&mut input).unwrap().as_deref(), Some("{\"name\":\"é\"}"));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+}
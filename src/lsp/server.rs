@@ -0,0 +1,541 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use crate::lsp::completion::CompletionKind;
+use crate::lsp::document::{DeclKind, Document, RefKind};
+use crate::lsp::json::Value;
+use crate::lsp::workspace::{Target, Workspace};
+
+const METHOD_NOT_FOUND: isize = -32601;
+const INVALID_PARAMS: isize = -32602;
+const REQUEST_FAILED: isize = -32803;
+
+/// State of a language server session.
+///
+/// Documents the client opened are kept as sent; the other `.mex` files under the workspace
+/// root are loaded from disk on `initialize` so names resolve across the whole project.
+#[derive(Default)]
+pub struct Server {
+    workspace: Workspace,
+    open: BTreeSet<String>,
+    shutdown: bool,
+    exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn workspace(&self) -> &Workspace {
+        &self.workspace
+    }
+
+    /// Status the process should exit with once the client sent `exit`.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.exited {
+            true => Some(if self.shutdown { 0 } else { 1 }),
+            false => None,
+        }
+    }
+
+    /// Responses and notifications to send back for a client message.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let Some(method) = message.get("method").as_str() else {
+            // Responses to server requests; none are sent.
+            return vec![];
+        };
+        let params = message.get("params");
+        let id = message.get("id");
+
+        if id.is_null() {
+            return self.notify(method, params);
+        }
+        let response = match self.request(method, params) {
+            Ok(result) => Value::object(vec![("jsonrpc", "2.0".into()), ("id", id.clone()), ("result", result)]),
+            Err((code, message)) => Value::object(vec![
+                ("jsonrpc", "2.0".into()),
+                ("id", id.clone()),
+                ("error", Value::object(vec![("code", Value::Number(code as f64)), ("message", message.into())])),
+            ]),
+        };
+        vec![response]
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (isize, String)> {
+        match method {
+            "initialize" => {
+                let roots = params.get("workspaceFolders").as_array().iter()
+                    .filter_map(|folder| folder.get("uri").as_str())
+                    .chain(params.get("rootUri").as_str())
+                    .filter_map(uri_to_path)
+                    .collect::<Vec<_>>();
+                if let Some(root) = roots.first() {
+                    self.load(root);
+                }
+                Ok(Value::object(vec![
+                    ("capabilities", Value::object(vec![
+                        ("textDocumentSync", 1.into()),
+                        ("documentSymbolProvider", true.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("referencesProvider", true.into()),
+                        ("completionProvider", Value::object(vec![
+                            ("triggerCharacters", vec![":".into(), "<".into(), "[".into(), ",".into(), ".".into()].into()),
+                        ])),
+                        ("renameProvider", Value::object(vec![("prepareProvider", true.into())])),
+                    ])),
+                    ("serverInfo", Value::object(vec![("name", "mex-lsp".into()), ("version", env!("CARGO_PKG_VERSION").into())])),
+                ]))
+            },
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            },
+            "textDocument/documentSymbol" => {
+                let document = self.document(params)?;
+                Ok(symbols(document, None).into())
+            },
+            "textDocument/hover" => Ok(match self.target(params)? {
+                Some(target) => Value::object(vec![
+                    ("contents", Value::object(vec![("kind", "markdown".into()), ("value", self.hover(&target).into())])),
+                ]),
+                None => Value::Null,
+            }),
+            "textDocument/definition" => Ok(match self.target(params)? {
+                Some(target) => {
+                    let document = self.workspace.get(&target.uri).unwrap();
+                    location(&target.uri, document, &document.decls[target.decl].range)
+                },
+                None => Value::Null,
+            }),
+            "textDocument/references" => {
+                let Some(target) = self.target(params)? else {
+                    return Ok(Value::Array(vec![]));
+                };
+                let mut locations = vec![];
+                if params.get("context").get("includeDeclaration").as_bool().unwrap_or(true) {
+                    let document = self.workspace.get(&target.uri).unwrap();
+                    locations.push(location(&target.uri, document, &document.decls[target.decl].range));
+                }
+                for (uri, range) in self.workspace.references(&target) {
+                    locations.push(location(uri, self.workspace.get(uri).unwrap(), &range));
+                }
+                Ok(locations.into())
+            },
+            "textDocument/completion" => {
+                let document = self.document(params)?;
+                let uri = params.get("textDocument").get("uri").as_str().unwrap_or_default();
+                let offset = self.offset(document, params)?;
+                let items = self.workspace.completions(uri, offset).into_iter().map(|completion| {
+                    let kind: usize = match completion.kind {
+                        CompletionKind::Keyword => 14,
+                        CompletionKind::Decl(DeclKind::Record) => 7,
+                        CompletionKind::Decl(DeclKind::Fragment) => 8,
+                        CompletionKind::Decl(DeclKind::Tuple) => 22,
+                        CompletionKind::Decl(DeclKind::Enum) => 13,
+                        CompletionKind::Decl(DeclKind::Alias) => 18,
+                        CompletionKind::Decl(DeclKind::Generic) => 25,
+                        CompletionKind::Decl(DeclKind::Metadata) => 10,
+                        CompletionKind::Decl(_) => 12,
+                    };
+                    Value::object(vec![("label", completion.label.into()), ("kind", kind.into()), ("detail", completion.detail.into())])
+                });
+                Ok(items.collect::<Vec<_>>().into())
+            },
+            "textDocument/prepareRename" => {
+                let document = self.document(params)?;
+                let offset = self.offset(document, params)?;
+                let span = document.ref_at(offset).map(|i| &document.refs[i].range)
+                    .or_else(|| document.decl_at(offset).map(|i| &document.decls[i].range));
+                Ok(match (self.target(params)?, span) {
+                    (Some(_), Some(span)) => Value::object(vec![
+                        ("range", range(document, span)),
+                        ("placeholder", document.text[span.clone()].into()),
+                    ]),
+                    _ => Value::Null,
+                })
+            },
+            "textDocument/rename" => {
+                let Some(target) = self.target(params)? else {
+                    return Err((REQUEST_FAILED, "nothing to rename here".to_string()));
+                };
+                let name = params.get("newName").as_str().unwrap_or_default();
+                let ranges = self.workspace.rename(&target, name).map_err(|message| (REQUEST_FAILED, message))?;
+
+                let mut changes: Vec<(String, Value)> = vec![];
+                for (uri, span) in ranges {
+                    let edit = Value::object(vec![
+                        ("range", range(self.workspace.get(uri).unwrap(), &span)),
+                        ("newText", name.into()),
+                    ]);
+                    match changes.iter_mut().find(|(other, _)| other == uri) {
+                        Some((_, Value::Array(edits))) => edits.push(edit),
+                        _ => changes.push((uri.to_string(), Value::Array(vec![edit]))),
+                    }
+                }
+                Ok(Value::object(vec![("changes", Value::Object(changes))]))
+            },
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let document = params.get("textDocument");
+        let Some(uri) = document.get("uri").as_str().map(str::to_string) else {
+            if method == "exit" {
+                self.exited = true;
+            }
+            return vec![];
+        };
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = document.get("text").as_str().unwrap_or_default();
+                // The same file loaded from disk under a differently encoded URI.
+                let path = uri_to_path(&uri);
+                let loaded = self.workspace.documents()
+                    .filter(|(other, _)| *other != uri && path.is_some() && uri_to_path(other) == path)
+                    .map(|(other, _)| other.to_string())
+                    .collect::<Vec<_>>();
+                // Open under the other URI too, it is now only open under this one.
+                let closed = loaded.iter().filter(|other| self.open.remove(*other)).map(|other| publish(other, vec![])).collect();
+                loaded.iter().for_each(|other| { self.workspace.remove(other); });
+
+                self.workspace.insert(&uri, text.to_string());
+                self.open.insert(uri);
+                self.publish_all(closed)
+            },
+            "textDocument/didChange" => {
+                let Some(text) = params.get("contentChanges").as_array().last().and_then(|change| change.get("text").as_str()) else {
+                    return vec![];
+                };
+                self.workspace.insert(&uri, text.to_string());
+                self.publish_all(vec![])
+            },
+            "textDocument/didClose" => {
+                self.open.remove(&uri);
+                match uri_to_path(&uri).and_then(|path| std::fs::read_to_string(path).ok()) {
+                    Some(text) => self.workspace.insert(&uri, text),
+                    None => { self.workspace.remove(&uri); },
+                }
+                // Closed documents keep no diagnostics in the editor.
+                self.publish_all(vec![publish(&uri, vec![])])
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Loads the `.mex` files under the directory, skipping hidden ones.
+    fn load(&mut self, dir: &Path) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let mut entries = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect::<Vec<_>>();
+        entries.sort();
+
+        for entry in entries {
+            if entry.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+                continue;
+            }
+            if entry.is_dir() {
+                self.load(&entry);
+            } else if entry.extension().is_some_and(|ext| ext == "mex") {
+                if let Ok(text) = std::fs::read_to_string(&entry) {
+                    self.workspace.insert(&path_to_uri(&entry), text);
+                }
+            }
+        }
+    }
+
+    /// Diagnostics of every open document, as a change to one document may affect the others.
+    fn publish_all(&self, mut messages: Vec<Value>) -> Vec<Value> {
+        for uri in &self.open {
+            let Some(document) = self.workspace.get(uri) else {
+                continue;
+            };
+            let diagnostics = self.workspace.diagnostics(uri).into_iter().map(|diagnostic| Value::object(vec![
+                ("range", range(document, &diagnostic.range)),
+                ("severity", (diagnostic.severity as usize).into()),
+                ("source", "mex".into()),
+                ("message", diagnostic.message.into()),
+            ])).collect();
+            messages.push(publish(uri, diagnostics));
+        }
+        messages
+    }
+
+    fn document(&self, params: &Value) -> Result<&Document, (isize, String)> {
+        let uri = params.get("textDocument").get("uri").as_str().unwrap_or_default();
+        self.workspace.get(uri).ok_or_else(|| (INVALID_PARAMS, format!("unknown document `{}`", uri)))
+    }
+
+    /// Byte offset of the position of the params.
+    fn offset(&self, document: &Document, params: &Value) -> Result<usize, (isize, String)> {
+        let position = params.get("position");
+        match (position.get("line").as_usize(), position.get("character").as_usize()) {
+            (Some(line), Some(character)) => Ok(document.offset(line, character)),
+            _ => Err((INVALID_PARAMS, "missing `position`".to_string())),
+        }
+    }
+
+    /// Declaration at the position of the params.
+    fn target(&self, params: &Value) -> Result<Option<Target>, (isize, String)> {
+        let document = self.document(params)?;
+        let uri = params.get("textDocument").get("uri").as_str().unwrap_or_default();
+        Ok(self.workspace.target_at(uri, self.offset(document, params)?))
+    }
+
+    /// Source of the declaration, its qualified name, the types it uses and its docs.
+    fn hover(&self, target: &Target) -> String {
+        let document = self.workspace.get(&target.uri).unwrap();
+        let decl = &document.decls[target.decl];
+        let mut text = format!("```mex\n{}\n```", decl.detail);
+
+        if decl.kind != DeclKind::Generic {
+            text.push_str(&format!("\n\n`{}`", self.workspace.qualified_name(target)));
+        }
+
+        let mut resolved = vec![];
+        for (i, reference) in document.refs.iter().enumerate() {
+            if reference.owner != Some(target.decl) || matches!(reference.kind, RefKind::Metadata(_)) {
+                continue;
+            }
+            if let Some(model) = self.workspace.resolve(&target.uri, i) {
+                let line = format!("- `{}`: `{}`", reference.name, self.workspace.qualified_name(&model));
+                if !resolved.contains(&line) {
+                    resolved.push(line);
+                }
+            }
+        }
+        if !resolved.is_empty() {
+            text.push_str("\n\n");
+            text.push_str(&resolved.join("\n"));
+        }
+
+        if let Some(docs) = document.docs.get(&decl.doc_path) {
+            text.push_str("\n\n");
+            text.push_str(docs);
+        }
+        text
+    }
+}
+
+/// Hierarchical symbols of the declarations inside `parent`. Generic parameters are left out.
+fn symbols(document: &Document, parent: Option<usize>) -> Vec<Value> {
+    document.children(parent)
+        .filter(|(_, decl)| decl.kind != DeclKind::Generic)
+        .map(|(i, decl)| {
+            let kind: usize = match decl.kind {
+                DeclKind::Package => 4,
+                DeclKind::Record | DeclKind::Tuple => 23,
+                DeclKind::Fragment => 11,
+                DeclKind::Enum => 10,
+                DeclKind::Scalar | DeclKind::Alias => 5,
+                DeclKind::Field | DeclKind::Item => 8,
+                DeclKind::Variant => 22,
+                DeclKind::Metadata | DeclKind::Generic => 7,
+            };
+            let range = range(document, &decl.range);
+            Value::object(vec![
+                ("name", decl.name.as_str().into()),
+                ("detail", decl.detail.lines().next().unwrap_or_default().into()),
+                ("kind", kind.into()),
+                ("range", range.clone()),
+                ("selectionRange", range),
+                ("children", symbols(document, Some(i)).into()),
+            ])
+        })
+        .collect()
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    Value::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        ("params", Value::object(vec![("uri", uri.into()), ("diagnostics", diagnostics.into())])),
+    ])
+}
+
+fn range(document: &Document, range: &Range<usize>) -> Value {
+    let position = |offset| {
+        let (line, character) = document.position(offset);
+        Value::object(vec![("line", line.into()), ("character", character.into())])
+    };
+    Value::object(vec![("start", position(range.start)), ("end", position(range.end))])
+}
+
+fn location(uri: &str, document: &Document, span: &Range<usize>) -> Value {
+    Value::object(vec![("uri", uri.into()), ("range", range(document, span))])
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        let escaped = path.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (path[i], escaped) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                bytes.push(byte);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///tmp/mex-lsp-test/shop.mex";
+
+    const CODE: &str = "package shop;
+
+/// Amount in cents.
+scalar Money
+
+model Item {
+    /// Price of one item.
+    price: Money
+    count: Count
+}";
+
+    fn request(id: usize, method: &str, params: Value) -> Value {
+        Value::object(vec![("jsonrpc", "2.0".into()), ("id", id.into()), ("method", method.into()), ("params", params)])
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        Value::object(vec![("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+    }
+
+    fn at(line: usize, character: usize) -> Value {
+        Value::object(vec![
+            ("textDocument", Value::object(vec![("uri", URI.into())])),
+            ("position", Value::object(vec![("line", line.into()), ("character", character.into())])),
+        ])
+    }
+
+    fn open() -> Server {
+        let mut server = Server::new();
+        server.handle(&request(1, "initialize", Value::object(vec![])));
+        let messages = server.handle(&notification("textDocument/didOpen", Value::object(vec![
+            ("textDocument", Value::object(vec![("uri", URI.into()), ("text", CODE.into())])),
+        ])));
+
+        let diagnostics = messages[0].get("params").get("diagnostics").as_array();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].get("message").as_str(), Some("unknown type `Count`"));
+        assert_eq!(diagnostics[0].get("range").to_string(), r#"{"start":{"line":8,"character":11},"end":{"line":8,"character":16}}"#);
+        server
+    }
+
+    #[test]
+    fn check_hover() {
+        let mut server = open();
+        let response = server.handle(&request(2, "textDocument/hover", at(7, 5)));
+        let hover = response[0].get("result").get("contents").get("value").as_str().unwrap();
+        assert_eq!(hover, "```mex\nprice: Money\n```\n\n`shop.Item.price`\n\n- `Money`: `shop.Money`\n\nPrice of one item.");
+    }
+
+    #[test]
+    fn check_navigation() {
+        let mut server = open();
+        let definition = server.handle(&request(2, "textDocument/definition", at(7, 12)));
+        assert_eq!(definition[0].get("result").get("range").get("start").to_string(), r#"{"line":3,"character":7}"#);
+
+        let references = server.handle(&request(3, "textDocument/references", at(3, 8)));
+        let lines = references[0].get("result").as_array().iter()
+            .map(|location| location.get("range").get("start").get("line").as_usize().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [3, 7]);
+
+        let symbols = server.handle(&request(4, "textDocument/documentSymbol", at(0, 0)));
+        let package = &symbols[0].get("result").as_array()[0];
+        assert_eq!(package.get("name").as_str(), Some("shop"));
+        assert_eq!(package.get("children").as_array().len(), 2);
+    }
+
+    #[test]
+    fn check_editing() {
+        let mut server = open();
+        let completion = server.handle(&request(2, "textDocument/completion", at(7, 12)));
+        let items = completion[0].get("result").as_array();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].to_string(), r#"{"label":"Money","kind":12,"detail":"shop.Money"}"#);
+
+        let prepare = server.handle(&request(3, "textDocument/prepareRename", at(7, 13)));
+        assert_eq!(prepare[0].get("result").get("placeholder").as_str(), Some("Money"));
+
+        let mut params = at(3, 8);
+        if let Value::Object(entries) = &mut params {
+            entries.push(("newName".to_string(), "Cents".into()));
+        }
+        let rename = server.handle(&request(4, "textDocument/rename", params));
+        let edits = rename[0].get("result").get("changes").get(URI).as_array();
+        assert_eq!(edits.iter().map(|edit| edit.get("range").get("start").get("line").as_usize().unwrap()).collect::<Vec<_>>(), [3, 7]);
+        assert_eq!(edits[0].get("newText").as_str(), Some("Cents"));
+    }
+
+    #[test]
+    fn check_lifecycle() {
+        let mut server = open();
+        let unknown = server.handle(&request(2, "workspace/unknown", Value::Null));
+        assert_eq!(unknown[0].get("error").get("code"), &Value::Number(-32601.0));
+
+        let closed = server.handle(&notification("textDocument/didClose", Value::object(vec![
+            ("textDocument", Value::object(vec![("uri", URI.into())])),
+        ])));
+        assert_eq!(closed[0].get("params").get("diagnostics").as_array().len(), 0);
+        assert!(server.workspace().get(URI).is_none());
+
+        server.handle(&request(3, "shutdown", Value::Null));
+        assert_eq!(server.exit_code(), None);
+        server.handle(&notification("exit", Value::Null));
+        assert_eq!(server.exit_code(), Some(0));
+    }
+
+    #[test]
+    fn check_reopen_under_other_uri() {
+        let mut server = Server::new();
+        server.handle(&request(1, "initialize", Value::object(vec![])));
+        for uri in ["file:///tmp/a%20b.mex", "file:///tmp/a b.mex"] {
+            server.handle(&notification("textDocument/didOpen", Value::object(vec![
+                ("textDocument", Value::object(vec![("uri", uri.into()), ("text", CODE.into())])),
+            ])));
+        }
+
+        let changed = server.handle(&notification("textDocument/didChange", Value::object(vec![
+            ("textDocument", Value::object(vec![("uri", "file:///tmp/a b.mex".into())])),
+            ("contentChanges", Value::Array(vec![Value::object(vec![("text", CODE.into())])])),
+        ])));
+        assert_eq!(changed.len(), 1);
+        assert!(server.workspace().get("file:///tmp/a%20b.mex").is_none());
+    }
+
+    #[test]
+    fn check_uris() {
+        let uri = "file:///tmp/a%20b/%C3%A9.mex";
+        assert_eq!(uri_to_path(uri), Some(PathBuf::from("/tmp/a b/é.mex")));
+        assert_eq!(uri_to_path("untitled:1"), None);
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use crate::ast::{is_valid_name, resolve_name};
+use crate::lsp::document::{Decl, DeclKind, Diagnostic, Document, RefKind, Severity};
+use crate::transform::TransformError;
+
+/// Declaration in a document of the workspace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Target {
+    pub uri: String,
+    pub decl: usize,
+}
+
+/// Documents of the workspace, keyed by URI, with names resolved across all of them.
+///
+/// A type name resolves to a generic parameter in scope, then to a model by `resolve_name`,
+/// the rule the transformers resolve by, preferring the same document.
+#[derive(Default)]
+pub struct Workspace {
+    documents: BTreeMap<String, Document>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, uri: &str, text: String) {
+        self.documents.insert(uri.to_string(), Document::new(text));
+    }
+
+    pub fn remove(&mut self, uri: &str) -> Option<Document> {
+        self.documents.remove(uri)
+    }
+
+    pub fn get(&self, uri: &str) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = (&str, &Document)> {
+        self.documents.iter().map(|(uri, document)| (uri.as_str(), document))
+    }
+
+    pub fn decl(&self, target: &Target) -> Option<&Decl> {
+        self.documents.get(&target.uri).and_then(|document| document.decls.get(target.decl))
+    }
+
+    /// Declaration the reference with the index in the document refers to.
+    pub fn resolve(&self, uri: &str, reference: usize) -> Option<Target> {
+        self.resolve_renamed(uri, reference, None)
+    }
+
+    /// Model named `name` as seen from package `path` of the document `uri`, see `resolve_name`.
+    /// The declarations of the document come first, so they win a tie.
+    pub fn find_model(&self, name: &str, path: &[String], uri: &str) -> Option<Target> {
+        self.find_model_renamed(name, path, uri, None)
+    }
+
+    /// Like `resolve`, as if the `renamed` declaration and the references to it had the new name.
+    fn resolve_renamed(&self, uri: &str, reference: usize, renamed: Option<(&Target, &str)>) -> Option<Target> {
+        let document = self.documents.get(uri)?;
+        let name = match renamed {
+            Some((target, name)) if self.resolve(uri, reference).as_ref() == Some(target) => name,
+            _ => document.refs.get(reference)?.name.as_str(),
+        };
+        let reference = document.refs.get(reference)?;
+
+        match reference.kind {
+            RefKind::Metadata(owner) => {
+                let model = self.resolve_renamed(uri, owner, renamed)?;
+                self.documents[&model.uri].children(Some(model.decl))
+                    .find(|(i, decl)| decl.kind == DeclKind::Metadata && decl_name(&model.uri, *i, decl, renamed) == name)
+                    .map(|(decl, _)| Target { uri: model.uri.clone(), decl })
+            },
+            RefKind::Type | RefKind::Spread => {
+                let generic = reference.generics.iter().rev()
+                    .find(|&&generic| decl_name(uri, generic, &document.decls[generic], renamed) == name);
+                match generic {
+                    Some(&decl) => Some(Target { uri: uri.to_string(), decl }),
+                    None => self.find_model_renamed(name, &reference.path, uri, renamed),
+                }
+            },
+        }
+    }
+
+    /// Like `find_model`, as if the `renamed` declaration had the new name.
+    fn find_model_renamed(&self, name: &str, path: &[String], uri: &str, renamed: Option<(&Target, &str)>) -> Option<Target> {
+        let documents = self.documents.get_key_value(uri).into_iter()
+            .chain(self.documents.iter().filter(|(document_uri, _)| *document_uri != uri));
+        let declarations = documents.flat_map(|(document_uri, document)| {
+            document.decls.iter().enumerate()
+                .filter(move |(i, decl)| {
+                    let is_top = decl.parent.is_none_or(|parent| document.decls[parent].kind == DeclKind::Package);
+                    decl.kind.is_model() && is_top && decl_name(document_uri, *i, decl, renamed) == name
+                })
+                .map(move |(i, decl)| (Target { uri: document_uri.clone(), decl: i }, decl.path.as_slice()))
+        });
+        resolve_name(path, declarations)
+    }
+
+    /// Declaration at the offset, or the one the reference at the offset refers to.
+    pub fn target_at(&self, uri: &str, offset: usize) -> Option<Target> {
+        let document = self.documents.get(uri)?;
+        match document.ref_at(offset) {
+            Some(reference) => self.resolve(uri, reference),
+            None => document.decl_at(offset).map(|decl| Target { uri: uri.to_string(), decl }),
+        }
+    }
+
+    /// Ranges of every reference to the declaration, by document.
+    pub fn references(&self, target: &Target) -> Vec<(&str, Range<usize>)> {
+        let mut found = vec![];
+        for (uri, document) in &self.documents {
+            for (i, reference) in document.refs.iter().enumerate() {
+                if self.resolve(uri, i).as_ref() == Some(target) {
+                    found.push((uri.as_str(), reference.range.clone()));
+                }
+            }
+        }
+        found
+    }
+
+    /// Ranges to replace with `name` to rename the declaration and every reference to it.
+    pub fn rename(&self, target: &Target, name: &str) -> Result<Vec<(&str, Range<usize>)>, String> {
+        if !is_valid_name(name) {
+            return Err(format!("`{}` is not a valid name", name));
+        }
+
+        let (uri, document) = self.documents.get_key_value(&target.uri).ok_or_else(|| format!("unknown document `{}`", target.uri))?;
+        let decl = document.decls.get(target.decl).ok_or_else(|| "unknown declaration".to_string())?;
+        if decl.name == name {
+            return Ok(vec![]);
+        }
+
+        let clashes = document.decls.iter().any(|other| {
+            other.parent == decl.parent && other.name == name && namespace(other.kind) == namespace(decl.kind)
+        });
+        let is_top = |document: &Document, decl: &Decl| {
+            decl.kind.is_model() && decl.parent.is_none_or(|parent| document.decls[parent].kind == DeclKind::Package)
+        };
+        let clashes_across = is_top(document, decl) && self.documents.values().any(|other| {
+            other.decls.iter().any(|other_decl| is_top(other, other_decl) && other_decl.name == name && other_decl.path == decl.path)
+        });
+        if clashes || clashes_across {
+            return Err(format!("`{}` is already declared", name));
+        }
+
+        // Under the new name every reference has to keep its declaration, neither leaving this
+        // one for another nor coming to this one from another, a generic or no declaration.
+        let renamed = Some((target, name));
+        let rebound = self.documents.iter().find_map(|(uri, document)| {
+            (0..document.refs.len()).find(|&i| self.resolve_renamed(uri, i, renamed) != self.resolve(uri, i)).map(|i| &document.refs[i])
+        });
+        if let Some(reference) = rebound {
+            return Err(format!("renaming to `{}` would change what `{}` in package `{}` refers to", name, reference.name, reference.path.join(".")));
+        }
+
+        let mut ranges = vec![(uri.as_str(), decl.range.clone())];
+        ranges.extend(self.references(target));
+        Ok(ranges)
+    }
+
+    /// Dotted path of packages, models and parts down to the declaration.
+    pub fn qualified_name(&self, target: &Target) -> String {
+        let document = &self.documents[&target.uri];
+        let mut names = vec![];
+        let mut decl = Some(target.decl);
+        while let Some(i) = decl {
+            if document.decls[i].kind != DeclKind::Package {
+                names.push(document.decls[i].name.as_str());
+            }
+            decl = document.decls[i].parent;
+        }
+
+        let decl = &document.decls[target.decl];
+        decl.path.iter().map(String::as_str).chain(names.into_iter().rev()).collect::<Vec<_>>().join(".")
+    }
+
+    /// Syntax errors of the document, and the names it cannot resolve or declares twice.
+    pub fn diagnostics(&self, uri: &str) -> Vec<Diagnostic> {
+        let Some(document) = self.documents.get(uri) else {
+            return vec![];
+        };
+        let mut diagnostics = document.errors.clone();
+        let mut push = |range: Range<usize>, severity, message: String| {
+            diagnostics.push(Diagnostic { range, severity, message });
+        };
+
+        for (i, reference) in document.refs.iter().enumerate() {
+            let target = self.resolve(uri, i);
+            match (reference.kind, &target) {
+                (RefKind::Type | RefKind::Spread, None) => {
+                    push(reference.range.clone(), Severity::Warning, TransformError::UnknownType(reference.name.clone()).to_string());
+                },
+                (RefKind::Metadata(owner), None) => {
+                    if let Some(model) = self.resolve(uri, owner) {
+                        let model = self.qualified_name(&model);
+                        push(reference.range.clone(), Severity::Warning, format!("`{}` has no metadata `{}`", model, reference.name));
+                    }
+                },
+                (RefKind::Spread, Some(target)) => {
+                    let model = reference.model.map(|decl| Target { uri: uri.to_string(), decl });
+                    if model.is_some_and(|model| *target == model || self.spreads_reach(target, &model, &mut vec![])) {
+                        push(reference.range.clone(), Severity::Error, TransformError::RecursiveSpread(reference.name.clone()).to_string());
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        for (i, decl) in document.decls.iter().enumerate() {
+            let duplicate = document.decls[..i].iter().any(|other| {
+                other.parent == decl.parent && other.name == decl.name && namespace(other.kind) == namespace(decl.kind)
+            });
+            if duplicate && decl.kind != DeclKind::Package {
+                push(decl.range.clone(), Severity::Error, format!("duplicate declaration `{}`", decl.name));
+            }
+        }
+
+        diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+        diagnostics
+    }
+
+    /// Whether `goal` is spread by `from`, directly or through other spreads.
+    fn spreads_reach(&self, from: &Target, goal: &Target, seen: &mut Vec<Target>) -> bool {
+        if seen.contains(from) {
+            return false;
+        }
+        seen.push(from.clone());
+
+        let document = &self.documents[&from.uri];
+        document.refs.iter().enumerate()
+            .filter(|(_, reference)| reference.kind == RefKind::Spread && reference.model == Some(from.decl))
+            .filter_map(|(i, _)| self.resolve(&from.uri, i))
+            .any(|target| target == *goal || self.spreads_reach(&target, goal, seen))
+    }
+}
+
+/// Declarations of the same parent clash only within a namespace.
+fn namespace(kind: DeclKind) -> u8 {
+    match kind {
+        DeclKind::Generic | DeclKind::Metadata => 0,
+        DeclKind::Field | DeclKind::Item | DeclKind::Variant => 1,
+        _ => 2,
+    }
+}
+
+/// Name of the declaration with the index in the document, or the new one if it is renamed.
+fn decl_name<'a>(uri: &str, decl: usize, value: &'a Decl, renamed: Option<(&Target, &'a str)>) -> &'a str {
+    match renamed {
+        Some((target, name)) if target.uri == uri && target.decl == decl => name,
+        _ => &value.name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMON: &str = "package common;
+
+scalar Money
+scalar Int
+scalar Bytes
+scalar DateTime
+
+fragment Audit {
+    ... Owned
+    created: DateTime
+}
+
+fragment Owned {
+    ... Audit
+}";
+
+    const SHOP: &str = "package shop;
+
+scalar Money
+
+model Item<T> {
+    price: Money
+    tags: List<T>
+    ... Audit
+}
+
+model Hash[len: Int = 8] = Bytes
+
+model User {
+    hash: Hash[len=4, max=8]
+    id: Int
+    id: Uuid
+}";
+
+    fn workspace() -> Workspace {
+        let mut workspace = Workspace::new();
+        workspace.insert("file:///common.mex", COMMON.to_string());
+        workspace.insert("file:///shop.mex", SHOP.to_string());
+        workspace
+    }
+
+    fn target_name(workspace: &Workspace, uri: &str, text: &str, nth: usize) -> Option<String> {
+        let document = workspace.get(uri).unwrap();
+        let offset = document.text.match_indices(text).nth(nth).unwrap().0;
+        workspace.target_at(uri, offset).map(|target| format!("{} {}", target.uri, workspace.qualified_name(&target)))
+    }
+
+    #[test]
+    fn check_resolve() {
+        let workspace = workspace();
+        let shop = "file:///shop.mex";
+        assert_eq!(target_name(&workspace, shop, "Money", 1).as_deref(), Some("file:///shop.mex shop.Money"));
+        assert_eq!(target_name(&workspace, shop, "T", 1).as_deref(), Some("file:///shop.mex shop.Item.T"));
+        assert_eq!(target_name(&workspace, shop, "Audit", 0).as_deref(), Some("file:///common.mex common.Audit"));
+        assert_eq!(target_name(&workspace, shop, "len", 1).as_deref(), Some("file:///shop.mex shop.Hash.len"));
+        assert_eq!(target_name(&workspace, shop, "List", 0), None);
+    }
+
+    #[test]
+    fn check_references() {
+        let workspace = workspace();
+        let audit = workspace.find_model("Audit", &[], "file:///common.mex").unwrap();
+        let references = workspace.references(&audit).into_iter().map(|(uri, range)| (uri, range.start)).collect::<Vec<_>>();
+        assert_eq!(references, [("file:///common.mex", COMMON.rfind("Audit").unwrap()), ("file:///shop.mex", SHOP.find("Audit").unwrap())]);
+    }
+
+    #[test]
+    fn check_rename() {
+        let workspace = workspace();
+        let audit = workspace.find_model("Audit", &[], "file:///common.mex").unwrap();
+        let ranges = workspace.rename(&audit, "Trail").unwrap().into_iter().map(|(uri, range)| (uri, range.start)).collect::<Vec<_>>();
+        assert_eq!(ranges, [
+            ("file:///common.mex", COMMON.find("Audit").unwrap()),
+            ("file:///common.mex", COMMON.rfind("Audit").unwrap()),
+            ("file:///shop.mex", SHOP.find("Audit").unwrap()),
+        ]);
+
+        assert_eq!(workspace.rename(&audit, "Owned"), Err("`Owned` is already declared".to_string()));
+        assert_eq!(workspace.rename(&audit, "model"), Err("`model` is not a valid name".to_string()));
+        assert_eq!(workspace.rename(&audit, "1st"), Err("`1st` is not a valid name".to_string()));
+        assert_eq!(workspace.rename(&audit, "Money"), Err("`Money` is already declared".to_string()));
+        assert_eq!(workspace.rename(&audit, "Item"), Err("renaming to `Item` would change what `Audit` in package `shop` refers to".to_string()));
+
+        let money = workspace.find_model("Money", &["shop".to_string()], "file:///shop.mex").unwrap();
+        assert_eq!(workspace.rename(&money, "T"), Err("renaming to `T` would change what `Money` in package `shop` refers to".to_string()));
+        assert_eq!(workspace.rename(&money, "List"), Err("renaming to `List` would change what `List` in package `shop` refers to".to_string()));
+        assert!(workspace.rename(&money, "Cents").is_ok());
+    }
+
+    #[test]
+    fn check_diagnostics() {
+        let workspace = workspace();
+        let messages = |uri| workspace.diagnostics(uri).into_iter().map(|d| (d.severity, d.message)).collect::<Vec<_>>();
+
+        assert_eq!(messages("file:///common.mex"), [
+            (Severity::Error, "recursive spread of `Owned`".to_string()),
+            (Severity::Error, "recursive spread of `Audit`".to_string()),
+        ]);
+        assert_eq!(messages("file:///shop.mex"), [
+            (Severity::Warning, "unknown type `List`".to_string()),
+            (Severity::Warning, "`shop.Hash` has no metadata `max`".to_string()),
+            (Severity::Error, "duplicate declaration `id`".to_string()),
+            // Scalars are declared like any other model, as the transformers reject undeclared ones.
+            (Severity::Warning, "unknown type `Uuid`".to_string()),
+        ]);
+    }
+}
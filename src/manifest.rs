@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::ast::Error;
+use crate::transform::TransformError;
+
+/// Project manifest, a `mex.toml` file in the project root.
+///
+/// Only the subset of TOML the settings need is read: `[section]` headers and `key = value`
+/// entries with bare keys, and values that are basic (`"..."`) or literal (`'...'`) strings,
+/// numbers or booleans, with `#` comments. Dotted keys, quoted keys, arrays, inline tables,
+/// `[[table]]` headers, multi-line strings and repeated keys or sections are rejected with the
+/// line they are on.
+///
+/// ```toml
+/// [format]
+/// width = 100
+/// indent = "tab"
+/// ```
+#[derive(Debug, Default)]
+pub struct Manifest {
+    sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Manifest {
+    pub const FILE_NAME: &'static str = "mex.toml";
+
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<'static, Self> {
+        let text = std::fs::read_to_string(path)?;
+        text.parse().map_err(|error| Error::Transform(vec![error]))
+    }
+
+    /// Nearest manifest in `dir` or one of its ancestors.
+    pub fn find<P: AsRef<Path>>(dir: P) -> Option<PathBuf> {
+        dir.as_ref().ancestors()
+            .map(|dir| dir.join(Self::FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Raw value of an entry, with the quotes of a string removed.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections.get(section).and_then(|entries| entries.get(key)).map(String::as_str)
+    }
+
+    /// Entries of a section in key order.
+    pub fn entries(&self, section: &str) -> impl Iterator<Item = (&str, &str)> {
+        self.sections.get(section).into_iter()
+            .flat_map(|entries| entries.iter().map(|(key, value)| (key.as_str(), value.as_str())))
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = TransformError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut manifest = Manifest::default();
+        let mut section = String::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let error = |reason: String| TransformError::InvalidManifestSyntax(i + 1, reason);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                if header.starts_with('[') {
+                    return Err(error("arrays of tables are not supported".to_string()));
+                }
+                let (name, rest) = header.split_once(']').ok_or_else(|| error("`[` is not closed".to_string()))?;
+                let name = Self::key(name.trim()).map_err(error)?;
+                Self::end(rest).map_err(error)?;
+                if manifest.sections.contains_key(name) {
+                    return Err(error(format!("duplicate section `[{}]`", name)));
+                }
+                manifest.sections.insert(name.to_string(), BTreeMap::new());
+                section = name.to_string();
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| error(format!("expected `key = value`, found `{}`", line)))?;
+            let key = Self::key(key.trim()).map_err(error)?;
+            let value = Self::value(value.trim()).map_err(error)?;
+
+            let entries = manifest.sections.entry(section.clone()).or_default();
+            if entries.insert(key.to_string(), value).is_some() {
+                return Err(error(format!("duplicate key `{}`", key)));
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+impl Manifest {
+    fn key(key: &str) -> Result<&str, String> {
+        match key {
+            "" => Err("expected a key".to_string()),
+            _ if key.contains('.') => Err(format!("dotted key `{}` is not supported", key)),
+            _ if key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') => Ok(key),
+            _ => Err(format!("`{}` is not a bare key", key)),
+        }
+    }
+
+    /// Value of an entry with the rest of the line, strings unquoted and unescaped.
+    fn value(text: &str) -> Result<String, String> {
+        let mut chars = text.chars();
+        let value = match chars.next() {
+            Some('"') => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => value.push(match chars.next() {
+                            Some('"') => '"',
+                            Some('\\') => '\\',
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('r') => '\r',
+                            Some(c) => return Err(format!("unsupported escape `\\{}`", c)),
+                            None => return Err("string is not closed".to_string()),
+                        }),
+                        Some(c) => value.push(c),
+                        None => return Err("string is not closed".to_string()),
+                    }
+                }
+                value
+            },
+            Some('\'') => {
+                let value = chars.by_ref().take_while(|c| *c != '\'').collect::<String>();
+                if !text[1..].contains('\'') {
+                    return Err("string is not closed".to_string());
+                }
+                value
+            },
+            Some('[' | '{') => return Err("arrays and inline tables are not supported".to_string()),
+            _ => {
+                let value = text.split('#').next().unwrap_or_default().trim();
+                if value.is_empty() {
+                    return Err("expected a value".to_string());
+                }
+                if !value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '.' | ':')) {
+                    return Err(format!("unsupported value `{}`", value));
+                }
+                return Ok(value.to_string());
+            },
+        };
+
+        Self::end(chars.as_str())?;
+        Ok(value)
+    }
+
+    /// Checks that nothing but a comment follows.
+    fn end(rest: &str) -> Result<(), String> {
+        let rest = rest.trim();
+        match rest.is_empty() || rest.starts_with('#') {
+            true => Ok(()),
+            false => Err(format!("unexpected `{}`", rest)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("[format]\nwidth = 80", "format", "width", Some("80"); "number")]
+    #[test_case("# style\n[format]\nindent = \"tab\" # tabs", "format", "indent", Some("tab"); "string with comment")]
+    #[test_case("[format] # style\nwidth = 80 # wide", "format", "width", Some("80"); "header with comment")]
+    #[test_case("name = \"say \\\"hi\\\" # not a comment\"", "", "name", Some("say \"hi\" # not a comment"); "escaped quotes")]
+    #[test_case("name = 'C:\\shop'", "", "name", Some("C:\\shop"); "literal string")]
+    #[test_case("name = \"shop\"\n[format]\nwidth = 80", "", "name", Some("shop"); "root entry")]
+    #[test_case("[format]\nwidth = 80", "lint", "width", None; "missing section")]
+    fn check(text: &str, section: &str, key: &str, expected: Option<&str>) {
+        let manifest = text.parse::<Manifest>().unwrap();
+        assert_eq!(manifest.get(section, key), expected);
+    }
+
+    #[test_case("[format", "line 1: `[` is not closed"; "unclosed section")]
+    #[test_case("[format] width = 80", "line 1: unexpected `width = 80`"; "entry after header")]
+    #[test_case("width", "line 1: expected `key = value`, found `width`"; "missing value")]
+    #[test_case("name = \"shop", "line 1: string is not closed"; "unclosed string")]
+    #[test_case("name = \"shop\" x", "line 1: unexpected `x`"; "text after string")]
+    #[test_case("name = \"a\\q\"", "line 1: unsupported escape `\\q`"; "unknown escape")]
+    #[test_case("[format]\nindent.size = 2", "line 2: dotted key `indent.size` is not supported"; "dotted key")]
+    #[test_case("[format.rules]", "line 1: dotted key `format.rules` is not supported"; "dotted section")]
+    #[test_case("[[format]]", "line 1: arrays of tables are not supported"; "array of tables")]
+    #[test_case("tags = [\"a\"]", "line 1: arrays and inline tables are not supported"; "array")]
+    #[test_case("[format]\nwidth = 80\n\nwidth = 100", "line 4: duplicate key `width`"; "duplicate key")]
+    #[test_case("[format]\n[lint]\n[format]", "line 3: duplicate section `[format]`"; "duplicate section")]
+    fn check_errors(text: &str, expected: &str) {
+        let error = text.parse::<Manifest>().unwrap_err();
+        assert_eq!(error.to_string(), format!("invalid manifest: {}", expected));
+    }
+}
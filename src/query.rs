@@ -0,0 +1,295 @@
+//! Questions about a set of models, answered from a resolved scope tree.
+//!
+//! [`ModelSet`] resolves every type reference of every model once, the way
+//! [`ScopeTree::resolve`] sees it, and keeps the reference and spread graphs in both directions:
+//! which models use `DateTime`, which records spread `AuditTrail`, what the variants of
+//! `CommonStatus` are.
+
+use std::collections::{HashMap, HashSet};
+use crate::ast::owned::{EnumItem, Id, ItemType, ModelDefinition, ModelParamDefinition, RecordItem};
+use crate::ast::visit::{walk_item_type, walk_model, walk_record_item, Visitor};
+use crate::ast::{RefScope, ScopeId, ScopeTree, Symbol};
+use crate::transform::model_index::ModelKind;
+
+/// Type name a model uses that no scope declares, such as an undeclared scalar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unresolved {
+    pub model: ScopeId,
+    pub name: Symbol,
+}
+
+pub struct ModelSet {
+    tree: ScopeTree,
+    references: HashMap<ScopeId, Vec<ScopeId>>,
+    referenced_by: HashMap<ScopeId, Vec<ScopeId>>,
+    spreads: HashMap<ScopeId, Vec<ScopeId>>,
+    spread_by: HashMap<ScopeId, Vec<ScopeId>>,
+    unresolved: Vec<Unresolved>,
+}
+
+impl ModelSet {
+    pub fn new(tree: ScopeTree) -> Self {
+        let mut set = ModelSet {
+            tree,
+            references: HashMap::new(),
+            referenced_by: HashMap::new(),
+            spreads: HashMap::new(),
+            spread_by: HashMap::new(),
+            unresolved: vec![],
+        };
+
+        let models = set.tree.models().map(|(id, _)| id).collect::<Vec<_>>();
+        for model in models {
+            let mut collector = Collector::default();
+            collector.visit_model(set.tree[model].as_model().unwrap());
+
+            for (name, is_spread) in collector.names {
+                match set.tree.resolve(model, &name) {
+                    Some(target) => {
+                        push_unique(set.references.entry(model).or_default(), target);
+                        push_unique(set.referenced_by.entry(target).or_default(), model);
+                        if is_spread {
+                            push_unique(set.spreads.entry(model).or_default(), target);
+                            push_unique(set.spread_by.entry(target).or_default(), model);
+                        }
+                    },
+                    None if set.unresolved.iter().any(|u| u.model == model && u.name == name) => {},
+                    None => set.unresolved.push(Unresolved { model, name }),
+                }
+            }
+        }
+        set
+    }
+
+    /// Parses the code into a set. Recovered syntax errors are kept in the tree as `Error` scopes.
+    pub fn parse(code: &str) -> crate::Result<'static, Self> {
+        ScopeTree::parse(code).map(ModelSet::new)
+    }
+
+    pub fn from_ast(scope: &RefScope) -> Self {
+        ModelSet::new(ScopeTree::from_ast(scope))
+    }
+
+    pub fn tree(&self) -> &ScopeTree {
+        &self.tree
+    }
+
+    /// Model or package at a dotted path such as `shop.orders.Order`.
+    pub fn find(&self, qualified_name: &str) -> Option<ScopeId> {
+        let path = qualified_name.split('.').map(Symbol::intern).collect::<Vec<_>>();
+        self.tree.find(&path)
+    }
+
+    pub fn qualified_name(&self, id: ScopeId) -> String {
+        self.tree.path(id).iter().map(|name| name.as_str()).collect::<Vec<_>>().join(".")
+    }
+
+    pub fn model(&self, id: ScopeId) -> Option<&ModelDefinition> {
+        self.tree.get(id).and_then(|node| node.as_model())
+    }
+
+    pub fn kind(&self, id: ScopeId) -> Option<ModelKind> {
+        self.model(id).map(kind)
+    }
+
+    /// Models in declaration order.
+    pub fn models(&self) -> impl Iterator<Item = (ScopeId, &ModelDefinition)> {
+        self.tree.models()
+    }
+
+    pub fn models_of_kind(&self, model_kind: ModelKind) -> impl Iterator<Item = (ScopeId, &ModelDefinition)> {
+        self.models().filter(move |(_, def)| kind(def) == model_kind)
+    }
+
+    /// Models the model uses directly, in the order they first appear.
+    pub fn references(&self, id: ScopeId) -> &[ScopeId] {
+        self.references.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Models using the model directly, in declaration order.
+    pub fn referenced_by(&self, id: ScopeId) -> &[ScopeId] {
+        self.referenced_by.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Records and fragments the model spreads directly.
+    pub fn spreads(&self, id: ScopeId) -> &[ScopeId] {
+        self.spreads.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Records and fragments spreading the model directly.
+    pub fn spread_by(&self, id: ScopeId) -> &[ScopeId] {
+        self.spread_by.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Everything the model spreads, directly or through other spreads, nearest first.
+    pub fn all_spreads(&self, id: ScopeId) -> Vec<ScopeId> {
+        closure(id, |id| self.spreads(id))
+    }
+
+    /// Every model spreading the model, directly or through other spreads, nearest first.
+    pub fn all_spread_by(&self, id: ScopeId) -> Vec<ScopeId> {
+        closure(id, |id| self.spread_by(id))
+    }
+
+    /// Variants of an enum, empty for other models.
+    pub fn variants(&self, id: ScopeId) -> &[EnumItem] {
+        match self.model(id) {
+            Some(ModelDefinition::Enum(_, items, _)) => items,
+            _ => &[],
+        }
+    }
+
+    pub fn unresolved(&self) -> &[Unresolved] {
+        &self.unresolved
+    }
+}
+
+fn kind(def: &ModelDefinition) -> ModelKind {
+    match def {
+        ModelDefinition::Scalar(_) => ModelKind::Scalar,
+        ModelDefinition::Record(..) => ModelKind::Record,
+        ModelDefinition::Fragment(..) => ModelKind::Fragment,
+        ModelDefinition::Tuple(..) => ModelKind::Tuple,
+        ModelDefinition::Enum(..) => ModelKind::Enum,
+        ModelDefinition::Alias(..) => ModelKind::Alias,
+    }
+}
+
+fn push_unique(ids: &mut Vec<ScopeId>, id: ScopeId) {
+    if !ids.contains(&id) {
+        ids.push(id);
+    }
+}
+
+/// Nodes reachable from `start` breadth first, without `start` itself.
+fn closure<'a>(start: ScopeId, next: impl Fn(ScopeId) -> &'a [ScopeId]) -> Vec<ScopeId> {
+    let mut seen = HashSet::from([start]);
+    let mut found = vec![];
+    let mut i = 0;
+
+    found.extend(next(start).iter().copied().filter(|id| seen.insert(*id)));
+    while i < found.len() {
+        let ids = next(found[i]).iter().copied().filter(|id| seen.insert(*id)).collect::<Vec<_>>();
+        found.extend(ids);
+        i += 1;
+    }
+    found
+}
+
+/// Type names used by a model, with whether they are spread, skipping its generic parameters.
+#[derive(Default)]
+struct Collector {
+    generics: Vec<Symbol>,
+    names: Vec<(Symbol, bool)>,
+    spread: bool,
+}
+
+impl<'ast> Visitor<'ast> for Collector {
+    fn visit_model(&mut self, def: &'ast ModelDefinition) {
+        let params = match def {
+            ModelDefinition::Fragment(_, _, params) | ModelDefinition::Record(_, _, params) |
+            ModelDefinition::Tuple(_, _, params) | ModelDefinition::Enum(_, _, params) |
+            ModelDefinition::Alias(_, params, _) => params.as_slice(),
+            ModelDefinition::Scalar(_) => &[],
+        };
+        self.generics.extend(params.iter().filter_map(|param| match param {
+            ModelParamDefinition::Generic { id, .. } => id.as_name(),
+            _ => None,
+        }));
+        walk_model(self, def)
+    }
+
+    fn visit_record_item(&mut self, item: &'ast RecordItem) {
+        self.spread = matches!(item, RecordItem::Spread(_));
+        walk_record_item(self, item);
+        self.spread = false;
+    }
+
+    fn visit_item_type(&mut self, item_type: &'ast ItemType) {
+        if let ItemType::Model(Id::Name(name), _) = item_type {
+            if !self.generics.contains(name) {
+                self.names.push((name.clone(), self.spread));
+            }
+        }
+        // Only the spread type itself is spread, not its arguments.
+        self.spread = false;
+        walk_item_type(self, item_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "package shop;
+
+scalar DateTime
+scalar Int
+
+fragment AuditTrail {
+    created: DateTime
+    updated: DateTime?
+}
+
+fragment Owned {
+    ... AuditTrail
+    owner: Int
+}
+
+model CommonStatus enum {
+    Active
+    Blocked(DateTime)
+}
+
+model Page<T> {
+    items: List<T>
+}
+
+package orders {
+    model Order {
+        ... Owned
+        status: CommonStatus
+        lines: Page<Line>
+    }
+
+    model Line(Int, Int)
+}";
+
+    fn names(set: &ModelSet, ids: &[ScopeId]) -> Vec<String> {
+        ids.iter().map(|id| set.qualified_name(*id)).collect()
+    }
+
+    #[test]
+    fn check_references() {
+        let set = ModelSet::parse(CODE).unwrap();
+        let date_time = set.find("shop.DateTime").unwrap();
+        let order = set.find("shop.orders.Order").unwrap();
+
+        assert_eq!(names(&set, set.referenced_by(date_time)), ["shop.AuditTrail", "shop.CommonStatus"]);
+        assert_eq!(names(&set, set.references(order)), ["shop.Owned", "shop.CommonStatus", "shop.Page", "shop.orders.Line"]);
+        assert_eq!(set.unresolved().iter().map(|u| u.name.as_str()).collect::<Vec<_>>(), ["List"]);
+    }
+
+    #[test]
+    fn check_spreads() {
+        let set = ModelSet::parse(CODE).unwrap();
+        let audit = set.find("shop.AuditTrail").unwrap();
+        let order = set.find("shop.orders.Order").unwrap();
+
+        assert_eq!(names(&set, set.spread_by(audit)), ["shop.Owned"]);
+        assert_eq!(names(&set, &set.all_spread_by(audit)), ["shop.Owned", "shop.orders.Order"]);
+        assert_eq!(names(&set, &set.all_spreads(order)), ["shop.Owned", "shop.AuditTrail"]);
+    }
+
+    #[test]
+    fn check_kinds() {
+        let set = ModelSet::parse(CODE).unwrap();
+        let status = set.find("shop.CommonStatus").unwrap();
+        let fragments = set.models_of_kind(ModelKind::Fragment).map(|(id, _)| id).collect::<Vec<_>>();
+
+        assert_eq!(names(&set, &fragments), ["shop.AuditTrail", "shop.Owned"]);
+        assert_eq!(set.kind(status), Some(ModelKind::Enum));
+        assert_eq!(set.variants(status).len(), 2);
+        assert_eq!(set.find("shop.Missing"), None);
+    }
+}
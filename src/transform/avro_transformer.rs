@@ -0,0 +1,610 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::{to_pascal_case, to_snake_case};
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+enum Json {
+    Str(String),
+    Raw(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn str<S: Into<String>>(value: S) -> Self {
+        Json::Str(value.into())
+    }
+
+    fn literal(literal: &Literal) -> Self {
+        match literal {
+            Literal::Number(value) => Json::Raw(value.to_string()),
+            Literal::String(value @ ("true" | "false" | "null")) => Json::Raw(value.to_string()),
+            Literal::String(value) => Json::str(*value),
+        }
+    }
+}
+
+/// Generates Avro `.avsc` schemas, one file per record and enum.
+///
+/// Every file is self-contained: a named type is defined on its first use in the file
+/// and referenced by its full name afterwards.
+pub struct AvroTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    scalars: HashMap<String, (String, Option<String>)>,
+
+    path: RefCell<Vec<String>>,
+    models: RefCell<Vec<String>>,
+    defined: RefCell<HashSet<String>>,
+
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for AvroTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> AvroTransformer<'input> {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "string", None),
+            ("Int", "long", None),
+            ("Long", "long", None),
+            ("Byte", "int", None),
+            ("Float", "float", None),
+            ("Double", "double", None),
+            ("Bool", "boolean", None),
+            ("Boolean", "boolean", None),
+            ("Bytes", "bytes", None),
+            ("Uuid", "string", Some("uuid")),
+            ("Date", "int", Some("date")),
+            ("DateTime", "long", Some("timestamp-millis")),
+        ];
+
+        AvroTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            scalars: scalars.iter()
+                .map(|(k, v, logical)| (k.to_string(), (v.to_string(), logical.map(String::from))))
+                .collect(),
+
+            path: RefCell::new(Vec::new()),
+            models: RefCell::new(Vec::new()),
+            defined: RefCell::new(HashSet::new()),
+
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to an Avro primitive with an optional logical type, e.g. `("Day", "int", Some("date"))`.
+    pub fn with_scalar(mut self, scalar: &str, avro_type: &str, logical_type: Option<&str>) -> Self {
+        self.scalars.insert(scalar.to_string(), (avro_type.to_string(), logical_type.map(String::from)));
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        let model = self.models.borrow().first().cloned().unwrap_or_default();
+        self.error(TransformError::Unsupported(model, reason.to_string()));
+    }
+
+    fn namespace(path: &[String]) -> String {
+        path.iter().map(|p| to_snake_case(p)).collect::<Vec<_>>().join(".")
+    }
+
+    fn full_name(path: &[String], name: &str) -> String {
+        match path.is_empty() {
+            true => name.to_string(),
+            false => format!("{}.{}", Self::namespace(path), name),
+        }
+    }
+
+    /// Returns the full name of a named type if it is already defined in the current file.
+    fn reference(&self, path: &[String], name: &str) -> Option<Json> {
+        let full_name = Self::full_name(path, name);
+        match self.defined.borrow().contains(&full_name) {
+            true => Some(Json::Str(full_name)),
+            false => None,
+        }
+    }
+
+    fn named(&self, kind: &str, name: &str, mut body: Vec<(&'static str, Json)>) -> Json {
+        let path = self.path.borrow();
+        self.defined.borrow_mut().insert(Self::full_name(&path, name));
+
+        let mut object = vec![("type", Json::str(kind)), ("name", Json::str(name))];
+        if !path.is_empty() {
+            object.push(("namespace", Json::str(Self::namespace(&path))));
+        }
+        object.append(&mut body);
+        Json::Object(object)
+    }
+
+    fn default_of(params: &[ModelParamDefinition]) -> Option<Json> {
+        params.iter().find_map(|param| match param {
+            ModelParamDefinition::Metadata { id, def_value: Some(value), .. } if id.as_name() == Some("default") => {
+                Some(Json::literal(value))
+            },
+            _ => None,
+        })
+    }
+
+    /// Resolves the Avro schema of a field and the default its metadata asks for.
+    fn schema(&self, hint: &str, item_type: &ItemType, bindings: &Bindings) -> Option<(Json, Option<Json>)> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+                    self.unsupported(&format!("generic type `{}` cannot be expressed in Avro", name));
+                    return None;
+                }
+
+                let field_default = params.iter().find_map(|param| match param {
+                    ModelParam::Metadata(id, value) if id.as_name() == Some("default") => Some(Json::literal(value)),
+                    _ => None,
+                });
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                let scope = entry.scope.borrow();
+                let Scope::Model(ref def) = **scope else { unreachable!() };
+                let default = field_default.or_else(|| Self::default_of(model_params_def(def)));
+
+                if entry.kind == ModelKind::Scalar {
+                    let Some((avro_type, logical_type)) = self.scalars.get(name) else {
+                        self.unsupported(&format!("scalar `{}` has no Avro mapping", name));
+                        return None;
+                    };
+
+                    let schema = match logical_type {
+                        Some(logical_type) => Json::Object(vec![
+                            ("type", Json::str(avro_type)),
+                            ("logicalType", Json::str(logical_type)),
+                        ]),
+                        None => Json::str(avro_type),
+                    };
+                    return Some((schema, default));
+                }
+
+                if entry.kind == ModelKind::Fragment {
+                    self.unsupported(&format!("fragment `{}` can only be spread", name));
+                    return None;
+                }
+
+                if !entry.generics().is_empty() {
+                    self.unsupported(&format!("generic type `{}` cannot be expressed in Avro", name));
+                    return None;
+                }
+
+                let path = self.path.replace(entry.path.clone());
+                let models = self.models.replace(vec![entry.name.clone()]);
+                let schema = self.definition(&entry.name, def, &Bindings::root());
+                *self.models.borrow_mut() = models;
+                *self.path.borrow_mut() = path;
+
+                Some((schema?, default))
+            },
+            ItemType::Inline(def) => {
+                let parent = self.models.borrow().last().cloned().unwrap_or_default();
+                let name = parent + &to_pascal_case(hint);
+
+                self.models.borrow_mut().push(name.clone());
+                let schema = self.definition(&name, def, bindings);
+                self.models.borrow_mut().pop();
+
+                Some((schema?, None))
+            },
+            ItemType::Optional(item_type) => {
+                let (schema, default) = self.schema(hint, item_type, bindings)?;
+                let null = Json::str("null");
+
+                let (branches, default) = match default {
+                    Some(default) => (vec![schema, null], default),
+                    None => (vec![null, schema], Json::Raw("null".to_string())),
+                };
+
+                let branches = branches.into_iter()
+                    .flat_map(|branch| match branch {
+                        Json::Array(items) => items,
+                        branch => vec![branch],
+                    })
+                    .collect();
+
+                Some((Json::Array(branches), Some(default)))
+            },
+        }
+    }
+
+    fn field(&self, name: &str, item_type: &ItemType, bindings: &Bindings) -> Option<Json> {
+        let (schema, default) = self.schema(name, item_type, bindings)?;
+        let mut field = vec![("name", Json::str(name)), ("type", schema)];
+        if let Some(default) = default {
+            field.push(("default", default));
+        }
+        Some(Json::Object(field))
+    }
+
+    fn record_fields(&self, items: &[RecordItem], bindings: &Bindings) -> Vec<Json> {
+        let mut fields = vec![];
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            fields.extend(self.field(id.as_name().unwrap_or_default(), item_type, bindings));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        fields
+    }
+
+    fn tuple_fields(&self, items: &[TupleItem], bindings: &Bindings) -> Vec<Json> {
+        items.iter()
+            .enumerate()
+            .filter_map(|(i, item)| match item {
+                TupleItem::Item(item_type) if items.len() == 1 => self.field("value", item_type, bindings),
+                TupleItem::Item(item_type) => self.field(&format!("item{}", i + 1), item_type, bindings),
+                TupleItem::NamedItem(id, item_type) => self.field(id.as_name().unwrap_or_default(), item_type, bindings),
+            })
+            .collect()
+    }
+
+    fn definition(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) -> Option<Json> {
+        if let Some(reference) = self.reference(&self.path.borrow(), name) {
+            return Some(reference);
+        }
+
+        match def {
+            ModelDefinition::Record(_, items, _) => Some(self.record(name, || self.record_fields(items, bindings))),
+            ModelDefinition::Tuple(_, items, _) => match items.as_slice() {
+                [TupleItem::Item(item_type)] => self.schema("value", item_type, bindings).map(|(schema, _)| schema),
+                _ => Some(self.record(name, || self.tuple_fields(items, bindings))),
+            },
+            ModelDefinition::Enum(_, items, _) => Some(self.enumeration(name, items, bindings)),
+            ModelDefinition::Alias(_, _, item_type) => self.schema(name, item_type, bindings).map(|(schema, _)| schema),
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => None,
+        }
+    }
+
+    /// Defines a record before its fields so recursive references resolve to its name.
+    fn record(&self, name: &str, fields: impl FnOnce() -> Vec<Json>) -> Json {
+        let Json::Object(mut record) = self.named("record", name, vec![]) else { unreachable!() };
+        record.push(("fields", Json::Array(fields())));
+        Json::Object(record)
+    }
+
+    fn enumeration(&self, name: &str, items: &[EnumItem], bindings: &Bindings) -> Json {
+        if items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            let symbols = items.iter()
+                .filter_map(|item| match item {
+                    EnumItem::Item(id) => id.as_name().map(Json::str),
+                    _ => None,
+                })
+                .collect();
+
+            return self.named("enum", name, vec![("symbols", Json::Array(symbols))]);
+        }
+
+        let variants = items.iter()
+            .map(|item| {
+                let (id, payload) = match item {
+                    EnumItem::Item(id) => (id, None),
+                    EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                        (id, Some(item_type))
+                    },
+                };
+
+                let variant = format!("{}{}", name, to_pascal_case(id.as_name().unwrap_or_default()));
+                if let Some(reference) = self.reference(&self.path.borrow(), &variant) {
+                    return reference;
+                }
+
+                self.models.borrow_mut().push(variant.clone());
+                let record = self.record(&variant, || match payload {
+                    Some(ItemType::Inline(ModelDefinition::Record(_, items, _))) => self.record_fields(items, bindings),
+                    Some(ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => self.tuple_fields(items, bindings),
+                    Some(item_type) => self.field("value", item_type, bindings).into_iter().collect(),
+                    None => vec![],
+                });
+                self.models.borrow_mut().pop();
+                record
+            })
+            .collect();
+
+        Json::Array(variants)
+    }
+
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str) -> Option<Json>) {
+        let name = id.as_name().unwrap_or_default();
+
+        if !generic_names(params).is_empty() {
+            self.error(TransformError::Unsupported(
+                name.to_string(),
+                "generic models cannot be expressed in Avro".to_string(),
+            ));
+            return;
+        }
+
+        self.defined.borrow_mut().clear();
+        *self.models.borrow_mut() = vec![name.to_string()];
+
+        let tokens = self.writer.capture(|| {
+            if let Some(schema) = f(name) {
+                self.json(&schema);
+                self.writer.render(TextToken::NewLine);
+            }
+        });
+
+        let path = self.path.borrow();
+        let file = match path.is_empty() {
+            true => format!("{}.avsc", name),
+            false => format!("{}/{}.avsc", path.iter().map(|p| to_snake_case(p)).collect::<Vec<_>>().join("/"), name),
+        };
+
+        self.writer.render(TextToken::File(file));
+        self.writer.extend(tokens);
+    }
+
+    fn json(&self, json: &Json) {
+        match json {
+            Json::Str(value) => self.writer.text(format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))),
+            Json::Raw(value) => self.writer.text(value.clone()),
+            Json::Array(items) if items.iter().all(|item| matches!(item, Json::Str(_) | Json::Raw(_))) => {
+                self.writer.text("[");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.writer.text(", ");
+                    }
+                    self.json(item);
+                }
+                self.writer.text("]");
+            },
+            Json::Array(items) => {
+                self.writer.line("[");
+                self.writer.indent();
+                for (i, item) in items.iter().enumerate() {
+                    self.json(item);
+                    if i + 1 < items.len() {
+                        self.writer.text(",");
+                    }
+                    self.writer.render(TextToken::NewLine);
+                }
+                self.writer.dedent();
+                self.writer.text("]");
+            },
+            Json::Object(entries) => {
+                self.writer.line("{");
+                self.writer.indent();
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    self.writer.text(format!("\"{}\": ", key));
+                    self.json(value);
+                    if i + 1 < entries.len() {
+                        self.writer.text(",");
+                    }
+                    self.writer.render(TextToken::NewLine);
+                }
+                self.writer.dedent();
+                self.writer.text("}");
+            },
+        }
+    }
+}
+
+impl Transformer<'_> for AvroTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(format!("\"{}\"", name));
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        self.json(&Json::literal(literal));
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        if let Some((schema, _)) = self.schema("value", item_type, &Bindings::root()) {
+            self.json(&schema);
+        }
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+            self.unsupported("generic types cannot be expressed in Avro");
+        }
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        for item in items {
+            self.visit_scope(item, false);
+        }
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        for item in items {
+            self.visit_scope(item, false);
+        }
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Alias(_, _, _) => {
+
+            }
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| Some(self.record(name, || self.record_fields(items, &Bindings::root()))));
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        if items.len() > 1 {
+            self.top_level(id, params, |name| Some(self.record(name, || self.tuple_fields(items, &Bindings::root()))));
+        }
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| Some(self.enumeration(name, items, &Bindings::root())));
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref item_type) = item {
+            if let Some(field) = self.field(id.as_name().unwrap_or_default(), item_type, &Bindings::root()) {
+                self.json(&field);
+            }
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.writer.text(format!("\"{}\"", id.as_name().unwrap_or_default()));
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    #[test_case(
+        "package Shop;\n\nmodel Item {\n    id: Int\n    note: String?\n    count: Int[default=1]\n}\nscalar Int;\nscalar String;",
+        "shop/Item.avsc",
+        "{\n  \"type\": \"record\",\n  \"name\": \"Item\",\n  \"namespace\": \"shop\",\n  \"fields\": [\n    \
+        {\n      \"name\": \"id\",\n      \"type\": \"long\"\n    },\n    \
+        {\n      \"name\": \"note\",\n      \"type\": [\"null\", \"string\"],\n      \"default\": null\n    },\n    \
+        {\n      \"name\": \"count\",\n      \"type\": \"long\",\n      \"default\": 1\n    }\n  ]\n}\n";
+        "record"
+    )]
+    #[test_case(
+        "model Status enum {\n    Draft\n    Published\n}",
+        "Status.avsc",
+        "{\n  \"type\": \"enum\",\n  \"name\": \"Status\",\n  \"symbols\": [\"Draft\", \"Published\"]\n}\n";
+        "enum"
+    )]
+    #[test_case(
+        "model Password enum {\n    Open(String)\n    Empty\n}\nscalar String;",
+        "Password.avsc",
+        "[\n  {\n    \"type\": \"record\",\n    \"name\": \"PasswordOpen\",\n    \"fields\": [\n      \
+        {\n        \"name\": \"value\",\n        \"type\": \"string\"\n      }\n    ]\n  },\n  \
+        {\n    \"type\": \"record\",\n    \"name\": \"PasswordEmpty\",\n    \"fields\": []\n  }\n]\n";
+        "union"
+    )]
+    fn check(code: &str, file: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = AvroTransformer::new();
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_files(2);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].0, file);
+        assert_eq!(&result[0].1, expected)
+    }
+
+    #[test]
+    fn check_references() {
+        let code = "model Order {\n    status: Status\n    previous: Status?\n}\nmodel Status enum {\n    Open\n    Closed\n}";
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        AvroTransformer::new().apply(&ast, &render).unwrap();
+        let files = render.as_files(2);
+
+        assert_eq!(files[0].0, "Order.avsc");
+        assert_eq!(files[0].1.matches("\"type\": \"enum\"").count(), 1);
+        assert!(files[0].1.contains("\"type\": [\"null\", \"Status\"]"));
+    }
+}
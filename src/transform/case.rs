@@ -0,0 +1,77 @@
+fn words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = vec![];
+    let mut current = String::new();
+    let chars: Vec<char> = name.chars().collect();
+
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_' || *c == '-' || c.is_whitespace() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+
+        let prev_lower = i > 0 && (chars[i - 1].is_lowercase() || chars[i - 1].is_ascii_digit());
+        let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+        let prev_upper = i > 0 && chars[i - 1].is_uppercase();
+
+        if c.is_uppercase() && !current.is_empty() && (prev_lower || (prev_upper && next_lower)) {
+            words.push(std::mem::take(&mut current));
+        }
+
+        current.push(*c);
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(|c| c.to_lowercase())).collect(),
+        None => String::new(),
+    }
+}
+
+pub fn to_snake_case(name: &str) -> String {
+    words(name).iter().map(|w| w.to_lowercase()).collect::<Vec<_>>().join("_")
+}
+
+pub fn to_screaming_snake_case(name: &str) -> String {
+    words(name).iter().map(|w| w.to_uppercase()).collect::<Vec<_>>().join("_")
+}
+
+pub fn to_pascal_case(name: &str) -> String {
+    words(name).iter().map(|w| capitalize(w)).collect()
+}
+
+pub fn to_camel_case(name: &str) -> String {
+    let pascal = to_pascal_case(name);
+    let mut chars = pascal.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("HashedPassword", "hashed_password", "HASHED_PASSWORD", "HashedPassword", "hashedPassword")]
+    #[test_case("created", "created", "CREATED", "Created", "created")]
+    #[test_case("min_len", "min_len", "MIN_LEN", "MinLen", "minLen")]
+    #[test_case("HTTPServer", "http_server", "HTTP_SERVER", "HttpServer", "httpServer")]
+    #[test_case("f3", "f3", "F3", "F3", "f3")]
+    fn check(name: &str, snake: &str, screaming: &str, pascal: &str, camel: &str) {
+        assert_eq!(to_snake_case(name), snake);
+        assert_eq!(to_screaming_snake_case(name), screaming);
+        assert_eq!(to_pascal_case(name), pascal);
+        assert_eq!(to_camel_case(name), camel);
+    }
+}
@@ -1,55 +1,67 @@
 use std::cell::RefCell;
-use crate::transform::{Target, TextToken};
+use crate::transform::{Layout, Target, TextToken};
 
+/// Prints the tokens of each file laid out into the line width, see `Layout`, once the next
+/// file starts, on `flush` or when the render is dropped.
 pub struct ConsoleRender {
     indent_count: usize,
-    indent: RefCell<usize>,
-    last_token: RefCell<TextToken>,
+    width: usize,
+    tabs: bool,
+    tokens: RefCell<Vec<TextToken>>,
 }
 
 impl ConsoleRender {
     pub fn new(indent_count: usize) -> Self {
         ConsoleRender {
             indent_count,
-            indent: RefCell::new(0),
-            last_token: RefCell::new(TextToken::None),
+            width: usize::MAX,
+            tabs: false,
+            tokens: RefCell::new(Vec::new()),
         }
     }
+
+    /// Line width groups are fitted into; unlimited by default.
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Indents with a tab per level instead of `indent_count` spaces.
+    pub fn with_tabs(mut self, tabs: bool) -> Self {
+        self.tabs = tabs;
+        self
+    }
+
+    /// Prints the tokens rendered so far.
+    pub fn flush(&self) {
+        let tokens = self.tokens.take();
+        if tokens.is_empty() {
+            return;
+        }
+
+        let lines = Layout::new(self.indent_count, self.width)
+            .with_tabs(self.tabs)
+            .lines(&tokens);
+        println!("{}", lines.join("\n"));
+    }
 }
 
 impl Target<TextToken> for ConsoleRender {
 
     fn render(&self, token: TextToken) {
-        match &token {
-            TextToken::None => {
-
-            },
-            TextToken::Space => {
-                if let TextToken::Text(_) = *self.last_token.borrow() {
-                    print!(" ");
-                }
-            },
-            TextToken::NewLine | TextToken::LineIndent => {
+        match token {
+            TextToken::File(name) => {
+                self.flush();
                 println!();
-                let n = *self.indent.borrow() * self.indent_count;
-                print!("{:}", " ".repeat(n));
-            },
-            TextToken::IncIndent => {
-                *self.indent.borrow_mut() += 1;
-            },
-            TextToken::DecIndent => {
-                *self.indent.borrow_mut() -= 1;
-            },
-            TextToken::Text(text) => {
-                if let  TextToken::IncIndent | TextToken::DecIndent = *self.last_token.borrow() {
-                    let n = *self.indent.borrow() * self.indent_count;
-                    print!("\r{:}", " ".repeat(n));
-                }
-
-                print!("{}", text);
+                println!("==> {} <==", name);
             },
+            token => self.tokens.borrow_mut().push(token),
         }
+    }
+}
 
-        *self.last_token.borrow_mut() = token;
+impl Drop for ConsoleRender {
+    fn drop(&mut self) {
+        self.flush();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,603 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::to_pascal_case;
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+/// Generates C# positional records, one file per Mex package with nullable annotations enabled.
+///
+/// Enums with payloads become abstract records with a sealed nested record per variant.
+/// C# aliases cannot be generic or shared between files, so aliases are replaced by their targets.
+pub struct CSharpTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    scalars: HashMap<String, (String, Option<String>)>,
+    namespace: String,
+
+    path: RefCell<Vec<String>>,
+    models: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    usings: RefCell<BTreeSet<String>>,
+    pending: RefCell<Vec<Vec<TextToken>>>,
+    files: RefCell<Vec<Vec<TextToken>>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for CSharpTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> CSharpTransformer<'input> {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "string", None),
+            ("Int", "long", None),
+            ("Long", "long", None),
+            ("Byte", "byte", None),
+            ("Float", "float", None),
+            ("Double", "double", None),
+            ("Decimal", "decimal", None),
+            ("Bool", "bool", None),
+            ("Boolean", "bool", None),
+            ("Bytes", "byte[]", None),
+            ("Uuid", "Guid", Some("System")),
+            ("Date", "DateOnly", Some("System")),
+            ("DateTime", "DateTimeOffset", Some("System")),
+        ];
+
+        CSharpTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            scalars: scalars.iter()
+                .map(|(k, v, using)| (k.to_string(), (v.to_string(), using.map(String::from))))
+                .collect(),
+            namespace: String::new(),
+
+            path: RefCell::new(Vec::new()),
+            models: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            usings: RefCell::new(BTreeSet::new()),
+            pending: RefCell::new(Vec::new()),
+            files: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a C# type and the namespace it needs, e.g. `("Uri", "Uri", Some("System"))`.
+    pub fn with_scalar(mut self, scalar: &str, cs_type: &str, using: Option<&str>) -> Self {
+        self.scalars.insert(scalar.to_string(), (cs_type.to_string(), using.map(String::from)));
+        self
+    }
+
+    /// Namespace every Mex package is nested in, e.g. `Acme.Api`.
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = namespace.to_string();
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.files
+            .into_inner().into_iter().flatten()
+            .for_each(|t| render.render(t));
+
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        let model = self.models.borrow().first().cloned().unwrap_or_default();
+        self.error(TransformError::Unsupported(model, reason.to_string()));
+    }
+
+    fn namespace_name(&self, path: &[String]) -> String {
+        let mut parts = match self.namespace.is_empty() {
+            true => vec![],
+            false => vec![self.namespace.clone()],
+        };
+        parts.extend(path.iter().map(|p| to_pascal_case(p)));
+        parts.join(".")
+    }
+
+    fn file_name(path: &[String]) -> String {
+        let mut parts = path.iter().map(|p| to_pascal_case(p)).collect::<Vec<_>>();
+        parts.push("Models.cs".to_string());
+        parts.join("/")
+    }
+
+    fn type_params(&self) -> String {
+        let generics = self.generics.borrow();
+        match generics.is_empty() {
+            true => String::new(),
+            false => format!("<{}>", generics.join(", ")),
+        }
+    }
+
+    /// Writes a top-level declaration followed by the declarations of its inline types.
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str)) {
+        let name = id.as_name().unwrap_or_default();
+
+        *self.generics.borrow_mut() = generic_names(params);
+        self.models.borrow_mut().push(name.to_string());
+        let tokens = self.writer.capture(|| f(name));
+        self.models.borrow_mut().pop();
+        self.generics.borrow_mut().clear();
+
+        if tokens.is_empty() {
+            return;
+        }
+
+        self.writer.block(|| self.writer.extend(tokens));
+        for pending in self.pending.take() {
+            self.writer.block(|| self.writer.extend(pending));
+        }
+    }
+
+    fn file(&self, items: &[RefScope]) {
+        let path = self.path.borrow().clone();
+        let outer_usings = self.usings.replace(BTreeSet::new());
+
+        let body = self.writer.capture(|| {
+            for item in items {
+                if let Scope::Package(_, _) = **item.borrow() {
+                    continue;
+                }
+                self.visit_scope(item, false);
+            }
+        });
+
+        let usings = self.usings.replace(outer_usings);
+
+        if !body.is_empty() {
+            let namespace = self.namespace_name(&path);
+            let file = self.writer.capture(|| {
+                self.writer.render(TextToken::File(Self::file_name(&path)));
+                self.writer.line("#nullable enable");
+                self.writer.empty_line();
+
+                let usings = usings.iter().filter(|u| **u != namespace).collect::<Vec<_>>();
+                if !usings.is_empty() {
+                    for using in usings {
+                        self.writer.line(format!("using {};", using));
+                    }
+                    self.writer.empty_line();
+                }
+
+                if !namespace.is_empty() {
+                    self.writer.line(format!("namespace {};", namespace));
+                    self.writer.empty_line();
+                }
+
+                self.writer.extend(body);
+            });
+            self.files.borrow_mut().push(file);
+        }
+
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                self.visit_scope(item, false);
+            }
+        }
+    }
+
+    /// Resolves the C# type of a field, declaring records for inline models.
+    fn cs_type(&self, hint: &str, item_type: &ItemType, bindings: &Bindings) -> Option<String> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if self.generics.borrow().iter().any(|g| g == name) {
+                    return Some(name.to_string());
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                if entry.kind == ModelKind::Alias {
+                    let scope = entry.scope.borrow();
+                    let Scope::Model(ModelDefinition::Alias(_, ref defs, ref target)) = **scope else {
+                        return None;
+                    };
+                    let inner = Bindings::new(defs, params, bindings);
+                    return self.cs_type(hint, target, &inner);
+                }
+
+                let args = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Generic(item_type) => Some(self.cs_type(hint, item_type, bindings)),
+                        ModelParam::Metadata(_, _) => None,
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                let cs_type = match entry.kind {
+                    ModelKind::Scalar => {
+                        let Some((cs_type, using)) = self.scalars.get(name) else {
+                            self.unsupported(&format!("scalar `{}` has no C# mapping", name));
+                            return None;
+                        };
+
+                        if let Some(using) = using {
+                            self.usings.borrow_mut().insert(using.clone());
+                        }
+                        cs_type.clone()
+                    },
+                    ModelKind::Fragment => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                        return None;
+                    },
+                    _ => {
+                        let namespace = self.namespace_name(&entry.path);
+                        if entry.path != *self.path.borrow() && !namespace.is_empty() {
+                            self.usings.borrow_mut().insert(namespace);
+                        }
+                        entry.name.clone()
+                    },
+                };
+
+                match args.is_empty() {
+                    true => Some(cs_type),
+                    false => Some(format!("{}<{}>", cs_type, args.join(", "))),
+                }
+            },
+            ItemType::Inline(def) => {
+                let parent = self.models.borrow().last().cloned().unwrap_or_default();
+                let name = parent + &to_pascal_case(hint);
+
+                let tokens = self.writer.capture(|| self.declaration(&name, def, bindings));
+                self.pending.borrow_mut().push(tokens);
+                Some(name + &self.type_params())
+            },
+            ItemType::Optional(item_type) => {
+                let cs_type = self.cs_type(hint, item_type, bindings)?;
+                Some(format!("{}?", cs_type))
+            },
+        }
+    }
+
+    fn parameter(&self, name: &str, item_type: &ItemType, bindings: &Bindings) -> Option<String> {
+        let cs_type = self.cs_type(name, item_type, bindings)?;
+        Some(format!("{} {}", cs_type, to_pascal_case(name)))
+    }
+
+    fn declaration(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) {
+        self.models.borrow_mut().push(name.to_string());
+
+        match def {
+            ModelDefinition::Record(_, items, _) => self.record(name, items, bindings),
+            ModelDefinition::Tuple(_, items, _) => self.tuple(name, items, bindings),
+            ModelDefinition::Enum(_, items, _) => self.enumeration(name, items, bindings),
+            ModelDefinition::Alias(_, _, _) | ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => {},
+        }
+
+        self.models.borrow_mut().pop();
+    }
+
+    fn record_parameters(&self, items: &[RecordItem], bindings: &Bindings) -> Vec<String> {
+        let mut parameters = vec![];
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            parameters.extend(self.parameter(id.as_name().unwrap_or_default(), item_type, bindings));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        parameters
+    }
+
+    fn tuple_parameters(&self, items: &[TupleItem], bindings: &Bindings) -> Vec<String> {
+        items.iter()
+            .enumerate()
+            .filter_map(|(i, item)| match item {
+                TupleItem::Item(item_type) if items.len() == 1 => self.parameter("value", item_type, bindings),
+                TupleItem::Item(item_type) => self.parameter(&format!("item{}", i + 1), item_type, bindings),
+                TupleItem::NamedItem(id, item_type) => self.parameter(id.as_name().unwrap_or_default(), item_type, bindings),
+            })
+            .collect()
+    }
+
+    /// Writes `head(parameters)tail;`, one parameter per line when there are several.
+    fn positional(&self, head: String, parameters: &[String], tail: &str) {
+        match parameters {
+            [] => self.writer.line(format!("{}(){};", head, tail)),
+            [single] => self.writer.line(format!("{}({}){};", head, single, tail)),
+            _ => {
+                self.writer.line(format!("{}(", head));
+                self.writer.indent();
+                let count = parameters.len();
+                for (i, parameter) in parameters.iter().enumerate() {
+                    match i + 1 < count {
+                        true => self.writer.line(format!("{},", parameter)),
+                        false => self.writer.line(parameter.clone()),
+                    }
+                }
+                self.writer.dedent();
+                self.writer.line(format!("){};", tail));
+            },
+        }
+    }
+
+    fn record(&self, name: &str, items: &[RecordItem], bindings: &Bindings) {
+        let parameters = self.record_parameters(items, bindings);
+        self.positional(format!("public record {}{}", name, self.type_params()), &parameters, "");
+    }
+
+    fn tuple(&self, name: &str, items: &[TupleItem], bindings: &Bindings) {
+        let parameters = self.tuple_parameters(items, bindings);
+        let head = match (items, self.generics.borrow().is_empty()) {
+            ([TupleItem::Item(_)], true) => format!("public readonly record struct {}", name),
+            _ => format!("public record {}{}", name, self.type_params()),
+        };
+        self.positional(head, &parameters, "");
+    }
+
+    fn enumeration(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        if items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            self.writer.line(format!("public enum {}", name));
+            self.writer.line("{");
+            self.writer.indent();
+            for item in items {
+                if let EnumItem::Item(id) = item {
+                    self.writer.line(format!("{},", to_pascal_case(id.as_name().unwrap_or_default())));
+                }
+            }
+            self.writer.dedent();
+            self.writer.line("}");
+            return;
+        }
+
+        let parent = format!("{}{}", name, self.type_params());
+        self.writer.line(format!("public abstract record {}", parent));
+        self.writer.line("{");
+        self.writer.indent();
+        self.writer.line(format!("private {}() {{}}", name));
+
+        for item in items {
+            let (id, payload) = match item {
+                EnumItem::Item(id) => (id, None),
+                EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                    (id, Some(item_type))
+                },
+            };
+
+            let mut variant = to_pascal_case(id.as_name().unwrap_or_default());
+            // A nested type cannot be named like the type enclosing it (CS0542).
+            if variant == name {
+                variant.push_str("Variant");
+            }
+            self.models.borrow_mut().push(format!("{}{}", name, variant));
+            let parameters = match payload {
+                Some(ItemType::Inline(ModelDefinition::Record(_, items, _))) => self.record_parameters(items, bindings),
+                Some(ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => self.tuple_parameters(items, bindings),
+                Some(item_type) => self.parameter("value", item_type, bindings).into_iter().collect(),
+                None => vec![],
+            };
+            self.models.borrow_mut().pop();
+
+            self.writer.empty_line();
+            self.positional(format!("public sealed record {}", variant), &parameters, &format!(" : {}", parent));
+        }
+
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+}
+
+impl Transformer<'_> for CSharpTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(to_pascal_case(name));
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        match literal {
+            Literal::String(ref str) => self.writer.text(format!("\"{}\"", str)),
+            Literal::Number(ref str) => self.writer.text(str.to_string()),
+        }
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        if let Some(cs_type) = self.cs_type("value", item_type, &Bindings::root()) {
+            self.writer.text(cs_type);
+        }
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        let args = params.iter()
+            .filter_map(|param| match param {
+                ModelParam::Generic(item_type) => self.cs_type("value", item_type, &Bindings::root()),
+                ModelParam::Metadata(_, _) => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !args.is_empty() {
+            self.writer.text(format!("<{}>", args.join(", ")));
+        }
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        self.file(items);
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        self.file(items);
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(_, _, _) | ModelDefinition::Fragment(_, _, _) => {
+
+            }
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.record(name, items, &Bindings::root()));
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.tuple(name, items, &Bindings::root()));
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.enumeration(name, items, &Bindings::root()));
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref item_type) = item {
+            if let Some(parameter) = self.parameter(id.as_name().unwrap_or_default(), item_type, &Bindings::root()) {
+                self.writer.line(parameter);
+            }
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.writer.line(format!("{},", to_pascal_case(id.as_name().unwrap_or_default())));
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    #[test_case(
+        "package Shop;\n\nmodel Item {\n    id: Id\n    title: String?\n    created: DateTime\n}\nmodel Id = Int\nscalar Int;\nscalar String;\nscalar DateTime;",
+        "Shop/Models.cs",
+        "#nullable enable\n\nusing System;\n\nnamespace Acme.Shop;\n\n\
+        public record Item(\n    long Id,\n    string? Title,\n    DateTimeOffset Created\n);\n";
+        "record"
+    )]
+    #[test_case(
+        "model Email(String)\nmodel Status enum {\n    Draft\n    Published\n}\nscalar String;",
+        "Models.cs",
+        "#nullable enable\n\nnamespace Acme;\n\n\
+        public readonly record struct Email(string Value);\n\n\
+        public enum Status\n{\n    Draft,\n    Published,\n}\n";
+        "tuple and plain enum"
+    )]
+    #[test_case(
+        "model Page<T> {\n    items: T\n    meta: {\n        total: Int\n    }\n}\nscalar Int;",
+        "Models.cs",
+        "#nullable enable\n\nnamespace Acme;\n\n\
+        public record Page<T>(\n    T Items,\n    PageMeta<T> Meta\n);\n\n\
+        public record PageMeta<T>(long Total);\n";
+        "generic"
+    )]
+    #[test_case(
+        "model Password enum {\n    Open(String)\n    Card {\n        number: String\n        cvv: String\n    }\n    Empty\n}\nscalar String;",
+        "Models.cs",
+        "#nullable enable\n\nnamespace Acme;\n\n\
+        public abstract record Password\n{\n    private Password() {}\n\n    \
+        public sealed record Open(string Value) : Password;\n\n    \
+        public sealed record Card(\n        string Number,\n        string Cvv\n    ) : Password;\n\n    \
+        public sealed record Empty() : Password;\n}\n";
+        "abstract record hierarchy"
+    )]
+    #[test_case(
+        "model Password[min_len: Int = 12] enum {\n    Password(String),\n    HashedPassword(value: String[len=32], salt: String[len=5])\n}\nscalar String;",
+        "Models.cs",
+        "#nullable enable\n\nnamespace Acme;\n\n\
+        public abstract record Password\n{\n    private Password() {}\n\n    \
+        public sealed record PasswordVariant(string Value) : Password;\n\n    \
+        public sealed record HashedPassword(\n        string Value,\n        string Salt\n    ) : Password;\n}\n";
+        "variant named like its enum"
+    )]
+    fn check(code: &str, file: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = CSharpTransformer::new().with_namespace("Acme");
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_files(4);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].0, file);
+        assert_eq!(&result[0].1, expected)
+    }
+}
@@ -0,0 +1,578 @@
+use std::cell::RefCell;
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::to_snake_case;
+use crate::transform::model_index::{generic_names, model_params_def, ModelIndex, ModelKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagramFormat {
+    Mermaid,
+    Dot,
+}
+
+struct Node {
+    path: Vec<String>,
+    name: String,
+    kind: &'static str,
+    generics: Vec<String>,
+    members: Vec<String>,
+}
+
+#[derive(PartialEq)]
+enum EdgeKind {
+    Spread,
+    Reference(String),
+}
+
+#[derive(PartialEq)]
+struct Edge {
+    from: (Vec<String>, String),
+    to: (Vec<String>, String),
+    kind: EdgeKind,
+}
+
+/// Exports the model set as a Mermaid `classDiagram` or a Graphviz DOT graph.
+///
+/// Spreads become inheritance edges, fields referencing other models become associations
+/// and packages become namespaces (Mermaid) or clusters (DOT).
+pub struct DiagramTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    format: DiagramFormat,
+
+    path: RefCell<Vec<String>>,
+    model: RefCell<String>,
+    generics: RefCell<Vec<String>>,
+
+    nodes: RefCell<Vec<Node>>,
+    edges: RefCell<Vec<Edge>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl<'input> DiagramTransformer<'input> {
+    pub fn new(format: DiagramFormat) -> Self {
+        DiagramTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            format,
+
+            path: RefCell::new(Vec::new()),
+            model: RefCell::new(String::new()),
+            generics: RefCell::new(Vec::new()),
+
+            nodes: RefCell::new(Vec::new()),
+            edges: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.take();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        match self.format {
+            DiagramFormat::Mermaid => self.mermaid(),
+            DiagramFormat::Dot => self.dot(),
+        }
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn type_text(&self, item_type: &ItemType) -> String {
+        match item_type {
+            ItemType::Model(id, params) => {
+                let args = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Generic(item_type) => Some(self.type_text(item_type)),
+                        ModelParam::Metadata(_, _) => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                let name = id.as_name().unwrap_or_default().to_string();
+                match args.is_empty() {
+                    true => name,
+                    false => format!("{}<{}>", name, args.join(", ")),
+                }
+            },
+            ItemType::Optional(item_type) => format!("{}?", self.type_text(item_type)),
+            ItemType::Inline(ModelDefinition::Tuple(_, items, _)) => {
+                let items = items.iter()
+                    .map(|item| match item {
+                        TupleItem::Item(item_type) | TupleItem::NamedItem(_, item_type) => self.type_text(item_type),
+                    })
+                    .collect::<Vec<_>>();
+                format!("Tuple<{}>", items.join(", "))
+            },
+            ItemType::Inline(ModelDefinition::Enum(_, _, _)) => "Enum".to_string(),
+            ItemType::Inline(_) => "Record".to_string(),
+        }
+    }
+
+    fn current(&self) -> (Vec<String>, String) {
+        (self.path.borrow().clone(), self.model.borrow().clone())
+    }
+
+    fn edge(&self, to: &str, kind: EdgeKind) {
+        if self.generics.borrow().iter().any(|g| g == to) {
+            return;
+        }
+
+        let index = self.index.borrow();
+        let Some(entry) = index.find(to, &self.path.borrow()) else {
+            self.errors.borrow_mut().push(TransformError::UnknownType(to.to_string()));
+            return;
+        };
+
+        if entry.kind == ModelKind::Scalar {
+            return;
+        }
+
+        let edge = Edge { from: self.current(), to: (entry.path.clone(), entry.name.clone()), kind };
+        let mut edges = self.edges.borrow_mut();
+        if !edges.contains(&edge) {
+            edges.push(edge);
+        }
+    }
+
+    /// Adds an association to every model `item_type` refers to, including generic arguments and inline types.
+    fn references(&self, label: &str, item_type: &ItemType) {
+        match item_type {
+            ItemType::Model(id, params) => {
+                self.edge(id.as_name().unwrap_or_default(), EdgeKind::Reference(label.to_string()));
+                for param in params {
+                    if let ModelParam::Generic(item_type) = param {
+                        self.references(label, item_type);
+                    }
+                }
+            },
+            ItemType::Optional(item_type) => self.references(label, item_type),
+            ItemType::Inline(ModelDefinition::Record(_, items, _)) => self.record_references(label, items),
+            ItemType::Inline(ModelDefinition::Tuple(_, items, _)) => {
+                for item in items {
+                    match item {
+                        TupleItem::Item(item_type) | TupleItem::NamedItem(_, item_type) => self.references(label, item_type),
+                    }
+                }
+            },
+            ItemType::Inline(ModelDefinition::Enum(_, items, _)) => self.enum_references(items),
+            ItemType::Inline(_) => {},
+        }
+    }
+
+    fn record_references(&self, label: &str, items: &[RecordItem]) {
+        for item in items {
+            match item {
+                RecordItem::Item(id, item_type) => {
+                    let field = id.as_name().unwrap_or_default();
+                    match label.is_empty() {
+                        true => self.references(field, item_type),
+                        false => self.references(&format!("{}.{}", label, field), item_type),
+                    }
+                },
+                RecordItem::Spread(ItemType::Model(id, _)) => {
+                    self.edge(id.as_name().unwrap_or_default(), EdgeKind::Spread);
+                },
+                RecordItem::Spread(item_type) => self.references(label, item_type),
+            }
+        }
+    }
+
+    fn enum_references(&self, items: &[EnumItem]) {
+        for item in items {
+            if let EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) = item {
+                self.references(id.as_name().unwrap_or_default(), item_type);
+            }
+        }
+    }
+
+    fn node(&self, id: &Id, kind: &'static str, params: &[ModelParamDefinition], members: Vec<String>) {
+        self.nodes.borrow_mut().push(Node {
+            path: self.path.borrow().clone(),
+            name: id.as_name().unwrap_or_default().to_string(),
+            kind,
+            generics: generic_names(params),
+            members,
+        });
+    }
+
+    fn enter(&self, id: &Id, params: &[ModelParamDefinition]) {
+        *self.model.borrow_mut() = id.as_name().unwrap_or_default().to_string();
+        *self.generics.borrow_mut() = generic_names(params);
+    }
+
+    fn record_members(&self, items: &[RecordItem]) -> Vec<String> {
+        items.iter()
+            .filter_map(|item| match item {
+                RecordItem::Item(id, item_type) => {
+                    Some(format!("{}: {}", id.as_name().unwrap_or_default(), self.type_text(item_type)))
+                },
+                RecordItem::Spread(_) => None,
+            })
+            .collect()
+    }
+
+    fn packages(&self) -> Vec<Vec<String>> {
+        let mut packages: Vec<Vec<String>> = vec![];
+        for node in self.nodes.borrow().iter() {
+            if !packages.contains(&node.path) {
+                packages.push(node.path.clone());
+            }
+        }
+        packages
+    }
+
+    fn mermaid(&self) {
+        self.writer.line("classDiagram");
+        self.writer.indent();
+
+        let generic = |text: &str| text.replace(['<', '>'], "~");
+        let nodes = self.nodes.borrow();
+
+        for path in self.packages() {
+            if !path.is_empty() {
+                self.writer.line(format!("namespace {} {{", path.join("_")));
+                self.writer.indent();
+            }
+
+            for node in nodes.iter().filter(|node| node.path == path) {
+                let generics = match node.generics.is_empty() {
+                    true => String::new(),
+                    false => format!("~{}~", node.generics.join(", ")),
+                };
+
+                let id = Self::mermaid_id(&(node.path.clone(), node.name.clone()));
+                let label = match node.path.is_empty() {
+                    true => String::new(),
+                    false => format!("[\"{}\"]", node.name),
+                };
+
+                self.writer.line(format!("class {}{}{} {{", id, generics, label));
+                self.writer.indent();
+                self.writer.line(format!("<<{}>>", node.kind));
+                for member in &node.members {
+                    self.writer.line(generic(member));
+                }
+                self.writer.dedent();
+                self.writer.line("}");
+            }
+
+            if !path.is_empty() {
+                self.writer.dedent();
+                self.writer.line("}");
+            }
+        }
+
+        for edge in self.edges.borrow().iter() {
+            match &edge.kind {
+                EdgeKind::Spread => {
+                    self.writer.line(format!("{} <|-- {}", Self::mermaid_id(&edge.to), Self::mermaid_id(&edge.from)));
+                },
+                EdgeKind::Reference(label) => {
+                    self.writer.line(format!("{} --> {} : {}", Self::mermaid_id(&edge.from), Self::mermaid_id(&edge.to), label));
+                },
+            }
+        }
+
+        self.writer.dedent();
+    }
+
+    /// Mermaid ids cannot contain dots, so package models are prefixed with their path
+    /// joined by underscores and labelled with the bare name.
+    fn mermaid_id((path, name): &(Vec<String>, String)) -> String {
+        let mut parts = path.iter().map(|p| to_snake_case(p)).collect::<Vec<_>>();
+        parts.push(name.clone());
+        parts.join("_")
+    }
+
+    fn dot_id((path, name): &(Vec<String>, String)) -> String {
+        let mut parts = path.iter().map(|p| to_snake_case(p)).collect::<Vec<_>>();
+        parts.push(name.clone());
+        format!("\"{}\"", parts.join("."))
+    }
+
+    fn dot_escape(text: &str) -> String {
+        text.chars()
+            .flat_map(|c| match c {
+                '{' | '}' | '|' | '<' | '>' | '"' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect()
+    }
+
+    fn dot(&self) {
+        self.writer.line("digraph Models {");
+        self.writer.indent();
+        self.writer.line("node [shape=record];");
+
+        let nodes = self.nodes.borrow();
+
+        for path in self.packages() {
+            self.writer.empty_line();
+            if !path.is_empty() {
+                let cluster = path.iter().map(|p| to_snake_case(p)).collect::<Vec<_>>().join("_");
+                self.writer.line(format!("subgraph cluster_{} {{", cluster));
+                self.writer.indent();
+                self.writer.line(format!("label = \"{}\";", path.join(".")));
+            }
+
+            for node in nodes.iter().filter(|node| node.path == path) {
+                let title = match node.generics.is_empty() {
+                    true => node.name.clone(),
+                    false => format!("{}<{}>", node.name, node.generics.join(", ")),
+                };
+                let members = node.members.iter()
+                    .map(|member| format!("{}\\l", Self::dot_escape(member)))
+                    .collect::<String>();
+
+                self.writer.line(format!(
+                    "{} [label=\"{{«{}»\\n{}|{}}}\"];",
+                    Self::dot_id(&(node.path.clone(), node.name.clone())), node.kind, Self::dot_escape(&title), members,
+                ));
+            }
+
+            if !path.is_empty() {
+                self.writer.dedent();
+                self.writer.line("}");
+            }
+        }
+
+        let edges = self.edges.borrow();
+        if !edges.is_empty() {
+            self.writer.empty_line();
+        }
+        for edge in edges.iter() {
+            let attributes = match &edge.kind {
+                EdgeKind::Spread => "arrowhead=empty".to_string(),
+                EdgeKind::Reference(label) => format!("label=\"{}\"", label),
+            };
+            self.writer.line(format!("{} -> {} [{}];", Self::dot_id(&edge.from), Self::dot_id(&edge.to), attributes));
+        }
+
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+}
+
+impl Transformer<'_> for DiagramTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_literal(&self, _literal: &Literal) {
+
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        self.writer.text(self.type_text(item_type));
+    }
+
+    fn visit_model_params(&self, _params: &[ModelParam]) {
+
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        for item in items {
+            self.visit_scope(item, false);
+        }
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        for item in items {
+            self.visit_scope(item, false);
+        }
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Fragment(ref id, ref items, ref params) => {
+                self.enter(id, params);
+                self.node(id, "fragment", params, self.record_members(items));
+                self.record_references("", items);
+            }
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, ref item_type) => {
+                self.enter(id, params);
+                self.node(id, "alias", params, vec![format!("= {}", self.type_text(item_type))]);
+                self.references("", item_type);
+            },
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.enter(id, params);
+        self.node(id, "record", params, self.record_members(items));
+        self.record_references("", items);
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.enter(id, params);
+
+        let members = items.iter()
+            .enumerate()
+            .map(|(i, item)| match item {
+                TupleItem::Item(item_type) => (i.to_string(), item_type),
+                TupleItem::NamedItem(id, item_type) => (id.as_name().unwrap_or_default().to_string(), item_type),
+            })
+            .collect::<Vec<_>>();
+
+        self.node(id, "tuple", params, members.iter().map(|(name, t)| format!("{}: {}", name, self.type_text(t))).collect());
+        for (name, item_type) in members {
+            self.references(&name, item_type);
+        }
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.enter(id, params);
+
+        let members = items.iter()
+            .map(|item| match item {
+                EnumItem::Item(id) => id.as_name().unwrap_or_default().to_string(),
+                EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                    format!("{}: {}", id.as_name().unwrap_or_default(), self.type_text(item_type))
+                },
+            })
+            .collect();
+
+        self.node(id, "enumeration", params, members);
+        self.enum_references(items);
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref item_type) = item {
+            self.writer.text(format!("{}: {}", id.as_name().unwrap_or_default(), self.type_text(item_type)));
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.visit_id(id);
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.errors.borrow_mut().push(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::StringRender;
+
+    const CODE: &str = "package Shop {\n    fragment Entity<Key> {\n        id: Key\n    }\n\
+        model Order {\n        ... Entity<Int>\n        item: Item?\n        status: Status\n    }\n}\n\
+        model Item {\n    title: String\n}\n\
+        model Status enum {\n    Open\n    Closed\n}\n\
+        scalar Int;\nscalar String;";
+
+    #[test_case(
+        DiagramFormat::Mermaid,
+        "classDiagram\n    \
+        namespace Shop {\n        class shop_Entity~Key~[\"Entity\"] {\n            <<fragment>>\n            id: Key\n        }\n        \
+        class shop_Order[\"Order\"] {\n            <<record>>\n            item: Item?\n            status: Status\n        }\n    }\n    \
+        class Item {\n        <<record>>\n        title: String\n    }\n    \
+        class Status {\n        <<enumeration>>\n        Open\n        Closed\n    }\n    \
+        shop_Entity <|-- shop_Order\n    shop_Order --> Item : item\n    shop_Order --> Status : status";
+        "mermaid"
+    )]
+    #[test_case(
+        DiagramFormat::Dot,
+        "digraph Models {\n    node [shape=record];\n\n    \
+        subgraph cluster_shop {\n        label = \"Shop\";\n        \
+        \"shop.Entity\" [label=\"{«fragment»\\nEntity\\<Key\\>|id: Key\\l}\"];\n        \
+        \"shop.Order\" [label=\"{«record»\\nOrder|item: Item?\\lstatus: Status\\l}\"];\n    }\n\n    \
+        \"Item\" [label=\"{«record»\\nItem|title: String\\l}\"];\n    \
+        \"Status\" [label=\"{«enumeration»\\nStatus|Open\\lClosed\\l}\"];\n\n    \
+        \"shop.Order\" -> \"shop.Entity\" [arrowhead=empty];\n    \
+        \"shop.Order\" -> \"Item\" [label=\"item\"];\n    \
+        \"shop.Order\" -> \"Status\" [label=\"status\"];\n}";
+        "dot"
+    )]
+    fn check(format: DiagramFormat, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(CODE);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        DiagramTransformer::new(format).apply(&ast, &render).unwrap();
+
+        assert_eq!(render.as_string(4), expected)
+    }
+
+    #[test]
+    fn check_packages() {
+        let code = "model Name {\n    first: String\n}\n\npackage billing {\n    model Name {\n        id: String\n    }\n}\n\nscalar String;";
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        DiagramTransformer::new(DiagramFormat::Mermaid).apply(&ast, &render).unwrap();
+        let result = render.as_string(4);
+
+        assert!(result.contains("    class Name {\n"));
+        assert!(result.contains("        class billing_Name[\"Name\"] {\n"));
+    }
+}
@@ -0,0 +1,725 @@
+use std::cell::RefCell;
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::to_snake_case;
+use crate::transform::model_index::{generic_names, model_params_def, ModelIndex, ModelKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocsFormat {
+    Markdown,
+    Html,
+}
+
+enum Inline {
+    Text(String),
+    Code(String),
+    Link(String, String),
+}
+
+enum Block {
+    Heading(usize, String),
+    Paragraph(Vec<Inline>),
+    Table(Vec<&'static str>, Vec<Vec<Vec<Inline>>>),
+}
+
+struct Page {
+    path: Vec<String>,
+    blocks: Vec<Block>,
+}
+
+/// Generates browsable documentation: one page per package plus an index page.
+pub struct DocsTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    docs: DocComments,
+    format: DocsFormat,
+
+    path: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    blocks: RefCell<Vec<Block>>,
+    pages: RefCell<Vec<Page>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for DocsTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> DocsTransformer<'input> {
+    pub fn new() -> Self {
+        DocsTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            docs: DocComments::default(),
+            format: DocsFormat::Markdown,
+
+            path: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            blocks: RefCell::new(Vec::new()),
+            pages: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Doc comments collected from the source with `DocComments::parse`.
+    pub fn with_docs(mut self, docs: DocComments) -> Self {
+        self.docs = docs;
+        self
+    }
+
+    pub fn with_format(mut self, format: DocsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.take();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        let index = self.index_page();
+        self.page("index", "Models", index);
+        for page in self.pages.take() {
+            let title = match page.path.is_empty() {
+                true => "Global".to_string(),
+                false => format!("Package {}", page.path.join(".")),
+            };
+            self.page(&Self::page_name(&page.path), &title, page.blocks);
+        }
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn page_name(path: &[String]) -> String {
+        match path.is_empty() {
+            true => "global".to_string(),
+            false => path.iter().map(|p| to_snake_case(p)).collect::<Vec<_>>().join("."),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.format {
+            DocsFormat::Markdown => "md",
+            DocsFormat::Html => "html",
+        }
+    }
+
+    fn anchor(name: &str) -> String {
+        name.to_lowercase().replace(' ', "-")
+    }
+
+    fn href(&self, path: &[String], name: &str) -> String {
+        match *path == *self.path.borrow() {
+            true => format!("#{}", Self::anchor(name)),
+            false => format!("{}.{}#{}", Self::page_name(path), self.extension(), Self::anchor(name)),
+        }
+    }
+
+    /// Dotted path a model's doc comments are keyed by, see `DocComments`.
+    fn doc_key(path: &[String], name: &str) -> String {
+        path.iter().map(String::as_str).chain([name]).collect::<Vec<_>>().join(".")
+    }
+
+    fn doc(&self, key: &str) -> Vec<Inline> {
+        self.docs.get(key)
+            .map(|doc| vec![Inline::Text(doc.replace('\n', " "))])
+            .unwrap_or_default()
+    }
+
+    fn push(&self, block: Block) {
+        self.blocks.borrow_mut().push(block);
+    }
+
+    fn index_page(&self) -> Vec<Block> {
+        let index = self.index.borrow();
+        let mut packages: Vec<Vec<String>> = vec![];
+        for entry in index.entries() {
+            if !packages.contains(&entry.path) {
+                packages.push(entry.path.clone());
+            }
+        }
+
+        let package_rows = packages.iter()
+            .map(|path| {
+                let page = Self::page_name(path);
+                let count = index.entries().iter().filter(|e| e.path == *path).count();
+                vec![
+                    vec![Inline::Link(page.clone(), format!("{}.{}", page, self.extension()))],
+                    vec![Inline::Text(count.to_string())],
+                ]
+            })
+            .collect();
+
+        let mut entries = index.entries().iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let model_rows = entries.into_iter()
+            .map(|entry| {
+                let page = Self::page_name(&entry.path);
+                let href = format!("{}.{}#{}", page, self.extension(), Self::anchor(&entry.name));
+                vec![
+                    vec![Inline::Link(entry.name.clone(), href)],
+                    vec![Inline::Text(page)],
+                    vec![Inline::Text(Self::kind_name(entry.kind).to_string())],
+                    self.doc(&Self::doc_key(&entry.path, &entry.name)),
+                ]
+            })
+            .collect();
+
+        vec![
+            Block::Heading(1, "Models".to_string()),
+            Block::Table(vec!["Package", "Models"], package_rows),
+            Block::Table(vec!["Model", "Package", "Kind", "Description"], model_rows),
+        ]
+    }
+
+    fn kind_name(kind: ModelKind) -> &'static str {
+        match kind {
+            ModelKind::Scalar => "scalar",
+            ModelKind::Record => "record",
+            ModelKind::Fragment => "fragment",
+            ModelKind::Tuple => "tuple",
+            ModelKind::Enum => "enum",
+            ModelKind::Alias => "alias",
+        }
+    }
+
+    /// Renders a type the way it is written in Mex, linking named models to their definitions.
+    fn type_inlines(&self, item_type: &ItemType, out: &mut Vec<Inline>) {
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+                let is_generic = self.generics.borrow().iter().any(|g| g == name);
+                let index = self.index.borrow();
+
+                match index.find(name, &self.path.borrow()) {
+                    Some(entry) if !is_generic => out.push(Inline::Link(name.to_string(), self.href(&entry.path, name))),
+                    Some(_) | None => out.push(Inline::Text(name.to_string())),
+                }
+
+                let args = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Generic(item_type) => Some(item_type),
+                        ModelParam::Metadata(_, _) => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                if !args.is_empty() {
+                    out.push(Inline::Text("<".to_string()));
+                    for (i, arg) in args.into_iter().enumerate() {
+                        if i > 0 {
+                            out.push(Inline::Text(", ".to_string()));
+                        }
+                        self.type_inlines(arg, out);
+                    }
+                    out.push(Inline::Text(">".to_string()));
+                }
+
+                let metadata = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Metadata(id, value) => Some(format!("{}={}", id.as_name().unwrap_or_default(), Self::literal(value))),
+                        ModelParam::Generic(_) => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                if !metadata.is_empty() {
+                    out.push(Inline::Text(format!("[{}]", metadata.join(", "))));
+                }
+            },
+            ItemType::Optional(item_type) => {
+                self.type_inlines(item_type, out);
+                out.push(Inline::Text("?".to_string()));
+            },
+            ItemType::Inline(ModelDefinition::Tuple(_, items, _)) => {
+                out.push(Inline::Text("(".to_string()));
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(Inline::Text(", ".to_string()));
+                    }
+                    match item {
+                        TupleItem::Item(item_type) => self.type_inlines(item_type, out),
+                        TupleItem::NamedItem(id, item_type) => {
+                            out.push(Inline::Text(format!("{}: ", id.as_name().unwrap_or_default())));
+                            self.type_inlines(item_type, out);
+                        },
+                    }
+                }
+                out.push(Inline::Text(")".to_string()));
+            },
+            ItemType::Inline(ModelDefinition::Record(_, items, _)) => {
+                out.push(Inline::Text("{ ".to_string()));
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(Inline::Text(", ".to_string()));
+                    }
+                    match item {
+                        RecordItem::Item(id, item_type) => {
+                            out.push(Inline::Text(format!("{}: ", id.as_name().unwrap_or_default())));
+                            self.type_inlines(item_type, out);
+                        },
+                        RecordItem::Spread(item_type) => {
+                            out.push(Inline::Text("...".to_string()));
+                            self.type_inlines(item_type, out);
+                        },
+                    }
+                }
+                out.push(Inline::Text(" }".to_string()));
+            },
+            ItemType::Inline(ModelDefinition::Enum(_, items, _)) => {
+                let names = items.iter()
+                    .filter_map(|item| match item {
+                        EnumItem::Item(id) | EnumItem::Record(id, _) | EnumItem::Tuple(id, _) | EnumItem::Enum(id, _) => id.as_name(),
+                    })
+                    .collect::<Vec<_>>();
+                out.push(Inline::Text(format!("enum {{ {} }}", names.join(", "))));
+            },
+            ItemType::Inline(_) => {},
+        }
+    }
+
+    fn type_cell(&self, item_type: &ItemType) -> Vec<Inline> {
+        let mut out = vec![];
+        self.type_inlines(item_type, &mut out);
+        out
+    }
+
+    fn literal(literal: &Literal) -> String {
+        match literal {
+            Literal::String(value) | Literal::Number(value) => value.to_string(),
+        }
+    }
+
+    /// Writes the heading of a model and returns the key of its doc comments.
+    fn section(&self, id: &Id, kind: Vec<Inline>, params: &[ModelParamDefinition]) -> String {
+        let name = id.as_name().unwrap_or_default().to_string();
+        let key = Self::doc_key(&self.path.borrow(), &name);
+        *self.generics.borrow_mut() = generic_names(params);
+
+        self.push(Block::Heading(2, name));
+        if let Some(doc) = self.docs.get(&key) {
+            self.push(Block::Paragraph(vec![Inline::Text(doc.to_string())]));
+        }
+        self.push(Block::Paragraph(kind));
+
+        let generics = params.iter()
+            .filter_map(|param| match param {
+                ModelParamDefinition::Generic { id, constraint_type } => Some(vec![
+                    vec![Inline::Code(id.as_name().unwrap_or_default().to_string())],
+                    constraint_type.as_ref().map(|t| self.type_cell(t)).unwrap_or_default(),
+                ]),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !generics.is_empty() {
+            self.push(Block::Table(vec!["Generic", "Constraint"], generics));
+        }
+
+        let metadata = params.iter()
+            .filter_map(|param| match param {
+                ModelParamDefinition::Metadata { id, type_id, def_value } => Some(vec![
+                    vec![Inline::Code(id.as_name().unwrap_or_default().to_string())],
+                    self.type_cell(type_id),
+                    def_value.as_ref().map(|v| vec![Inline::Code(Self::literal(v))]).unwrap_or_default(),
+                ]),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !metadata.is_empty() {
+            self.push(Block::Table(vec!["Metadata", "Type", "Default"], metadata));
+        }
+
+        key
+    }
+
+    fn fields(&self, key: &str, items: &[RecordItem]) {
+        let rows = items.iter()
+            .map(|item| match item {
+                RecordItem::Item(id, item_type) => {
+                    let field = id.as_name().unwrap_or_default();
+                    vec![
+                        vec![Inline::Code(field.to_string())],
+                        self.type_cell(item_type),
+                        self.doc(&format!("{}.{}", key, field)),
+                    ]
+                },
+                RecordItem::Spread(item_type) => vec![
+                    vec![Inline::Code("...".to_string())],
+                    self.type_cell(item_type),
+                    vec![],
+                ],
+            })
+            .collect::<Vec<_>>();
+
+        if !rows.is_empty() {
+            self.push(Block::Table(vec!["Field", "Type", "Description"], rows));
+        }
+    }
+
+    fn page(&self, name: &str, title: &str, blocks: Vec<Block>) {
+        self.writer.render(TextToken::File(format!("{}.{}", name, self.extension())));
+
+        match self.format {
+            DocsFormat::Markdown => {
+                let mut blocks = blocks.into_iter();
+                if let Some(block) = blocks.next() {
+                    self.markdown(block);
+                }
+                for block in blocks {
+                    self.writer.empty_line();
+                    self.markdown(block);
+                }
+            },
+            DocsFormat::Html => {
+                self.writer.line("<!DOCTYPE html>");
+                self.writer.line("<html>");
+                self.writer.line("<head>");
+                self.writer.indent();
+                self.writer.line("<meta charset=\"utf-8\">");
+                self.writer.line(format!("<title>{}</title>", Self::escape(title)));
+                self.writer.dedent();
+                self.writer.line("</head>");
+                self.writer.line("<body>");
+                self.writer.indent();
+                for block in blocks {
+                    self.html(block);
+                }
+                self.writer.dedent();
+                self.writer.line("</body>");
+                self.writer.line("</html>");
+            },
+        }
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+    }
+
+    fn inlines(&self, inlines: &[Inline]) -> String {
+        inlines.iter()
+            .map(|inline| match (self.format, inline) {
+                (_, Inline::Text(text)) => Self::escape(text),
+                (DocsFormat::Markdown, Inline::Code(code)) => format!("`{}`", code),
+                (DocsFormat::Markdown, Inline::Link(text, href)) => format!("[{}]({})", Self::escape(text), href),
+                (DocsFormat::Html, Inline::Code(code)) => format!("<code>{}</code>", Self::escape(code)),
+                (DocsFormat::Html, Inline::Link(text, href)) => format!("<a href=\"{}\">{}</a>", href, Self::escape(text)),
+            })
+            .collect()
+    }
+
+    fn markdown(&self, block: Block) {
+        match block {
+            Block::Heading(level, text) => self.writer.line(format!("{} {}", "#".repeat(level), text)),
+            Block::Paragraph(inlines) => self.writer.line(self.inlines(&inlines)),
+            Block::Table(headers, rows) => {
+                self.writer.line(format!("| {} |", headers.join(" | ")));
+                self.writer.line(format!("|{}", " --- |".repeat(headers.len())));
+                for row in rows {
+                    let cells = row.iter()
+                        .map(|cell| match self.inlines(cell) {
+                            cell if cell.is_empty() => " |".to_string(),
+                            cell => format!(" {} |", cell),
+                        })
+                        .collect::<String>();
+                    self.writer.line(format!("|{}", cells));
+                }
+            },
+        }
+    }
+
+    fn html(&self, block: Block) {
+        match block {
+            Block::Heading(level, text) => {
+                self.writer.line(format!("<h{} id=\"{}\">{}</h{}>", level, Self::anchor(&text), Self::escape(&text), level));
+            },
+            Block::Paragraph(inlines) => self.writer.line(format!("<p>{}</p>", self.inlines(&inlines))),
+            Block::Table(headers, rows) => {
+                self.writer.line("<table>");
+                self.writer.indent();
+                let headers = headers.iter().map(|h| format!("<th>{}</th>", h)).collect::<String>();
+                self.writer.line(format!("<tr>{}</tr>", headers));
+                for row in rows {
+                    let cells = row.iter().map(|cell| format!("<td>{}</td>", self.inlines(cell))).collect::<String>();
+                    self.writer.line(format!("<tr>{}</tr>", cells));
+                }
+                self.writer.dedent();
+                self.writer.line("</table>");
+            },
+        }
+    }
+}
+
+impl Transformer<'_> for DocsTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        self.writer.text(Self::literal(literal));
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        let cell = self.type_cell(item_type);
+        self.writer.text(self.inlines(&cell));
+    }
+
+    fn visit_model_params(&self, _params: &[ModelParam]) {
+
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        let outer = self.blocks.replace(Vec::new());
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                continue;
+            }
+            self.visit_scope(item, false);
+        }
+
+        let blocks = self.blocks.replace(outer);
+        if !blocks.is_empty() {
+            let path = self.path.borrow().clone();
+            let mut pages = self.pages.borrow_mut();
+            match pages.iter_mut().find(|page| page.path == path) {
+                Some(page) => page.blocks.extend(blocks),
+                None => {
+                    let mut page_blocks = vec![match path.is_empty() {
+                        true => Block::Heading(1, "Global".to_string()),
+                        false => Block::Heading(1, format!("Package {}", path.join("."))),
+                    }];
+                    page_blocks.extend(blocks);
+                    pages.push(Page { path, blocks: page_blocks });
+                },
+            }
+        }
+
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                self.visit_scope(item, false);
+            }
+        }
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        self.visit_global(items);
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Fragment(ref id, ref items, ref params) => {
+                let key = self.section(id, vec![Inline::Text("Fragment".to_string())], params);
+                self.fields(&key, items);
+            }
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, ref item_type) => {
+                *self.generics.borrow_mut() = generic_names(params);
+                let mut target = vec![Inline::Text("Alias of ".to_string())];
+                self.type_inlines(item_type, &mut target);
+                self.section(id, target, params);
+            },
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        let key = self.section(id, vec![Inline::Text("Record".to_string())], params);
+        self.fields(&key, items);
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        let key = self.section(id, vec![Inline::Text("Tuple".to_string())], params);
+
+        let rows = items.iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let (item, item_type) = match item {
+                    TupleItem::Item(item_type) => (i.to_string(), item_type),
+                    TupleItem::NamedItem(id, item_type) => (id.as_name().unwrap_or_default().to_string(), item_type),
+                };
+                let doc = self.doc(&format!("{}.{}", key, item));
+                vec![vec![Inline::Code(item)], self.type_cell(item_type), doc]
+            })
+            .collect::<Vec<_>>();
+
+        if !rows.is_empty() {
+            self.push(Block::Table(vec!["Item", "Type", "Description"], rows));
+        }
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        let key = self.section(id, vec![Inline::Text("Enum".to_string())], params);
+
+        let rows = items.iter()
+            .map(|item| {
+                let (id, payload) = match item {
+                    EnumItem::Item(id) => (id, vec![]),
+                    EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                        (id, self.type_cell(item_type))
+                    },
+                };
+                let variant = id.as_name().unwrap_or_default();
+                vec![vec![Inline::Code(variant.to_string())], payload, self.doc(&format!("{}.{}", key, variant))]
+            })
+            .collect::<Vec<_>>();
+
+        if !rows.is_empty() {
+            self.push(Block::Table(vec!["Variant", "Payload", "Description"], rows));
+        }
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref item_type) = item {
+            self.writer.text(format!("{}: ", id.as_name().unwrap_or_default()));
+            self.visit_item_type(item_type);
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.visit_id(id);
+        }
+    }
+
+    fn visit_scalar(&self, id: &Id) {
+        self.section(id, vec![Inline::Text("Scalar".to_string())], &[]);
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.errors.borrow_mut().push(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    const CODE: &str = "package Shop;\n\n\
+        /// Something for sale.\nmodel Item<T>[currency: String = EUR] {\n    /// Identifier\n    id: Int\n    tags: T?\n}\n\
+        model Status enum {\n    Draft\n    Sold(Int)\n}\n\
+        scalar Int;\nscalar String;";
+
+    #[test_case(
+        DocsFormat::Markdown,
+        "shop.md",
+        "# Package Shop\n\n## Item\n\nSomething for sale.\n\nRecord\n\n\
+        | Generic | Constraint |\n| --- | --- |\n| `T` | |\n\n\
+        | Metadata | Type | Default |\n| --- | --- | --- |\n| `currency` | [String](#string) | `EUR` |\n\n\
+        | Field | Type | Description |\n| --- | --- | --- |\n| `id` | [Int](#int) | Identifier |\n| `tags` | T? | |\n\n\
+        ## Status\n\nEnum\n\n\
+        | Variant | Payload | Description |\n| --- | --- | --- |\n| `Draft` | | |\n| `Sold` | ([Int](#int)) | |\n\n\
+        ## Int\n\nScalar\n\n## String\n\nScalar\n";
+        "markdown"
+    )]
+    #[test_case(
+        DocsFormat::Html,
+        "shop.html",
+        "<!DOCTYPE html>\n<html>\n<head>\n  <meta charset=\"utf-8\">\n  <title>Package Shop</title>\n</head>\n<body>\n  \
+        <h1 id=\"package-shop\">Package Shop</h1>\n  <h2 id=\"item\">Item</h2>\n  <p>Something for sale.</p>\n  <p>Record</p>\n  \
+        <table>\n    <tr><th>Generic</th><th>Constraint</th></tr>\n    <tr><td><code>T</code></td><td></td></tr>\n  </table>\n  \
+        <table>\n    <tr><th>Metadata</th><th>Type</th><th>Default</th></tr>\n    \
+        <tr><td><code>currency</code></td><td><a href=\"#string\">String</a></td><td><code>EUR</code></td></tr>\n  </table>\n  \
+        <table>\n    <tr><th>Field</th><th>Type</th><th>Description</th></tr>\n    \
+        <tr><td><code>id</code></td><td><a href=\"#int\">Int</a></td><td>Identifier</td></tr>\n    \
+        <tr><td><code>tags</code></td><td>T?</td><td></td></tr>\n  </table>\n  \
+        <h2 id=\"status\">Status</h2>\n  <p>Enum</p>\n  \
+        <table>\n    <tr><th>Variant</th><th>Payload</th><th>Description</th></tr>\n    \
+        <tr><td><code>Draft</code></td><td></td><td></td></tr>\n    \
+        <tr><td><code>Sold</code></td><td>(<a href=\"#int\">Int</a>)</td><td></td></tr>\n  </table>\n  \
+        <h2 id=\"int\">Int</h2>\n  <p>Scalar</p>\n  <h2 id=\"string\">String</h2>\n  <p>Scalar</p>\n</body>\n</html>\n";
+        "html"
+    )]
+    fn check(format: DocsFormat, file: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(CODE);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = DocsTransformer::new().with_docs(DocComments::parse(CODE)).with_format(format);
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_files(2);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1].0, file);
+        assert_eq!(&result[1].1, expected)
+    }
+
+    #[test]
+    fn check_packages() {
+        let code = "package shop;\n\n/// Shop name\nmodel Name(String)\n\npackage billing {\n    /// Billing name\n    model Name(String)\n}\n\nscalar String;";
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = DocsTransformer::new().with_docs(DocComments::parse(code));
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_files(2);
+
+        let page = |name: &str| result.iter().find(|(file, _)| file == name).map(|(_, text)| text.as_str()).unwrap();
+        assert!(page("shop.md").contains("## Name\n\nShop name\n"));
+        assert!(page("shop.billing.md").contains("## Name\n\nBilling name\n"));
+        assert!(!page("shop.md").contains("Billing name"));
+    }
+}
@@ -0,0 +1,31 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransformError {
+    UnknownType(String),
+    Unsupported(String, String),
+    RecursiveSpread(String),
+    InvalidLock(String),
+    InvalidManifest(String),
+    /// Manifest text outside the supported TOML subset, with its line.
+    InvalidManifestSyntax(usize, String),
+    Parsing(String),
+    InvalidAst(String),
+    Template(String),
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransformError::UnknownType(name) => write!(f, "unknown type `{}`", name),
+            TransformError::Unsupported(model, reason) => write!(f, "`{}`: {}", model, reason),
+            TransformError::RecursiveSpread(name) => write!(f, "recursive spread of `{}`", name),
+            TransformError::InvalidLock(line) => write!(f, "invalid lock entry `{}`", line),
+            TransformError::InvalidManifest(line) => write!(f, "invalid manifest entry `{}`", line),
+            TransformError::InvalidManifestSyntax(line, reason) => write!(f, "invalid manifest: line {}: {}", line, reason),
+            TransformError::Parsing(error) => write!(f, "syntax error: {}", error),
+            TransformError::InvalidAst(error) => write!(f, "invalid syntax tree: {}", error),
+            TransformError::Template(error) => write!(f, "template error: {}", error),
+        }
+    }
+}
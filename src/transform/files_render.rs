@@ -0,0 +1,90 @@
+use std::cell::RefCell;
+use std::path::Path;
+use crate::transform::{StringRender, Target, TextToken};
+
+/// Splits the token stream into files on every `TextToken::File`.
+///
+/// Text emitted before the first `TextToken::File` is kept in a file with an empty name,
+/// which `write_to` refuses to write.
+pub struct FilesRender {
+    files: RefCell<Vec<(String, StringRender)>>,
+    width: usize,
+}
+
+impl Default for FilesRender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FilesRender {
+    pub fn new() -> Self {
+        FilesRender {
+            files: RefCell::new(Vec::new()),
+            width: usize::MAX,
+        }
+    }
+
+    /// Line width groups are fitted into; unlimited by default.
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn as_files(&self, indent_count: usize) -> Vec<(String, String)> {
+        self.files.borrow().iter()
+            .map(|(name, render)| (name.clone(), render.as_string(indent_count)))
+            .filter(|(name, text)| !name.is_empty() || !text.trim().is_empty())
+            .map(|(name, text)| (name, text + "\n"))
+            .collect()
+    }
+
+    pub fn write_to<P: AsRef<Path>>(&self, dir: P, indent_count: usize) -> std::io::Result<()> {
+        for (name, text) in self.as_files(indent_count) {
+            if name.is_empty() {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "text was emitted before the first file"));
+            }
+            let path = dir.as_ref().join(name);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, text)?;
+        }
+        Ok(())
+    }
+}
+
+impl Target<TextToken> for FilesRender {
+
+    fn render(&self, token: TextToken) {
+        if let TextToken::File(name) = token {
+            self.files.borrow_mut().push((name, StringRender::new().with_width(self.width)));
+            return;
+        }
+
+        let mut files = self.files.borrow_mut();
+        if files.is_empty() {
+            files.push((String::new(), StringRender::new().with_width(self.width)));
+        }
+        if let Some((_, render)) = files.last() {
+            render.render(token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_text_before_first_file() {
+        let render = FilesRender::new();
+        render.render(TextToken::NewLine);
+        render.render(TextToken::Text("header".into()));
+        render.render(TextToken::File("a.txt".into()));
+        render.render(TextToken::Text("a".into()));
+
+        assert_eq!(render.as_files(4), vec![(String::new(), "header\n".to_string()), ("a.txt".to_string(), "a\n".to_string())]);
+        assert!(render.write_to(std::env::temp_dir(), 4).is_err());
+    }
+}
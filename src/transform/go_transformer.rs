@@ -0,0 +1,660 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::{to_pascal_case, to_snake_case};
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+struct Field {
+    name: String,
+    go_type: String,
+    tag: String,
+}
+
+/// Generates one Go package per Mex package; enums with payloads become sealed interfaces.
+pub struct GoTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    scalars: HashMap<String, (String, Option<String>)>,
+    module: String,
+
+    path: RefCell<Vec<String>>,
+    models: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    imports: RefCell<BTreeSet<String>>,
+    pending: RefCell<Vec<Vec<TextToken>>>,
+    files: RefCell<Vec<Vec<TextToken>>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for GoTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> GoTransformer<'input> {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "string", None),
+            ("Int", "int64", None),
+            ("Long", "int64", None),
+            ("Byte", "byte", None),
+            ("Float", "float32", None),
+            ("Double", "float64", None),
+            ("Decimal", "string", None),
+            ("Bool", "bool", None),
+            ("Boolean", "bool", None),
+            ("Bytes", "[]byte", None),
+            ("Uuid", "string", None),
+            ("Date", "time.Time", Some("time")),
+            ("DateTime", "time.Time", Some("time")),
+        ];
+
+        GoTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            scalars: scalars.iter()
+                .map(|(k, v, import)| (k.to_string(), (v.to_string(), import.map(String::from))))
+                .collect(),
+            module: String::new(),
+
+            path: RefCell::new(Vec::new()),
+            models: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            imports: RefCell::new(BTreeSet::new()),
+            pending: RefCell::new(Vec::new()),
+            files: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a Go type and the import it needs, e.g. `("Uuid", "uuid.UUID", Some("github.com/google/uuid"))`.
+    pub fn with_scalar(mut self, scalar: &str, go_type: &str, import: Option<&str>) -> Self {
+        self.scalars.insert(scalar.to_string(), (go_type.to_string(), import.map(String::from)));
+        self
+    }
+
+    /// Go module path the package directories are imported from, e.g. `github.com/acme/api`.
+    pub fn with_module(mut self, module: &str) -> Self {
+        self.module = module.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.files
+            .into_inner().into_iter().flatten()
+            .for_each(|t| render.render(t));
+
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        let model = self.models.borrow().first().cloned().unwrap_or_default();
+        self.error(TransformError::Unsupported(model, reason.to_string()));
+    }
+
+    fn package_dir(path: &[String]) -> String {
+        match path.is_empty() {
+            true => "models".to_string(),
+            false => path.iter().map(|p| to_snake_case(p).replace('_', "")).collect::<Vec<_>>().join("/"),
+        }
+    }
+
+    fn package_name(path: &[String]) -> String {
+        let dir = Self::package_dir(path);
+        dir.rsplit('/').next().unwrap_or_default().to_string()
+    }
+
+    fn import_path(&self, path: &[String]) -> String {
+        match self.module.is_empty() {
+            true => Self::package_dir(path),
+            false => format!("{}/{}", self.module, Self::package_dir(path)),
+        }
+    }
+
+    /// Exported Go identifier with the common initialisms kept upper case.
+    fn exported(name: &str) -> String {
+        const INITIALISMS: &[&str] = &["id", "url", "uri", "http", "json", "api", "uuid", "ip", "sql", "xml"];
+
+        to_snake_case(name)
+            .split('_')
+            .map(|word| match INITIALISMS.contains(&word) {
+                true => word.to_uppercase(),
+                false => to_pascal_case(word),
+            })
+            .collect()
+    }
+
+    fn type_params(&self) -> String {
+        let generics = self.generics.borrow();
+        match generics.is_empty() {
+            true => String::new(),
+            false => format!("[{}]", generics.iter().map(|g| format!("{} any", g)).collect::<Vec<_>>().join(", ")),
+        }
+    }
+
+    fn type_args(&self) -> String {
+        let generics = self.generics.borrow();
+        match generics.is_empty() {
+            true => String::new(),
+            false => format!("[{}]", generics.join(", ")),
+        }
+    }
+
+    /// Writes a top-level declaration preceded by the declarations of its inline types.
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str)) {
+        let name = id.as_name().unwrap_or_default();
+
+        *self.generics.borrow_mut() = generic_names(params);
+        self.models.borrow_mut().push(name.to_string());
+        let tokens = self.writer.capture(|| f(name));
+        self.models.borrow_mut().pop();
+        self.generics.borrow_mut().clear();
+
+        for pending in self.pending.take() {
+            self.writer.block(|| self.writer.extend(pending));
+        }
+        self.writer.block(|| self.writer.extend(tokens));
+    }
+
+    fn file(&self, items: &[RefScope]) {
+        let path = self.path.borrow().clone();
+        let outer_imports = self.imports.replace(BTreeSet::new());
+
+        let body = self.writer.capture(|| {
+            for item in items {
+                if let Scope::Package(_, _) = **item.borrow() {
+                    continue;
+                }
+                self.visit_scope(item, false);
+            }
+        });
+
+        let imports = self.imports.replace(outer_imports);
+
+        if !body.is_empty() {
+            let file = self.writer.capture(|| {
+                self.writer.render(TextToken::File(Self::package_dir(&path) + "/models.go"));
+                self.writer.line(format!("package {}", Self::package_name(&path)));
+
+                match imports.len() {
+                    0 => {},
+                    1 => {
+                        self.writer.empty_line();
+                        self.writer.line(format!("import \"{}\"", imports.iter().next().unwrap()));
+                    },
+                    _ => {
+                        self.writer.empty_line();
+                        self.writer.line("import (");
+                        self.writer.indent();
+                        for import in imports {
+                            self.writer.line(format!("\"{}\"", import));
+                        }
+                        self.writer.dedent();
+                        self.writer.line(")");
+                    },
+                }
+
+                self.writer.empty_line();
+                self.writer.extend(body);
+            });
+            self.files.borrow_mut().push(file);
+        }
+
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                self.visit_scope(item, false);
+            }
+        }
+    }
+
+    /// Resolves the Go type of a field, declaring named types for inline models.
+    fn go_type(&self, hint: &str, item_type: &ItemType, bindings: &Bindings) -> Option<String> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if self.generics.borrow().iter().any(|g| g == name) {
+                    return Some(name.to_string());
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                let args = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Generic(item_type) => Some(self.go_type(hint, item_type, bindings)),
+                        ModelParam::Metadata(_, _) => None,
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                let go_type = match entry.kind {
+                    ModelKind::Scalar => {
+                        let Some((go_type, import)) = self.scalars.get(name) else {
+                            self.unsupported(&format!("scalar `{}` has no Go mapping", name));
+                            return None;
+                        };
+
+                        if let Some(import) = import {
+                            self.imports.borrow_mut().insert(import.clone());
+                        }
+                        go_type.clone()
+                    },
+                    ModelKind::Fragment => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                        return None;
+                    },
+                    _ => {
+                        match entry.path == *self.path.borrow() {
+                            true => entry.name.clone(),
+                            false => {
+                                self.imports.borrow_mut().insert(self.import_path(&entry.path));
+                                format!("{}.{}", Self::package_name(&entry.path), entry.name)
+                            },
+                        }
+                    },
+                };
+
+                match args.is_empty() {
+                    true => Some(go_type),
+                    false => Some(format!("{}[{}]", go_type, args.join(", "))),
+                }
+            },
+            ItemType::Inline(def) => {
+                let parent = self.models.borrow().last().cloned().unwrap_or_default();
+                let name = parent + &Self::exported(hint);
+
+                let tokens = self.writer.capture(|| self.declaration(&name, def, bindings));
+                self.pending.borrow_mut().push(tokens);
+                Some(name + &self.type_args())
+            },
+            ItemType::Optional(item_type) => {
+                let go_type = self.go_type(hint, item_type, bindings)?;
+                Some(format!("*{}", go_type))
+            },
+        }
+    }
+
+    fn field(&self, name: &str, item_type: &ItemType, bindings: &Bindings) -> Option<Field> {
+        let go_type = self.go_type(name, item_type, bindings)?;
+        let omit = match bindings.resolve(item_type).0 {
+            ItemType::Optional(_) => ",omitempty",
+            _ => "",
+        };
+
+        Some(Field {
+            name: Self::exported(name),
+            go_type,
+            tag: format!("`json:\"{}{}\"`", name, omit),
+        })
+    }
+
+    fn declaration(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) {
+        self.models.borrow_mut().push(name.to_string());
+
+        match def {
+            ModelDefinition::Record(_, items, _) => self.record_struct(name, items, bindings),
+            ModelDefinition::Tuple(_, items, _) => self.tuple_type(name, items, bindings),
+            ModelDefinition::Enum(_, items, _) => self.enum_type(name, items, bindings),
+            ModelDefinition::Alias(_, _, item_type) => {
+                if let Some(go_type) = self.go_type(name, item_type, bindings) {
+                    self.writer.line(format!("type {}{} = {}", name, self.type_params(), go_type));
+                }
+            },
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => {},
+        }
+
+        self.models.borrow_mut().pop();
+    }
+
+    fn record_fields(&self, items: &[RecordItem], bindings: &Bindings) -> Vec<Field> {
+        let mut fields = vec![];
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            fields.extend(self.field(id.as_name().unwrap_or_default(), item_type, bindings));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        fields
+    }
+
+    fn tuple_fields(&self, items: &[TupleItem], bindings: &Bindings) -> Vec<Field> {
+        items.iter()
+            .enumerate()
+            .filter_map(|(i, item)| match item {
+                TupleItem::Item(item_type) if items.len() == 1 => self.field("value", item_type, bindings),
+                TupleItem::Item(item_type) => self.field(&format!("item{}", i + 1), item_type, bindings),
+                TupleItem::NamedItem(id, item_type) => self.field(id.as_name().unwrap_or_default(), item_type, bindings),
+            })
+            .collect()
+    }
+
+    fn record_struct(&self, name: &str, items: &[RecordItem], bindings: &Bindings) {
+        let fields = self.record_fields(items, bindings);
+        self.struct_type(name, fields);
+    }
+
+    fn tuple_type(&self, name: &str, items: &[TupleItem], bindings: &Bindings) {
+        if let ([TupleItem::Item(item_type)], true) = (items, self.generics.borrow().is_empty()) {
+            if let Some(go_type) = self.go_type("value", item_type, bindings) {
+                self.writer.line(format!("type {} {}", name, go_type));
+            }
+            return;
+        }
+
+        let fields = self.tuple_fields(items, bindings);
+        self.struct_type(name, fields);
+    }
+
+    fn enum_type(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        if items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            let members = items.iter()
+                .filter_map(|item| match item {
+                    EnumItem::Item(id) => id.as_name(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            self.writer.line(format!("type {} string", name));
+            if members.is_empty() {
+                return;
+            }
+
+            let width = members.iter().map(|m| name.len() + Self::exported(m).len()).max().unwrap_or_default();
+            self.writer.empty_line();
+            self.writer.line("const (");
+            self.writer.indent();
+            for member in members {
+                let constant = format!("{}{}", name, Self::exported(member));
+                self.writer.line(format!("{:width$} {} = \"{}\"", constant, name, member, width = width));
+            }
+            self.writer.dedent();
+            self.writer.line(")");
+            return;
+        }
+
+        let marker = format!("is{}", name);
+        self.writer.line(format!("type {}{} interface {{", name, self.type_params()));
+        self.writer.indent();
+        self.writer.line(format!("{}()", marker));
+        self.writer.dedent();
+        self.writer.line("}");
+
+        for item in items {
+            let (id, payload) = match item {
+                EnumItem::Item(id) => (id, None),
+                EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                    (id, Some(item_type))
+                },
+            };
+
+            let variant = format!("{}{}", name, Self::exported(id.as_name().unwrap_or_default()));
+            self.models.borrow_mut().push(variant.clone());
+            let fields = match payload {
+                Some(ItemType::Inline(ModelDefinition::Record(_, items, _))) => self.record_fields(items, bindings),
+                Some(ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => self.tuple_fields(items, bindings),
+                Some(item_type) => self.field("value", item_type, bindings).into_iter().collect(),
+                None => vec![],
+            };
+            self.models.borrow_mut().pop();
+
+            self.writer.empty_line();
+            self.struct_type(&variant, fields);
+            self.writer.empty_line();
+            self.writer.line(format!("func ({}{}) {}() {{}}", variant, self.type_args(), marker));
+        }
+    }
+
+    fn struct_type(&self, name: &str, fields: Vec<Field>) {
+        if fields.is_empty() {
+            self.writer.line(format!("type {}{} struct{{}}", name, self.type_params()));
+            return;
+        }
+
+        let name_width = fields.iter().map(|f| f.name.len()).max().unwrap_or_default();
+        let type_width = fields.iter().map(|f| f.go_type.len()).max().unwrap_or_default();
+
+        self.writer.line(format!("type {}{} struct {{", name, self.type_params()));
+        self.writer.indent();
+        for field in fields {
+            self.writer.line(format!(
+                "{:name_width$} {:type_width$} {}",
+                field.name, field.go_type, field.tag,
+                name_width = name_width, type_width = type_width,
+            ));
+        }
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+}
+
+impl Transformer<'_> for GoTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(Self::exported(name));
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        match literal {
+            Literal::String(ref str) => self.writer.text(format!("\"{}\"", str)),
+            Literal::Number(ref str) => self.writer.text(str.to_string()),
+        }
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        if let Some(go_type) = self.go_type("value", item_type, &Bindings::root()) {
+            self.writer.text(go_type);
+        }
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        let args = params.iter()
+            .filter_map(|param| match param {
+                ModelParam::Generic(item_type) => self.go_type("value", item_type, &Bindings::root()),
+                ModelParam::Metadata(_, _) => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !args.is_empty() {
+            self.writer.text(format!("[{}]", args.join(", ")));
+        }
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        self.file(items);
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        self.file(items);
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, _) => {
+                self.top_level(id, params, |name| self.declaration(name, def, &Bindings::root()));
+            },
+            ModelDefinition::Fragment(_, _, _) => {
+
+            }
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.record_struct(name, items, &Bindings::root()));
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.tuple_type(name, items, &Bindings::root()));
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.enum_type(name, items, &Bindings::root()));
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref item_type) = item {
+            if let Some(field) = self.field(id.as_name().unwrap_or_default(), item_type, &Bindings::root()) {
+                self.writer.line(format!("{} {} {}", field.name, field.go_type, field.tag));
+            }
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            let member = id.as_name().unwrap_or_default();
+            self.writer.line(format!("{} = \"{}\"", Self::exported(member), member));
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    #[test_case(
+        "package Shop;\n\nmodel Item {\n    id: Int\n    title: String?\n    created: DateTime\n}\nscalar Int;\nscalar String;\nscalar DateTime;",
+        "package shop\n\nimport \"time\"\n\n\
+        type Item struct {\n    ID      int64     `json:\"id\"`\n    Title   *string   `json:\"title,omitempty\"`\n    Created time.Time `json:\"created\"`\n}\n";
+        "record"
+    )]
+    #[test_case(
+        "model Email(String)\nmodel Status enum {\n    Draft\n    Published\n}\nscalar String;",
+        "package models\n\ntype Email string\n\n\
+        type Status string\n\nconst (\n    StatusDraft     Status = \"Draft\"\n    StatusPublished Status = \"Published\"\n)\n";
+        "tuple and plain enum"
+    )]
+    #[test_case(
+        "model Page<T> {\n    items: T\n    meta: {\n        total: Int\n    }\n}\nscalar Int;",
+        "package models\n\n\
+        type PageMeta[T any] struct {\n    Total int64 `json:\"total\"`\n}\n\n\
+        type Page[T any] struct {\n    Items T           `json:\"items\"`\n    Meta  PageMeta[T] `json:\"meta\"`\n}\n";
+        "generic"
+    )]
+    #[test_case(
+        "model Password enum {\n    Open(String)\n    Empty\n}\nscalar String;",
+        "package models\n\n\
+        type Password interface {\n    isPassword()\n}\n\n\
+        type PasswordOpen struct {\n    Value string `json:\"value\"`\n}\n\n\
+        func (PasswordOpen) isPassword() {}\n\n\
+        type PasswordEmpty struct{}\n\n\
+        func (PasswordEmpty) isPassword() {}\n";
+        "sealed interface"
+    )]
+    fn check(code: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = GoTransformer::new();
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_files(4);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(&result[0].1, expected)
+    }
+
+    #[test]
+    fn check_packages() {
+        let code = "package Shop {\n    model Order {\n        item: Item\n    }\n}\n\
+            package Catalog {\n    model Item {\n        id: Int\n    }\n}\nscalar Int;";
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        GoTransformer::new().with_module("example.com/api").apply(&ast, &render).unwrap();
+        let files = render.as_files(4);
+
+        assert_eq!(files[0].0, "shop/models.go");
+        assert_eq!(
+            files[0].1,
+            "package shop\n\nimport \"example.com/api/catalog\"\n\ntype Order struct {\n    Item catalog.Item `json:\"item\"`\n}\n"
+        );
+    }
+}
@@ -0,0 +1,622 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::{to_camel_case, to_pascal_case, to_screaming_snake_case};
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+struct Field {
+    name: String,
+    output: Option<String>,
+    input: Option<String>,
+}
+
+/// Generates a GraphQL SDL schema. Packages share the single GraphQL namespace, so a name
+/// declared in several packages is prefixed with its package path, e.g. `ShopBillingName`.
+pub struct GraphQlTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    scalars: HashMap<String, String>,
+    inputs: bool,
+
+    path: RefCell<Vec<String>>,
+    models: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    definitions: RefCell<Vec<Vec<TextToken>>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for GraphQlTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> GraphQlTransformer<'input> {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "String"),
+            ("Int", "Int"),
+            ("Float", "Float"),
+            ("Double", "Float"),
+            ("Bool", "Boolean"),
+            ("Boolean", "Boolean"),
+            ("Uuid", "ID"),
+            ("Id", "ID"),
+        ];
+
+        GraphQlTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            scalars: scalars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            inputs: true,
+
+            path: RefCell::new(Vec::new()),
+            models: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            definitions: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a GraphQL type instead of declaring a custom `scalar`.
+    pub fn with_scalar(mut self, scalar: &str, graphql_type: &str) -> Self {
+        self.scalars.insert(scalar.to_string(), graphql_type.to_string());
+        self
+    }
+
+    /// Enables or disables the `input` counterparts of object types.
+    pub fn with_inputs(mut self, inputs: bool) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        for definition in self.definitions.into_inner() {
+            self.writer.block(|| self.writer.extend(definition));
+        }
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        let model = self.models.borrow().first().cloned().unwrap_or_default();
+        self.error(TransformError::Unsupported(model, reason.to_string()));
+    }
+
+    fn define<F: FnOnce()>(&self, at: usize, f: F) {
+        let tokens = self.writer.capture(f);
+        self.definitions.borrow_mut().insert(at, tokens);
+    }
+
+    /// GraphQL name of the model `name` declared in package `path`.
+    fn flat_name(&self, name: &str, path: &[String]) -> String {
+        self.index.borrow().flat_name(name, path)
+    }
+
+    fn input_name(name: &str) -> String {
+        format!("{}Input", name)
+    }
+
+    /// Resolves the GraphQL type of a field; `input` selects the input-object flavour.
+    fn type_ref(&self, hint: &str, item_type: &ItemType, bindings: &Bindings, input: bool) -> Option<String> {
+        match self.named_type(hint, item_type, bindings, input) {
+            Some((name, true)) => Some(name),
+            Some((name, false)) => Some(format!("{}!", name)),
+            None => None,
+        }
+    }
+
+    /// Returns the type name and whether it is nullable.
+    fn named_type(&self, hint: &str, item_type: &ItemType, bindings: &Bindings, input: bool) -> Option<(String, bool)> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Optional(item_type) => {
+                self.named_type(hint, item_type, bindings, input).map(|(name, _)| (name, true))
+            },
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+                    self.unsupported(&format!("generic type `{}` cannot be expressed in GraphQL", name));
+                    return None;
+                }
+
+                if self.generics.borrow().iter().any(|g| g == name) {
+                    self.unsupported(&format!("generic parameter `{}` cannot be expressed in GraphQL", name));
+                    return None;
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                match entry.kind {
+                    ModelKind::Scalar => {
+                        let name = self.scalars.get(name).cloned().unwrap_or_else(|| self.flat_name(name, &entry.path));
+                        Some((name, false))
+                    },
+                    ModelKind::Fragment => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                        None
+                    },
+                    ModelKind::Alias => {
+                        let scope = entry.scope.borrow();
+                        let Scope::Model(ModelDefinition::Alias(_, _, ref target)) = **scope else { unreachable!() };
+                        match **target {
+                            ItemType::Inline(_) => Some((self.type_name(&self.flat_name(name, &entry.path), input), false)),
+                            _ => self.named_type(hint, target, &Bindings::root(), input),
+                        }
+                    },
+                    ModelKind::Enum if entry.is_plain_enum() => Some((self.flat_name(name, &entry.path), false)),
+                    _ => Some((self.type_name(&self.flat_name(name, &entry.path), input), false)),
+                }
+            },
+            ItemType::Inline(def) => {
+                let parent = self.models.borrow().last().cloned().unwrap_or_default();
+                let name = match def {
+                    ModelDefinition::Record(id, _, _)
+                    | ModelDefinition::Tuple(id, _, _)
+                    | ModelDefinition::Enum(id, _, _) => id.as_name().map(String::from),
+                    _ => None,
+                }.unwrap_or_else(|| format!("{}{}", parent, to_pascal_case(hint)));
+
+                if !input {
+                    self.definition(&name, def, bindings);
+                }
+
+                let is_plain_enum = matches!(def, ModelDefinition::Enum(_, items, _)
+                    if items.iter().all(|item| matches!(item, EnumItem::Item(_))));
+
+                match is_plain_enum {
+                    true => Some((name, false)),
+                    false => Some((self.type_name(&name, input), false)),
+                }
+            },
+        }
+    }
+
+    fn type_name(&self, name: &str, input: bool) -> String {
+        match input {
+            true => Self::input_name(name),
+            false => name.to_string(),
+        }
+    }
+
+    fn field(&self, name: &str, item_type: &ItemType, bindings: &Bindings) -> Field {
+        Field {
+            name: to_camel_case(name),
+            output: self.type_ref(name, item_type, bindings, false),
+            input: match self.inputs {
+                true => self.type_ref(name, item_type, bindings, true),
+                false => None,
+            },
+        }
+    }
+
+    fn definition(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) {
+        match def {
+            ModelDefinition::Record(_, items, _) => self.record_object(name, items, bindings),
+            ModelDefinition::Tuple(_, items, _) => self.tuple_object(name, items, bindings),
+            ModelDefinition::Enum(_, items, _) => self.enum_definition(name, items, bindings),
+            ModelDefinition::Alias(_, _, item_type) => {
+                if let ItemType::Inline(def) = &**item_type {
+                    self.definition(name, def, bindings);
+                }
+            },
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => {},
+        }
+    }
+
+    fn record_object(&self, name: &str, items: &[RecordItem], bindings: &Bindings) {
+        let at = self.definitions.borrow().len();
+        let mut fields = vec![];
+        self.models.borrow_mut().push(name.to_string());
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            fields.push(self.field(id.as_name().unwrap_or_default(), item_type, bindings));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        self.models.borrow_mut().pop();
+        self.object(at, name, fields);
+    }
+
+    fn tuple_object(&self, name: &str, items: &[TupleItem], bindings: &Bindings) {
+        let at = self.definitions.borrow().len();
+        self.models.borrow_mut().push(name.to_string());
+
+        let fields = items.iter().enumerate()
+            .map(|(i, item)| match item {
+                TupleItem::Item(item_type) if items.len() == 1 => self.field("value", item_type, bindings),
+                TupleItem::Item(item_type) => self.field(&format!("item{}", i + 1), item_type, bindings),
+                TupleItem::NamedItem(id, item_type) => self.field(id.as_name().unwrap_or_default(), item_type, bindings),
+            })
+            .collect();
+
+        self.models.borrow_mut().pop();
+        self.object(at, name, fields);
+    }
+
+    fn object(&self, at: usize, name: &str, fields: Vec<Field>) {
+        if self.inputs {
+            self.define(at, || {
+                self.writer.line(format!("input {} {{", Self::input_name(name)));
+                self.writer.indent();
+                for field in &fields {
+                    if let Some(ref input) = field.input {
+                        self.writer.line(format!("{}: {}", field.name, input));
+                    }
+                }
+                self.writer.dedent();
+                self.writer.line("}");
+            });
+        }
+
+        self.define(at, || {
+            self.writer.line(format!("type {} {{", name));
+            self.writer.indent();
+            for field in &fields {
+                if let Some(ref output) = field.output {
+                    self.writer.line(format!("{}: {}", field.name, output));
+                }
+            }
+            self.writer.dedent();
+            self.writer.line("}");
+        });
+    }
+
+    fn enum_definition(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        match items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            true => self.plain_enum(name, items),
+            false => self.union(name, items, bindings),
+        }
+    }
+
+    fn plain_enum(&self, name: &str, items: &[EnumItem]) {
+        let at = self.definitions.borrow().len();
+        self.define(at, || {
+            self.writer.line(format!("enum {} {{", name));
+            self.writer.indent();
+            for item in items {
+                if let EnumItem::Item(id) = item {
+                    self.writer.line(to_screaming_snake_case(id.as_name().unwrap_or_default()));
+                }
+            }
+            self.writer.dedent();
+            self.writer.line("}");
+        });
+    }
+
+    /// Enums with payloads become a union of one object type per variant, and
+    /// a `@oneOf` input object for the input side.
+    fn union(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        let at = self.definitions.borrow().len();
+        let mut members = vec![];
+        let mut choices = vec![];
+        self.models.borrow_mut().push(name.to_string());
+
+        for item in items {
+            let (id, payload) = match item {
+                EnumItem::Item(id) => (id, None),
+                EnumItem::Record(id, item_type)
+                | EnumItem::Tuple(id, item_type)
+                | EnumItem::Enum(id, item_type) => (id, Some(item_type)),
+            };
+
+            let variant = id.as_name().unwrap_or_default();
+            let member = format!("{}{}", name, to_pascal_case(variant));
+
+            let choice = match payload {
+                None => {
+                    let at = self.definitions.borrow().len();
+                    self.define(at, || {
+                        self.writer.line(format!("type {} {{", member));
+                        self.writer.indent();
+                        self.writer.line("_empty: Boolean");
+                        self.writer.dedent();
+                        self.writer.line("}");
+                    });
+                    self.inputs.then(|| "Boolean".to_string())
+                },
+                Some(ItemType::Inline(ModelDefinition::Tuple(_, tuple, _)))
+                    if matches!(tuple.as_slice(), [TupleItem::Item(_)]) => {
+                    let TupleItem::Item(item_type) = &tuple[0] else { unreachable!() };
+                    self.tuple_object(&member, tuple, bindings);
+                    match self.inputs {
+                        true => self.named_type(variant, item_type, bindings, true).map(|(name, _)| name),
+                        false => None,
+                    }
+                },
+                Some(ItemType::Inline(ModelDefinition::Enum(_, variants, _))) => {
+                    let at = self.definitions.borrow().len();
+                    let value = format!("{}Value", member);
+                    self.enum_definition(&value, variants, bindings);
+                    let is_plain = variants.iter().all(|item| matches!(item, EnumItem::Item(_)));
+                    let field = Field {
+                        name: "value".to_string(),
+                        output: Some(format!("{}!", value)),
+                        input: Some(format!("{}!", self.type_name(&value, self.inputs && !is_plain))),
+                    };
+                    self.object(at, &member, vec![field]);
+                    self.inputs.then(|| Self::input_name(&member))
+                },
+                Some(ItemType::Inline(def)) => {
+                    self.definition(&member, def, bindings);
+                    self.inputs.then(|| Self::input_name(&member))
+                },
+                Some(item_type) => {
+                    let at = self.definitions.borrow().len();
+                    let field = self.field("value", item_type, bindings);
+                    self.object(at, &member, vec![field]);
+                    self.inputs.then(|| Self::input_name(&member))
+                },
+            };
+
+            members.push(member);
+            if let Some(choice) = choice {
+                choices.push((to_camel_case(variant), choice));
+            }
+        }
+
+        self.models.borrow_mut().pop();
+
+        if self.inputs {
+            self.define(at, || {
+                self.writer.line(format!("input {} @oneOf {{", Self::input_name(name)));
+                self.writer.indent();
+                for (choice, input) in &choices {
+                    self.writer.line(format!("{}: {}", choice, input));
+                }
+                self.writer.dedent();
+                self.writer.line("}");
+            });
+        }
+
+        self.define(at, || {
+            self.writer.line(format!("union {} = {}", name, members.join(" | ")));
+        });
+    }
+
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str)) {
+        let name = id.as_name().unwrap_or_default();
+
+        if !generic_names(params).is_empty() {
+            self.error(TransformError::Unsupported(
+                name.to_string(),
+                "generic models cannot be expressed in GraphQL".to_string(),
+            ));
+            return;
+        }
+
+        f(&self.flat_name(name, &self.path.borrow()));
+    }
+}
+
+impl Transformer<'_> for GraphQlTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        match literal {
+            Literal::String(ref str) => self.writer.text(format!("\"{}\"", str)),
+            Literal::Number(ref str) => self.writer.text(str.to_string()),
+        }
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        if let Some(name) = self.type_ref("value", item_type, &Bindings::root(), false) {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+            self.unsupported("generic types cannot be expressed in GraphQL");
+        }
+    }
+
+    fn visit_model_params_def(&self, params: &[ModelParamDefinition]) {
+        *self.generics.borrow_mut() = generic_names(params);
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        for item in items {
+            self.visit_scope(item, false);
+        }
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        for item in items {
+            self.visit_scope(item, false);
+        }
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Fragment(_, _, _) => {
+
+            }
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, _) => {
+                self.top_level(id, params, |name| self.definition(name, def, &Bindings::root()));
+            }
+        }
+
+        self.generics.borrow_mut().clear();
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.record_object(name, items, &Bindings::root()));
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.tuple_object(name, items, &Bindings::root()));
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.enum_definition(name, items, &Bindings::root()));
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref type_id) = item {
+            self.writer.text(to_camel_case(id.as_name().unwrap_or_default()));
+            self.writer.text(": ");
+            self.visit_item_type(type_id);
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.writer.text(to_screaming_snake_case(id.as_name().unwrap_or_default()));
+        }
+    }
+
+    fn visit_scalar(&self, id: &Id) {
+        let name = id.as_name().unwrap_or_default();
+        if self.scalars.contains_key(name) {
+            return;
+        }
+
+        let at = self.definitions.borrow().len();
+        let name = self.flat_name(name, &self.path.borrow());
+        self.define(at, || self.writer.line(format!("scalar {}", name)));
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::StringRender;
+
+    #[test_case(
+        "model Item {\n    id: Int\n    createdAt: DateTime\n    comment: String?\n}\nscalar Int;\nscalar String;\nscalar DateTime;",
+        "type Item {\n    id: Int!\n    createdAt: DateTime!\n    comment: String\n}\n\ninput ItemInput {\n    id: Int!\n    createdAt: DateTime!\n    comment: String\n}\n\nscalar DateTime";
+        "record"
+    )]
+    #[test_case(
+        "model Status enum {\n    Draft\n    InReview\n}",
+        "enum Status {\n    DRAFT\n    IN_REVIEW\n}";
+        "plain enum"
+    )]
+    #[test_case(
+        "model Password enum {\n    Open(String)\n    Hashed(value: String, salt: String)\n}\nscalar String;",
+        "union Password = PasswordOpen | PasswordHashed\n\ninput PasswordInput @oneOf {\n    open: String\n    hashed: PasswordHashedInput\n}\n\ntype PasswordOpen {\n    value: String!\n}\n\ninput PasswordOpenInput {\n    value: String!\n}\n\ntype PasswordHashed {\n    value: String!\n    salt: String!\n}\n\ninput PasswordHashedInput {\n    value: String!\n    salt: String!\n}";
+        "payload enum"
+    )]
+    fn check(code: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        let transformer = GraphQlTransformer::new();
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_string(4);
+
+        assert_eq!(&result, expected)
+    }
+
+    #[test]
+    fn check_packages() {
+        let code = "package shop;\n\nmodel Name {\n    first: String\n}\n\npackage billing {\n    model Name {\n        id: Int\n    }\n\n    model Invoice {\n        name: Name\n    }\n}\n\nscalar Int;\nscalar String;";
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        GraphQlTransformer::new().with_inputs(false).apply(&ast, &render).unwrap();
+
+        let expected = "type ShopName {\n    first: String!\n}\n\ntype ShopBillingName {\n    id: Int!\n}\n\ntype Invoice {\n    name: ShopBillingName!\n}";
+        assert_eq!(render.as_string(4), expected);
+    }
+
+    #[test]
+    fn check_without_inputs() {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str("model Point {\n    x: Int\n    pos: (Int, Int)\n}\nscalar Int;");
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        GraphQlTransformer::new().with_inputs(false).apply(&ast, &render).unwrap();
+
+        let expected = "type Point {\n    x: Int!\n    pos: PointPos!\n}\n\ntype PointPos {\n    item1: Int!\n    item2: Int!\n}";
+        assert_eq!(render.as_string(4), expected);
+    }
+}
@@ -0,0 +1,314 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use crate::ast::*;
+use crate::transform::{Target, TextToken, TokenWriter, TransformError};
+use crate::transform::jvm_model::{package_name, JvmDecl, JvmField, JvmLowering, JvmType};
+
+/// Generates Java `record`s and sealed interfaces, one file per top-level type.
+///
+/// Java has no type aliases, so references to named aliases are replaced by their targets.
+pub struct JavaTransformer {
+    scalars: HashMap<String, String>,
+    prefix: String,
+
+    path: RefCell<Vec<String>>,
+    imports: RefCell<BTreeSet<String>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for JavaTransformer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JavaTransformer {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "String"),
+            ("Int", "long"),
+            ("Long", "long"),
+            ("Byte", "byte"),
+            ("Float", "float"),
+            ("Double", "double"),
+            ("Decimal", "java.math.BigDecimal"),
+            ("Bool", "boolean"),
+            ("Boolean", "boolean"),
+            ("Bytes", "byte[]"),
+            ("Uuid", "java.util.UUID"),
+            ("Date", "java.time.LocalDate"),
+            ("DateTime", "java.time.Instant"),
+        ];
+
+        JavaTransformer {
+            scalars: scalars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            prefix: String::new(),
+
+            path: RefCell::new(Vec::new()),
+            imports: RefCell::new(BTreeSet::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a Java type; qualified names are imported.
+    pub fn with_scalar(mut self, scalar: &str, java_type: &str) -> Self {
+        self.scalars.insert(scalar.to_string(), java_type.to_string());
+        self
+    }
+
+    /// Package every Mex package is nested in, e.g. `com.acme`.
+    pub fn with_package_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope, render: &R) -> crate::Result<'static, ()> {
+        let files = JvmLowering::lower(scope)?;
+        for file in files {
+            *self.path.borrow_mut() = file.path;
+            for decl in &file.decls {
+                self.file(decl);
+            }
+        }
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn file(&self, decl: &JvmDecl) {
+        let name = match decl {
+            JvmDecl::Record { name, .. } | JvmDecl::Enum { name, .. } | JvmDecl::Sealed { name, .. } => name,
+            JvmDecl::Alias { .. } => return,
+        };
+
+        let package = package_name(&self.prefix, &self.path.borrow());
+        let body = self.writer.capture(|| self.declaration(decl, "public ", None));
+        let imports = self.imports.take();
+
+        self.writer.render(TextToken::File(match package.is_empty() {
+            true => format!("{}.java", name),
+            false => format!("{}/{}.java", package.replace('.', "/"), name),
+        }));
+
+        if !package.is_empty() {
+            self.writer.line(format!("package {};", package));
+            self.writer.empty_line();
+        }
+        if !imports.is_empty() {
+            for import in imports {
+                self.writer.line(format!("import {};", import));
+            }
+            self.writer.empty_line();
+        }
+        self.writer.extend(body);
+    }
+
+    fn qualified(&self, name: &str) -> String {
+        match name.rsplit_once('.') {
+            Some((_, simple)) => {
+                self.imports.borrow_mut().insert(name.to_string());
+                simple.to_string()
+            },
+            None => name.to_string(),
+        }
+    }
+
+    fn boxed(java_type: String) -> String {
+        let boxed = match java_type.as_str() {
+            "long" => "Long",
+            "int" => "Integer",
+            "short" => "Short",
+            "byte" => "Byte",
+            "float" => "Float",
+            "double" => "Double",
+            "boolean" => "Boolean",
+            "char" => "Character",
+            _ => return java_type,
+        };
+        boxed.to_string()
+    }
+
+    fn args(&self, args: &[JvmType]) -> String {
+        match args.is_empty() {
+            true => String::new(),
+            false => format!("<{}>", args.iter().map(|a| self.type_name(a, true)).collect::<Vec<_>>().join(", ")),
+        }
+    }
+
+    /// `boxed` asks for a reference type, as needed by generic arguments and nullable fields.
+    fn type_name(&self, jvm_type: &JvmType, boxed: bool) -> String {
+        match jvm_type {
+            JvmType::Scalar(name) => {
+                let Some(java_type) = self.scalars.get(name) else {
+                    self.errors.borrow_mut().push(TransformError::Unsupported(
+                        name.clone(),
+                        "scalar has no Java mapping".to_string(),
+                    ));
+                    return name.clone();
+                };
+
+                let java_type = self.qualified(java_type);
+                match boxed {
+                    true => Self::boxed(java_type),
+                    false => java_type,
+                }
+            },
+            JvmType::Model { path, name, args } => {
+                let name = match *path == *self.path.borrow() {
+                    true => name.clone(),
+                    false => self.qualified(&format!("{}.{}", package_name(&self.prefix, path), name)),
+                };
+                name + &self.args(args)
+            },
+            JvmType::Alias { target: Some(target), .. } => self.type_name(target, boxed),
+            JvmType::Alias { name, .. } => {
+                self.errors.borrow_mut().push(TransformError::Unsupported(
+                    name.clone(),
+                    "generic aliases cannot be expressed in Java".to_string(),
+                ));
+                name.clone()
+            },
+            JvmType::Nested { name, args } => name.clone() + &self.args(args),
+            JvmType::Param(name) => name.clone(),
+            JvmType::Optional(jvm_type) => self.type_name(jvm_type, true),
+        }
+    }
+
+    fn generics(generics: &[String]) -> String {
+        match generics.is_empty() {
+            true => String::new(),
+            false => format!("<{}>", generics.join(", ")),
+        }
+    }
+
+    /// Writes `head(components)tail` without the final line break, one component per line when there are several.
+    fn components(&self, head: String, fields: &[JvmField], tail: &str) {
+        let component = |field: &JvmField| format!("{} {}", self.type_name(&field.field_type, false), field.name);
+
+        match fields {
+            [] => self.writer.text(format!("{}(){}", head, tail)),
+            [single] => self.writer.text(format!("{}({}){}", head, component(single), tail)),
+            _ => {
+                self.writer.line(format!("{}(", head));
+                self.writer.indent();
+                let count = fields.len();
+                for (i, field) in fields.iter().enumerate() {
+                    match i + 1 < count {
+                        true => self.writer.line(format!("{},", component(field))),
+                        false => self.writer.line(component(field)),
+                    }
+                }
+                self.writer.dedent();
+                self.writer.text(format!("){}", tail));
+            },
+        }
+    }
+
+    fn body(&self, decls: &[JvmDecl], modifier: &str, parent: Option<&str>) {
+        if decls.is_empty() {
+            self.writer.line(" {}");
+            return;
+        }
+
+        self.writer.line(" {");
+        self.writer.indent();
+        let body = self.writer.capture(|| {
+            for decl in decls {
+                self.writer.block(|| self.declaration(decl, modifier, parent));
+            }
+        });
+        self.writer.extend(body);
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+
+    /// `parent` is the sealed interface variants implement.
+    fn declaration(&self, decl: &JvmDecl, modifier: &str, parent: Option<&str>) {
+        match decl {
+            JvmDecl::Record { name, generics, fields, nested, .. } => {
+                let implements = parent.map(|p| format!(" implements {}", p)).unwrap_or_default();
+                let head = format!("{}record {}{}", modifier, name, Self::generics(generics));
+                self.components(head, fields, &implements);
+                self.body(nested, "public ", None);
+            },
+            JvmDecl::Enum { name, members } => {
+                self.writer.line(format!("{}enum {} {{", modifier, name));
+                self.writer.indent();
+                let count = members.len();
+                for (i, member) in members.iter().enumerate() {
+                    match i + 1 < count {
+                        true => self.writer.line(format!("{},", member)),
+                        false => self.writer.line(member.clone()),
+                    }
+                }
+                self.writer.dedent();
+                self.writer.line("}");
+            },
+            JvmDecl::Sealed { name, generics, variants } => {
+                let parent = format!("{}{}", name, Self::generics(generics));
+                self.writer.text(format!("{}sealed interface {}", modifier, parent));
+                self.body(variants, "", Some(&parent));
+            },
+            JvmDecl::Alias { .. } => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    #[test_case(
+        "package Shop;\n\nmodel Item {\n    id: Id\n    title: String?\n    count: Int?\n    created: DateTime\n}\nmodel Id = Int\nscalar Int;\nscalar String;\nscalar DateTime;",
+        "com/acme/shop/Item.java",
+        "package com.acme.shop;\n\nimport java.time.Instant;\n\n\
+        public record Item(\n    long id,\n    String title,\n    Long count,\n    Instant created\n) {}\n";
+        "record"
+    )]
+    #[test_case(
+        "model Status enum {\n    Draft\n    Published\n}",
+        "com/acme/Status.java",
+        "package com.acme;\n\npublic enum Status {\n    DRAFT,\n    PUBLISHED\n}\n";
+        "enum"
+    )]
+    #[test_case(
+        "model Page<T> {\n    items: T\n    meta: {\n        total: Int\n    }\n}\nscalar Int;",
+        "com/acme/Page.java",
+        "package com.acme;\n\n\
+        public record Page<T>(\n    T items,\n    Meta<T> meta\n) {\n    public record Meta<T>(long total) {}\n}\n";
+        "generic"
+    )]
+    #[test_case(
+        "model Password enum {\n    Open(String)\n    Empty\n}\nscalar String;",
+        "com/acme/Password.java",
+        "package com.acme;\n\n\
+        public sealed interface Password {\n    record Open(String value) implements Password {}\n\n    \
+        record Empty() implements Password {}\n}\n";
+        "sealed interface"
+    )]
+    fn check(code: &str, file: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = JavaTransformer::new().with_package_prefix("com.acme");
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_files(4);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].0, file);
+        assert_eq!(&result[0].1, expected)
+    }
+}
@@ -0,0 +1,406 @@
+use std::cell::RefCell;
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{TransformError, Transformer};
+use crate::transform::case::{to_camel_case, to_pascal_case, to_screaming_snake_case, to_snake_case};
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+/// Type reference of the lowered JVM model.
+pub enum JvmType {
+    Scalar(String),
+    Model { path: Vec<String>, name: String, args: Vec<JvmType> },
+    /// Reference to a named alias; `target` is `None` for generic aliases.
+    Alias { path: Vec<String>, name: String, args: Vec<JvmType>, target: Option<Box<JvmType>> },
+    /// Reference to a type nested in the enclosing declaration.
+    Nested { name: String, args: Vec<JvmType> },
+    Param(String),
+    Optional(Box<JvmType>),
+}
+
+pub struct JvmField {
+    pub name: String,
+    pub field_type: JvmType,
+}
+
+/// Declaration of the lowered JVM model, shared by the Kotlin and Java generators.
+pub enum JvmDecl {
+    Record { name: String, generics: Vec<String>, fields: Vec<JvmField>, nested: Vec<JvmDecl>, wrapper: bool },
+    Enum { name: String, members: Vec<String> },
+    Sealed { name: String, generics: Vec<String>, variants: Vec<JvmDecl> },
+    Alias { name: String, generics: Vec<String>, target: JvmType },
+}
+
+pub struct JvmFile {
+    pub path: Vec<String>,
+    pub decls: Vec<JvmDecl>,
+}
+
+/// Returns the JVM package of a Mex package path, e.g. `com.acme` + `[Shop]` => `com.acme.shop`.
+pub fn package_name(prefix: &str, path: &[String]) -> String {
+    std::iter::once(prefix.to_string())
+        .chain(path.iter().map(|p| to_snake_case(p)))
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Lowers a scope tree into JVM declarations grouped by package.
+pub struct JvmLowering<'input> {
+    index: RefCell<ModelIndex<'input>>,
+
+    path: RefCell<Vec<String>>,
+    model: RefCell<String>,
+    generics: RefCell<Vec<String>>,
+
+    decls: RefCell<Vec<JvmDecl>>,
+    files: RefCell<Vec<JvmFile>>,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl<'input> JvmLowering<'input> {
+    pub fn lower(scope: &RefScope<'input>) -> Result<Vec<JvmFile>, Vec<TransformError>> {
+        let lowering = JvmLowering {
+            index: RefCell::new(ModelIndex::new(scope)),
+
+            path: RefCell::new(Vec::new()),
+            model: RefCell::new(String::new()),
+            generics: RefCell::new(Vec::new()),
+
+            decls: RefCell::new(Vec::new()),
+            files: RefCell::new(Vec::new()),
+            errors: RefCell::new(Vec::new()),
+        };
+
+        lowering.visit_scope(scope, true);
+
+        let errors = lowering.errors.into_inner();
+        match errors.is_empty() {
+            true => Ok(lowering.files.into_inner()),
+            false => Err(errors),
+        }
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        self.error(TransformError::Unsupported(self.model.borrow().clone(), reason.to_string()));
+    }
+
+    fn params(&self) -> Vec<JvmType> {
+        self.generics.borrow().iter().map(|g| JvmType::Param(g.clone())).collect()
+    }
+
+    fn file(&self, items: &[RefScope]) {
+        let outer = self.decls.replace(Vec::new());
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                continue;
+            }
+            self.visit_scope(item, false);
+        }
+
+        let decls = self.decls.replace(outer);
+        if !decls.is_empty() {
+            self.files.borrow_mut().push(JvmFile { path: self.path.borrow().clone(), decls });
+        }
+
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                self.visit_scope(item, false);
+            }
+        }
+    }
+
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str) -> Option<JvmDecl>) {
+        let name = id.as_name().unwrap_or_default();
+
+        *self.model.borrow_mut() = name.to_string();
+        *self.generics.borrow_mut() = generic_names(params);
+        let decl = f(name);
+        self.generics.borrow_mut().clear();
+
+        self.decls.borrow_mut().extend(decl);
+    }
+
+    fn lower_type(&self, hint: &str, item_type: &ItemType, bindings: &Bindings, nested: &mut Vec<JvmDecl>) -> Option<JvmType> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if self.generics.borrow().iter().any(|g| g == name) {
+                    return Some(JvmType::Param(name.to_string()));
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                let args = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Generic(item_type) => Some(self.lower_type(hint, item_type, bindings, nested)),
+                        ModelParam::Metadata(_, _) => None,
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                let scope = entry.scope.borrow();
+                let Scope::Model(ref def) = **scope else { unreachable!() };
+
+                match (entry.kind, def) {
+                    (ModelKind::Scalar, _) => Some(JvmType::Scalar(name.to_string())),
+                    (ModelKind::Fragment, _) => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                        None
+                    },
+                    (ModelKind::Alias, ModelDefinition::Alias(_, params, target)) if !matches!(**target, ItemType::Inline(_)) => {
+                        let target = match generic_names(params).is_empty() {
+                            true => {
+                                let path = self.path.replace(entry.path.clone());
+                                let target = self.lower_type(hint, target, &Bindings::root(), nested);
+                                *self.path.borrow_mut() = path;
+                                Some(Box::new(target?))
+                            },
+                            false => None,
+                        };
+
+                        Some(JvmType::Alias { path: entry.path.clone(), name: entry.name.clone(), args, target })
+                    },
+                    _ => Some(JvmType::Model { path: entry.path.clone(), name: entry.name.clone(), args }),
+                }
+            },
+            ItemType::Inline(def) => {
+                let name = to_pascal_case(hint);
+                nested.extend(self.declaration(&name, def, bindings));
+                Some(JvmType::Nested { name, args: self.params() })
+            },
+            ItemType::Optional(item_type) => {
+                let item_type = self.lower_type(hint, item_type, bindings, nested)?;
+                Some(JvmType::Optional(Box::new(item_type)))
+            },
+        }
+    }
+
+    fn field(&self, name: &str, item_type: &ItemType, bindings: &Bindings, nested: &mut Vec<JvmDecl>) -> Option<JvmField> {
+        let field_type = self.lower_type(name, item_type, bindings, nested)?;
+        Some(JvmField { name: to_camel_case(name), field_type })
+    }
+
+    fn record_fields(&self, items: &[RecordItem], bindings: &Bindings, nested: &mut Vec<JvmDecl>) -> Vec<JvmField> {
+        let mut fields = vec![];
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            fields.extend(self.field(id.as_name().unwrap_or_default(), item_type, bindings, nested));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        fields
+    }
+
+    fn tuple_fields(&self, items: &[TupleItem], bindings: &Bindings, nested: &mut Vec<JvmDecl>) -> Vec<JvmField> {
+        items.iter()
+            .enumerate()
+            .filter_map(|(i, item)| match item {
+                TupleItem::Item(item_type) if items.len() == 1 => self.field("value", item_type, bindings, nested),
+                TupleItem::Item(item_type) => self.field(&format!("item{}", i + 1), item_type, bindings, nested),
+                TupleItem::NamedItem(id, item_type) => self.field(id.as_name().unwrap_or_default(), item_type, bindings, nested),
+            })
+            .collect()
+    }
+
+    fn declaration(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) -> Option<JvmDecl> {
+        match def {
+            ModelDefinition::Record(_, items, _) => Some(self.record_decl(name, items, bindings)),
+            ModelDefinition::Tuple(_, items, _) => Some(self.tuple_decl(name, items, bindings)),
+            ModelDefinition::Enum(_, items, _) => Some(self.enum_decl(name, items, bindings)),
+            ModelDefinition::Alias(_, _, item_type) => self.alias_decl(name, item_type, bindings),
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => None,
+        }
+    }
+
+    fn record_decl(&self, name: &str, items: &[RecordItem], bindings: &Bindings) -> JvmDecl {
+        let mut nested = vec![];
+        let fields = self.record_fields(items, bindings, &mut nested);
+        let generics = self.generics.borrow().clone();
+        JvmDecl::Record { name: name.to_string(), generics, fields, nested, wrapper: false }
+    }
+
+    fn tuple_decl(&self, name: &str, items: &[TupleItem], bindings: &Bindings) -> JvmDecl {
+        let mut nested = vec![];
+        let fields = self.tuple_fields(items, bindings, &mut nested);
+        let generics = self.generics.borrow().clone();
+        let wrapper = matches!(items, [TupleItem::Item(_)]) && generics.is_empty();
+        JvmDecl::Record { name: name.to_string(), generics, fields, nested, wrapper }
+    }
+
+    fn enum_decl(&self, name: &str, items: &[EnumItem], bindings: &Bindings) -> JvmDecl {
+        if items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            let members = items.iter()
+                .filter_map(|item| match item {
+                    EnumItem::Item(id) => id.as_name().map(to_screaming_snake_case),
+                    _ => None,
+                })
+                .collect();
+
+            return JvmDecl::Enum { name: name.to_string(), members };
+        }
+
+        let generics = self.generics.borrow().clone();
+        let variants = items.iter()
+            .map(|item| {
+                let mut nested = vec![];
+                let (id, fields) = match item {
+                    EnumItem::Item(id) => (id, vec![]),
+                    EnumItem::Record(id, ItemType::Inline(ModelDefinition::Record(_, items, _))) => {
+                        (id, self.record_fields(items, bindings, &mut nested))
+                    },
+                    EnumItem::Tuple(id, ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => {
+                        (id, self.tuple_fields(items, bindings, &mut nested))
+                    },
+                    EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                        (id, self.field("value", item_type, bindings, &mut nested).into_iter().collect())
+                    },
+                };
+
+                JvmDecl::Record {
+                    name: to_pascal_case(id.as_name().unwrap_or_default()),
+                    generics: generics.clone(),
+                    fields,
+                    nested,
+                    wrapper: false,
+                }
+            })
+            .collect();
+
+        JvmDecl::Sealed { name: name.to_string(), generics, variants }
+    }
+
+    fn alias_decl(&self, name: &str, item_type: &ItemType, bindings: &Bindings) -> Option<JvmDecl> {
+        match item_type {
+            ItemType::Inline(def) => self.declaration(name, def, bindings),
+            item_type => {
+                let target = self.lower_type(name, item_type, bindings, &mut vec![])?;
+                let generics = self.generics.borrow().clone();
+                Some(JvmDecl::Alias { name: name.to_string(), generics, target })
+            },
+        }
+    }
+}
+
+impl Transformer<'_> for JvmLowering<'_> {
+
+    fn visit_id(&self, _id: &Id) {
+
+    }
+
+    fn visit_literal(&self, _literal: &Literal) {
+
+    }
+
+    fn visit_item_type(&self, _item_type: &ItemType) {
+
+    }
+
+    fn visit_model_params(&self, _params: &[ModelParam]) {
+
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        self.file(items);
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        self.file(items);
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, ref item_type) => {
+                self.top_level(id, params, |name| self.alias_decl(name, item_type, &Bindings::root()));
+            },
+            ModelDefinition::Fragment(_, _, _) => {
+
+            }
+        }
+    }
+
+    fn visit_header_model(&self, _keyword: &str) {
+
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| Some(self.record_decl(name, items, &Bindings::root())));
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| Some(self.tuple_decl(name, items, &Bindings::root())));
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| Some(self.enum_decl(name, items, &Bindings::root())));
+    }
+
+    fn visit_model_item(&self, _item: &RecordItem) {
+
+    }
+
+    fn visit_enum_item(&self, _item: &EnumItem) {
+
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
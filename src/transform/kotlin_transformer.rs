@@ -0,0 +1,282 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use crate::ast::*;
+use crate::transform::{Target, TextToken, TokenWriter, TransformError};
+use crate::transform::jvm_model::{package_name, JvmDecl, JvmField, JvmFile, JvmLowering, JvmType};
+
+/// Generates Kotlin `data class` / `sealed class` hierarchies, one file per package.
+pub struct KotlinTransformer {
+    scalars: HashMap<String, String>,
+    prefix: String,
+
+    path: RefCell<Vec<String>>,
+    imports: RefCell<BTreeSet<String>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for KotlinTransformer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KotlinTransformer {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "String"),
+            ("Int", "Long"),
+            ("Long", "Long"),
+            ("Byte", "Byte"),
+            ("Float", "Float"),
+            ("Double", "Double"),
+            ("Decimal", "java.math.BigDecimal"),
+            ("Bool", "Boolean"),
+            ("Boolean", "Boolean"),
+            ("Bytes", "ByteArray"),
+            ("Uuid", "java.util.UUID"),
+            ("Date", "java.time.LocalDate"),
+            ("DateTime", "java.time.Instant"),
+        ];
+
+        KotlinTransformer {
+            scalars: scalars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            prefix: String::new(),
+
+            path: RefCell::new(Vec::new()),
+            imports: RefCell::new(BTreeSet::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a Kotlin type; qualified names are imported.
+    pub fn with_scalar(mut self, scalar: &str, kotlin_type: &str) -> Self {
+        self.scalars.insert(scalar.to_string(), kotlin_type.to_string());
+        self
+    }
+
+    /// Package every Mex package is nested in, e.g. `com.acme`.
+    pub fn with_package_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope, render: &R) -> crate::Result<'static, ()> {
+        let files = JvmLowering::lower(scope)?;
+        for file in files {
+            self.file(file);
+        }
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn file(&self, file: JvmFile) {
+        let package = package_name(&self.prefix, &file.path);
+        *self.path.borrow_mut() = file.path;
+
+        let body = self.writer.capture(|| {
+            for decl in &file.decls {
+                self.writer.block(|| self.declaration(decl, None));
+            }
+        });
+        let imports = self.imports.take();
+
+        self.writer.render(TextToken::File(match package.is_empty() {
+            true => "Models.kt".to_string(),
+            false => package.replace('.', "/") + "/Models.kt",
+        }));
+
+        if !package.is_empty() {
+            self.writer.line(format!("package {}", package));
+            self.writer.empty_line();
+        }
+        if !imports.is_empty() {
+            for import in imports {
+                self.writer.line(format!("import {}", import));
+            }
+            self.writer.empty_line();
+        }
+        self.writer.extend(body);
+    }
+
+    fn qualified(&self, name: &str) -> String {
+        match name.rsplit_once('.') {
+            Some((_, simple)) => {
+                self.imports.borrow_mut().insert(name.to_string());
+                simple.to_string()
+            },
+            None => name.to_string(),
+        }
+    }
+
+    fn args(&self, args: &[JvmType]) -> String {
+        match args.is_empty() {
+            true => String::new(),
+            false => format!("<{}>", args.iter().map(|a| self.type_name(a)).collect::<Vec<_>>().join(", ")),
+        }
+    }
+
+    fn type_name(&self, jvm_type: &JvmType) -> String {
+        match jvm_type {
+            JvmType::Scalar(name) => match self.scalars.get(name) {
+                Some(kotlin_type) => self.qualified(kotlin_type),
+                None => {
+                    self.errors.borrow_mut().push(TransformError::Unsupported(
+                        name.clone(),
+                        "scalar has no Kotlin mapping".to_string(),
+                    ));
+                    name.clone()
+                },
+            },
+            JvmType::Model { path, name, args } | JvmType::Alias { path, name, args, .. } => {
+                let name = match *path == *self.path.borrow() {
+                    true => name.clone(),
+                    false => self.qualified(&format!("{}.{}", package_name(&self.prefix, path), name)),
+                };
+                name + &self.args(args)
+            },
+            JvmType::Nested { name, args } => name.clone() + &self.args(args),
+            JvmType::Param(name) => name.clone(),
+            JvmType::Optional(jvm_type) => format!("{}?", self.type_name(jvm_type)),
+        }
+    }
+
+    fn generics(generics: &[String]) -> String {
+        match generics.is_empty() {
+            true => String::new(),
+            false => format!("<{}>", generics.join(", ")),
+        }
+    }
+
+    /// Writes `head(params)tail` without the final line break, one parameter per line when there are several.
+    fn params(&self, head: String, fields: &[JvmField], tail: &str) {
+        let field = |field: &JvmField| format!("val {}: {}", field.name, self.type_name(&field.field_type));
+
+        match fields {
+            [] => self.writer.text(format!("{}{}", head, tail)),
+            [single] => self.writer.text(format!("{}({}){}", head, field(single), tail)),
+            _ => {
+                self.writer.line(format!("{}(", head));
+                self.writer.indent();
+                for f in fields {
+                    self.writer.line(format!("{},", field(f)));
+                }
+                self.writer.dedent();
+                self.writer.text(format!("){}", tail));
+            },
+        }
+    }
+
+    fn body(&self, decls: &[JvmDecl], parent: Option<&str>) {
+        if decls.is_empty() {
+            self.writer.render(TextToken::NewLine);
+            return;
+        }
+
+        self.writer.line(" {");
+        self.writer.indent();
+        let body = self.writer.capture(|| {
+            for decl in decls {
+                self.writer.block(|| self.declaration(decl, parent));
+            }
+        });
+        self.writer.extend(body);
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+
+    /// `parent` is the supertype of sealed variants.
+    fn declaration(&self, decl: &JvmDecl, parent: Option<&str>) {
+        match decl {
+            JvmDecl::Record { name, fields, wrapper: true, .. } => {
+                self.writer.line("@JvmInline");
+                self.params(format!("value class {}", name), fields, "");
+                self.writer.render(TextToken::NewLine);
+            },
+            JvmDecl::Record { name, generics, fields, nested, .. } => {
+                let supertype = parent.map(|p| format!(" : {}()", p)).unwrap_or_default();
+                match (fields.is_empty(), generics.is_empty(), parent.is_some()) {
+                    (true, true, true) => self.writer.text(format!("data object {}{}", name, supertype)),
+                    (true, _, _) => self.writer.text(format!("class {}{}{}", name, Self::generics(generics), supertype)),
+                    (false, _, _) => self.params(format!("data class {}{}", name, Self::generics(generics)), fields, &supertype),
+                }
+                self.body(nested, None);
+            },
+            JvmDecl::Enum { name, members } => {
+                self.writer.line(format!("enum class {} {{", name));
+                self.writer.indent();
+                for member in members {
+                    self.writer.line(format!("{},", member));
+                }
+                self.writer.dedent();
+                self.writer.line("}");
+            },
+            JvmDecl::Sealed { name, generics, variants } => {
+                let parent = format!("{}{}", name, Self::generics(generics));
+                self.writer.text(format!("sealed class {}", parent));
+                self.body(variants, Some(&parent));
+            },
+            JvmDecl::Alias { name, generics, target } => {
+                self.writer.line(format!("typealias {}{} = {}", name, Self::generics(generics), self.type_name(target)));
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    #[test_case(
+        "package Shop;\n\nmodel Item {\n    id: Int\n    title: String?\n    created: DateTime\n}\nscalar Int;\nscalar String;\nscalar DateTime;",
+        "package com.acme.shop\n\nimport java.time.Instant\n\n\
+        data class Item(\n    val id: Long,\n    val title: String?,\n    val created: Instant,\n)\n";
+        "record"
+    )]
+    #[test_case(
+        "model Email(String)\nmodel Status enum {\n    Draft\n    Published\n}\nmodel Id = Int\nscalar Int;\nscalar String;",
+        "package com.acme\n\n\
+        @JvmInline\nvalue class Email(val value: String)\n\n\
+        enum class Status {\n    DRAFT,\n    PUBLISHED,\n}\n\n\
+        typealias Id = Long\n";
+        "wrapper, enum and alias"
+    )]
+    #[test_case(
+        "model Page<T> {\n    items: T\n    meta: {\n        total: Int\n    }\n}\nscalar Int;",
+        "package com.acme\n\n\
+        data class Page<T>(\n    val items: T,\n    val meta: Meta<T>,\n) {\n    data class Meta<T>(val total: Long)\n}\n";
+        "generic"
+    )]
+    #[test_case(
+        "model Password enum {\n    Open(String)\n    Hashed(value: String, salt: String)\n    Empty\n}\nscalar String;",
+        "package com.acme\n\n\
+        sealed class Password {\n    data class Open(val value: String) : Password()\n\n    \
+        data class Hashed(\n        val value: String,\n        val salt: String,\n    ) : Password()\n\n    \
+        data object Empty : Password()\n}\n";
+        "sealed class"
+    )]
+    fn check(code: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = KotlinTransformer::new().with_package_prefix("com.acme");
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_files(4);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(&result[0].1, expected)
+    }
+}
@@ -0,0 +1,201 @@
+use crate::transform::TextToken;
+
+/// Width-aware layout of a token stream, after Wadler's "prettier printer".
+///
+/// Tokens between `BeginGroup` and `EndGroup` are laid out flat when they fit into the line
+/// width from the current column, up to the next break after the group: `Break` becomes a space
+/// and `SoftBreak` disappears. Otherwise the group is broken and its own breaks start new lines,
+/// while nested groups are measured again. A hard line break inside a group always breaks it.
+///
+/// A line is indented by the `IncIndent` level in effect when its first text is written,
+/// with `indent_count` spaces or, when set, a tab per level counted as `indent_count` columns.
+pub struct Layout {
+    indent_count: usize,
+    width: usize,
+    tabs: bool,
+}
+
+impl Layout {
+    pub fn new(indent_count: usize, width: usize) -> Self {
+        Layout {
+            indent_count,
+            width,
+            tabs: false,
+        }
+    }
+
+    pub fn with_tabs(mut self, tabs: bool) -> Self {
+        self.tabs = tabs;
+        self
+    }
+
+    pub fn lines(&self, tokens: &[TextToken]) -> Vec<String> {
+        let mut state = State {
+            lines: vec![],
+            line: String::new(),
+            line_level: 0,
+            level: 0,
+            indent_count: self.indent_count,
+            tabs: self.tabs,
+        };
+        // Open groups laid out flat; breaks are only taken when this is zero.
+        let mut flat = 0usize;
+        let mut last_text = false;
+
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TextToken::None | TextToken::File(_) => {},
+                TextToken::Space => {
+                    if last_text {
+                        state.text(" ");
+                    }
+                },
+                TextToken::NewLine | TextToken::LineIndent => state.flush(),
+                TextToken::EmptyLine => {
+                    state.flush();
+                    state.lines.push(String::new());
+                },
+                TextToken::IncIndent => state.level += 1,
+                TextToken::DecIndent => state.level = state.level.saturating_sub(1),
+                TextToken::Text(text) => state.text(text),
+                TextToken::BeginGroup => {
+                    if flat > 0 || self.fits(&tokens[i + 1..], state.column()) {
+                        flat += 1;
+                    }
+                },
+                TextToken::EndGroup => flat = flat.saturating_sub(1),
+                TextToken::Break if flat > 0 => state.text(" "),
+                TextToken::SoftBreak if flat > 0 => {},
+                TextToken::Break | TextToken::SoftBreak => state.flush(),
+            }
+
+            last_text = matches!(token, TextToken::Text(_));
+        }
+
+        state.flush();
+        state.lines
+    }
+
+    /// Whether the group opened right before `tokens` fits flat from `column`.
+    fn fits(&self, tokens: &[TextToken], column: usize) -> bool {
+        let mut width = column;
+        let mut depth = 1;
+
+        for token in tokens {
+            match token {
+                TextToken::Text(text) => width += text.chars().count(),
+                TextToken::Space => width += 1,
+                TextToken::Break | TextToken::SoftBreak if depth == 0 => return true,
+                TextToken::Break => width += 1,
+                TextToken::BeginGroup if depth == 0 => return true,
+                TextToken::BeginGroup => depth += 1,
+                TextToken::EndGroup => depth -= 1,
+                TextToken::NewLine | TextToken::LineIndent | TextToken::EmptyLine | TextToken::File(_) => return depth == 0 && width <= self.width,
+                TextToken::SoftBreak | TextToken::None | TextToken::IncIndent | TextToken::DecIndent => {},
+            }
+
+            if width > self.width {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+struct State {
+    lines: Vec<String>,
+    line: String,
+    line_level: usize,
+    level: usize,
+    indent_count: usize,
+    tabs: bool,
+}
+
+impl State {
+    fn text(&mut self, text: &str) {
+        if self.line.is_empty() {
+            self.line_level = self.level;
+        }
+        self.line += text;
+    }
+
+    fn column(&self) -> usize {
+        match self.line.is_empty() {
+            true => self.level * self.indent_count,
+            false => self.line_level * self.indent_count + self.line.chars().count(),
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            let indent = match self.tabs {
+                true => "\t".repeat(self.line_level),
+                false => " ".repeat(self.line_level * self.indent_count),
+            };
+            self.lines.push(format!("{}{}", indent, self.line));
+            self.line.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn tuple(items: &[&str]) -> Vec<TextToken> {
+        let mut tokens = vec![
+            TextToken::Text("model T(".into()),
+            TextToken::BeginGroup,
+            TextToken::IncIndent,
+            TextToken::SoftBreak,
+        ];
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                tokens.push(TextToken::Text(",".into()));
+                tokens.push(TextToken::Break);
+            }
+            tokens.push(TextToken::Text(item.to_string()));
+        }
+        tokens.extend([
+            TextToken::DecIndent,
+            TextToken::SoftBreak,
+            TextToken::Text(")".into()),
+            TextToken::EndGroup,
+            TextToken::NewLine,
+        ]);
+        tokens
+    }
+
+    #[test_case(80, "model T(a: Int, b: Int)"; "fits")]
+    #[test_case(23, "model T(a: Int, b: Int)"; "fits exactly")]
+    #[test_case(22, "model T(\n  a: Int,\n  b: Int\n)"; "broken")]
+    fn check(width: usize, expected: &str) {
+        let lines = Layout::new(2, width).lines(&tuple(&["a: Int", "b: Int"]));
+        assert_eq!(lines.join("\n"), expected);
+    }
+
+    #[test]
+    fn check_unbalanced_indent() {
+        let tokens = [TextToken::DecIndent, TextToken::Text("a".into()), TextToken::IncIndent, TextToken::NewLine, TextToken::Text("b".into())];
+        assert_eq!(Layout::new(2, 80).lines(&tokens).join("\n"), "a\n  b");
+    }
+
+    #[test]
+    fn check_tabs() {
+        let lines = Layout::new(4, 20).with_tabs(true).lines(&tuple(&["a: Int", "b: Int"]));
+        assert_eq!(lines.join("\n"), "model T(\n\ta: Int,\n\tb: Int\n)");
+    }
+
+    #[test]
+    fn check_nested() {
+        let mut tokens = vec![TextToken::Text("model T(".into()), TextToken::BeginGroup, TextToken::IncIndent, TextToken::SoftBreak];
+        tokens.extend(tuple(&["x: Int", "y: Int"]).into_iter().filter(|t| !matches!(t, TextToken::NewLine)));
+        tokens.extend([TextToken::Text(",".into()), TextToken::Break, TextToken::Text("c: Int".into())]);
+        tokens.extend([TextToken::DecIndent, TextToken::SoftBreak, TextToken::Text(")".into()), TextToken::EndGroup]);
+
+        let lines = Layout::new(2, 30).lines(&tokens);
+        assert_eq!(lines.join("\n"), "model T(\n  model T(x: Int, y: Int),\n  c: Int\n)");
+    }
+}
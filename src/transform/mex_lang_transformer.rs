@@ -2,28 +2,157 @@ use std::cell::RefCell;
 use lalrpop_util::ErrorRecovery;
 use crate::ast::*;
 use crate::lexer::{LexicalError, Token};
-use crate::transform::{Target, TextToken, Transformer};
+use crate::manifest::Manifest;
+use crate::transform::{Target, TextToken, TransformError, Transformer};
+
+/// Indentation of printed Mex code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Indent {
+    Spaces(usize),
+    Tabs,
+}
+
+/// Separator between record fields and enum variants.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Separator {
+    Newline,
+    Comma,
+}
+
+/// Declarations ended by a semicolon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Semicolons {
+    Never,
+    Scalars,
+    Always,
+}
+
+/// Style of printed Mex code, read from the `[format]` section of the manifest:
+///
+/// ```toml
+/// [format]
+/// indent = 4              # or "tab"
+/// width = 100
+/// separator = "newline"   # or "comma"
+/// trailing_commas = false
+/// semicolons = "scalars"  # "never", "scalars" or "always" (scalars and tuple models)
+/// blank_lines = 1
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    pub indent: Indent,
+    /// Line width long tuples are wrapped at.
+    pub width: usize,
+    pub separator: Separator,
+    /// Comma after the last field or variant, with the comma separator.
+    pub trailing_commas: bool,
+    pub semicolons: Semicolons,
+    /// Blank lines between declarations set apart by a block.
+    pub blank_lines: usize,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            indent: Indent::Spaces(4),
+            width: 100,
+            separator: Separator::Newline,
+            trailing_commas: false,
+            semicolons: Semicolons::Scalars,
+            blank_lines: 1,
+        }
+    }
+}
+
+impl Style {
+    pub fn from_manifest(manifest: &Manifest) -> Result<Self, TransformError> {
+        let mut style = Style::default();
+
+        for (key, value) in manifest.entries("format") {
+            let invalid = || TransformError::InvalidManifest(format!("{} = {}", key, value));
+
+            match (key, value) {
+                ("indent", "tab") => style.indent = Indent::Tabs,
+                ("indent", count) => style.indent = Indent::Spaces(count.parse().map_err(|_| invalid())?),
+                ("width", width) => style.width = width.parse().map_err(|_| invalid())?,
+                ("separator", "newline") => style.separator = Separator::Newline,
+                ("separator", "comma") => style.separator = Separator::Comma,
+                ("trailing_commas", flag) => style.trailing_commas = flag.parse().map_err(|_| invalid())?,
+                ("semicolons", "never") => style.semicolons = Semicolons::Never,
+                ("semicolons", "scalars") => style.semicolons = Semicolons::Scalars,
+                ("semicolons", "always") => style.semicolons = Semicolons::Always,
+                ("blank_lines", count) => style.blank_lines = count.parse().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(style)
+    }
+
+    /// Width of an indentation level, a tab counting as four columns.
+    pub fn indent_count(&self) -> usize {
+        match self.indent {
+            Indent::Spaces(count) => count,
+            Indent::Tabs => 4,
+        }
+    }
+}
 
 pub struct MexLangTransformer {
+    style: Style,
     tokens: RefCell<Vec<TextToken>>,
+    /// Set after a closing brace or the root package line, so the next declaration is set apart.
+    separate: RefCell<bool>,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for MexLangTransformer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MexLangTransformer {
     pub fn new() -> Self {
         MexLangTransformer {
+            style: Style::default(),
             tokens: RefCell::new(Vec::new()),
+            separate: RefCell::new(false),
+            errors: RefCell::new(Vec::new()),
         }
     }
 
-    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope, render: &R) {
-        self.visit_scope(&scope, true);
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Prints the tree, failing on ids the grammar has no syntax for.
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope, render: &R) -> crate::Result<'static, ()> {
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
 
         self.tokens
             .into_inner().into_iter()
             .for_each(|t| {render.render(t)});
+        Ok(())
+    }
+
+    /// Comma after the item `index` of `count` when the style separates items by commas.
+    fn visit_separator(&self, index: usize, count: usize) {
+        if self.style.separator == Separator::Comma && (index + 1 < count || self.style.trailing_commas) {
+            self.render(TextToken::Text(",".to_string()));
+        }
     }
 
     fn render(&self, token: TextToken) {
+        if let TextToken::Text(ref text) = token {
+            *self.separate.borrow_mut() = text == "}";
+        }
         self.tokens.borrow_mut().push(token);
     }
 }
@@ -33,8 +162,10 @@ impl Transformer<'_> for MexLangTransformer {
     fn visit_id(&self, id: &Id) {
         let token = match id {
             Id::Name(ref str) => TextToken::Text(str.to_string()),
-            Id::Index(ref _index) => todo!(),
-            Id::Branch(ref _str, ref _branch) => todo!(),
+            Id::Index(_) | Id::Branch(..) => {
+                self.errors.borrow_mut().push(TransformError::InvalidAst(format!("`{:?}` has no syntax", id)));
+                TextToken::None
+            },
             Id::Inline => TextToken::None,
         };
 
@@ -70,10 +201,14 @@ impl Transformer<'_> for MexLangTransformer {
                     },
                 }
             },
+            ItemType::Optional(ref item_type) => {
+                self.visit_item_type(item_type);
+                self.render(TextToken::Text("?".to_string()));
+            },
         }
     }
 
-    fn visit_model_params(&self, params: &Vec<ModelParam>) {
+    fn visit_model_params(&self, params: &[ModelParam]) {
 
         let mut generics: Vec<&ItemType> = vec!();
         let mut metadata: Vec<(&Id, &Literal)> = vec!();
@@ -94,7 +229,7 @@ impl Transformer<'_> for MexLangTransformer {
             for (i, item_type) in generics.iter().enumerate() {
                 self.visit_item_type(item_type);
                 if i < generics.len() - 1 {
-                    self.render(TextToken::Text(",".to_string()));
+                    self.render(TextToken::Text(", ".to_string()));
                 }
             }
             self.render(TextToken::Text(">".to_string()));
@@ -107,14 +242,14 @@ impl Transformer<'_> for MexLangTransformer {
                 self.render(TextToken::Text("=".to_string()));
                 self.visit_literal(value);
                 if i < metadata.len() - 1 {
-                    self.render(TextToken::Text(",".to_string()));
+                    self.render(TextToken::Text(", ".to_string()));
                 }
             }
             self.render(TextToken::Text("]".to_string()));
         }
     }
 
-    fn visit_model_params_def(&self, params: &Vec<ModelParamDefinition>) {
+    fn visit_model_params_def(&self, params: &[ModelParamDefinition]) {
 
         let mut generics: Vec<(&Id, &Option<ItemType>)> = vec!();
         let mut metadata: Vec<(&Id, &ItemType, &Option<Literal>)> = vec!();
@@ -139,12 +274,12 @@ impl Transformer<'_> for MexLangTransformer {
             for (i, (id, item_type)) in generics.iter().enumerate() {
                 self.visit_id(id);
                 if let Some(item_type) = item_type {
-                    self.render(TextToken::Text(":".to_string()));
+                    self.render(TextToken::Text(": ".to_string()));
                     self.visit_item_type(item_type);
                 }
 
                 if i < generics.len() - 1 {
-                    self.render(TextToken::Text(",".to_string()));
+                    self.render(TextToken::Text(", ".to_string()));
                 }
             }
             self.render(TextToken::Text(">".to_string()));
@@ -154,16 +289,16 @@ impl Transformer<'_> for MexLangTransformer {
             self.render(TextToken::Text("[".to_string()));
             for (i, (id, item_type, value)) in metadata.iter().enumerate() {
                 self.visit_id(id);
-                self.render(TextToken::Text(":".to_string()));
+                self.render(TextToken::Text(": ".to_string()));
                 self.visit_item_type(item_type);
 
                 if let Some(value) = value {
-                    self.render(TextToken::Text("=".to_string()));
+                    self.render(TextToken::Text(" = ".to_string()));
                     self.visit_literal(value);
                 }
 
                 if i < metadata.len() - 1 {
-                    self.render(TextToken::Text(",".to_string()));
+                    self.render(TextToken::Text(", ".to_string()));
                 }
             }
             self.render(TextToken::Text("]".to_string()));
@@ -190,19 +325,20 @@ impl Transformer<'_> for MexLangTransformer {
         }
     }
 
-    fn visit_global(&self, items: &Vec<RefScope>) {
+    fn visit_global(&self, items: &[RefScope]) {
         for item in items {
             self.visit_scope(item, false);
         }
     }
 
-    fn visit_package(&self, id: &Id, items: &Vec<RefScope>, is_root: bool) {
+    fn visit_package(&self, id: &Id, items: &[RefScope], is_root: bool) {
         if is_root {
             self.render(TextToken::Text("package".to_string()));
             self.render(TextToken::Space);
             self.visit_id(id);
             self.render(TextToken::Text(";".to_string()));
             self.render(TextToken::NewLine);
+            *self.separate.borrow_mut() = true;
 
             for item in items {
                 self.visit_scope(item, false);
@@ -236,18 +372,24 @@ impl Transformer<'_> for MexLangTransformer {
             ModelDefinition::Record(ref id, ref items, ref params) => {
                 self.visit_header_model("model");
                 self.visit_record_model(id, items, params);
+                self.render(TextToken::NewLine);
             },
             ModelDefinition::Fragment(ref id, ref items, ref params) => {
                 self.visit_header_model("fragment");
                 self.visit_record_model(id, items, params);
+                self.render(TextToken::NewLine);
             }
             ModelDefinition::Enum(ref id, ref items, ref params) => {
                 self.visit_header_model("model");
                 self.visit_enum_model(id, items, params);
+                self.render(TextToken::NewLine);
             },
             ModelDefinition::Tuple(ref id, ref items, ref params) => {
                 self.visit_header_model("model");
                 self.visit_tuple_model(id, items, params);
+                if self.style.semicolons == Semicolons::Always {
+                    self.render(TextToken::Text(";".to_string()));
+                }
                 self.render(TextToken::NewLine);
             },
             ModelDefinition::Alias(ref id, ref params, ref item_type) => {
@@ -263,12 +405,16 @@ impl Transformer<'_> for MexLangTransformer {
     }
 
     fn visit_header_model(&self, keyword: &str) {
-        self.render(TextToken::LineIndent);
+        if self.separate.replace(false) {
+            for _ in 0..self.style.blank_lines {
+                self.render(TextToken::EmptyLine);
+            }
+        }
         self.render(TextToken::Text(keyword.to_string()));
         self.render(TextToken::Space);
 }
 
-    fn visit_record_model(&self, id: &Id, items: &Vec<RecordItem>, params: &Vec<ModelParamDefinition>) {
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
 
             self.visit_id(id);
             self.visit_model_params_def(params);
@@ -277,27 +423,38 @@ impl Transformer<'_> for MexLangTransformer {
             self.render(TextToken::NewLine);
 
             self.render(TextToken::IncIndent);
-            for item in items {
+            for (i, item) in items.iter().enumerate() {
                 self.visit_model_item(item);
+                self.visit_separator(i, items.len());
+                self.render(TextToken::NewLine);
             }
             self.render(TextToken::DecIndent);
 
             self.render(TextToken::Text("}".into()));
-            self.render(TextToken::NewLine);
         }
 
-    fn visit_tuple_model(&self, id: &Id, items: &Vec<TupleItem>, params: &Vec<ModelParamDefinition>) {
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
 
         self.visit_id(id);
         self.visit_model_params_def(params);
         self.render(TextToken::Text("(".to_string()));
 
+        if items.is_empty() {
+            self.render(TextToken::Text(")".to_string()));
+            return;
+        }
+
+        self.render(TextToken::BeginGroup);
+        self.render(TextToken::IncIndent);
+        self.render(TextToken::SoftBreak);
+
         let mut is_next = false;
 
         for item in items {
 
             if is_next {
-                self.render(TextToken::Text(", ".to_string()));
+                self.render(TextToken::Text(",".to_string()));
+                self.render(TextToken::Break);
             }
 
             match item {
@@ -313,10 +470,14 @@ impl Transformer<'_> for MexLangTransformer {
 
             is_next = true;
         }
+
+        self.render(TextToken::DecIndent);
+        self.render(TextToken::SoftBreak);
         self.render(TextToken::Text(")".to_string()));
+        self.render(TextToken::EndGroup);
     }
 
-    fn visit_enum_model(&self, id: &Id, items: &Vec<EnumItem>, params: &Vec<ModelParamDefinition>) {
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
 
         self.visit_id(id);
         self.visit_model_params_def(params);
@@ -327,13 +488,14 @@ impl Transformer<'_> for MexLangTransformer {
         self.render(TextToken::NewLine);
 
         self.render(TextToken::IncIndent);
-        for item in items {
+        for (i, item) in items.iter().enumerate() {
             self.visit_enum_item(item);
+            self.visit_separator(i, items.len());
+            self.render(TextToken::NewLine);
         }
         self.render(TextToken::DecIndent);
 
         self.render(TextToken::Text("}".into()));
-        self.render(TextToken::NewLine);
     }
 
     fn visit_model_item(&self, item: &RecordItem) {
@@ -342,12 +504,10 @@ impl Transformer<'_> for MexLangTransformer {
                 self.visit_id(id);
                 self.render(TextToken::Text(": ".to_string()));
                 self.visit_item_type(type_id);
-                self.render(TextToken::NewLine);
             }
             RecordItem::Spread(ref type_id) => {
                 self.render(TextToken::Text("... ".to_string()));
                 self.visit_item_type(type_id);
-                self.render(TextToken::NewLine);
             }
         }
     }
@@ -356,7 +516,6 @@ impl Transformer<'_> for MexLangTransformer {
         match item {
             EnumItem::Item(ref id) => {
                 self.visit_id(id);
-                self.render(TextToken::NewLine);
             },
             EnumItem::Record(ref id, ref type_id) => {
                 self.visit_id(id);
@@ -366,7 +525,6 @@ impl Transformer<'_> for MexLangTransformer {
             EnumItem::Tuple(ref id, ref type_id) => {
                 self.visit_id(id);
                 self.visit_item_type(type_id);
-                self.render(TextToken::NewLine);
             },
             EnumItem::Enum(ref id, ref type_id) => {
                 self.visit_id(id);
@@ -380,14 +538,16 @@ impl Transformer<'_> for MexLangTransformer {
 
         self.visit_header_model("scalar");
         self.visit_id(id);
-        self.render(TextToken::Text(";".to_string()));
+        if self.style.semicolons != Semicolons::Never {
+            self.render(TextToken::Text(";".to_string()));
+        }
         self.render(TextToken::NewLine);
     }
 
     fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
-        self.render(TextToken::LineIndent);
+        self.render(TextToken::NewLine);
 
-        let text = format!("error: {:?}", error).into();
+        let text = format!("error: {:?}", error);
         self.render(TextToken::Text(text));
         self.render(TextToken::NewLine);
     }
@@ -2,26 +2,78 @@ use lalrpop_util::ErrorRecovery;
 use crate::ast::*;
 use crate::lexer::{LexicalError, Token};
 
+mod error;
 mod string_render;
 mod console_render;
+mod files_render;
+mod writer;
+mod layout;
 mod mex_lang_transformer;
+mod proto_transformer;
+mod graphql_transformer;
+mod openapi_transformer;
+mod sql_transformer;
+mod python_transformer;
+mod go_transformer;
+mod jvm_model;
+mod kotlin_transformer;
+mod java_transformer;
+mod avro_transformer;
+mod docs_transformer;
+mod diagram_transformer;
+mod csharp_transformer;
+mod swift_transformer;
+mod rust_transformer;
+mod template_transformer;
 
+pub mod case;
+pub mod model_index;
+pub mod template;
+
+pub use error::TransformError;
 pub use string_render::StringRender;
 pub use console_render::ConsoleRender;
-pub use mex_lang_transformer::MexLangTransformer;
+pub use files_render::FilesRender;
+pub use writer::TokenWriter;
+pub use layout::Layout;
+pub use mex_lang_transformer::{Indent, MexLangTransformer, Semicolons, Separator, Style};
+pub use proto_transformer::{ProtoLock, ProtoTransformer};
+pub use graphql_transformer::GraphQlTransformer;
+pub use openapi_transformer::OpenApiTransformer;
+pub use sql_transformer::{SqlDialect, SqlTransformer};
+pub use python_transformer::{PythonStyle, PythonTransformer};
+pub use go_transformer::GoTransformer;
+pub use kotlin_transformer::KotlinTransformer;
+pub use java_transformer::JavaTransformer;
+pub use avro_transformer::AvroTransformer;
+pub use docs_transformer::{DocsFormat, DocsTransformer};
+pub use diagram_transformer::{DiagramFormat, DiagramTransformer};
+pub use csharp_transformer::CSharpTransformer;
+pub use swift_transformer::SwiftTransformer;
+pub use rust_transformer::{EnumTagging, RenameRule, RustTransformer};
+pub use template_transformer::TemplateTransformer;
 
 pub enum TextToken {
     None,
 
     Space,
     NewLine,
+    EmptyLine,
 
     LineIndent,
     IncIndent,
     DecIndent,
 
+    /// Opens a group laid out flat when it fits into the line width, see `Layout`.
+    BeginGroup,
+    EndGroup,
+    /// A space in a flat group, a line break otherwise.
+    Break,
+    /// Nothing in a flat group, a line break otherwise.
+    SoftBreak,
+
     Text(String),
-    //Template(String, HashMap<String, String>),
+    File(String),
 }
 
 pub trait Target<Token> {
@@ -32,16 +84,16 @@ pub trait Transformer<'a> {
     fn visit_id(&'a self, id: &'a Id);
     fn visit_literal(&self, literal: &Literal);
     fn visit_item_type(&'a self, item_type: &'a ItemType<'a>);
-    fn visit_model_params(&self, params: &Vec<ModelParam>);
-    fn visit_model_params_def(&self, params: &Vec<ModelParamDefinition>);
+    fn visit_model_params(&self, params: &[ModelParam]);
+    fn visit_model_params_def(&self, params: &[ModelParamDefinition]);
     fn visit_scope(&'a self, item: &'a RefScope, is_root: bool);
-    fn visit_global(&'a self, items: &'a Vec<RefScope>);
-    fn visit_package(&'a self, id: &'a Id, items: &'a Vec<RefScope>, is_root: bool);
+    fn visit_global(&'a self, items: &'a [RefScope]);
+    fn visit_package(&'a self, id: &'a Id, items: &'a [RefScope], is_root: bool);
     fn visit_model(&'a self, def: &'a ModelDefinition);
     fn visit_header_model(&self, keyword: &str);
-    fn visit_record_model(&self, id: &Id, items: &Vec<RecordItem>, _params: &Vec<ModelParamDefinition>);
-    fn visit_tuple_model(&self, id: &Id, items: &Vec<TupleItem>, _params: &Vec<ModelParamDefinition>);
-    fn visit_enum_model(&self, id: &Id, items: &Vec<EnumItem>, _params: &Vec<ModelParamDefinition>);
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], _params: &[ModelParamDefinition]);
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], _params: &[ModelParamDefinition]);
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], _params: &[ModelParamDefinition]);
     fn visit_model_item(&'a self, item: &'a RecordItem);
     fn visit_enum_item(&self, item: &EnumItem);
     fn visit_scalar(&'a self, id: &'a Id);
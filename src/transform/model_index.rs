@@ -0,0 +1,241 @@
+use crate::ast::*;
+use crate::transform::TransformError;
+use crate::transform::case::to_pascal_case;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelKind {
+    Scalar,
+    Record,
+    Fragment,
+    Tuple,
+    Enum,
+    Alias,
+}
+
+pub struct ModelEntry<'input> {
+    pub name: String,
+    pub path: Vec<String>,
+    pub kind: ModelKind,
+    pub scope: RefScope<'input>,
+}
+
+impl<'input> ModelEntry<'input> {
+    /// Returns true for enums whose variants carry no payload.
+    pub fn is_plain_enum(&self) -> bool {
+        match **self.scope.borrow() {
+            Scope::Model(ModelDefinition::Enum(_, ref items, _)) => {
+                items.iter().all(|item| matches!(item, EnumItem::Item(_)))
+            },
+            _ => false,
+        }
+    }
+
+    pub fn generics(&self) -> Vec<String> {
+        match **self.scope.borrow() {
+            Scope::Model(ref def) => generic_names(model_params_def(def)),
+            _ => vec![],
+        }
+    }
+}
+
+/// Flat lookup table over every named model of a scope tree.
+#[derive(Default)]
+pub struct ModelIndex<'input> {
+    entries: Vec<ModelEntry<'input>>,
+}
+
+impl<'input> ModelIndex<'input> {
+    pub fn new(scope: &RefScope<'input>) -> Self {
+        let mut index = ModelIndex::default();
+        index.collect(scope, &mut vec![]);
+        index
+    }
+
+    fn collect(&mut self, scope: &RefScope<'input>, path: &mut Vec<String>) {
+        match **scope.borrow() {
+            Scope::Global(ref items) => {
+                items.iter().for_each(|item| self.collect(item, path));
+            },
+            Scope::Package(ref id, ref items) => {
+                path.push(id.as_name().unwrap_or_default().to_string());
+                items.iter().for_each(|item| self.collect(item, path));
+                path.pop();
+            },
+            Scope::Model(ref def) => {
+                let (id, kind) = match def {
+                    ModelDefinition::Scalar(id) => (id, ModelKind::Scalar),
+                    ModelDefinition::Record(id, _, _) => (id, ModelKind::Record),
+                    ModelDefinition::Fragment(id, _, _) => (id, ModelKind::Fragment),
+                    ModelDefinition::Tuple(id, _, _) => (id, ModelKind::Tuple),
+                    ModelDefinition::Enum(id, _, _) => (id, ModelKind::Enum),
+                    ModelDefinition::Alias(id, _, _) => (id, ModelKind::Alias),
+                };
+
+                if let Some(name) = id.as_name() {
+                    self.entries.push(ModelEntry {
+                        name: name.to_string(),
+                        path: path.clone(),
+                        kind,
+                        scope: scope.clone(),
+                    });
+                }
+            },
+            Scope::Error(_) => {},
+        }
+    }
+
+    pub fn entries(&self) -> &[ModelEntry<'input>] {
+        &self.entries
+    }
+
+    /// Whether models of different packages share the name, so targets with a single
+    /// namespace must qualify it.
+    pub fn is_ambiguous(&self, name: &str) -> bool {
+        let mut paths = self.entries.iter().filter(|entry| entry.name == name).map(|entry| &entry.path);
+        paths.next().is_some_and(|first| paths.any(|path| path != first))
+    }
+
+    /// Name of a model for targets with a single namespace: prefixed with its package path,
+    /// in PascalCase, when other packages declare the same name.
+    pub fn flat_name(&self, name: &str, path: &[String]) -> String {
+        match self.is_ambiguous(name) {
+            true => path.iter().map(|p| to_pascal_case(p)).collect::<String>() + name,
+            false => name.to_string(),
+        }
+    }
+
+    /// Finds a model by name the way a reference inside package `path` sees it, see `resolve_name`.
+    pub fn find(&self, name: &str, path: &[String]) -> Option<&ModelEntry<'input>> {
+        let entries = self.entries.iter().filter(|entry| entry.name == name);
+        resolve_name(path, entries.map(|entry| (entry, entry.path.as_slice())))
+    }
+
+    /// Calls `f` for every field of a record, expanding spreads of fragments and records
+    /// and binding their generic parameters to the spread arguments.
+    pub fn walk_record<'b, 'i>(
+        &self,
+        items: &'b [RecordItem<'i>],
+        bindings: &'b Bindings<'b, 'i>,
+        path: &[String],
+        f: &mut dyn for<'c, 'j> FnMut(&'c Id<'j>, &'c ItemType<'j>, &'c Bindings<'c, 'j>),
+    ) -> Result<(), TransformError> {
+        self.walk_record_items(items, bindings, path, f, &mut vec![])
+    }
+
+    fn walk_record_items<'b, 'i>(
+        &self,
+        items: &'b [RecordItem<'i>],
+        bindings: &'b Bindings<'b, 'i>,
+        path: &[String],
+        f: &mut dyn for<'c, 'j> FnMut(&'c Id<'j>, &'c ItemType<'j>, &'c Bindings<'c, 'j>),
+        stack: &mut Vec<String>,
+    ) -> Result<(), TransformError> {
+        for item in items {
+            match item {
+                RecordItem::Item(id, item_type) => f(id, item_type, bindings),
+                RecordItem::Spread(item_type) => {
+                    let (item_type, outer) = bindings.resolve(item_type);
+                    match item_type {
+                        ItemType::Inline(ModelDefinition::Record(_, items, _)) => {
+                            self.walk_record_items(items, outer, path, f, stack)?;
+                        },
+                        ItemType::Model(id, args) => {
+                            let name = id.as_name().unwrap_or_default();
+                            let entry = self.find(name, path)
+                                .ok_or_else(|| TransformError::UnknownType(name.to_string()))?;
+
+                            if stack.iter().any(|n| n == name) {
+                                return Err(TransformError::RecursiveSpread(name.to_string()));
+                            }
+
+                            let scope = entry.scope.borrow();
+                            let (items, params) = match **scope {
+                                Scope::Model(ModelDefinition::Fragment(_, ref items, ref params)) => (items, params),
+                                Scope::Model(ModelDefinition::Record(_, ref items, ref params)) => (items, params),
+                                _ => return Err(TransformError::Unsupported(
+                                    name.to_string(),
+                                    "only fragments and records can be spread".to_string(),
+                                )),
+                            };
+
+                            let inner = Bindings::new(params, args, outer);
+                            stack.push(name.to_string());
+                            self.walk_record_items(items, &inner, &entry.path, f, stack)?;
+                            stack.pop();
+                        },
+                        ItemType::Inline(_) | ItemType::Optional(_) => {
+                            return Err(TransformError::Unsupported(
+                                "...".to_string(),
+                                "only fragments and records can be spread".to_string(),
+                            ));
+                        },
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Generic arguments of a spread fragment, resolved in the scope the spread appears in.
+pub struct Bindings<'b, 'input> {
+    params: Vec<(&'input str, &'b ItemType<'input>)>,
+    parent: Option<&'b Bindings<'b, 'input>>,
+}
+
+impl<'b, 'input> Bindings<'b, 'input> {
+    pub fn root() -> Self {
+        Bindings { params: vec![], parent: None }
+    }
+
+    pub fn new(defs: &'b [ModelParamDefinition<'input>], args: &'b [ModelParam<'input>], parent: &'b Bindings<'b, 'input>) -> Self {
+        let args = args.iter().filter_map(|arg| match arg {
+            ModelParam::Generic(item_type) => Some(item_type),
+            ModelParam::Metadata(_, _) => None,
+        });
+
+        let params = defs.iter()
+            .filter_map(|def| match def {
+                ModelParamDefinition::Generic { id, .. } => id.as_name(),
+                _ => None,
+            })
+            .zip(args)
+            .collect();
+
+        Bindings { params, parent: Some(parent) }
+    }
+
+    /// Follows generic parameters to the types bound to them.
+    pub fn resolve<'s>(&'s self, item_type: &'s ItemType<'input>) -> (&'s ItemType<'input>, &'s Bindings<'s, 'input>) {
+        if let ItemType::Model(Id::Name(name), params) = item_type {
+            if params.is_empty() {
+                if let (Some((_, bound)), Some(parent)) = (self.params.iter().find(|(n, _)| n == name), self.parent) {
+                    return parent.resolve(bound);
+                }
+            }
+        }
+
+        (item_type, self)
+    }
+}
+
+pub fn model_params_def<'a, 'input>(def: &'a ModelDefinition<'input>) -> &'a [ModelParamDefinition<'input>] {
+    match def {
+        ModelDefinition::Fragment(_, _, params) => params,
+        ModelDefinition::Record(_, _, params) => params,
+        ModelDefinition::Tuple(_, _, params) => params,
+        ModelDefinition::Enum(_, _, params) => params,
+        ModelDefinition::Alias(_, params, _) => params,
+        ModelDefinition::Scalar(_) => &[],
+    }
+}
+
+pub fn generic_names(params: &[ModelParamDefinition]) -> Vec<String> {
+    params.iter()
+        .filter_map(|param| match param {
+            ModelParamDefinition::Generic { id, .. } => id.as_name().map(String::from),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,606 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::to_pascal_case;
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+/// Generates the `components.schemas` section of an OpenAPI 3.1 document as YAML.
+pub struct OpenApiTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    scalars: HashMap<String, (String, Option<String>)>,
+    discriminator: String,
+
+    path: RefCell<Vec<String>>,
+    models: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    schemas: RefCell<Vec<Vec<TextToken>>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for OpenApiTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> OpenApiTransformer<'input> {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "string", None),
+            ("Int", "integer", Some("int64")),
+            ("Long", "integer", Some("int64")),
+            ("Byte", "integer", Some("int32")),
+            ("Float", "number", Some("float")),
+            ("Double", "number", Some("double")),
+            ("Bool", "boolean", None),
+            ("Boolean", "boolean", None),
+            ("Bytes", "string", Some("byte")),
+            ("Uuid", "string", Some("uuid")),
+            ("Date", "string", Some("date")),
+            ("DateTime", "string", Some("date-time")),
+            ("Duration", "string", Some("duration")),
+        ];
+
+        OpenApiTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            scalars: scalars.iter()
+                .map(|(k, t, f)| (k.to_string(), (t.to_string(), f.map(String::from))))
+                .collect(),
+            discriminator: "kind".to_string(),
+
+            path: RefCell::new(Vec::new()),
+            models: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            schemas: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a JSON schema `type` and optional `format`.
+    pub fn with_scalar(mut self, scalar: &str, schema_type: &str, format: Option<&str>) -> Self {
+        self.scalars.insert(scalar.to_string(), (schema_type.to_string(), format.map(String::from)));
+        self
+    }
+
+    /// Sets the property that tags the variants of enums with payloads, `kind` by default.
+    pub fn with_discriminator(mut self, property: &str) -> Self {
+        self.discriminator = property.to_string();
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.writer.line("components:");
+        self.writer.indent();
+        self.writer.line("schemas:");
+        self.writer.indent();
+        for schema in self.schemas.into_inner() {
+            self.writer.extend(schema);
+        }
+        self.writer.dedent();
+        self.writer.dedent();
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        let model = self.models.borrow().first().cloned().unwrap_or_default();
+        self.error(TransformError::Unsupported(model, reason.to_string()));
+    }
+
+    /// Component name of a model, unique across packages as `components.schemas` needs.
+    fn flat_name(&self, name: &str, path: &[String]) -> String {
+        self.index.borrow().flat_name(name, path)
+    }
+
+    fn reference(name: &str) -> String {
+        format!("'#/components/schemas/{}'", name)
+    }
+
+    fn component<F: FnOnce()>(&self, at: usize, name: &str, f: F) {
+        let tokens = self.writer.capture(|| {
+            self.writer.line(format!("{}:", name));
+            self.writer.indent();
+            f();
+            self.writer.dedent();
+        });
+        self.schemas.borrow_mut().insert(at, tokens);
+    }
+
+    fn is_optional(item_type: &ItemType, bindings: &Bindings) -> bool {
+        matches!(bindings.resolve(item_type).0, ItemType::Optional(_))
+    }
+
+    /// Writes the schema of a type at the current indentation.
+    fn schema(&self, hint: &str, item_type: &ItemType, bindings: &Bindings) {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Optional(item_type) => self.schema(hint, item_type, bindings),
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+                    self.unsupported(&format!("generic type `{}` cannot be expressed in OpenAPI", name));
+                    return;
+                }
+
+                if self.generics.borrow().iter().any(|g| g == name) {
+                    self.unsupported(&format!("generic parameter `{}` cannot be expressed in OpenAPI", name));
+                    return;
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return;
+                };
+
+                match (entry.kind, self.scalars.get(name)) {
+                    (ModelKind::Scalar, Some((schema_type, format))) => {
+                        self.writer.line(format!("type: {}", schema_type));
+                        if let Some(format) = format {
+                            self.writer.line(format!("format: {}", format));
+                        }
+                    },
+                    (ModelKind::Fragment, _) => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                    },
+                    _ => self.writer.line(format!("$ref: {}", Self::reference(&self.flat_name(name, &entry.path)))),
+                }
+            },
+            ItemType::Inline(def) => {
+                let parent = self.models.borrow().last().cloned().unwrap_or_default();
+                match def {
+                    ModelDefinition::Enum(id, items, _) if items.iter().any(|i| !matches!(i, EnumItem::Item(_))) => {
+                        let name = id.as_name().map(String::from)
+                            .unwrap_or_else(|| format!("{}{}", parent, to_pascal_case(hint)));
+                        let at = self.schemas.borrow().len();
+                        self.component(at, &name, || self.definition(&name, def, bindings));
+                        self.writer.line(format!("$ref: {}", Self::reference(&name)));
+                    },
+                    _ => {
+                        let name = format!("{}{}", parent, to_pascal_case(hint));
+                        self.models.borrow_mut().push(name.clone());
+                        self.definition(&name, def, bindings);
+                        self.models.borrow_mut().pop();
+                    },
+                }
+            },
+        }
+    }
+
+    fn definition(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) {
+        match def {
+            ModelDefinition::Record(_, items, _) => self.record_schema(items, bindings, None),
+            ModelDefinition::Tuple(_, items, _) => self.tuple_schema(items, bindings),
+            ModelDefinition::Enum(_, items, _) => self.enum_schema(name, items, bindings),
+            ModelDefinition::Alias(_, _, item_type) => self.schema(name, item_type, bindings),
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => {},
+        }
+    }
+
+    fn object(&self, properties: Vec<(String, Vec<TextToken>, bool)>, tag: Option<&str>) {
+        self.writer.line("type: object");
+
+        if properties.is_empty() && tag.is_none() {
+            self.writer.line("properties: {}");
+            return;
+        }
+
+        let mut required = vec![];
+        self.writer.line("properties:");
+        self.writer.indent();
+        if let Some(tag) = tag {
+            self.writer.line(format!("{}:", self.discriminator));
+            self.writer.indent();
+            self.writer.line("type: string");
+            self.writer.line(format!("const: {}", tag));
+            self.writer.dedent();
+            required.push(self.discriminator.clone());
+        }
+        for (name, schema, is_optional) in properties {
+            self.writer.line(format!("{}:", name));
+            self.writer.indent();
+            self.writer.extend(schema);
+            self.writer.dedent();
+            if !is_optional {
+                required.push(name);
+            }
+        }
+        self.writer.dedent();
+
+        if !required.is_empty() {
+            self.writer.line("required:");
+            self.writer.indent();
+            for name in required {
+                self.writer.line(format!("- {}", name));
+            }
+            self.writer.dedent();
+        }
+    }
+
+    fn record_properties(&self, items: &[RecordItem], bindings: &Bindings) -> Vec<(String, Vec<TextToken>, bool)> {
+        let mut properties = vec![];
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            let name = id.as_name().unwrap_or_default();
+            let schema = self.writer.capture(|| self.schema(name, item_type, bindings));
+            properties.push((name.to_string(), schema, Self::is_optional(item_type, bindings)));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        properties
+    }
+
+    fn record_schema(&self, items: &[RecordItem], bindings: &Bindings, tag: Option<&str>) {
+        let properties = self.record_properties(items, bindings);
+        self.object(properties, tag);
+    }
+
+    fn tuple_properties(&self, items: &[TupleItem], bindings: &Bindings) -> Vec<(String, Vec<TextToken>, bool)> {
+        items.iter().enumerate()
+            .map(|(i, item)| {
+                let (name, item_type) = match item {
+                    TupleItem::Item(item_type) => (format!("item{}", i + 1), item_type),
+                    TupleItem::NamedItem(id, item_type) => (id.as_name().unwrap_or_default().to_string(), item_type),
+                };
+                let schema = self.writer.capture(|| self.schema(&name, item_type, bindings));
+                (name, schema, Self::is_optional(item_type, bindings))
+            })
+            .collect()
+    }
+
+    /// Single-value tuples are transparent wrappers, named tuples are objects and
+    /// positional tuples are fixed-length arrays.
+    fn tuple_schema(&self, items: &[TupleItem], bindings: &Bindings) {
+        match items {
+            [TupleItem::Item(item_type)] => self.schema("value", item_type, bindings),
+            _ if items.iter().all(|i| matches!(i, TupleItem::NamedItem(_, _))) => {
+                let properties = self.tuple_properties(items, bindings);
+                self.object(properties, None);
+            },
+            _ => {
+                self.writer.line("type: array");
+                self.writer.line("prefixItems:");
+                self.writer.indent();
+                for (i, item) in items.iter().enumerate() {
+                    let item_type = match item {
+                        TupleItem::Item(item_type) | TupleItem::NamedItem(_, item_type) => item_type,
+                    };
+                    let schema = self.writer.capture(|| self.schema(&format!("item{}", i + 1), item_type, bindings));
+                    self.list_item(schema);
+                }
+                self.writer.dedent();
+                self.writer.line(format!("minItems: {}", items.len()));
+                self.writer.line(format!("maxItems: {}", items.len()));
+            },
+        }
+    }
+
+    /// Writes a schema as a YAML sequence item, indenting its continuation lines.
+    fn list_item(&self, schema: Vec<TextToken>) {
+        let mut is_first = true;
+        let mut is_indented = false;
+        for token in schema {
+            match token {
+                TextToken::Text(text) if is_first => {
+                    self.writer.text(format!("- {}", text));
+                    is_first = false;
+                },
+                TextToken::NewLine if !is_indented => {
+                    self.writer.render(TextToken::NewLine);
+                    self.writer.indent();
+                    is_indented = true;
+                },
+                token => self.writer.render(token),
+            }
+        }
+        if is_indented {
+            self.writer.dedent();
+        }
+    }
+
+    fn enum_schema(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        if items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            self.writer.line("type: string");
+            self.writer.line("enum:");
+            self.writer.indent();
+            for item in items {
+                if let EnumItem::Item(id) = item {
+                    self.writer.line(format!("- {}", id.as_name().unwrap_or_default()));
+                }
+            }
+            self.writer.dedent();
+            return;
+        }
+
+        let mut variants = vec![];
+        self.models.borrow_mut().push(name.to_string());
+
+        for item in items {
+            let (id, payload) = match item {
+                EnumItem::Item(id) => (id, None),
+                EnumItem::Record(id, item_type)
+                | EnumItem::Tuple(id, item_type)
+                | EnumItem::Enum(id, item_type) => (id, Some(item_type)),
+            };
+
+            let tag = id.as_name().unwrap_or_default();
+            let variant = format!("{}{}", name, to_pascal_case(tag));
+            let at = self.schemas.borrow().len();
+
+            self.models.borrow_mut().push(variant.clone());
+            self.component(at, &variant, || match payload {
+                None => self.object(vec![], Some(tag)),
+                Some(ItemType::Inline(ModelDefinition::Record(_, items, _))) => {
+                    self.record_schema(items, bindings, Some(tag));
+                },
+                Some(ItemType::Inline(ModelDefinition::Tuple(_, items, _)))
+                    if items.iter().all(|i| matches!(i, TupleItem::NamedItem(_, _))) => {
+                    let properties = self.tuple_properties(items, bindings);
+                    self.object(properties, Some(tag));
+                },
+                Some(item_type) => {
+                    let schema = self.writer.capture(|| match item_type {
+                        ItemType::Inline(ModelDefinition::Tuple(_, items, _)) => self.tuple_schema(items, bindings),
+                        _ => self.schema("value", item_type, bindings),
+                    });
+                    self.object(vec![("value".to_string(), schema, false)], Some(tag));
+                },
+            });
+            self.models.borrow_mut().pop();
+
+            variants.push((tag.to_string(), variant));
+        }
+
+        self.models.borrow_mut().pop();
+
+        self.writer.line("oneOf:");
+        self.writer.indent();
+        for (_, variant) in &variants {
+            self.writer.line(format!("- $ref: {}", Self::reference(variant)));
+        }
+        self.writer.dedent();
+
+        self.writer.line("discriminator:");
+        self.writer.indent();
+        self.writer.line(format!("propertyName: {}", self.discriminator));
+        self.writer.line("mapping:");
+        self.writer.indent();
+        for (tag, variant) in &variants {
+            self.writer.line(format!("{}: {}", tag, Self::reference(variant)));
+        }
+        self.writer.dedent();
+        self.writer.dedent();
+    }
+
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str)) {
+        let name = id.as_name().unwrap_or_default();
+
+        if !generic_names(params).is_empty() {
+            self.error(TransformError::Unsupported(
+                name.to_string(),
+                "generic models cannot be expressed in OpenAPI".to_string(),
+            ));
+            return;
+        }
+
+        let name = self.flat_name(name, &self.path.borrow());
+        let at = self.schemas.borrow().len();
+        self.models.borrow_mut().push(name.clone());
+        self.component(at, &name, || f(&name));
+        self.models.borrow_mut().pop();
+    }
+}
+
+impl Transformer<'_> for OpenApiTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        match literal {
+            Literal::String(ref str) => self.writer.text(format!("'{}'", str)),
+            Literal::Number(ref str) => self.writer.text(str.to_string()),
+        }
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        self.schema("value", item_type, &Bindings::root());
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+            self.unsupported("generic types cannot be expressed in OpenAPI");
+        }
+    }
+
+    fn visit_model_params_def(&self, params: &[ModelParamDefinition]) {
+        *self.generics.borrow_mut() = generic_names(params);
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        for item in items {
+            self.visit_scope(item, false);
+        }
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        for item in items {
+            self.visit_scope(item, false);
+        }
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Fragment(_, _, _) => {
+
+            }
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, _) => {
+                self.top_level(id, params, |name| self.definition(name, def, &Bindings::root()));
+            }
+        }
+
+        self.generics.borrow_mut().clear();
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.line(format!("{}:", keyword));
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |_| self.record_schema(items, &Bindings::root(), None));
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |_| self.tuple_schema(items, &Bindings::root()));
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.enum_schema(name, items, &Bindings::root()));
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref type_id) = item {
+            self.visit_header_model(id.as_name().unwrap_or_default());
+            self.writer.indent();
+            self.visit_item_type(type_id);
+            self.writer.dedent();
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.writer.line(format!("- {}", id.as_name().unwrap_or_default()));
+        }
+    }
+
+    fn visit_scalar(&self, id: &Id) {
+        let name = id.as_name().unwrap_or_default();
+        if self.scalars.contains_key(name) {
+            return;
+        }
+
+        let name = self.flat_name(name, &self.path.borrow());
+        let at = self.schemas.borrow().len();
+        self.component(at, &name, || self.writer.line("description: Mex scalar"));
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::StringRender;
+
+    #[test_case(
+        "model Item {\n    id: Int\n    note: String?\n}\nscalar Int;\nscalar String;",
+        "components:\n  schemas:\n    Item:\n      type: object\n      properties:\n        id:\n          type: integer\n          format: int64\n        note:\n          type: string\n      required:\n        - id";
+        "record"
+    )]
+    #[test_case(
+        "model Email(String)\nmodel Pair(Int, Int)\nscalar Int;\nscalar String;",
+        "components:\n  schemas:\n    Email:\n      type: string\n    Pair:\n      type: array\n      prefixItems:\n        - type: integer\n          format: int64\n        - type: integer\n          format: int64\n      minItems: 2\n      maxItems: 2";
+        "tuple"
+    )]
+    #[test_case(
+        "model Status enum {\n    Draft\n    Published\n}",
+        "components:\n  schemas:\n    Status:\n      type: string\n      enum:\n        - Draft\n        - Published";
+        "plain enum"
+    )]
+    #[test_case(
+        "model Password enum {\n    Open(String)\n    Hashed {\n        salt: String\n    }\n}\nscalar String;",
+        "components:\n  schemas:\n    Password:\n      oneOf:\n        - $ref: '#/components/schemas/PasswordOpen'\n        - $ref: '#/components/schemas/PasswordHashed'\n      discriminator:\n        propertyName: kind\n        mapping:\n          Open: '#/components/schemas/PasswordOpen'\n          Hashed: '#/components/schemas/PasswordHashed'\n    PasswordOpen:\n      type: object\n      properties:\n        kind:\n          type: string\n          const: Open\n        value:\n          type: string\n      required:\n        - kind\n        - value\n    PasswordHashed:\n      type: object\n      properties:\n        kind:\n          type: string\n          const: Hashed\n        salt:\n          type: string\n      required:\n        - kind\n        - salt";
+        "discriminated enum"
+    )]
+    #[test_case(
+        "model Empty {}",
+        "components:\n  schemas:\n    Empty:\n      type: object\n      properties: {}";
+        "empty record"
+    )]
+    #[test_case(
+        "package shop;\nmodel Name(String)\npackage billing {\n    model Name(String)\n    model Invoice {\n        name: Name\n    }\n}\nscalar String;",
+        "components:\n  schemas:\n    ShopName:\n      type: string\n    ShopBillingName:\n      type: string\n    Invoice:\n      type: object\n      properties:\n        name:\n          $ref: '#/components/schemas/ShopBillingName'\n      required:\n        - name";
+        "same name in several packages"
+    )]
+    fn check(code: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        let transformer = OpenApiTransformer::new();
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_string(2);
+
+        assert_eq!(&result, expected)
+    }
+}
@@ -0,0 +1,762 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::{to_pascal_case, to_screaming_snake_case, to_snake_case};
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+/// Field numbers handed out so far, keyed by fully qualified message (or enum) name.
+///
+/// Numbers are never reused: a field that disappears from the model stays in the lock
+/// and is emitted as `reserved`. The lock is the only place numbers are pinned; Mex has no
+/// syntax for explicit field indices, and `Id::Index` items in a built tree are rejected.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProtoLock {
+    numbers: BTreeMap<String, BTreeMap<String, u32>>,
+}
+
+impl ProtoLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pins the number of a field, as if it had been read from a lock file.
+    pub fn set(&mut self, message: &str, field: &str, number: u32) {
+        self.numbers.entry(message.to_string()).or_default().insert(field.to_string(), number);
+    }
+
+    pub fn get(&self, message: &str, field: &str) -> Option<u32> {
+        self.numbers.get(message).and_then(|fields| fields.get(field)).copied()
+    }
+
+    /// Returns the numbers of `fields` in order and the numbers reserved for removed fields.
+    fn assign(&mut self, message: &str, fields: &[String], first: u32) -> (Vec<u32>, Vec<u32>) {
+        let known = self.numbers.entry(message.to_string()).or_default();
+        let mut next = known.values().max().map(|n| n + 1).unwrap_or(first).max(first);
+
+        let numbers = fields.iter()
+            .map(|field| {
+                *known.entry(field.clone()).or_insert_with(|| {
+                    next += 1;
+                    next - 1
+                })
+            })
+            .collect();
+
+        let reserved = known.iter()
+            .filter(|(field, _)| !fields.contains(field))
+            .map(|(_, number)| *number)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        (numbers, reserved)
+    }
+}
+
+impl FromStr for ProtoLock {
+    type Err = TransformError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lock = ProtoLock::new();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || TransformError::InvalidLock(line.to_string());
+            let (key, number) = line.split_once('=').ok_or_else(invalid)?;
+            let (message, field) = key.trim().rsplit_once('.').ok_or_else(invalid)?;
+            let number = number.trim().parse().map_err(|_| invalid())?;
+            lock.set(message, field, number);
+        }
+
+        Ok(lock)
+    }
+}
+
+impl fmt::Display for ProtoLock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (message, fields) in &self.numbers {
+            let mut fields = fields.iter().collect::<Vec<_>>();
+            fields.sort_by_key(|(_, number)| **number);
+            for (field, number) in fields {
+                writeln!(f, "{}.{} = {}", message, field, number)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Generates proto3 files, one per Mex package.
+pub struct ProtoTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    lock: RefCell<ProtoLock>,
+    scalars: HashMap<String, String>,
+
+    path: RefCell<Vec<String>>,
+    messages: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    writer: TokenWriter,
+    imports: RefCell<BTreeSet<String>>,
+    files: RefCell<Vec<Vec<TextToken>>>,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for ProtoTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> ProtoTransformer<'input> {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "string"),
+            ("Int", "int64"),
+            ("Long", "int64"),
+            ("Float", "float"),
+            ("Double", "double"),
+            ("Bool", "bool"),
+            ("Boolean", "bool"),
+            ("Byte", "uint32"),
+            ("Bytes", "bytes"),
+            ("Uuid", "string"),
+            ("DateTime", "google.protobuf.Timestamp"),
+            ("Duration", "google.protobuf.Duration"),
+        ];
+
+        ProtoTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            lock: RefCell::new(ProtoLock::new()),
+            scalars: scalars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+
+            path: RefCell::new(Vec::new()),
+            messages: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            writer: TokenWriter::new(),
+            imports: RefCell::new(BTreeSet::new()),
+            files: RefCell::new(Vec::new()),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a proto type, e.g. `("Money", "string")`.
+    pub fn with_scalar(mut self, scalar: &str, proto_type: &str) -> Self {
+        self.scalars.insert(scalar.to_string(), proto_type.to_string());
+        self
+    }
+
+    pub fn with_lock(self, lock: ProtoLock) -> Self {
+        *self.lock.borrow_mut() = lock;
+        self
+    }
+
+    /// Renders every package and returns the lock updated with newly assigned field numbers.
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ProtoLock> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.files
+            .into_inner().into_iter().flatten()
+            .for_each(|t| render.render(t));
+
+        Ok(self.lock.into_inner())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn package_name(path: &[String]) -> String {
+        path.iter().map(|p| to_snake_case(p)).collect::<Vec<_>>().join(".")
+    }
+
+    fn file_name(path: &[String]) -> String {
+        match path.is_empty() {
+            true => "global.proto".to_string(),
+            false => path.iter().map(|p| to_snake_case(p)).collect::<Vec<_>>().join("/") + ".proto",
+        }
+    }
+
+    fn qualified_name(&self, name: &str) -> String {
+        let mut parts = vec![];
+        let package = Self::package_name(&self.path.borrow());
+        if !package.is_empty() {
+            parts.push(package);
+        }
+        parts.extend(self.messages.borrow().iter().cloned());
+        parts.push(name.to_string());
+        parts.join(".")
+    }
+
+    fn current_model(&self) -> String {
+        self.messages.borrow().first().cloned().unwrap_or_default()
+    }
+
+    fn unsupported(&self, reason: &str) {
+        self.error(TransformError::Unsupported(self.current_model(), reason.to_string()));
+    }
+
+    /// Name of a field or variant.
+    fn item_name<'a>(&self, id: &Id<'a>) -> &'a str {
+        if let Id::Index(index) = id {
+            self.unsupported(&format!("explicit field index `{}` is not supported, pin field numbers in the lock file", index));
+        }
+        id.as_name().unwrap_or_default()
+    }
+
+    fn models(&self, items: &[RefScope]) {
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                continue;
+            }
+
+            self.writer.block(|| self.visit_scope(item, false));
+        }
+    }
+
+    fn packages(&self, items: &[RefScope]) {
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                self.visit_scope(item, false);
+            }
+        }
+    }
+
+    fn file(&self, items: &[RefScope]) {
+        let path = self.path.borrow().clone();
+        let outer_imports = self.imports.replace(BTreeSet::new());
+        let body = self.writer.capture(|| self.models(items));
+        let imports = self.imports.replace(outer_imports);
+
+        if !body.is_empty() {
+            let file = self.writer.capture(|| {
+                self.writer.render(TextToken::File(Self::file_name(&path)));
+                self.writer.line("syntax = \"proto3\";");
+                if !path.is_empty() {
+                    self.writer.empty_line();
+                    self.writer.line(format!("package {};", Self::package_name(&path)));
+                }
+                if !imports.is_empty() {
+                    self.writer.empty_line();
+                    for import in imports {
+                        self.writer.line(format!("import \"{}\";", import));
+                    }
+                }
+                self.writer.empty_line();
+                self.writer.extend(body);
+            });
+            self.files.borrow_mut().push(file);
+        }
+
+        self.packages(items);
+    }
+
+    /// Resolves the proto type of a field, generating nested messages for inline models.
+    fn field_type(&self, hint: &str, item_type: &ItemType, bindings: &Bindings, nested: &mut Vec<TextToken>) -> Option<String> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+                    self.unsupported(&format!("generic type `{}` cannot be expressed in proto3", name));
+                    return None;
+                }
+
+                if self.generics.borrow().iter().any(|g| g == name) {
+                    self.unsupported(&format!("generic parameter `{}` cannot be expressed in proto3", name));
+                    return None;
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                match entry.kind {
+                    ModelKind::Scalar => {
+                        let Some(proto_type) = self.scalars.get(name) else {
+                            self.unsupported(&format!("scalar `{}` has no proto mapping", name));
+                            return None;
+                        };
+
+                        if let Some(message) = proto_type.strip_prefix("google.protobuf.") {
+                            self.imports.borrow_mut().insert(format!("google/protobuf/{}.proto", to_snake_case(message)));
+                        }
+
+                        Some(proto_type.clone())
+                    },
+                    ModelKind::Fragment => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                        None
+                    },
+                    _ => {
+                        if entry.path == *self.path.borrow() {
+                            return Some(entry.name.clone());
+                        }
+
+                        self.imports.borrow_mut().insert(Self::file_name(&entry.path));
+                        Some(format!("{}.{}", Self::package_name(&entry.path), entry.name))
+                    },
+                }
+            },
+            ItemType::Inline(def) => {
+                let name = match def {
+                    ModelDefinition::Record(id, _, _)
+                    | ModelDefinition::Tuple(id, _, _)
+                    | ModelDefinition::Enum(id, _, _) => id.as_name().map(String::from),
+                    _ => None,
+                }.unwrap_or_else(|| to_pascal_case(hint));
+
+                let tokens = self.writer.capture(|| self.definition(&name, def, bindings));
+                nested.push(TextToken::EmptyLine);
+                nested.extend(tokens);
+                Some(name)
+            },
+            ItemType::Optional(item_type) => {
+                let field_type = self.field_type(hint, item_type, bindings, nested)?;
+                match field_type.starts_with("optional ") {
+                    true => Some(field_type),
+                    false => Some(format!("optional {}", field_type)),
+                }
+            },
+        }
+    }
+
+    fn definition(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) {
+        match def {
+            ModelDefinition::Record(_, items, _) => self.record_message(name, items, bindings),
+            ModelDefinition::Tuple(_, items, _) => self.tuple_message(name, items, bindings),
+            ModelDefinition::Enum(_, items, _) => self.enum_definition(name, items, bindings),
+            ModelDefinition::Alias(_, _, item_type) => match &**item_type {
+                ItemType::Inline(def) => self.definition(name, def, bindings),
+                _ => self.unsupported("aliases of named types cannot be expressed in proto3"),
+            },
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => {},
+        }
+    }
+
+    fn record_message(&self, name: &str, items: &[RecordItem], bindings: &Bindings) {
+        let mut fields: Vec<(String, Option<String>)> = vec![];
+        let mut nested = vec![];
+        self.messages.borrow_mut().push(name.to_string());
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            let field = self.item_name(id);
+            let field_type = self.field_type(field, item_type, bindings, &mut nested);
+            fields.push((to_snake_case(field), field_type));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        self.messages.borrow_mut().pop();
+        self.message(name, fields, nested);
+    }
+
+    fn tuple_message(&self, name: &str, items: &[TupleItem], bindings: &Bindings) {
+        let mut fields: Vec<(String, Option<String>)> = vec![];
+        let mut nested = vec![];
+        self.messages.borrow_mut().push(name.to_string());
+
+        for (i, item) in items.iter().enumerate() {
+            let (field, item_type) = match item {
+                TupleItem::Item(item_type) if items.len() == 1 => ("value".to_string(), item_type),
+                TupleItem::Item(item_type) => (format!("item_{}", i + 1), item_type),
+                TupleItem::NamedItem(id, item_type) => (to_snake_case(self.item_name(id)), item_type),
+            };
+
+            let field_type = self.field_type(&field, item_type, bindings, &mut nested);
+            fields.push((field, field_type));
+        }
+
+        self.messages.borrow_mut().pop();
+        self.message(name, fields, nested);
+    }
+
+    fn message(&self, name: &str, fields: Vec<(String, Option<String>)>, nested: Vec<TextToken>) {
+        let names = fields.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+        let (numbers, reserved) = self.lock.borrow_mut().assign(&self.qualified_name(name), &names, 1);
+
+        self.writer.line(format!("message {} {{", name));
+        self.writer.indent();
+
+        for ((field, field_type), number) in fields.into_iter().zip(numbers) {
+            if let Some(field_type) = field_type {
+                self.writer.line(format!("{} {} = {};", field_type, field, number));
+            }
+        }
+
+        self.reserved(reserved);
+        self.writer.extend(nested);
+
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+
+    fn reserved(&self, reserved: Vec<u32>) {
+        if !reserved.is_empty() {
+            let numbers = reserved.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+            self.writer.line(format!("reserved {};", numbers.join(", ")));
+        }
+    }
+
+    fn enum_definition(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        match items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            true => self.plain_enum(name, items),
+            false => self.oneof_message(name, items, bindings),
+        }
+    }
+
+    fn plain_enum(&self, name: &str, items: &[EnumItem]) {
+        let prefix = to_screaming_snake_case(name);
+        let values = items.iter()
+            .map(|item| match item {
+                EnumItem::Item(id) => format!("{}_{}", prefix, to_screaming_snake_case(self.item_name(id))),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        // Enum values share one namespace, with the zero value proto3 requires among them.
+        let unspecified = format!("{}_UNSPECIFIED", prefix);
+        for (i, value) in values.iter().enumerate() {
+            if *value == unspecified || values[..i].contains(value) {
+                self.unsupported(&format!("variants map to the enum value `{}` more than once", value));
+                return;
+            }
+        }
+
+        let (numbers, reserved) = self.lock.borrow_mut().assign(&self.qualified_name(name), &values, 1);
+
+        self.writer.line(format!("enum {} {{", name));
+        self.writer.indent();
+
+        self.writer.line(format!("{}_UNSPECIFIED = 0;", prefix));
+        for (value, number) in values.into_iter().zip(numbers) {
+            self.writer.line(format!("{} = {};", value, number));
+        }
+        self.reserved(reserved);
+
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+
+    fn oneof_message(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        let mut fields: Vec<(String, Option<String>)> = vec![];
+        let mut nested = vec![];
+        self.messages.borrow_mut().push(name.to_string());
+
+        for item in items {
+            let (id, field_type) = match item {
+                EnumItem::Item(id) => {
+                    let variant = to_pascal_case(self.item_name(id));
+                    let tokens = self.writer.capture(|| self.message(&variant, vec![], vec![]));
+                    nested.push(TextToken::EmptyLine);
+                    nested.extend(tokens);
+                    (id, Some(variant))
+                },
+                EnumItem::Tuple(id, ItemType::Inline(ModelDefinition::Tuple(_, tuple, _)))
+                    if matches!(tuple.as_slice(), [TupleItem::Item(_)]) => {
+                    let TupleItem::Item(item_type) = &tuple[0] else { unreachable!() };
+                    let variant = self.item_name(id);
+                    if let ItemType::Optional(_) = item_type {
+                        self.unsupported("optional variant payloads cannot be expressed in a oneof");
+                    }
+                    (id, self.field_type(variant, item_type, bindings, &mut nested))
+                },
+                EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                    let variant = self.item_name(id);
+                    let field_type = match item_type {
+                        ItemType::Inline(def) => {
+                            let variant = to_pascal_case(variant);
+                            let tokens = self.writer.capture(|| self.definition(&variant, def, bindings));
+                            nested.push(TextToken::EmptyLine);
+                            nested.extend(tokens);
+                            Some(variant)
+                        },
+                        _ => self.field_type(variant, item_type, bindings, &mut nested),
+                    };
+                    (id, field_type)
+                },
+            };
+
+            fields.push((to_snake_case(id.as_name().unwrap_or_default()), field_type));
+        }
+
+        self.messages.borrow_mut().pop();
+
+        let names = fields.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+        let (numbers, reserved) = self.lock.borrow_mut().assign(&self.qualified_name(name), &names, 1);
+
+        self.writer.line(format!("message {} {{", name));
+        self.writer.indent();
+
+        self.writer.line("oneof value {");
+        self.writer.indent();
+        for ((field, field_type), number) in fields.into_iter().zip(numbers) {
+            if let Some(field_type) = field_type {
+                self.writer.line(format!("{} {} = {};", field_type, field, number));
+            }
+        }
+        self.writer.dedent();
+        self.writer.line("}");
+
+        self.reserved(reserved);
+        self.writer.extend(nested);
+
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str)) {
+        let name = id.as_name().unwrap_or_default();
+        let generics = generic_names(params);
+
+        if !generics.is_empty() {
+            self.error(TransformError::Unsupported(
+                name.to_string(),
+                "generic models cannot be expressed in proto3".to_string(),
+            ));
+            return;
+        }
+
+        f(name);
+    }
+}
+
+impl Transformer<'_> for ProtoTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_literal(&self, _literal: &Literal) {
+
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        let mut nested = vec![];
+        if let Some(name) = self.field_type("value", item_type, &Bindings::root(), &mut nested) {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+            self.unsupported("generic types cannot be expressed in proto3");
+        }
+    }
+
+    fn visit_model_params_def(&self, params: &[ModelParamDefinition]) {
+        *self.generics.borrow_mut() = generic_names(params);
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        self.file(items);
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        self.file(items);
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Fragment(_, _, _) => {
+
+            }
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, _) => {
+                self.top_level(id, params, |name| self.definition(name, def, &Bindings::root()));
+            }
+        }
+
+        self.generics.borrow_mut().clear();
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.record_message(name, items, &Bindings::root()));
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.tuple_message(name, items, &Bindings::root()));
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.enum_definition(name, items, &Bindings::root()));
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref type_id) = item {
+            self.visit_item_type(type_id);
+            self.writer.render(TextToken::Space);
+            self.writer.text(to_snake_case(id.as_name().unwrap_or_default()));
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.writer.text(to_screaming_snake_case(id.as_name().unwrap_or_default()));
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    #[test_case(
+        "package Shop;\n\nmodel Item {\n    id: Int\n    title: String\n}\nscalar Int;\nscalar String;",
+        "syntax = \"proto3\";\n\npackage shop;\n\nmessage Item {\n    int64 id = 1;\n    string title = 2;\n}\n";
+        "record"
+    )]
+    #[test_case(
+        "model Email(String)\nmodel Point(x: Int, y: Int)\nscalar Int;\nscalar String;",
+        "syntax = \"proto3\";\n\nmessage Email {\n    string value = 1;\n}\n\nmessage Point {\n    int64 x = 1;\n    int64 y = 2;\n}\n";
+        "tuple"
+    )]
+    #[test_case(
+        "model Status enum {\n    Draft\n    Published\n}",
+        "syntax = \"proto3\";\n\nenum Status {\n    STATUS_UNSPECIFIED = 0;\n    STATUS_DRAFT = 1;\n    STATUS_PUBLISHED = 2;\n}\n";
+        "plain enum"
+    )]
+    #[test_case(
+        "model Password enum {\n    Open(String)\n    Hashed(value: String, salt: String)\n    Empty\n}\nscalar String;",
+        "syntax = \"proto3\";\n\nmessage Password {\n    oneof value {\n        string open = 1;\n        Hashed hashed = 2;\n        Empty empty = 3;\n    }\n\n    message Hashed {\n        string value = 1;\n        string salt = 2;\n    }\n\n    message Empty {\n    }\n}\n";
+        "payload enum"
+    )]
+    #[test_case(
+        "fragment Dic<Key, Data> {\n    id: Key\n    name: Data\n}\nmodel Right {\n    ... Dic<Int, String>\n    created: DateTime\n}\nscalar Int;\nscalar String;\nscalar DateTime;",
+        "syntax = \"proto3\";\n\nimport \"google/protobuf/timestamp.proto\";\n\nmessage Right {\n    int64 id = 1;\n    string name = 2;\n    google.protobuf.Timestamp created = 3;\n}\n";
+        "generic spread"
+    )]
+    fn check(code: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = ProtoTransformer::new();
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_files(4);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(&result[0].1, expected)
+    }
+
+    #[test_case("model Box<T>(T)"; "generic model")]
+    #[test_case("model Item { id: Money }\nscalar Money;"; "unmapped scalar")]
+    #[test_case("model Item { id: Missing }"; "unknown type")]
+    #[test_case("model Id = Int\nscalar Int;"; "named alias")]
+    #[test_case("model Status enum {\n    Unspecified\n    Draft\n}"; "unspecified variant")]
+    #[test_case("model Status enum {\n    DraftItem\n    Draft_item\n}"; "variants with one value")]
+    fn check_error(code: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = ProtoTransformer::new();
+        assert!(matches!(transformer.apply(&ast, &render), Err(Error::Transform(_))));
+    }
+
+    #[test]
+    fn check_lock() {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str("model Item {\n    title: String\n    price: Int\n}\nscalar Int;\nscalar String;");
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let lock: ProtoLock = "Item.id = 1\nItem.title = 2\n".parse().unwrap();
+        let render = FilesRender::new();
+        let lock = ProtoTransformer::new().with_lock(lock).apply(&ast, &render).unwrap();
+
+        let expected = "syntax = \"proto3\";\n\nmessage Item {\n    string title = 2;\n    int64 price = 3;\n    reserved 1;\n}\n";
+        assert_eq!(render.as_files(4)[0].1, expected);
+        assert_eq!(lock.to_string(), "Item.id = 1\nItem.title = 2\nItem.price = 3\n");
+    }
+
+    #[test]
+    fn check_explicit_index() {
+        let int = ItemType::Model(Id::Name("Int"), vec![]);
+        let item = ModelDefinition::Record(Id::Name("Item"), vec![RecordItem::Item(Id::Index(3), int)], vec![]);
+        let ast: RefScope = Scope::Global(vec![Scope::new_model(item), Scope::new_model(ModelDefinition::Scalar(Id::Name("Int")))]).into();
+
+        let render = FilesRender::new();
+        let Err(Error::Transform(errors)) = ProtoTransformer::new().apply(&ast, &render) else {
+            panic!("explicit indices must be rejected");
+        };
+        assert_eq!(errors[0].to_string(), "`Item`: explicit field index `3` is not supported, pin field numbers in the lock file");
+    }
+}
@@ -0,0 +1,741 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::{to_pascal_case, to_screaming_snake_case, to_snake_case};
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PythonStyle {
+    Dataclass,
+    TypedDict,
+}
+
+/// Generates one Python module per package with `typing` annotated classes.
+pub struct PythonTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    scalars: HashMap<String, String>,
+    style: PythonStyle,
+    tag: String,
+
+    path: RefCell<Vec<String>>,
+    models: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    imports: RefCell<BTreeMap<String, BTreeSet<String>>>,
+    type_vars: RefCell<Vec<String>>,
+    pending: RefCell<Vec<Vec<TextToken>>>,
+    files: RefCell<Vec<Vec<TextToken>>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for PythonTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> PythonTransformer<'input> {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "str"),
+            ("Int", "int"),
+            ("Long", "int"),
+            ("Byte", "int"),
+            ("Float", "float"),
+            ("Double", "float"),
+            ("Decimal", "decimal.Decimal"),
+            ("Bool", "bool"),
+            ("Boolean", "bool"),
+            ("Bytes", "bytes"),
+            ("Uuid", "uuid.UUID"),
+            ("Date", "datetime.date"),
+            ("DateTime", "datetime.datetime"),
+        ];
+
+        PythonTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            scalars: scalars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            style: PythonStyle::Dataclass,
+            tag: "kind".to_string(),
+
+            path: RefCell::new(Vec::new()),
+            models: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            imports: RefCell::new(BTreeMap::new()),
+            type_vars: RefCell::new(Vec::new()),
+            pending: RefCell::new(Vec::new()),
+            files: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a Python type; dotted names are imported, e.g. `("Money", "decimal.Decimal")`.
+    pub fn with_scalar(mut self, scalar: &str, python_type: &str) -> Self {
+        self.scalars.insert(scalar.to_string(), python_type.to_string());
+        self
+    }
+
+    pub fn with_style(mut self, style: PythonStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Name of the literal field that tags the variants of enums with payloads.
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tag = tag.to_string();
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.files
+            .into_inner().into_iter().flatten()
+            .for_each(|t| render.render(t));
+
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        let model = self.models.borrow().first().cloned().unwrap_or_default();
+        self.error(TransformError::Unsupported(model, reason.to_string()));
+    }
+
+    fn module_name(path: &[String]) -> String {
+        match path.is_empty() {
+            true => "models".to_string(),
+            false => path.iter().map(|p| to_snake_case(p)).collect::<Vec<_>>().join("."),
+        }
+    }
+
+    fn file_name(path: &[String]) -> String {
+        Self::module_name(path).replace('.', "/") + ".py"
+    }
+
+    fn field_name(name: &str) -> String {
+        const KEYWORDS: &[&str] = &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in",
+            "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+            "with", "yield", "None", "True", "False",
+        ];
+
+        let name = to_snake_case(name);
+        match KEYWORDS.contains(&name.as_str()) {
+            true => name + "_",
+            false => name,
+        }
+    }
+
+    fn import(&self, module: &str, name: &str) {
+        self.imports.borrow_mut()
+            .entry(module.to_string())
+            .or_default()
+            .insert(name.to_string());
+    }
+
+    fn typing(&self, name: &str) -> String {
+        self.import("typing", name);
+        name.to_string()
+    }
+
+    /// Writes top-level statements separated by two empty lines, as PEP 8 asks.
+    fn statement(&self, tokens: Vec<TextToken>) {
+        if tokens.is_empty() {
+            return;
+        }
+
+        if !self.writer.is_empty() {
+            self.writer.empty_line();
+            self.writer.empty_line();
+        }
+        self.writer.extend(tokens);
+    }
+
+    /// Writes a top-level definition preceded by the definitions of its inline types.
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str)) {
+        let name = id.as_name().unwrap_or_default();
+        let generics = generic_names(params);
+
+        for generic in &generics {
+            if !self.type_vars.borrow().contains(generic) {
+                self.type_vars.borrow_mut().push(generic.clone());
+            }
+        }
+
+        *self.generics.borrow_mut() = generics;
+        let tokens = self.writer.capture(|| f(name));
+        self.generics.borrow_mut().clear();
+
+        for pending in self.pending.take() {
+            self.statement(pending);
+        }
+        self.statement(tokens);
+    }
+
+    fn file(&self, items: &[RefScope]) {
+        let path = self.path.borrow().clone();
+        let outer_imports = self.imports.replace(BTreeMap::new());
+        let outer_type_vars = self.type_vars.replace(Vec::new());
+
+        let body = self.writer.capture(|| {
+            for item in items {
+                if let Scope::Package(_, _) = **item.borrow() {
+                    continue;
+                }
+                self.visit_scope(item, false);
+            }
+        });
+
+        let type_vars = self.type_vars.replace(outer_type_vars);
+        if !type_vars.is_empty() {
+            self.import("typing", "TypeVar");
+        }
+        let imports = self.imports.replace(outer_imports);
+
+        if !body.is_empty() {
+            let file = self.writer.capture(|| {
+                self.writer.render(TextToken::File(Self::file_name(&path)));
+                self.writer.line("from __future__ import annotations");
+
+                let (local, external): (Vec<_>, Vec<_>) = imports.iter()
+                    .partition(|(module, _)| self.is_local_module(module));
+
+                for group in [external, local] {
+                    if group.is_empty() {
+                        continue;
+                    }
+                    self.writer.empty_line();
+                    for (module, names) in group {
+                        let names = names.iter().cloned().collect::<Vec<_>>();
+                        self.writer.line(format!("from {} import {}", module, names.join(", ")));
+                    }
+                }
+
+                if !type_vars.is_empty() {
+                    self.writer.empty_line();
+                    for type_var in &type_vars {
+                        self.writer.line(format!("{} = TypeVar(\"{}\")", type_var, type_var));
+                    }
+                }
+
+                self.writer.empty_line();
+                self.writer.empty_line();
+                self.writer.extend(body);
+            });
+            self.files.borrow_mut().push(file);
+        }
+
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                self.visit_scope(item, false);
+            }
+        }
+    }
+
+    fn is_local_module(&self, module: &str) -> bool {
+        self.index.borrow().entries().iter().any(|entry| Self::module_name(&entry.path) == module)
+    }
+
+    fn generic_args(&self) -> String {
+        let generics = self.generics.borrow();
+        match generics.is_empty() {
+            true => String::new(),
+            false => format!("[{}]", generics.join(", ")),
+        }
+    }
+
+    /// Resolves the annotation of a field, defining classes for inline records and enums.
+    fn type_expr(&self, hint: &str, item_type: &ItemType, bindings: &Bindings) -> Option<String> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if self.generics.borrow().iter().any(|g| g == name) {
+                    return Some(name.to_string());
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                let args = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Generic(item_type) => Some(self.type_expr(hint, item_type, bindings)),
+                        ModelParam::Metadata(_, _) => None,
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                let python_type = match entry.kind {
+                    ModelKind::Scalar => {
+                        let Some(python_type) = self.scalars.get(name) else {
+                            self.unsupported(&format!("scalar `{}` has no Python mapping", name));
+                            return None;
+                        };
+
+                        match python_type.rsplit_once('.') {
+                            Some((module, python_type)) => {
+                                self.import(module, python_type);
+                                python_type.to_string()
+                            },
+                            None => python_type.clone(),
+                        }
+                    },
+                    ModelKind::Fragment => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                        return None;
+                    },
+                    _ => {
+                        if entry.path != *self.path.borrow() {
+                            self.import(&Self::module_name(&entry.path), &entry.name);
+                        }
+                        entry.name.clone()
+                    },
+                };
+
+                match args.is_empty() {
+                    true => Some(python_type),
+                    false => Some(format!("{}[{}]", python_type, args.join(", "))),
+                }
+            },
+            ItemType::Inline(ModelDefinition::Tuple(_, items, _)) => {
+                let items = items.iter()
+                    .map(|item| match item {
+                        TupleItem::Item(item_type) | TupleItem::NamedItem(_, item_type) => {
+                            self.type_expr(hint, item_type, bindings)
+                        },
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                Some(format!("{}[{}]", self.typing("Tuple"), items.join(", ")))
+            },
+            ItemType::Inline(def) => {
+                let parent = self.models.borrow().last().cloned().unwrap_or_default();
+                let name = parent + &to_pascal_case(hint);
+
+                let tokens = self.writer.capture(|| self.definition(&name, def, bindings));
+                self.pending.borrow_mut().push(tokens);
+                Some(name + &self.generic_args())
+            },
+            ItemType::Optional(item_type) => {
+                let python_type = self.type_expr(hint, item_type, bindings)?;
+                Some(format!("{}[{}]", self.typing("Optional"), python_type))
+            },
+        }
+    }
+
+    fn definition(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) {
+        self.models.borrow_mut().push(name.to_string());
+
+        match def {
+            ModelDefinition::Record(_, items, _) => self.record_class(name, items, bindings, None),
+            ModelDefinition::Tuple(_, items, _) => self.tuple_class(name, items, bindings),
+            ModelDefinition::Enum(_, items, _) => self.enum_class(name, items, bindings),
+            ModelDefinition::Alias(_, _, item_type) => {
+                if let Some(python_type) = self.type_expr(name, item_type, bindings) {
+                    self.writer.line(format!("{} = {}", name, python_type));
+                }
+            },
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => {},
+        }
+
+        self.models.borrow_mut().pop();
+    }
+
+    fn record_class(&self, name: &str, items: &[RecordItem], bindings: &Bindings, tag: Option<&str>) {
+        let mut fields = vec![];
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            let field = id.as_name().unwrap_or_default();
+            if let Some(python_type) = self.type_expr(field, item_type, bindings) {
+                fields.push((Self::field_name(field), python_type));
+            }
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        self.class(name, fields, tag);
+    }
+
+    fn tuple_fields(&self, items: &[TupleItem], bindings: &Bindings) -> Vec<(String, String)> {
+        let mut fields = vec![];
+
+        for (i, item) in items.iter().enumerate() {
+            let (field, item_type) = match item {
+                TupleItem::Item(item_type) if items.len() == 1 => ("value".to_string(), item_type),
+                TupleItem::Item(item_type) => (format!("item_{}", i + 1), item_type),
+                TupleItem::NamedItem(id, item_type) => (Self::field_name(id.as_name().unwrap_or_default()), item_type),
+            };
+
+            if let Some(python_type) = self.type_expr(&field, item_type, bindings) {
+                fields.push((field, python_type));
+            }
+        }
+
+        fields
+    }
+
+    fn tuple_class(&self, name: &str, items: &[TupleItem], bindings: &Bindings) {
+        if let ([TupleItem::Item(item_type)], true) = (items, self.generics.borrow().is_empty()) {
+            if let Some(python_type) = self.type_expr("value", item_type, bindings) {
+                self.writer.line(format!("{} = {}(\"{}\", {})", name, self.typing("NewType"), name, python_type));
+            }
+            return;
+        }
+
+        let fields = self.tuple_fields(items, bindings);
+        let mut bases = vec![self.typing("NamedTuple")];
+        bases.extend(self.generic_base());
+        self.class_body(name, None, bases, fields, None);
+    }
+
+    fn enum_class(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        if items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            self.import("enum", "Enum");
+
+            let members = items.iter()
+                .filter_map(|item| match item {
+                    EnumItem::Item(id) => id.as_name(),
+                    _ => None,
+                })
+                .map(|member| (to_screaming_snake_case(member), format!("\"{}\"", member)))
+                .collect();
+
+            self.writer.line(format!("class {}(Enum):", name));
+            self.members(members, " = ");
+            return;
+        }
+
+        let mut variants = vec![];
+        for item in items {
+            let (id, payload) = match item {
+                EnumItem::Item(id) => (id, None),
+                EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                    (id, Some(item_type))
+                },
+            };
+
+            let variant = id.as_name().unwrap_or_default();
+            let class_name = format!("{}{}", name, to_pascal_case(variant));
+
+            let tokens = self.writer.capture(|| {
+                self.models.borrow_mut().push(class_name.clone());
+                match payload {
+                    Some(ItemType::Inline(ModelDefinition::Record(_, items, _))) => {
+                        self.record_class(&class_name, items, bindings, Some(variant));
+                    },
+                    Some(ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => {
+                        let fields = self.tuple_fields(items, bindings);
+                        self.class(&class_name, fields, Some(variant));
+                    },
+                    Some(item_type) => {
+                        let fields = self.type_expr("value", item_type, bindings)
+                            .map(|python_type| vec![("value".to_string(), python_type)])
+                            .unwrap_or_default();
+                        self.class(&class_name, fields, Some(variant));
+                    },
+                    None => self.class(&class_name, vec![], Some(variant)),
+                }
+                self.models.borrow_mut().pop();
+            });
+
+            self.pending.borrow_mut().push(tokens);
+            variants.push(class_name + &self.generic_args());
+        }
+
+        self.writer.line(format!("{} = {}[{}]", name, self.typing("Union"), variants.join(", ")));
+    }
+
+    fn generic_base(&self) -> Option<String> {
+        match self.generics.borrow().is_empty() {
+            true => None,
+            false => Some(format!("{}{}", self.typing("Generic"), self.generic_args())),
+        }
+    }
+
+    fn class(&self, name: &str, fields: Vec<(String, String)>, tag: Option<&str>) {
+        let (decorator, mut bases) = match self.style {
+            PythonStyle::Dataclass => {
+                self.import("dataclasses", "dataclass");
+                (Some("@dataclass"), vec![])
+            },
+            PythonStyle::TypedDict => (None, vec![self.typing("TypedDict")]),
+        };
+        bases.extend(self.generic_base());
+
+        self.class_body(name, decorator, bases, fields, tag);
+    }
+
+    fn class_body(&self, name: &str, decorator: Option<&str>, bases: Vec<String>, mut fields: Vec<(String, String)>, tag: Option<&str>) {
+        if let Some(tag) = tag {
+            let literal = format!("{}[\"{}\"]", self.typing("Literal"), tag);
+            match self.style {
+                PythonStyle::Dataclass => fields.push((self.tag.clone(), format!("{} = \"{}\"", literal, tag))),
+                PythonStyle::TypedDict => fields.push((self.tag.clone(), literal)),
+            }
+        }
+
+        if let Some(decorator) = decorator {
+            self.writer.line(decorator);
+        }
+
+        match bases.is_empty() {
+            true => self.writer.line(format!("class {}:", name)),
+            false => self.writer.line(format!("class {}({}):", name, bases.join(", "))),
+        }
+
+        self.members(fields, ": ");
+    }
+
+    fn members(&self, members: Vec<(String, String)>, separator: &str) {
+        self.writer.indent();
+        if members.is_empty() {
+            self.writer.line("pass");
+        }
+        for (name, value) in members {
+            self.writer.line(format!("{}{}{}", name, separator, value));
+        }
+        self.writer.dedent();
+    }
+}
+
+impl Transformer<'_> for PythonTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        match literal {
+            Literal::String(ref str) => self.writer.text(format!("\"{}\"", str)),
+            Literal::Number(ref str) => self.writer.text(str.to_string()),
+        }
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        if let Some(python_type) = self.type_expr("value", item_type, &Bindings::root()) {
+            self.writer.text(python_type);
+        }
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        let args = params.iter()
+            .filter_map(|param| match param {
+                ModelParam::Generic(item_type) => self.type_expr("value", item_type, &Bindings::root()),
+                ModelParam::Metadata(_, _) => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !args.is_empty() {
+            self.writer.text(format!("[{}]", args.join(", ")));
+        }
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        self.file(items);
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        self.file(items);
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, _) => {
+                self.top_level(id, params, |name| self.definition(name, def, &Bindings::root()));
+            },
+            ModelDefinition::Fragment(_, _, _) => {
+
+            }
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| {
+            self.models.borrow_mut().push(name.to_string());
+            self.record_class(name, items, &Bindings::root(), None);
+            self.models.borrow_mut().pop();
+        });
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| {
+            self.models.borrow_mut().push(name.to_string());
+            self.tuple_class(name, items, &Bindings::root());
+            self.models.borrow_mut().pop();
+        });
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| {
+            self.models.borrow_mut().push(name.to_string());
+            self.enum_class(name, items, &Bindings::root());
+            self.models.borrow_mut().pop();
+        });
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref item_type) = item {
+            let field = id.as_name().unwrap_or_default();
+            if let Some(python_type) = self.type_expr(field, item_type, &Bindings::root()) {
+                self.writer.line(format!("{}: {}", Self::field_name(field), python_type));
+            }
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            let member = id.as_name().unwrap_or_default();
+            self.writer.line(format!("{} = \"{}\"", to_screaming_snake_case(member), member));
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    #[test_case(
+        PythonStyle::Dataclass,
+        "package Shop;\n\nmodel Item {\n    id: Int\n    from: String?\n    size: (Int, Int)\n}\nscalar Int;\nscalar String;",
+        "from __future__ import annotations\n\nfrom dataclasses import dataclass\nfrom typing import Optional, Tuple\n\n\n\
+        @dataclass\nclass Item:\n    id: int\n    from_: Optional[str]\n    size: Tuple[int, int]\n";
+        "dataclass"
+    )]
+    #[test_case(
+        PythonStyle::TypedDict,
+        "model Page<T> {\n    items: T\n    meta: {\n        total: Int\n    }\n}\nscalar Int;",
+        "from __future__ import annotations\n\nfrom typing import Generic, TypeVar, TypedDict\n\nT = TypeVar(\"T\")\n\n\n\
+        class PageMeta(TypedDict, Generic[T]):\n    total: int\n\n\n\
+        class Page(TypedDict, Generic[T]):\n    items: T\n    meta: PageMeta[T]\n";
+        "typed dict"
+    )]
+    #[test_case(
+        PythonStyle::Dataclass,
+        "model Email(String)\nmodel Status enum {\n    Draft\n    Published\n}\nscalar String;",
+        "from __future__ import annotations\n\nfrom enum import Enum\nfrom typing import NewType\n\n\n\
+        Email = NewType(\"Email\", str)\n\n\n\
+        class Status(Enum):\n    DRAFT = \"Draft\"\n    PUBLISHED = \"Published\"\n";
+        "tuple and plain enum"
+    )]
+    #[test_case(
+        PythonStyle::Dataclass,
+        "model Password enum {\n    Open(String)\n    Hashed(value: String, salt: String)\n    Empty\n}\nscalar String;",
+        "from __future__ import annotations\n\nfrom dataclasses import dataclass\nfrom typing import Literal, Union\n\n\n\
+        @dataclass\nclass PasswordOpen:\n    value: str\n    kind: Literal[\"Open\"] = \"Open\"\n\n\n\
+        @dataclass\nclass PasswordHashed:\n    value: str\n    salt: str\n    kind: Literal[\"Hashed\"] = \"Hashed\"\n\n\n\
+        @dataclass\nclass PasswordEmpty:\n    kind: Literal[\"Empty\"] = \"Empty\"\n\n\n\
+        Password = Union[PasswordOpen, PasswordHashed, PasswordEmpty]\n";
+        "tagged union"
+    )]
+    fn check(style: PythonStyle, code: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        let transformer = PythonTransformer::new().with_style(style);
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_files(4);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(&result[0].1, expected)
+    }
+
+    #[test]
+    fn check_packages() {
+        let code = "package Shop {\n    model Order {\n        item: Item\n    }\n}\n\
+            package Catalog {\n    model Item {\n        id: Uuid\n    }\n}\nscalar Uuid;";
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        PythonTransformer::new().apply(&ast, &render).unwrap();
+        let names = render.as_files(4).into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["shop.py", "catalog.py"]);
+    }
+}
@@ -0,0 +1,1100 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::{to_pascal_case, to_snake_case};
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+/// How enums with payloads are represented on the wire, see the serde enum representations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnumTagging {
+    External,
+    Internal(String),
+    Adjacent(String, String),
+    Untagged,
+}
+
+/// Naming convention of serialized fields and variants, emitted as `#[serde(rename_all = "...")]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenameRule {
+    CamelCase,
+    SnakeCase,
+    PascalCase,
+    ScreamingSnakeCase,
+    KebabCase,
+}
+
+impl RenameRule {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RenameRule::CamelCase => "camelCase",
+            RenameRule::SnakeCase => "snake_case",
+            RenameRule::PascalCase => "PascalCase",
+            RenameRule::ScreamingSnakeCase => "SCREAMING_SNAKE_CASE",
+            RenameRule::KebabCase => "kebab-case",
+        }
+    }
+}
+
+/// Metadata keys `validate()` enforces.
+const CHECKED: &[&str] = &["len", "min_len", "max_len", "min", "max"];
+
+/// Rust value a constraint is checked on: lengths apply to text and sequences, bounds to numbers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValueKind {
+    Text,
+    Sequence,
+    Integer,
+    Float,
+}
+
+/// A metadata constraint checked by `validate()`, e.g. `len = 32`.
+struct Check {
+    key: String,
+    value: String,
+    kind: ValueKind,
+    /// Path from the field to the constrained value, `.0` for single-item tuple models.
+    access: &'static str,
+}
+
+struct Field {
+    name: String,
+    key: String,
+    rust_type: String,
+    optional: bool,
+    checks: Vec<Check>,
+    /// Whether the value has a `validate()` of its own to call.
+    nested: bool,
+}
+
+/// Generates Rust structs and enums, one inline `mod` per Mex package.
+///
+/// With serde enabled the types derive `Serialize`/`Deserialize` and keep their Mex names on the wire.
+/// With validation enabled, records, tuples and enums with payloads get a `validate()` method enforcing
+/// the `len`, `min_len`, `max_len`, `min` and `max` metadata of their fields and validating the models
+/// they contain. Single-item tuple models are checked in place, with the metadata given where they are used.
+pub struct RustTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    scalars: HashMap<String, String>,
+    serde: bool,
+    tagging: EnumTagging,
+    rename: Option<RenameRule>,
+    validation: bool,
+
+    path: RefCell<Vec<String>>,
+    models: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    pending: RefCell<Vec<Vec<TextToken>>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for RustTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> RustTransformer<'input> {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "String"),
+            ("Int", "i64"),
+            ("Long", "i64"),
+            ("Byte", "u8"),
+            ("Float", "f32"),
+            ("Double", "f64"),
+            ("Decimal", "String"),
+            ("Bool", "bool"),
+            ("Boolean", "bool"),
+            ("Bytes", "Vec<u8>"),
+            ("Uuid", "String"),
+            ("Date", "String"),
+            ("DateTime", "String"),
+        ];
+
+        RustTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            scalars: scalars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            serde: false,
+            tagging: EnumTagging::External,
+            rename: None,
+            validation: false,
+
+            path: RefCell::new(Vec::new()),
+            models: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            pending: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a Rust type path, e.g. `("Uuid", "uuid::Uuid")`.
+    pub fn with_scalar(mut self, scalar: &str, rust_type: &str) -> Self {
+        self.scalars.insert(scalar.to_string(), rust_type.to_string());
+        self
+    }
+
+    /// Derives `serde::Serialize` and `serde::Deserialize`.
+    pub fn with_serde(mut self) -> Self {
+        self.serde = true;
+        self
+    }
+
+    /// Representation of enums with payloads; plain enums are always serialized as strings.
+    pub fn with_enum_tagging(mut self, tagging: EnumTagging) -> Self {
+        self.tagging = tagging;
+        self
+    }
+
+    /// Renames every field and variant by `rule` instead of keeping the Mex names.
+    pub fn with_rename_all(mut self, rule: RenameRule) -> Self {
+        self.rename = Some(rule);
+        self
+    }
+
+    /// Adds `validate()` methods and the `ValidationError` type they return.
+    pub fn with_validation(mut self) -> Self {
+        self.validation = true;
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+
+        if self.validation {
+            self.writer.block(|| self.validation_error());
+        }
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        let model = self.models.borrow().first().cloned().unwrap_or_default();
+        self.error(TransformError::Unsupported(model, reason.to_string()));
+    }
+
+    fn identifier(name: String) -> String {
+        const KEYWORDS: &[&str] = &[
+            "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+            "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+            "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+            "async", "await", "dyn", "abstract", "become", "box", "do", "final", "macro", "override",
+            "priv", "typeof", "unsized", "virtual", "yield", "try",
+        ];
+
+        match KEYWORDS.contains(&name.as_str()) {
+            true => format!("r#{}", name),
+            false => name,
+        }
+    }
+
+    /// Path of a type declared in package `path` as seen from the current module.
+    fn relative(&self, path: &[String], name: &str) -> String {
+        let current = self.path.borrow();
+        let common = current.iter().zip(path).take_while(|(a, b)| a == b).count();
+
+        let mut parts = vec!["super".to_string(); current.len() - common];
+        parts.extend(path[common..].iter().map(|p| to_snake_case(p)));
+        parts.push(name.to_string());
+        parts.join("::")
+    }
+
+    fn type_params(&self) -> String {
+        let generics = self.generics.borrow();
+        match generics.is_empty() {
+            true => String::new(),
+            false => format!("<{}>", generics.join(", ")),
+        }
+    }
+
+    fn derive(&self) {
+        match self.serde {
+            true => self.writer.line("#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]"),
+            false => self.writer.line("#[derive(Debug, Clone, PartialEq)]"),
+        }
+    }
+
+    fn rename_all(&self, fields: bool) {
+        if let (true, Some(rule)) = (self.serde, self.rename) {
+            match fields {
+                true => self.writer.line(format!("#[serde(rename_all = \"{0}\", rename_all_fields = \"{0}\")]", rule.as_str())),
+                false => self.writer.line(format!("#[serde(rename_all = \"{}\")]", rule.as_str())),
+            }
+        }
+    }
+
+    /// Keeps the Mex name on the wire when the Rust name differs and no rename rule is set.
+    fn rename(&self, rust_name: &str, key: &str) {
+        if self.serde && self.rename.is_none() && rust_name.trim_start_matches("r#") != key {
+            self.writer.line(format!("#[serde(rename = \"{}\")]", key));
+        }
+    }
+
+    fn validation_error(&self) {
+        self.writer.line("#[derive(Debug, Clone, PartialEq)]");
+        self.writer.line("pub struct ValidationError {");
+        self.writer.indent();
+        self.writer.line("pub field: &'static str,");
+        self.writer.line("pub message: &'static str,");
+        self.writer.dedent();
+        self.writer.line("}");
+        self.writer.empty_line();
+        self.writer.line("impl std::fmt::Display for ValidationError {");
+        self.writer.indent();
+        self.writer.line("fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {");
+        self.writer.indent();
+        self.writer.line("write!(f, \"{}: {}\", self.field, self.message)");
+        self.writer.dedent();
+        self.writer.line("}");
+        self.writer.dedent();
+        self.writer.line("}");
+        self.writer.empty_line();
+        self.writer.line("impl std::error::Error for ValidationError {}");
+    }
+
+    /// Writes a top-level declaration followed by the declarations of its inline types.
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str)) {
+        let name = id.as_name().unwrap_or_default();
+
+        *self.generics.borrow_mut() = generic_names(params);
+        self.models.borrow_mut().push(name.to_string());
+        let tokens = self.writer.capture(|| f(name));
+        self.models.borrow_mut().pop();
+        self.generics.borrow_mut().clear();
+
+        self.writer.block(|| self.writer.extend(tokens));
+        for pending in self.pending.take() {
+            self.writer.block(|| self.writer.extend(pending));
+        }
+    }
+
+    /// Resolves the Rust type of a field, declaring named types for inline models.
+    fn rust_type(&self, hint: &str, item_type: &ItemType, bindings: &Bindings) -> Option<String> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if self.generics.borrow().iter().any(|g| g == name) {
+                    return Some(name.to_string());
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                let args = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Generic(item_type) => Some(self.rust_type(hint, item_type, bindings)),
+                        ModelParam::Metadata(_, _) => None,
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                let rust_type = match entry.kind {
+                    ModelKind::Scalar => {
+                        let Some(rust_type) = self.scalars.get(name) else {
+                            self.unsupported(&format!("scalar `{}` has no Rust mapping", name));
+                            return None;
+                        };
+                        rust_type.clone()
+                    },
+                    ModelKind::Fragment => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                        return None;
+                    },
+                    _ => self.relative(&entry.path, &entry.name),
+                };
+
+                match args.is_empty() {
+                    true => Some(rust_type),
+                    false => Some(format!("{}<{}>", rust_type, args.join(", "))),
+                }
+            },
+            ItemType::Inline(def) => {
+                let parent = self.models.borrow().last().cloned().unwrap_or_default();
+                let name = parent + &to_pascal_case(hint);
+
+                let tokens = self.writer.capture(|| self.declaration(&name, def, bindings));
+                self.pending.borrow_mut().push(tokens);
+                Some(name + &self.type_params())
+            },
+            ItemType::Optional(item_type) => {
+                let rust_type = self.rust_type(hint, item_type, bindings)?;
+                Some(format!("Option<{}>", rust_type))
+            },
+        }
+    }
+
+    /// Kind of the Rust value a type maps to, none for the types constraints cannot apply to.
+    fn value_kind(&self, item_type: &ItemType, bindings: &Bindings) -> Option<ValueKind> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        let id = match item_type {
+            ItemType::Optional(item_type) => return self.value_kind(item_type, bindings),
+            ItemType::Model(id, _) => id,
+            ItemType::Inline(_) => return None,
+        };
+
+        let name = id.as_name().unwrap_or_default();
+        if self.generics.borrow().iter().any(|g| g == name) {
+            return None;
+        }
+
+        let index = self.index.borrow();
+        let entry = index.find(name, &self.path.borrow())?;
+        let scope = entry.scope.borrow();
+        let kind = match (entry.kind, &**scope) {
+            (ModelKind::Scalar, _) => match self.scalars.get(name)?.as_str() {
+                "String" => Some(ValueKind::Text),
+                "f32" | "f64" => Some(ValueKind::Float),
+                "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => {
+                    Some(ValueKind::Integer)
+                },
+                rust_type if rust_type.starts_with("Vec<") => Some(ValueKind::Sequence),
+                _ => None,
+            },
+            (ModelKind::Alias, Scope::Model(ModelDefinition::Alias(_, _, target))) => self.value_kind(target, &Bindings::root()),
+            _ => None,
+        };
+        kind
+    }
+
+    /// Whether values of the type have a `validate()` to call. Single-item tuple models are
+    /// checked in place instead, see `checks`.
+    fn validates(&self, item_type: &ItemType, bindings: &Bindings) -> bool {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        let id = match item_type {
+            ItemType::Optional(item_type) => return self.validates(item_type, bindings),
+            ItemType::Inline(def) => return Self::has_validate(def),
+            ItemType::Model(id, _) => id,
+        };
+
+        let name = id.as_name().unwrap_or_default();
+        if self.generics.borrow().iter().any(|g| g == name) {
+            return false;
+        }
+
+        let index = self.index.borrow();
+        let Some(entry) = index.find(name, &self.path.borrow()) else {
+            return false;
+        };
+
+        let scope = entry.scope.borrow();
+        let validates = match **scope {
+            Scope::Model(ModelDefinition::Tuple(_, ref items, _)) => items.len() != 1,
+            Scope::Model(ModelDefinition::Alias(_, _, ref target)) => self.validates(target, &Bindings::root()),
+            Scope::Model(ref def) => Self::has_validate(def),
+            _ => false,
+        };
+        validates
+    }
+
+    fn has_validate(def: &ModelDefinition) -> bool {
+        match def {
+            ModelDefinition::Record(_, _, _) | ModelDefinition::Tuple(_, _, _) => true,
+            ModelDefinition::Enum(_, items, _) => items.iter().any(|item| !matches!(item, EnumItem::Item(_))),
+            _ => false,
+        }
+    }
+
+    /// Metadata declared with a numeric default.
+    fn defaults(defs: &[ModelParamDefinition]) -> Vec<(String, String)> {
+        defs.iter()
+            .filter_map(|def| match def {
+                ModelParamDefinition::Metadata { id, def_value: Some(Literal::Number(value)), .. } => {
+                    Some((id.as_name().unwrap_or_default().to_string(), value.to_string()))
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Collects the constraints of a field: metadata defaults of a single-item tuple model
+    /// overridden by the metadata given where the type is used.
+    fn checks(&self, field: &str, item_type: &ItemType, bindings: &Bindings) -> Vec<Check> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        let (id, params) = match item_type {
+            ItemType::Optional(item_type) => return self.checks(field, item_type, bindings),
+            ItemType::Model(id, params) => (id, params),
+            ItemType::Inline(_) => return vec![],
+        };
+
+        let mut metadata = vec![];
+        let mut access = "";
+        let mut kind = None;
+
+        let index = self.index.borrow();
+        if let Some(entry) = index.find(id.as_name().unwrap_or_default(), &self.path.borrow()) {
+            if let Scope::Model(ModelDefinition::Tuple(_, ref items, ref defs)) = **entry.scope.borrow() {
+                if let [TupleItem::Item(inner) | TupleItem::NamedItem(_, inner)] = items.as_slice() {
+                    access = ".0";
+                    kind = self.value_kind(inner, &Bindings::root());
+                    metadata.extend(Self::defaults(defs));
+                }
+            }
+        }
+        if access.is_empty() {
+            kind = self.value_kind(item_type, bindings);
+        }
+
+        for param in params {
+            if let ModelParam::Metadata(id, Literal::Number(value)) = param {
+                let key = id.as_name().unwrap_or_default().to_string();
+                metadata.retain(|(k, _)| *k != key);
+                metadata.push((key, value.to_string()));
+            }
+        }
+
+        self.constraints(field, metadata, kind, access)
+    }
+
+    /// Turns metadata into checks, rejecting the constraints the type of the value cannot have.
+    fn constraints(&self, field: &str, metadata: Vec<(String, String)>, kind: Option<ValueKind>, access: &'static str) -> Vec<Check> {
+        metadata.into_iter()
+            .filter(|(key, _)| CHECKED.contains(&key.as_str()))
+            .filter_map(|(key, value)| {
+                let kind = match (key.as_str(), kind) {
+                    ("len" | "min_len" | "max_len", Some(kind @ (ValueKind::Text | ValueKind::Sequence))) => kind,
+                    ("min" | "max", Some(kind @ (ValueKind::Integer | ValueKind::Float))) => kind,
+                    _ => {
+                        self.unsupported(&format!("`{}` cannot be checked on the type of `{}`", key, field));
+                        return None;
+                    },
+                };
+
+                // Mex numbers are integers, which Rust does not compare with floats.
+                let value = match kind {
+                    ValueKind::Float => format!("{}.0", value),
+                    _ => value,
+                };
+
+                Some(Check { key, value, kind, access })
+            })
+            .collect()
+    }
+
+    fn field(&self, name: &str, item_type: &ItemType, bindings: &Bindings) -> Option<Field> {
+        let rust_type = self.rust_type(name, item_type, bindings)?;
+        let optional = matches!(bindings.resolve(item_type).0, ItemType::Optional(_));
+
+        Some(Field {
+            name: Self::identifier(to_snake_case(name)),
+            key: name.to_string(),
+            rust_type,
+            optional,
+            checks: match self.validation {
+                true => self.checks(name, item_type, bindings),
+                false => vec![],
+            },
+            nested: self.validation && self.validates(item_type, bindings),
+        })
+    }
+
+    fn declaration(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) {
+        self.models.borrow_mut().push(name.to_string());
+
+        match def {
+            ModelDefinition::Record(_, items, _) => self.record(name, items, bindings),
+            ModelDefinition::Tuple(_, items, params) => self.tuple(name, items, params, bindings),
+            ModelDefinition::Enum(_, items, _) => self.enumeration(name, items, bindings),
+            ModelDefinition::Alias(_, _, item_type) => {
+                if let Some(rust_type) = self.rust_type(name, item_type, bindings) {
+                    self.writer.line(format!("pub type {}{} = {};", name, self.type_params(), rust_type));
+                }
+            },
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => {},
+        }
+
+        self.models.borrow_mut().pop();
+    }
+
+    fn record_fields(&self, items: &[RecordItem], bindings: &Bindings) -> Vec<Field> {
+        let mut fields = vec![];
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            fields.extend(self.field(id.as_name().unwrap_or_default(), item_type, bindings));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        fields
+    }
+
+    fn tuple_fields(&self, items: &[TupleItem], bindings: &Bindings) -> Vec<Field> {
+        items.iter()
+            .enumerate()
+            .filter_map(|(i, item)| match item {
+                TupleItem::Item(item_type) => self.field(&i.to_string(), item_type, bindings),
+                TupleItem::NamedItem(id, item_type) => {
+                    let field = self.field(id.as_name().unwrap_or_default(), item_type, bindings)?;
+                    Some(Field { name: i.to_string(), ..field })
+                },
+            })
+            .collect()
+    }
+
+    /// Writes the named fields of a struct or struct variant with their serde attributes.
+    fn named_fields(&self, fields: &[Field], visibility: &str) {
+        for field in fields {
+            self.rename(&field.name, &field.key);
+            if self.serde && field.optional {
+                self.writer.line("#[serde(default, skip_serializing_if = \"Option::is_none\")]");
+            }
+            self.writer.line(format!("{}{}: {},", visibility, field.name, field.rust_type));
+        }
+    }
+
+    fn record(&self, name: &str, items: &[RecordItem], bindings: &Bindings) {
+        let fields = self.record_fields(items, bindings);
+
+        self.derive();
+        self.rename_all(false);
+        match fields.is_empty() {
+            true => self.writer.line(format!("pub struct {}{} {{}}", name, self.type_params())),
+            false => {
+                self.writer.line(format!("pub struct {}{} {{", name, self.type_params()));
+                self.writer.indent();
+                self.named_fields(&fields, "pub ");
+                self.writer.dedent();
+                self.writer.line("}");
+            },
+        }
+
+        self.validate(name, &fields);
+    }
+
+    fn tuple(&self, name: &str, items: &[TupleItem], params: &[ModelParamDefinition], bindings: &Bindings) {
+        let mut fields = self.tuple_fields(items, bindings);
+
+        // A single-item tuple declaring metadata checks its own value with the defaults.
+        if let ([field], [TupleItem::Item(inner) | TupleItem::NamedItem(_, inner)], true) = (fields.as_mut_slice(), items, self.validation) {
+            let kind = self.value_kind(inner, bindings);
+            field.checks.extend(self.constraints(&field.key, Self::defaults(params), kind, ""));
+        }
+
+        let types = fields.iter().map(|f| format!("pub {}", f.rust_type)).collect::<Vec<_>>();
+        self.derive();
+        self.writer.line(format!("pub struct {}{}({});", name, self.type_params(), types.join(", ")));
+
+        self.validate(name, &fields);
+    }
+
+    fn enumeration(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        let is_plain = items.iter().all(|item| matches!(item, EnumItem::Item(_)));
+        let has_fields = items.iter().any(|item| matches!(item, EnumItem::Record(_, _)));
+
+        self.derive();
+        if self.serde && !is_plain {
+            match &self.tagging {
+                EnumTagging::External => {},
+                EnumTagging::Internal(tag) => self.writer.line(format!("#[serde(tag = \"{}\")]", tag)),
+                EnumTagging::Adjacent(tag, content) => {
+                    self.writer.line(format!("#[serde(tag = \"{}\", content = \"{}\")]", tag, content));
+                },
+                EnumTagging::Untagged => self.writer.line("#[serde(untagged)]"),
+            }
+        }
+        self.rename_all(has_fields);
+
+        self.writer.line(format!("pub enum {}{} {{", name, self.type_params()));
+        self.writer.indent();
+
+        // Pattern of every variant with something to validate, and its fields by binding.
+        let mut arms: Vec<(String, Vec<(String, Field)>)> = vec![];
+
+        for item in items {
+            let (id, payload) = match item {
+                EnumItem::Item(id) => (id, None),
+                EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                    (id, Some(item_type))
+                },
+            };
+
+            let key = id.as_name().unwrap_or_default();
+            let variant = to_pascal_case(key);
+            self.rename(&variant, key);
+
+            self.models.borrow_mut().push(format!("{}{}", name, variant));
+            match payload {
+                Some(ItemType::Inline(ModelDefinition::Record(_, items, _))) => {
+                    let fields = self.record_fields(items, bindings);
+                    self.writer.line(format!("{} {{", variant));
+                    self.writer.indent();
+                    self.named_fields(&fields, "");
+                    self.writer.dedent();
+                    self.writer.line("},");
+
+                    let bound = fields.into_iter()
+                        .filter(|f| !f.checks.is_empty() || f.nested)
+                        .map(|f| (f.name.clone(), f))
+                        .collect::<Vec<_>>();
+                    let names = bound.iter().map(|(binding, _)| format!("{}, ", binding)).collect::<String>();
+                    arms.push((format!("Self::{} {{ {}.. }}", variant, names), bound));
+                },
+                Some(ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => {
+                    let fields = self.tuple_fields(items, bindings);
+                    let types = fields.iter().map(|f| f.rust_type.clone()).collect::<Vec<_>>();
+                    self.writer.line(format!("{}({}),", variant, types.join(", ")));
+
+                    let mut patterns = vec![];
+                    let mut bound = vec![];
+                    for (i, field) in fields.into_iter().enumerate() {
+                        match !field.checks.is_empty() || field.nested {
+                            true => {
+                                patterns.push(format!("item{}", i));
+                                bound.push((format!("item{}", i), field));
+                            },
+                            false => patterns.push("_".to_string()),
+                        }
+                    }
+                    arms.push((format!("Self::{}({})", variant, patterns.join(", ")), bound));
+                },
+                Some(item_type) => {
+                    if let Some(field) = self.field(key, item_type, bindings) {
+                        self.writer.line(format!("{}({}),", variant, field.rust_type));
+                        arms.push((format!("Self::{}(value)", variant), vec![("value".to_string(), field)]));
+                    }
+                },
+                None => self.writer.line(format!("{},", variant)),
+            }
+            self.models.borrow_mut().pop();
+        }
+
+        self.writer.dedent();
+        self.writer.line("}");
+
+        if self.validation && !is_plain {
+            let count = arms.len();
+            arms.retain(|(_, bound)| bound.iter().any(|(_, f)| !f.checks.is_empty() || f.nested));
+            let is_partial = arms.len() < count || arms.len() < items.len();
+
+            self.validate_impl(name, || {
+                if arms.is_empty() {
+                    return;
+                }
+
+                self.writer.line("match self {");
+                self.writer.indent();
+                for (pattern, bound) in &arms {
+                    self.writer.line(format!("{} => {{", pattern));
+                    self.writer.indent();
+                    for (binding, field) in bound {
+                        self.field_checks(field, binding, true);
+                    }
+                    self.writer.dedent();
+                    self.writer.line("},");
+                }
+                if is_partial {
+                    self.writer.line("_ => {},");
+                }
+                self.writer.dedent();
+                self.writer.line("}");
+            });
+        }
+    }
+
+    fn condition(check: &Check, target: &str) -> (String, String) {
+        let value = &check.value;
+        let (length, unit) = match check.kind {
+            ValueKind::Sequence => (format!("{}.len()", target), "items"),
+            _ => (format!("{}.chars().count()", target), "characters"),
+        };
+
+        match check.key.as_str() {
+            "len" => (format!("{} != {}", length, value), format!("must have exactly {} {}", value, unit)),
+            "max_len" => (format!("{} > {}", length, value), format!("must have at most {} {}", value, unit)),
+            "min_len" => (format!("{} < {}", length, value), format!("must have at least {} {}", value, unit)),
+            "min" => (format!("{} < {}", target, value), format!("must be at least {}", value)),
+            _ => (format!("{} > {}", target, value), format!("must be at most {}", value)),
+        }
+    }
+
+    /// Writes `impl` with a `validate()` whose checks `f` writes before the final `Ok(())`.
+    fn validate_impl(&self, name: &str, f: impl FnOnce()) {
+        self.writer.empty_line();
+        self.writer.line(format!("impl{0} {1}{0} {{", self.type_params(), name));
+        self.writer.indent();
+        self.writer.line(format!("pub fn validate(&self) -> Result<(), {}> {{", self.relative(&[], "ValidationError")));
+        self.writer.indent();
+        f();
+        self.writer.line("Ok(())");
+        self.writer.dedent();
+        self.writer.line("}");
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+
+    /// Writes the checks of a field, `target` being the field of `self` or, with `by_ref`,
+    /// a reference bound to it.
+    fn field_checks(&self, field: &Field, target: &str, by_ref: bool) {
+        let error = self.relative(&[], "ValidationError");
+
+        let (target, by_ref) = match field.optional {
+            true => {
+                let reference = if by_ref { "" } else { "&" };
+                self.writer.line(format!("if let Some(value) = {}{} {{", reference, target));
+                self.writer.indent();
+                ("value", true)
+            },
+            false => (target, by_ref),
+        };
+
+        for check in &field.checks {
+            let value = match (by_ref, check.access, check.kind) {
+                (true, "", ValueKind::Integer | ValueKind::Float) => format!("*{}", target),
+                (_, access, _) => format!("{}{}", target, access),
+            };
+
+            let (condition, message) = Self::condition(check, &value);
+            self.writer.line(format!("if {} {{", condition));
+            self.writer.indent();
+            self.writer.line(format!(
+                "return Err({} {{ field: \"{}\", message: \"{}\" }});",
+                error, field.key, message,
+            ));
+            self.writer.dedent();
+            self.writer.line("}");
+        }
+
+        if field.nested {
+            self.writer.line(format!("{}.validate()?;", target));
+        }
+
+        if field.optional {
+            self.writer.dedent();
+            self.writer.line("}");
+        }
+    }
+
+    fn validate(&self, name: &str, fields: &[Field]) {
+        if !self.validation {
+            return;
+        }
+
+        self.validate_impl(name, || {
+            for field in fields.iter().filter(|f| !f.checks.is_empty() || f.nested) {
+                self.field_checks(field, &format!("self.{}", field.name), false);
+            }
+        });
+    }
+}
+
+impl Transformer<'_> for RustTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        match literal {
+            Literal::String(ref str) => self.writer.text(format!("\"{}\"", str)),
+            Literal::Number(ref str) => self.writer.text(str.to_string()),
+        }
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        if let Some(rust_type) = self.rust_type("value", item_type, &Bindings::root()) {
+            self.writer.text(rust_type);
+        }
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        let args = params.iter()
+            .filter_map(|param| match param {
+                ModelParam::Generic(item_type) => self.rust_type("value", item_type, &Bindings::root()),
+                ModelParam::Metadata(_, _) => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !args.is_empty() {
+            self.writer.text(format!("<{}>", args.join(", ")));
+        }
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        for item in items {
+            self.visit_scope(item, false);
+        }
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        let name = id.as_name().unwrap_or_default();
+
+        let body = self.writer.capture(|| {
+            self.path.borrow_mut().push(name.to_string());
+            for item in items {
+                self.visit_scope(item, false);
+            }
+            self.path.borrow_mut().pop();
+        });
+
+        self.writer.block(|| {
+            self.writer.line(format!("pub mod {} {{", to_snake_case(name)));
+            self.writer.indent();
+            self.writer.extend(body);
+            self.writer.dedent();
+            self.writer.line("}");
+        });
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, _) => {
+                self.top_level(id, params, |name| self.declaration(name, def, &Bindings::root()));
+            },
+            ModelDefinition::Fragment(_, _, _) => {
+
+            }
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.record(name, items, &Bindings::root()));
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.tuple(name, items, params, &Bindings::root()));
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.enumeration(name, items, &Bindings::root()));
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref item_type) = item {
+            if let Some(field) = self.field(id.as_name().unwrap_or_default(), item_type, &Bindings::root()) {
+                self.writer.line(format!("pub {}: {},", field.name, field.rust_type));
+            }
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.writer.line(format!("{},", to_pascal_case(id.as_name().unwrap_or_default())));
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::StringRender;
+
+    fn render(code: &str, configure: impl FnOnce(RustTransformer<'_>) -> RustTransformer<'_>) -> String {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        configure(RustTransformer::new()).apply(&ast, &render).unwrap();
+        render.as_string(4)
+    }
+
+    #[test_case(
+        "model Item {\n    id: Int\n    createdAt: String?\n}\nscalar Int;\nscalar String;",
+        "#[derive(Debug, Clone, PartialEq)]\npub struct Item {\n    pub id: i64,\n    pub created_at: Option<String>,\n}";
+        "plain"
+    )]
+    #[test_case(
+        "package Shop {\n    model Order {\n        item: Item\n        total: Int\n    }\n}\nmodel Item(Int)\nscalar Int;",
+        "pub mod shop {\n    #[derive(Debug, Clone, PartialEq)]\n    pub struct Order {\n        pub item: super::Item,\n        pub total: i64,\n    }\n}\n\n\
+        #[derive(Debug, Clone, PartialEq)]\npub struct Item(pub i64);";
+        "packages"
+    )]
+    fn check(code: &str, expected: &str) {
+        assert_eq!(render(code, |t| t), expected)
+    }
+
+    #[test_case(
+        EnumTagging::External, None,
+        "#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]\npub struct Item {\n    \
+        #[serde(rename = \"createdAt\")]\n    #[serde(default, skip_serializing_if = \"Option::is_none\")]\n    pub created_at: Option<String>,\n}\n\n\
+        #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]\npub enum Payment {\n    \
+        Card {\n        number: String,\n    },\n    #[serde(rename = \"cash\")]\n    Cash,\n}";
+        "external"
+    )]
+    #[test_case(
+        EnumTagging::Internal("type".to_string()), Some(RenameRule::CamelCase),
+        "#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]\n#[serde(rename_all = \"camelCase\")]\npub struct Item {\n    \
+        #[serde(default, skip_serializing_if = \"Option::is_none\")]\n    pub created_at: Option<String>,\n}\n\n\
+        #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]\n#[serde(tag = \"type\")]\n\
+        #[serde(rename_all = \"camelCase\", rename_all_fields = \"camelCase\")]\npub enum Payment {\n    \
+        Card {\n        number: String,\n    },\n    Cash,\n}";
+        "internal"
+    )]
+    fn check_serde(tagging: EnumTagging, rule: Option<RenameRule>, expected: &str) {
+        let code = "model Item {\n    createdAt: String?\n}\n\
+            model Payment enum {\n    Card {\n        number: String\n    }\n    cash\n}\nscalar String;";
+
+        let result = render(code, |t| {
+            let t = t.with_serde().with_enum_tagging(tagging);
+            match rule {
+                Some(rule) => t.with_rename_all(rule),
+                None => t,
+            }
+        });
+        assert_eq!(result, expected)
+    }
+
+    #[test]
+    fn check_validation() {
+        let code = "model Account {\n    name: String[len=32]\n    password: Password\n    pin: Password[min_len=4]?\n    age: Int[min=18]?\n}\n\
+            model Password[min_len: Int = 12](String)\nscalar String;\nscalar Int;";
+
+        let result = render(code, |t| t.with_validation());
+        let account = result.split("\n\n").skip(3).take(2).collect::<Vec<_>>().join("\n\n");
+
+        assert!(result.starts_with("#[derive(Debug, Clone, PartialEq)]\npub struct ValidationError {\n"));
+        assert_eq!(
+            account,
+            "#[derive(Debug, Clone, PartialEq)]\npub struct Account {\n    pub name: String,\n    pub password: Password,\n    \
+            pub pin: Option<Password>,\n    pub age: Option<i64>,\n}\n\n\
+            impl Account {\n    pub fn validate(&self) -> Result<(), ValidationError> {\n        \
+            if self.name.chars().count() != 32 {\n            return Err(ValidationError { field: \"name\", message: \"must have exactly 32 characters\" });\n        }\n        \
+            if self.password.0.chars().count() < 12 {\n            return Err(ValidationError { field: \"password\", message: \"must have at least 12 characters\" });\n        }\n        \
+            if let Some(value) = &self.pin {\n            if value.0.chars().count() < 4 {\n                \
+            return Err(ValidationError { field: \"pin\", message: \"must have at least 4 characters\" });\n            }\n        }\n        \
+            if let Some(value) = &self.age {\n            if *value < 18 {\n                \
+            return Err(ValidationError { field: \"age\", message: \"must be at least 18\" });\n            }\n        }\n        \
+            Ok(())\n    }\n}"
+        );
+        assert!(result.ends_with(
+            "pub struct Password(pub String);\n\nimpl Password {\n    pub fn validate(&self) -> Result<(), ValidationError> {\n        \
+            if self.0.chars().count() < 12 {\n            return Err(ValidationError { field: \"0\", message: \"must have at least 12 characters\" });\n        }\n        \
+            Ok(())\n    }\n}"
+        ));
+    }
+
+    #[test]
+    fn check_validation_nested() {
+        let code = "model Order {\n    items: List<Line>\n    buyer: Buyer?\n    payment: Payment\n}\n\
+            model Buyer {\n    name: String[max_len=64]\n}\n\
+            model Payment enum {\n    Card {\n        number: String[len=16]\n        owner: Buyer\n    }\n    Cash(Double[min=1])\n    Split(Int, Int[max=10])\n    None\n}\n\
+            model List<T>(T)\nmodel Line {\n    total: Double\n}\nscalar String;\nscalar Int;\nscalar Double;";
+
+        let result = render(code, |t| t.with_validation());
+        let blocks = result.split("\n\n").collect::<Vec<_>>();
+        let block = |head: &str| blocks.iter().find(|b| b.starts_with(head)).copied().unwrap_or_default();
+
+        assert_eq!(
+            block("impl Order {"),
+            "impl Order {\n    pub fn validate(&self) -> Result<(), ValidationError> {\n        \
+            if let Some(value) = &self.buyer {\n            value.validate()?;\n        }\n        \
+            self.payment.validate()?;\n        Ok(())\n    }\n}"
+        );
+        assert_eq!(
+            block("impl Payment {"),
+            "impl Payment {\n    pub fn validate(&self) -> Result<(), ValidationError> {\n        match self {\n            \
+            Self::Card { number, owner, .. } => {\n                \
+            if number.chars().count() != 16 {\n                    \
+            return Err(ValidationError { field: \"number\", message: \"must have exactly 16 characters\" });\n                }\n                \
+            owner.validate()?;\n            },\n            \
+            Self::Cash(item0) => {\n                if *item0 < 1.0 {\n                    \
+            return Err(ValidationError { field: \"0\", message: \"must be at least 1.0\" });\n                }\n            },\n            \
+            Self::Split(_, item1) => {\n                if *item1 > 10 {\n                    \
+            return Err(ValidationError { field: \"1\", message: \"must be at most 10\" });\n                }\n            },\n            \
+            _ => {},\n        }\n        Ok(())\n    }\n}"
+        );
+        assert_eq!(
+            block("impl Line {"),
+            "impl Line {\n    pub fn validate(&self) -> Result<(), ValidationError> {\n        Ok(())\n    }\n}"
+        );
+    }
+
+    #[test_case("model A {\n    age: Int[len=3]\n}\nscalar Int;", "`len` cannot be checked on the type of `age`"; "length of a number")]
+    #[test_case("model A {\n    name: String[min=1]\n}\nscalar String;", "`min` cannot be checked on the type of `name`"; "bound of a string")]
+    #[test_case("model A {\n    b: B[max_len=3]\n}\nmodel B {\n}", "`max_len` cannot be checked on the type of `b`"; "length of a record")]
+    #[test_case("model Code[len: Int = 4](Int)\nscalar Int;", "`len` cannot be checked on the type of `0`"; "tuple default")]
+    fn check_validation_errors(code: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        match RustTransformer::new().with_validation().apply(&ast, &render) {
+            Err(Error::Transform(errors)) => assert!(errors.iter().any(|e| e.to_string().ends_with(expected)), "{:?}", errors),
+            result => panic!("{:?}", result),
+        }
+    }
+}
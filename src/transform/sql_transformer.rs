@@ -0,0 +1,658 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::to_snake_case;
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SqlDialect {
+    PostgreSql,
+    Sqlite,
+}
+
+enum ColumnKind {
+    Plain(String),
+    Enum(String, Vec<String>),
+    Reference(String),
+    Json,
+}
+
+struct Column {
+    name: String,
+    kind: ColumnKind,
+    nullable: bool,
+    is_key: bool,
+}
+
+struct Table {
+    /// Model name, qualified by its package when other packages declare it too.
+    model: String,
+    name: String,
+    columns: Vec<Column>,
+}
+
+/// Generates `CREATE TABLE` statements for records.
+///
+/// Fields marked with `[key=true]` form the primary key, fields typed with another
+/// record become foreign keys to that record's table.
+pub struct SqlTransformer<'input> {
+    dialect: SqlDialect,
+    index: RefCell<ModelIndex<'input>>,
+    scalars: HashMap<String, String>,
+
+    path: RefCell<Vec<String>>,
+    model: RefCell<String>,
+
+    /// Model, type name and values of plain enums.
+    enums: RefCell<Vec<(String, String, Vec<String>)>>,
+    tables: RefCell<Vec<Table>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl<'input> SqlTransformer<'input> {
+    pub fn new(dialect: SqlDialect) -> Self {
+        let scalars: &[(&str, &str)] = match dialect {
+            SqlDialect::PostgreSql => &[
+                ("String", "TEXT"),
+                ("Int", "BIGINT"),
+                ("Long", "BIGINT"),
+                ("Byte", "SMALLINT"),
+                ("Float", "REAL"),
+                ("Double", "DOUBLE PRECISION"),
+                ("Decimal", "NUMERIC"),
+                ("Bool", "BOOLEAN"),
+                ("Boolean", "BOOLEAN"),
+                ("Bytes", "BYTEA"),
+                ("Uuid", "UUID"),
+                ("Date", "DATE"),
+                ("DateTime", "TIMESTAMPTZ"),
+            ],
+            SqlDialect::Sqlite => &[
+                ("String", "TEXT"),
+                ("Int", "INTEGER"),
+                ("Long", "INTEGER"),
+                ("Byte", "INTEGER"),
+                ("Float", "REAL"),
+                ("Double", "REAL"),
+                ("Decimal", "NUMERIC"),
+                ("Bool", "INTEGER"),
+                ("Boolean", "INTEGER"),
+                ("Bytes", "BLOB"),
+                ("Uuid", "TEXT"),
+                ("Date", "TEXT"),
+                ("DateTime", "TEXT"),
+            ],
+        };
+
+        SqlTransformer {
+            dialect,
+            index: RefCell::new(ModelIndex::default()),
+            scalars: scalars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+
+            path: RefCell::new(Vec::new()),
+            model: RefCell::new(String::new()),
+
+            enums: RefCell::new(Vec::new()),
+            tables: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a column type, e.g. `("Money", "NUMERIC(12, 2)")`.
+    pub fn with_scalar(mut self, scalar: &str, column_type: &str) -> Self {
+        self.scalars.insert(scalar.to_string(), column_type.to_string());
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let tables = self.tables.take();
+        let enums = match self.dialect {
+            SqlDialect::PostgreSql => self.enums.take(),
+            SqlDialect::Sqlite => vec![],
+        };
+        self.check_names(&tables, &enums);
+
+        for (_, name, values) in enums {
+            let values = values.iter().map(|v| format!("'{}'", v)).collect::<Vec<_>>();
+            self.writer.block(|| {
+                self.writer.line(format!("CREATE TYPE {} AS ENUM ({});", Self::quote(&name), values.join(", ")));
+            });
+        }
+
+        let mut created = vec![];
+        let mut deferred = vec![];
+        for i in Self::order(&tables) {
+            created.push(tables[i].model.as_str());
+            self.writer.block(|| deferred.extend(self.create_table(&tables[i], &tables, &created)));
+        }
+        for constraint in deferred {
+            self.writer.block(|| self.writer.line(constraint));
+        }
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        self.error(TransformError::Unsupported(self.model.borrow().clone(), reason.to_string()));
+    }
+
+    /// Reports tables and types whose names clash, as PostgreSQL gives every table a type
+    /// of the same name.
+    fn check_names(&self, tables: &[Table], enums: &[(String, String, Vec<String>)]) {
+        let mut names: HashMap<&str, &str> = HashMap::new();
+        let types = enums.iter().map(|(model, name, _)| (model, name));
+        for (model, name) in types.chain(tables.iter().map(|table| (&table.model, &table.name))) {
+            if let Some(other) = names.insert(name, model) {
+                self.error(TransformError::Unsupported(model.clone(), format!("`{}` is also named `{}` in SQL", other, name)));
+            }
+        }
+    }
+
+    fn quote(name: &str) -> String {
+        const RESERVED: &[&str] = &[
+            "user", "order", "group", "table", "select", "from", "where", "key", "index",
+            "references", "default", "check", "primary", "foreign", "column", "constraint",
+        ];
+
+        match RESERVED.contains(&name) {
+            true => format!("\"{}\"", name),
+            false => name.to_string(),
+        }
+    }
+
+    /// Orders tables so that referenced tables are created first; cycles keep declaration order.
+    fn order(tables: &[Table]) -> Vec<usize> {
+        let mut order: Vec<usize> = vec![];
+
+        while order.len() < tables.len() {
+            let next = (0..tables.len())
+                .filter(|i| !order.contains(i))
+                .find(|&i| tables[i].columns.iter().all(|c| match &c.kind {
+                    ColumnKind::Reference(model) => model == &tables[i].model || tables.iter().enumerate()
+                        .all(|(j, t)| &t.model != model || order.contains(&j)),
+                    _ => true,
+                }))
+                .or_else(|| (0..tables.len()).find(|i| !order.contains(i)));
+
+            order.extend(next);
+        }
+
+        order
+    }
+
+    fn is_key(params: &[ModelParam]) -> bool {
+        params.iter().any(|param| matches!(param,
+            ModelParam::Metadata(id, Literal::String("true") | Literal::Number("1")) if id.as_name() == Some("key")))
+    }
+
+    fn length(params: &[ModelParam]) -> Option<String> {
+        params.iter().find_map(|param| match param {
+            ModelParam::Metadata(id, Literal::Number(len)) if id.as_name() == Some("len") => Some(len.to_string()),
+            _ => None,
+        })
+    }
+
+    /// Resolves the column of a field; returns its kind, nullability and key flag.
+    fn column(&self, item_type: &ItemType, bindings: &Bindings) -> Option<(ColumnKind, bool, bool)> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Optional(item_type) => {
+                self.column(item_type, bindings).map(|(kind, _, is_key)| (kind, true, is_key))
+            },
+            ItemType::Inline(_) => Some((ColumnKind::Json, false, false)),
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+                let is_key = Self::is_key(params);
+
+                if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+                    self.unsupported(&format!("generic type `{}` cannot be mapped to a column", name));
+                    return None;
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                let scope = entry.scope.borrow();
+                let Scope::Model(ref def) = **scope else { unreachable!() };
+
+                let kind = match (entry.kind, def) {
+                    (ModelKind::Scalar, _) => {
+                        let Some(column_type) = self.scalars.get(name) else {
+                            self.unsupported(&format!("scalar `{}` has no column mapping", name));
+                            return None;
+                        };
+
+                        match (self.dialect, column_type.as_str(), Self::length(params)) {
+                            (SqlDialect::PostgreSql, "TEXT", Some(len)) => ColumnKind::Plain(format!("VARCHAR({})", len)),
+                            _ => ColumnKind::Plain(column_type.clone()),
+                        }
+                    },
+                    (ModelKind::Record, _) => ColumnKind::Reference(index.flat_name(&entry.name, &entry.path)),
+                    (ModelKind::Tuple, ModelDefinition::Tuple(_, items, _)) => match items.as_slice() {
+                        [TupleItem::Item(inner)] => {
+                            let (kind, nullable, _) = self.column(inner, &Bindings::root())?;
+                            return Some((kind, nullable, is_key));
+                        },
+                        _ => ColumnKind::Json,
+                    },
+                    (ModelKind::Alias, ModelDefinition::Alias(_, _, target)) => {
+                        let (kind, nullable, _) = self.column(target, &Bindings::root())?;
+                        return Some((kind, nullable, is_key));
+                    },
+                    (ModelKind::Enum, ModelDefinition::Enum(_, items, _)) if entry.is_plain_enum() => {
+                        let values = items.iter().filter_map(|item| match item {
+                            EnumItem::Item(id) => id.as_name().map(String::from),
+                            _ => None,
+                        }).collect();
+                        ColumnKind::Enum(to_snake_case(&index.flat_name(&entry.name, &entry.path)), values)
+                    },
+                    (ModelKind::Fragment, _) => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                        return None;
+                    },
+                    _ => ColumnKind::Json,
+                };
+
+                Some((kind, false, is_key))
+            },
+        }
+    }
+
+    fn table(&self, name: &str, items: &[RecordItem]) {
+        let mut columns = vec![];
+
+        let path = self.path.borrow().clone();
+        let model = self.index.borrow().flat_name(name, &path);
+        let result = self.index.borrow().walk_record(items, &Bindings::root(), &path, &mut |id, item_type, bindings| {
+            if let Some((kind, nullable, is_key)) = self.column(item_type, bindings) {
+                let field = to_snake_case(id.as_name().unwrap_or_default());
+                let name = match kind {
+                    ColumnKind::Reference(_) => format!("{}_id", field),
+                    _ => field,
+                };
+                columns.push(Column { name, kind, nullable, is_key });
+            }
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        self.tables.borrow_mut().push(Table {
+            name: to_snake_case(&model),
+            model,
+            columns,
+        });
+    }
+
+    fn column_type(&self, column: &Column, tables: &[Table]) -> String {
+        match &column.kind {
+            ColumnKind::Plain(column_type) => column_type.clone(),
+            ColumnKind::Enum(name, _) => match self.dialect {
+                SqlDialect::PostgreSql => Self::quote(name),
+                SqlDialect::Sqlite => "TEXT".to_string(),
+            },
+            ColumnKind::Json => match self.dialect {
+                SqlDialect::PostgreSql => "JSONB".to_string(),
+                SqlDialect::Sqlite => "TEXT".to_string(),
+            },
+            ColumnKind::Reference(model) => {
+                match Self::primary_key(tables, model) {
+                    Some(key) => self.column_type(key, tables),
+                    None => String::new(),
+                }
+            },
+        }
+    }
+
+    fn primary_key<'t>(tables: &'t [Table], model: &str) -> Option<&'t Column> {
+        let table = tables.iter().find(|t| t.model == model)?;
+        match table.columns.iter().filter(|c| c.is_key).collect::<Vec<_>>().as_slice() {
+            [key] => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Writes the statement of a table created after the `created` ones. Returns the foreign keys
+    /// to tables created later, which PostgreSQL only accepts once they exist.
+    fn create_table(&self, table: &Table, tables: &[Table], created: &[&str]) -> Vec<String> {
+        let mut lines = vec![];
+        let mut constraints = vec![];
+        let mut deferred = vec![];
+
+        for column in &table.columns {
+            let mut line = format!("{} {}", Self::quote(&column.name), self.column_type(column, tables));
+            if !column.nullable {
+                line += " NOT NULL";
+            }
+
+            match &column.kind {
+                ColumnKind::Enum(_, values) if self.dialect == SqlDialect::Sqlite => {
+                    let values = values.iter().map(|v| format!("'{}'", v)).collect::<Vec<_>>();
+                    line += &format!(" CHECK ({} IN ({}))", Self::quote(&column.name), values.join(", "));
+                },
+                ColumnKind::Reference(model) => {
+                    let referenced = tables.iter().find(|t| &t.model == model);
+                    match (referenced, Self::primary_key(tables, model)) {
+                        (Some(referenced), Some(key)) => {
+                            let constraint = format!(
+                                "FOREIGN KEY ({}) REFERENCES {} ({})",
+                                Self::quote(&column.name), Self::quote(&referenced.name), Self::quote(&key.name),
+                            );
+                            match self.dialect == SqlDialect::PostgreSql && !created.contains(&model.as_str()) {
+                                true => deferred.push(format!("ALTER TABLE {} ADD {};", Self::quote(&table.name), constraint)),
+                                false => constraints.push(constraint),
+                            }
+                        },
+                        _ => self.error(TransformError::Unsupported(
+                            table.model.clone(),
+                            format!("`{}` needs a single key field to be referenced", model),
+                        )),
+                    }
+                },
+                _ => {},
+            }
+
+            lines.push(line);
+        }
+
+        let keys = table.columns.iter().filter(|c| c.is_key).map(|c| Self::quote(&c.name)).collect::<Vec<_>>();
+        if !keys.is_empty() {
+            constraints.insert(0, format!("PRIMARY KEY ({})", keys.join(", ")));
+        }
+        lines.extend(constraints);
+
+        self.writer.line(format!("CREATE TABLE {} (", Self::quote(&table.name)));
+        self.writer.indent();
+        let count = lines.len();
+        for (i, line) in lines.into_iter().enumerate() {
+            match i + 1 < count {
+                true => self.writer.line(line + ","),
+                false => self.writer.line(line),
+            }
+        }
+        self.writer.dedent();
+        self.writer.line(");");
+        deferred
+    }
+
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition]) -> Option<String> {
+        let name = id.as_name().unwrap_or_default().to_string();
+        *self.model.borrow_mut() = name.clone();
+
+        match generic_names(params).is_empty() {
+            true => Some(name),
+            false => {
+                self.unsupported("generic models cannot be mapped to tables");
+                None
+            },
+        }
+    }
+}
+
+impl Transformer<'_> for SqlTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(Self::quote(&to_snake_case(name)));
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        match literal {
+            Literal::String(ref str) => self.writer.text(format!("'{}'", str)),
+            Literal::Number(ref str) => self.writer.text(str.to_string()),
+        }
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        if let Some((kind, nullable, is_key)) = self.column(item_type, &Bindings::root()) {
+            let column = Column { name: String::new(), kind, nullable, is_key };
+            self.writer.text(self.column_type(&column, &self.tables.borrow()));
+        }
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        if params.iter().any(|p| matches!(p, ModelParam::Generic(_))) {
+            self.unsupported("generic types cannot be mapped to columns");
+        }
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        for item in items {
+            self.visit_scope(item, false);
+        }
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        for item in items {
+            self.visit_scope(item, false);
+        }
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Alias(_, _, _) => {
+
+            }
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        if let Some(name) = self.top_level(id, params) {
+            self.table(&name, items);
+        }
+    }
+
+    fn visit_tuple_model(&self, _id: &Id, _items: &[TupleItem], _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], _params: &[ModelParamDefinition]) {
+        if !items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            return;
+        }
+
+        let values = items.iter()
+            .filter_map(|item| match item {
+                EnumItem::Item(id) => id.as_name().map(String::from),
+                _ => None,
+            })
+            .collect();
+
+        let model = self.index.borrow().flat_name(id.as_name().unwrap_or_default(), &self.path.borrow());
+        let name = to_snake_case(&model);
+        self.enums.borrow_mut().push((model, name, values));
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref type_id) = item {
+            self.visit_id(id);
+            self.writer.render(TextToken::Space);
+            self.visit_item_type(type_id);
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.writer.text(format!("'{}'", id.as_name().unwrap_or_default()));
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::StringRender;
+
+    const CODE: &str = "model User {\n    id: Int[key=true]\n    email: String[len=64]\n}\n\
+        model Order {\n    id: Uuid[key=true]\n    owner: User\n    status: Status\n    note: String?\n}\n\
+        model Status enum {\n    Open\n    Closed\n}\n\
+        scalar Int;\nscalar String;\nscalar Uuid;";
+
+    #[test_case(
+        SqlDialect::PostgreSql,
+        "CREATE TYPE status AS ENUM ('Open', 'Closed');\n\n\
+        CREATE TABLE \"user\" (\n    id BIGINT NOT NULL,\n    email VARCHAR(64) NOT NULL,\n    PRIMARY KEY (id)\n);\n\n\
+        CREATE TABLE \"order\" (\n    id UUID NOT NULL,\n    owner_id BIGINT NOT NULL,\n    status status NOT NULL,\n    note TEXT,\n    PRIMARY KEY (id),\n    FOREIGN KEY (owner_id) REFERENCES \"user\" (id)\n);";
+        "postgresql"
+    )]
+    #[test_case(
+        SqlDialect::Sqlite,
+        "CREATE TABLE \"user\" (\n    id INTEGER NOT NULL,\n    email TEXT NOT NULL,\n    PRIMARY KEY (id)\n);\n\n\
+        CREATE TABLE \"order\" (\n    id TEXT NOT NULL,\n    owner_id INTEGER NOT NULL,\n    status TEXT NOT NULL CHECK (status IN ('Open', 'Closed')),\n    note TEXT,\n    PRIMARY KEY (id),\n    FOREIGN KEY (owner_id) REFERENCES \"user\" (id)\n);";
+        "sqlite"
+    )]
+    fn check(dialect: SqlDialect, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(CODE);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        let transformer = SqlTransformer::new(dialect);
+        transformer.apply(&ast, &render).unwrap();
+        let result = render.as_string(4);
+
+        assert_eq!(&result, expected)
+    }
+
+    fn transform(dialect: SqlDialect, code: &str) -> crate::Result<'static, String> {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = StringRender::new();
+        SqlTransformer::new(dialect).apply(&ast, &render)?;
+        Ok(render.as_string(4))
+    }
+
+    #[test_case(
+        SqlDialect::PostgreSql,
+        "model User {\n    id: Int[key=true]\n}\nmodel Order {\n    id: Int[key=true]\n    owner: User?\n}\nscalar Int;",
+        "CREATE TABLE \"user\" (\n    id BIGINT NOT NULL,\n    PRIMARY KEY (id)\n);\n\n\
+        CREATE TABLE \"order\" (\n    id BIGINT NOT NULL,\n    owner_id BIGINT,\n    PRIMARY KEY (id),\n    FOREIGN KEY (owner_id) REFERENCES \"user\" (id)\n);";
+        "nullable foreign key"
+    )]
+    #[test_case(
+        SqlDialect::PostgreSql,
+        "model Team {\n    id: Int[key=true]\n    lead: Member?\n}\nmodel Member {\n    id: Int[key=true]\n    team: Team\n}\nscalar Int;",
+        "CREATE TABLE team (\n    id BIGINT NOT NULL,\n    lead_id BIGINT,\n    PRIMARY KEY (id)\n);\n\n\
+        CREATE TABLE member (\n    id BIGINT NOT NULL,\n    team_id BIGINT NOT NULL,\n    PRIMARY KEY (id),\n    FOREIGN KEY (team_id) REFERENCES team (id)\n);\n\n\
+        ALTER TABLE team ADD FOREIGN KEY (lead_id) REFERENCES member (id);";
+        "reference cycle"
+    )]
+    #[test_case(
+        SqlDialect::Sqlite,
+        "model Team {\n    id: Int[key=true]\n    lead: Member?\n}\nmodel Member {\n    id: Int[key=true]\n    team: Team\n}\nscalar Int;",
+        "CREATE TABLE team (\n    id INTEGER NOT NULL,\n    lead_id INTEGER,\n    PRIMARY KEY (id),\n    FOREIGN KEY (lead_id) REFERENCES member (id)\n);\n\n\
+        CREATE TABLE member (\n    id INTEGER NOT NULL,\n    team_id INTEGER NOT NULL,\n    PRIMARY KEY (id),\n    FOREIGN KEY (team_id) REFERENCES team (id)\n);";
+        "reference cycle in sqlite"
+    )]
+    #[test_case(
+        SqlDialect::PostgreSql,
+        "package shop;\n\nscalar Int;\n\npackage a {\n    model Item {\n        id: Int[key=true]\n    }\n}\n\n\
+        package b {\n    model Item {\n        code: Int[key=true]\n    }\n\n    model Line {\n        item: Item\n    }\n}",
+        "CREATE TABLE shop_a_item (\n    id BIGINT NOT NULL,\n    PRIMARY KEY (id)\n);\n\n\
+        CREATE TABLE shop_b_item (\n    code BIGINT NOT NULL,\n    PRIMARY KEY (code)\n);\n\n\
+        CREATE TABLE line (\n    item_id BIGINT NOT NULL,\n    FOREIGN KEY (item_id) REFERENCES shop_b_item (code)\n);";
+        "models of one name in two packages"
+    )]
+    fn check_tables(dialect: SqlDialect, code: &str, expected: &str) {
+        assert_eq!(transform(dialect, code).unwrap(), expected);
+    }
+
+    #[test_case(
+        "model Pair {\n    a: Int[key=true]\n    b: Int[key=true]\n}\nmodel Use {\n    pair: Pair\n}\nscalar Int;",
+        "`Use`: `Pair` needs a single key field to be referenced";
+        "composite key referenced"
+    )]
+    #[test_case(
+        "model OrderStatus {\n    id: Int\n}\nmodel Order_Status enum {\n    Open\n}\nscalar Int;",
+        "`OrderStatus`: `Order_Status` is also named `order_status` in SQL";
+        "names clash"
+    )]
+    fn check_errors(code: &str, expected: &str) {
+        match transform(SqlDialect::PostgreSql, code) {
+            Err(Error::Transform(errors)) => assert_eq!(errors[0].to_string(), expected),
+            _ => panic!("{} is accepted", code),
+        }
+    }
+}
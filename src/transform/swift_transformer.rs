@@ -0,0 +1,662 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::{to_camel_case, to_pascal_case};
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+
+struct Field {
+    name: String,
+    key: String,
+    swift_type: String,
+}
+
+/// Generates `Codable` Swift structs and enums, one file per Mex package.
+///
+/// Inline types are declared as nested types of the model using them, and enums with payloads
+/// become enums with associated values. A Swift module has a single namespace, so models named
+/// like a model of another package are prefixed with their package path.
+pub struct SwiftTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    scalars: HashMap<String, (String, Option<String>)>,
+
+    path: RefCell<Vec<String>>,
+    models: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    imports: RefCell<BTreeSet<String>>,
+    pending: RefCell<Vec<Vec<TextToken>>>,
+    files: RefCell<Vec<Vec<TextToken>>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl Default for SwiftTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'input> SwiftTransformer<'input> {
+    pub fn new() -> Self {
+        let scalars = [
+            ("String", "String", None),
+            ("Int", "Int", None),
+            ("Long", "Int64", None),
+            ("Byte", "UInt8", None),
+            ("Float", "Float", None),
+            ("Double", "Double", None),
+            ("Decimal", "Decimal", Some("Foundation")),
+            ("Bool", "Bool", None),
+            ("Boolean", "Bool", None),
+            ("Bytes", "Data", Some("Foundation")),
+            ("Uuid", "UUID", Some("Foundation")),
+            ("Date", "Date", Some("Foundation")),
+            ("DateTime", "Date", Some("Foundation")),
+        ];
+
+        SwiftTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            scalars: scalars.iter()
+                .map(|(k, v, import)| (k.to_string(), (v.to_string(), import.map(String::from))))
+                .collect(),
+
+            path: RefCell::new(Vec::new()),
+            models: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            imports: RefCell::new(BTreeSet::new()),
+            pending: RefCell::new(Vec::new()),
+            files: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maps a Mex scalar to a Swift type and the module it needs, e.g. `("Url", "URL", Some("Foundation"))`.
+    pub fn with_scalar(mut self, scalar: &str, swift_type: &str, import: Option<&str>) -> Self {
+        self.scalars.insert(scalar.to_string(), (swift_type.to_string(), import.map(String::from)));
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.into_inner();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        self.files
+            .into_inner().into_iter().flatten()
+            .for_each(|t| render.render(t));
+
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn unsupported(&self, reason: &str) {
+        let model = self.models.borrow().first().cloned().unwrap_or_default();
+        self.error(TransformError::Unsupported(model, reason.to_string()));
+    }
+
+    fn file_name(path: &[String]) -> String {
+        match path.is_empty() {
+            true => "Models.swift".to_string(),
+            false => path.iter().map(|p| to_pascal_case(p)).collect::<Vec<_>>().join("/") + ".swift",
+        }
+    }
+
+    fn identifier(name: String) -> String {
+        const KEYWORDS: &[&str] = &[
+            "associatedtype", "class", "deinit", "enum", "extension", "func", "import", "init", "inout",
+            "internal", "let", "operator", "private", "protocol", "public", "static", "struct", "subscript",
+            "typealias", "var", "break", "case", "continue", "default", "defer", "do", "else", "fallthrough",
+            "for", "guard", "if", "in", "repeat", "return", "switch", "where", "while", "as", "catch",
+            "false", "is", "nil", "rethrows", "super", "self", "Self", "throw", "throws", "true", "try",
+        ];
+
+        match KEYWORDS.contains(&name.as_str()) {
+            true => format!("`{}`", name),
+            false => name,
+        }
+    }
+
+    /// Generic parameters of a top-level declaration; nested types see the parameters of their parent.
+    fn type_params(&self) -> String {
+        let generics = self.generics.borrow();
+        match generics.is_empty() || self.models.borrow().len() > 1 {
+            true => String::new(),
+            false => format!("<{}>", generics.iter().map(|g| format!("{}: Codable", g)).collect::<Vec<_>>().join(", ")),
+        }
+    }
+
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str)) {
+        let name = self.index.borrow().flat_name(id.as_name().unwrap_or_default(), &self.path.borrow());
+
+        *self.generics.borrow_mut() = generic_names(params);
+        self.models.borrow_mut().push(name.clone());
+        self.writer.block(|| f(&name));
+        self.models.borrow_mut().pop();
+        self.generics.borrow_mut().clear();
+    }
+
+    fn file(&self, items: &[RefScope]) {
+        let path = self.path.borrow().clone();
+        let outer_imports = self.imports.replace(BTreeSet::new());
+
+        let body = self.writer.capture(|| {
+            for item in items {
+                if let Scope::Package(_, _) = **item.borrow() {
+                    continue;
+                }
+                self.visit_scope(item, false);
+            }
+        });
+
+        let imports = self.imports.replace(outer_imports);
+
+        if !body.is_empty() {
+            let file = self.writer.capture(|| {
+                self.writer.render(TextToken::File(Self::file_name(&path)));
+                if !imports.is_empty() {
+                    for import in imports {
+                        self.writer.line(format!("import {}", import));
+                    }
+                    self.writer.empty_line();
+                }
+                self.writer.extend(body);
+            });
+            self.files.borrow_mut().push(file);
+        }
+
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                self.visit_scope(item, false);
+            }
+        }
+    }
+
+    /// Resolves the Swift type of a field, declaring nested types for inline models.
+    fn swift_type(&self, hint: &str, item_type: &ItemType, bindings: &Bindings) -> Option<String> {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                if self.generics.borrow().iter().any(|g| g == name) {
+                    return Some(name.to_string());
+                }
+
+                let index = self.index.borrow();
+                let Some(entry) = index.find(name, &self.path.borrow()) else {
+                    self.error(TransformError::UnknownType(name.to_string()));
+                    return None;
+                };
+
+                let args = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Generic(item_type) => Some(self.swift_type(hint, item_type, bindings)),
+                        ModelParam::Metadata(_, _) => None,
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                let swift_type = match entry.kind {
+                    ModelKind::Scalar => {
+                        let Some((swift_type, import)) = self.scalars.get(name) else {
+                            self.unsupported(&format!("scalar `{}` has no Swift mapping", name));
+                            return None;
+                        };
+
+                        if let Some(import) = import {
+                            self.imports.borrow_mut().insert(import.clone());
+                        }
+                        swift_type.clone()
+                    },
+                    ModelKind::Fragment => {
+                        self.unsupported(&format!("fragment `{}` can only be spread", name));
+                        return None;
+                    },
+                    _ => index.flat_name(&entry.name, &entry.path),
+                };
+
+                match args.is_empty() {
+                    true => Some(swift_type),
+                    false => Some(format!("{}<{}>", swift_type, args.join(", "))),
+                }
+            },
+            ItemType::Inline(def) => {
+                let name = to_pascal_case(hint);
+                let tokens = self.writer.capture(|| self.declaration(&name, def, bindings));
+                self.pending.borrow_mut().push(tokens);
+                Some(name)
+            },
+            ItemType::Optional(item_type) => {
+                let swift_type = self.swift_type(hint, item_type, bindings)?;
+                Some(format!("{}?", swift_type))
+            },
+        }
+    }
+
+    fn field(&self, name: &str, item_type: &ItemType, bindings: &Bindings) -> Option<Field> {
+        Some(Field {
+            name: to_camel_case(name),
+            key: name.to_string(),
+            swift_type: self.swift_type(name, item_type, bindings)?,
+        })
+    }
+
+    fn declaration(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) {
+        self.models.borrow_mut().push(name.to_string());
+
+        match def {
+            ModelDefinition::Record(_, items, _) => self.record(name, items, bindings),
+            ModelDefinition::Tuple(_, items, _) => self.tuple(name, items, bindings),
+            ModelDefinition::Enum(_, items, _) => self.enumeration(name, items, bindings),
+            ModelDefinition::Alias(_, _, item_type) => {
+                if let Some(swift_type) = self.swift_type(name, item_type, bindings) {
+                    self.writer.line(format!("typealias {}{} = {}", name, self.type_params(), swift_type));
+                }
+            },
+            ModelDefinition::Fragment(_, _, _) | ModelDefinition::Scalar(_) => {},
+        }
+
+        self.models.borrow_mut().pop();
+    }
+
+    /// Writes `head {`, the members written by `f`, the nested types it declared and the closing brace.
+    fn body(&self, head: String, f: impl FnOnce()) {
+        let outer = self.pending.take();
+
+        self.writer.line(format!("{} {{", head));
+        self.writer.indent();
+        let members = self.writer.capture(f);
+        let nested = self.pending.replace(outer);
+
+        let mut first = true;
+        for tokens in std::iter::once(members).chain(nested) {
+            if tokens.is_empty() {
+                continue;
+            }
+            if !first {
+                self.writer.empty_line();
+            }
+            self.writer.extend(tokens);
+            first = false;
+        }
+
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+
+    fn record_fields(&self, items: &[RecordItem], bindings: &Bindings) -> Vec<Field> {
+        let mut fields = vec![];
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            fields.extend(self.field(id.as_name().unwrap_or_default(), item_type, bindings));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        fields
+    }
+
+    fn tuple_fields(&self, items: &[TupleItem], bindings: &Bindings) -> Vec<Field> {
+        items.iter()
+            .enumerate()
+            .filter_map(|(i, item)| match item {
+                TupleItem::Item(item_type) => self.field(&format!("item{}", i + 1), item_type, bindings),
+                TupleItem::NamedItem(id, item_type) => self.field(id.as_name().unwrap_or_default(), item_type, bindings),
+            })
+            .collect()
+    }
+
+    fn record(&self, name: &str, items: &[RecordItem], bindings: &Bindings) {
+        let head = format!("struct {}{}: Codable", name, self.type_params());
+        self.body(head, || {
+            let fields = self.record_fields(items, bindings);
+            self.properties(&fields);
+        });
+    }
+
+    fn tuple(&self, name: &str, items: &[TupleItem], bindings: &Bindings) {
+        let head = format!("struct {}{}: Codable", name, self.type_params());
+
+        if let [TupleItem::Item(item_type)] = items {
+            self.body(head, || self.newtype(item_type, bindings));
+            return;
+        }
+
+        self.body(head, || {
+            let fields = self.tuple_fields(items, bindings);
+            self.properties(&fields);
+        });
+    }
+
+    fn properties(&self, fields: &[Field]) {
+        for field in fields {
+            self.writer.line(format!("let {}: {}", Self::identifier(field.name.clone()), field.swift_type));
+        }
+
+        if fields.iter().all(|f| f.name == f.key) {
+            return;
+        }
+
+        self.writer.empty_line();
+        self.writer.line("enum CodingKeys: String, CodingKey {");
+        self.writer.indent();
+        for field in fields {
+            match field.name == field.key {
+                true => self.writer.line(format!("case {}", Self::identifier(field.name.clone()))),
+                false => self.writer.line(format!("case {} = \"{}\"", Self::identifier(field.name.clone()), field.key)),
+            }
+        }
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+
+    /// A single-item tuple wraps its value and is encoded as the bare value.
+    fn newtype(&self, item_type: &ItemType, bindings: &Bindings) {
+        let Some(swift_type) = self.swift_type("value", item_type, bindings) else {
+            return;
+        };
+
+        self.writer.line(format!("let value: {}", swift_type));
+        self.writer.empty_line();
+        self.writer.line(format!("init(_ value: {}) {{", swift_type));
+        self.writer.indent();
+        self.writer.line("self.value = value");
+        self.writer.dedent();
+        self.writer.line("}");
+        self.writer.empty_line();
+        self.writer.line("init(from decoder: Decoder) throws {");
+        self.writer.indent();
+        self.writer.line(format!("value = try decoder.singleValueContainer().decode({}.self)", swift_type));
+        self.writer.dedent();
+        self.writer.line("}");
+        self.writer.empty_line();
+        self.writer.line("func encode(to encoder: Encoder) throws {");
+        self.writer.indent();
+        self.writer.line("var container = encoder.singleValueContainer()");
+        self.writer.line("try container.encode(value)");
+        self.writer.dedent();
+        self.writer.line("}");
+    }
+
+    fn enumeration(&self, name: &str, items: &[EnumItem], bindings: &Bindings) {
+        if items.iter().all(|item| matches!(item, EnumItem::Item(_))) {
+            self.body(format!("enum {}: String, Codable", name), || {
+                for item in items {
+                    if let EnumItem::Item(id) = item {
+                        let member = id.as_name().unwrap_or_default();
+                        let case = to_camel_case(member);
+                        match case == member {
+                            true => self.writer.line(format!("case {}", Self::identifier(case))),
+                            false => self.writer.line(format!("case {} = \"{}\"", Self::identifier(case), member)),
+                        }
+                    }
+                }
+            });
+            return;
+        }
+
+        let head = format!("enum {}{}: Codable", name, self.type_params());
+        self.body(head, || {
+            for item in items {
+                let (id, payload) = match item {
+                    EnumItem::Item(id) => (id, None),
+                    EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                        (id, Some(item_type))
+                    },
+                };
+
+                let variant = id.as_name().unwrap_or_default();
+                let values = match payload {
+                    Some(ItemType::Inline(ModelDefinition::Record(_, items, _))) => {
+                        self.record_fields(items, bindings).into_iter()
+                            .map(|f| format!("{}: {}", Self::identifier(f.name), f.swift_type))
+                            .collect()
+                    },
+                    Some(ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => {
+                        items.iter()
+                            .filter_map(|item| match item {
+                                TupleItem::Item(item_type) => self.swift_type(variant, item_type, bindings),
+                                TupleItem::NamedItem(id, item_type) => {
+                                    let field = self.field(id.as_name().unwrap_or_default(), item_type, bindings)?;
+                                    Some(format!("{}: {}", Self::identifier(field.name), field.swift_type))
+                                },
+                            })
+                            .collect()
+                    },
+                    Some(item_type) => self.swift_type(variant, item_type, bindings).into_iter().collect(),
+                    None => vec![],
+                };
+
+                let case = Self::identifier(to_camel_case(variant));
+                match values.is_empty() {
+                    true => self.writer.line(format!("case {}", case)),
+                    false => self.writer.line(format!("case {}({})", case, values.join(", "))),
+                }
+            }
+        });
+    }
+}
+
+impl Transformer<'_> for SwiftTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(Self::identifier(to_camel_case(name)));
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        match literal {
+            Literal::String(ref str) => self.writer.text(format!("\"{}\"", str)),
+            Literal::Number(ref str) => self.writer.text(str.to_string()),
+        }
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        if let Some(swift_type) = self.swift_type("value", item_type, &Bindings::root()) {
+            self.writer.text(swift_type);
+        }
+    }
+
+    fn visit_model_params(&self, params: &[ModelParam]) {
+        let args = params.iter()
+            .filter_map(|param| match param {
+                ModelParam::Generic(item_type) => self.swift_type("value", item_type, &Bindings::root()),
+                ModelParam::Metadata(_, _) => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !args.is_empty() {
+            self.writer.text(format!("<{}>", args.join(", ")));
+        }
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        self.file(items);
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        self.file(items);
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, _) => {
+                self.top_level(id, params, |name| self.declaration(name, def, &Bindings::root()));
+            },
+            ModelDefinition::Fragment(_, _, _) => {
+
+            }
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.record(name, items, &Bindings::root()));
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.tuple(name, items, &Bindings::root()));
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| self.enumeration(name, items, &Bindings::root()));
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref item_type) = item {
+            if let Some(field) = self.field(id.as_name().unwrap_or_default(), item_type, &Bindings::root()) {
+                self.writer.line(format!("let {}: {}", Self::identifier(field.name), field.swift_type));
+            }
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.writer.line(format!("case {}", Self::identifier(to_camel_case(id.as_name().unwrap_or_default()))));
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    #[test_case(
+        "package Shop;\n\nmodel Item {\n    id: Int\n    title: String?\n    created_at: DateTime\n}\nscalar Int;\nscalar String;\nscalar DateTime;",
+        "Shop.swift",
+        "import Foundation\n\n\
+        struct Item: Codable {\n    let id: Int\n    let title: String?\n    let createdAt: Date\n\n    \
+        enum CodingKeys: String, CodingKey {\n        case id\n        case title\n        case createdAt = \"created_at\"\n    }\n}\n";
+        "record"
+    )]
+    #[test_case(
+        "model Status enum {\n    Draft\n    Published\n}\nmodel Id = Int\nscalar Int;",
+        "Models.swift",
+        "enum Status: String, Codable {\n    case draft = \"Draft\"\n    case published = \"Published\"\n}\n\ntypealias Id = Int\n";
+        "plain enum and alias"
+    )]
+    #[test_case(
+        "model Page<T> {\n    items: T\n    meta: {\n        total: Int\n    }\n}\nscalar Int;",
+        "Models.swift",
+        "struct Page<T: Codable>: Codable {\n    let items: T\n    let meta: Meta\n\n    \
+        struct Meta: Codable {\n        let total: Int\n    }\n}\n";
+        "generic with nested type"
+    )]
+    #[test_case(
+        "model Password enum {\n    Open(String)\n    Card {\n        number: String\n        cvv: String\n    }\n    Empty\n}\nscalar String;",
+        "Models.swift",
+        "enum Password: Codable {\n    case open(String)\n    case card(number: String, cvv: String)\n    case empty\n}\n";
+        "associated values"
+    )]
+    #[test_case(
+        "model Email(String)\nscalar String;",
+        "Models.swift",
+        "struct Email: Codable {\n    let value: String\n\n    init(_ value: String) {\n        self.value = value\n    }\n\n    \
+        init(from decoder: Decoder) throws {\n        value = try decoder.singleValueContainer().decode(String.self)\n    }\n\n    \
+        func encode(to encoder: Encoder) throws {\n        var container = encoder.singleValueContainer()\n        try container.encode(value)\n    }\n}\n";
+        "newtype"
+    )]
+    fn check(code: &str, file: &str, expected: &str) {
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        SwiftTransformer::new().apply(&ast, &render).unwrap();
+        let result = render.as_files(4);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].0, file);
+        assert_eq!(&result[0].1, expected)
+    }
+
+    #[test]
+    fn check_packages() {
+        let code = "model Name {\n    first: String\n}\n\npackage Test {\n    model Name {\n        id: String\n    }\n\n    model Card {\n        name: Name\n    }\n}\n\nscalar String;";
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        SwiftTransformer::new().apply(&ast, &render).unwrap();
+        let result = render.as_files(4);
+
+        assert_eq!(result, vec![
+            ("Models.swift".to_string(), "struct Name: Codable {\n    let first: String\n}\n".to_string()),
+            ("Test.swift".to_string(), "struct TestName: Codable {\n    let id: String\n}\n\n\
+                struct Card: Codable {\n    let name: TestName\n}\n".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,593 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::ast::Error;
+use crate::transform::TransformError;
+use crate::transform::case::{to_camel_case, to_pascal_case, to_screaming_snake_case, to_snake_case};
+
+/// Data a template is rendered against.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    String(String),
+    List(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn object(fields: Vec<(&str, Value)>) -> Self {
+        Value::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(value) => *value,
+            Value::String(value) => !value.is_empty(),
+            Value::List(items) => !items.is_empty(),
+            Value::Object(fields) => !fields.is_empty(),
+        }
+    }
+
+    fn as_text(&self) -> Result<String, String> {
+        match self {
+            Value::Bool(value) => Ok(value.to_string()),
+            Value::String(value) => Ok(value.clone()),
+            Value::List(_) => Err("a list cannot be printed, use `join`".to_string()),
+            Value::Object(_) => Err("an object cannot be printed".to_string()),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    Path(Vec<String>),
+    Literal(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>),
+    Ne(Box<Expr>, Box<Expr>),
+    Filter(Box<Expr>, String, Option<String>),
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Print(Expr),
+    If(Expr, Vec<Node>, Vec<Node>),
+    For(String, Expr, Vec<Node>),
+    Include(String),
+}
+
+enum Segment {
+    Text(String),
+    Print(String),
+    Tag(String),
+}
+
+/// A parsed template.
+///
+/// The language is deliberately small:
+/// - `{{ expr }}` prints a value, `{# ... #}` is a comment;
+/// - `{% if expr %} ... {% else %} ... {% endif %}` and `{% for x in expr %} ... {% endfor %}`,
+///   where the loop also binds `loop.index` (from 1), `loop.first` and `loop.last`;
+/// - `{% include "name" %}` renders another template of the same set with the current variables;
+/// - expressions are dotted paths and string literals combined with `==`, `!=`, `not`, `and`, `or`
+///   and piped through the filters `snake`, `camel`, `pascal`, `screaming`, `upper`, `lower` and `join(", ")`.
+///
+/// A line holding nothing but a `{% %}` tag other than `include`, or a comment, is dropped entirely.
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TransformError> {
+        let segments = Self::segments(source).map_err(TransformError::Template)?;
+        let mut segments = segments.into_iter();
+
+        match Self::parse_nodes(&mut segments).map_err(TransformError::Template)? {
+            (nodes, None) => Ok(Template { nodes }),
+            (_, Some(tag)) => Err(TransformError::Template(format!("unexpected `{{% {} %}}`", tag))),
+        }
+    }
+
+    /// Expands a template over string variables.
+    pub fn expand(source: &str, vars: &HashMap<String, String>) -> Result<String, TransformError> {
+        let context = Value::Object(vars.iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect());
+        Template::parse(source)?.render(&context, &Templates::new())
+    }
+
+    pub fn render(&self, context: &Value, templates: &Templates) -> Result<String, TransformError> {
+        let mut out = String::new();
+        let mut scopes = vec![];
+        Self::render_nodes(&self.nodes, context, &mut scopes, templates, &mut out, 0)
+            .map_err(TransformError::Template)?;
+        Ok(out)
+    }
+
+    fn segments(source: &str) -> Result<Vec<Segment>, String> {
+        let mut segments = vec![];
+        let mut text = String::new();
+        let mut rest = source;
+        // Whether the current line already holds output from an expression or a kept tag.
+        let mut line_used = false;
+
+        while let Some(start) = ["{{", "{%", "{#"].iter().filter_map(|open| rest.find(open)).min() {
+            text.push_str(&rest[..start]);
+            if rest[..start].contains('\n') {
+                line_used = false;
+            }
+
+            let open = &rest[start..start + 2];
+            let close = match open {
+                "{{" => "}}",
+                "{%" => "%}",
+                _ => "#}",
+            };
+            let end = rest[start + 2..].find(close)
+                .map(|end| end + start + 2)
+                .ok_or_else(|| format!("`{}` is not closed", open))?;
+            let content = rest[start + 2..end].trim().to_string();
+            rest = &rest[end + 2..];
+
+            if open == "{{" {
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+                segments.push(Segment::Print(content));
+                line_used = true;
+                continue;
+            }
+
+            let line_start = text.rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let line_end = rest.find('\n');
+            let standalone = !line_used
+                && !content.starts_with("include")
+                && text[line_start..].trim().is_empty()
+                && rest[..line_end.unwrap_or(rest.len())].trim().is_empty();
+
+            if standalone {
+                text.truncate(line_start);
+                rest = &rest[line_end.map(|i| i + 1).unwrap_or(rest.len())..];
+            } else {
+                line_used = true;
+            }
+
+            segments.push(Segment::Text(std::mem::take(&mut text)));
+            if open == "{%" {
+                segments.push(Segment::Tag(content));
+            }
+        }
+
+        text.push_str(rest);
+        segments.push(Segment::Text(text));
+        segments.retain(|segment| !matches!(segment, Segment::Text(text) if text.is_empty()));
+        Ok(segments)
+    }
+
+    /// Parses nodes up to the first tag it cannot handle, which is returned to the caller.
+    fn parse_nodes(segments: &mut impl Iterator<Item = Segment>) -> Result<(Vec<Node>, Option<String>), String> {
+        let mut nodes = vec![];
+
+        while let Some(segment) = segments.next() {
+            match segment {
+                Segment::Text(text) => nodes.push(Node::Text(text)),
+                Segment::Print(expr) => nodes.push(Node::Print(Self::parse_expr(&expr)?)),
+                Segment::Tag(tag) => {
+                    let (keyword, args) = tag.split_once(char::is_whitespace).unwrap_or((&tag, ""));
+                    match keyword {
+                        "if" => {
+                            let condition = Self::parse_expr(args)?;
+                            let (then, end) = Self::parse_nodes(segments)?;
+                            let otherwise = match end.as_deref() {
+                                Some("endif") => vec![],
+                                Some("else") => match Self::parse_nodes(segments)? {
+                                    (otherwise, Some(end)) if end == "endif" => otherwise,
+                                    _ => return Err("`{% if %}` is not closed by `{% endif %}`".to_string()),
+                                },
+                                _ => return Err("`{% if %}` is not closed by `{% endif %}`".to_string()),
+                            };
+                            nodes.push(Node::If(condition, then, otherwise));
+                        },
+                        "for" => {
+                            let (var, expr) = args.split_once(" in ")
+                                .ok_or_else(|| format!("expected `for <name> in <expr>`, found `{}`", tag))?;
+                            let expr = Self::parse_expr(expr)?;
+                            match Self::parse_nodes(segments)? {
+                                (body, Some(end)) if end == "endfor" => nodes.push(Node::For(var.trim().to_string(), expr, body)),
+                                _ => return Err("`{% for %}` is not closed by `{% endfor %}`".to_string()),
+                            }
+                        },
+                        "include" => match Self::parse_expr(args)? {
+                            Expr::Literal(name) => nodes.push(Node::Include(name)),
+                            _ => return Err(format!("expected a template name, found `{}`", args)),
+                        },
+                        _ => return Ok((nodes, Some(tag))),
+                    }
+                },
+            }
+        }
+
+        Ok((nodes, None))
+    }
+
+    fn parse_expr(source: &str) -> Result<Expr, String> {
+        let tokens = Self::tokens(source)?;
+        let mut pos = 0;
+        let expr = Self::parse_or(&tokens, &mut pos)?;
+
+        match tokens.get(pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected `{}` in `{}`", token, source)),
+        }
+    }
+
+    fn tokens(source: &str) -> Result<Vec<String>, String> {
+        let mut tokens = vec![];
+        let chars = source.chars().collect::<Vec<_>>();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                c if c.is_whitespace() => i += 1,
+                '"' | '\'' => {
+                    let end = chars[i + 1..].iter().position(|&q| q == c)
+                        .ok_or_else(|| format!("unterminated string in `{}`", source))?;
+                    tokens.push(chars[i..i + end + 2].iter().collect());
+                    i += end + 2;
+                },
+                '=' | '!' if chars.get(i + 1) == Some(&'=') => {
+                    tokens.push(format!("{}=", c));
+                    i += 2;
+                },
+                '|' | '(' | ')' => {
+                    tokens.push(c.to_string());
+                    i += 1;
+                },
+                c if c.is_alphanumeric() || c == '_' || c == '.' => {
+                    let end = chars[i..].iter().position(|c| !(c.is_alphanumeric() || *c == '_' || *c == '.'))
+                        .map(|end| i + end)
+                        .unwrap_or(chars.len());
+                    tokens.push(chars[i..end].iter().collect());
+                    i = end;
+                },
+                c => return Err(format!("unexpected `{}` in `{}`", c, source)),
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    fn parse_or(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+        let mut expr = Self::parse_and(tokens, pos)?;
+        while tokens.get(*pos).is_some_and(|t| t == "or") {
+            *pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(Self::parse_and(tokens, pos)?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+        let mut expr = Self::parse_not(tokens, pos)?;
+        while tokens.get(*pos).is_some_and(|t| t == "and") {
+            *pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(Self::parse_not(tokens, pos)?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+        if tokens.get(*pos).is_some_and(|t| t == "not") {
+            *pos += 1;
+            return Ok(Expr::Not(Box::new(Self::parse_not(tokens, pos)?)));
+        }
+
+        let left = Self::parse_filtered(tokens, pos)?;
+        match tokens.get(*pos).map(String::as_str) {
+            Some("==") => {
+                *pos += 1;
+                Ok(Expr::Eq(Box::new(left), Box::new(Self::parse_filtered(tokens, pos)?)))
+            },
+            Some("!=") => {
+                *pos += 1;
+                Ok(Expr::Ne(Box::new(left), Box::new(Self::parse_filtered(tokens, pos)?)))
+            },
+            _ => Ok(left),
+        }
+    }
+
+    fn parse_filtered(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+        let mut expr = Self::parse_primary(tokens, pos)?;
+
+        while tokens.get(*pos).is_some_and(|t| t == "|") {
+            *pos += 1;
+            let name = tokens.get(*pos).cloned().ok_or("expected a filter name")?;
+            *pos += 1;
+
+            let arg = match tokens.get(*pos).map(String::as_str) {
+                Some("(") => {
+                    let Some(Expr::Literal(arg)) = Self::parse_primary(tokens, &mut (*pos + 1)).ok() else {
+                        return Err(format!("filter `{}` expects a string argument", name));
+                    };
+                    if tokens.get(*pos + 2).map(String::as_str) != Some(")") {
+                        return Err(format!("filter `{}` is missing `)`", name));
+                    }
+                    *pos += 3;
+                    Some(arg)
+                },
+                _ => None,
+            };
+
+            expr = Expr::Filter(Box::new(expr), name, arg);
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_primary(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+        let token = tokens.get(*pos).ok_or("expected an expression")?;
+        *pos += 1;
+
+        match token.chars().next() {
+            Some('"' | '\'') => Ok(Expr::Literal(token[1..token.len() - 1].to_string())),
+            Some(c) if c.is_alphanumeric() || c == '_' => Ok(Expr::Path(token.split('.').map(String::from).collect())),
+            _ => Err(format!("unexpected `{}`", token)),
+        }
+    }
+
+    fn eval(expr: &Expr, context: &Value, scopes: &[(String, Value)]) -> Result<Value, String> {
+        match expr {
+            Expr::Path(path) => {
+                let (head, tail) = path.split_first().ok_or("empty path")?;
+                let mut value = scopes.iter().rev()
+                    .find(|(name, _)| name == head)
+                    .map(|(_, value)| value)
+                    .or_else(|| context.get(head))
+                    .ok_or_else(|| format!("unknown variable `{}`", head))?;
+
+                // Missing keys are empty so that templates can test for optional data.
+                let empty = Value::String(String::new());
+                for key in tail {
+                    value = value.get(key).unwrap_or(&empty);
+                }
+                Ok(value.clone())
+            },
+            Expr::Literal(text) => Ok(Value::String(text.clone())),
+            Expr::Not(expr) => Ok(Value::Bool(!Self::eval(expr, context, scopes)?.is_truthy())),
+            Expr::And(left, right) => Ok(Value::Bool(
+                Self::eval(left, context, scopes)?.is_truthy() && Self::eval(right, context, scopes)?.is_truthy(),
+            )),
+            Expr::Or(left, right) => Ok(Value::Bool(
+                Self::eval(left, context, scopes)?.is_truthy() || Self::eval(right, context, scopes)?.is_truthy(),
+            )),
+            Expr::Eq(left, right) => Ok(Value::Bool(Self::eval(left, context, scopes)? == Self::eval(right, context, scopes)?)),
+            Expr::Ne(left, right) => Ok(Value::Bool(Self::eval(left, context, scopes)? != Self::eval(right, context, scopes)?)),
+            Expr::Filter(expr, name, arg) => {
+                let value = Self::eval(expr, context, scopes)?;
+
+                if name == "join" {
+                    let Value::List(items) = value else {
+                        return Err("`join` expects a list".to_string());
+                    };
+                    let items = items.iter().map(Value::as_text).collect::<Result<Vec<_>, _>>()?;
+                    return Ok(Value::String(items.join(arg.as_deref().unwrap_or(", "))));
+                }
+
+                let text = value.as_text()?;
+                let text = match name.as_str() {
+                    "snake" => to_snake_case(&text),
+                    "camel" => to_camel_case(&text),
+                    "pascal" => to_pascal_case(&text),
+                    "screaming" => to_screaming_snake_case(&text),
+                    "upper" => text.to_uppercase(),
+                    "lower" => text.to_lowercase(),
+                    _ => return Err(format!("unknown filter `{}`", name)),
+                };
+                Ok(Value::String(text))
+            },
+        }
+    }
+
+    fn render_nodes(
+        nodes: &[Node],
+        context: &Value,
+        scopes: &mut Vec<(String, Value)>,
+        templates: &Templates,
+        out: &mut String,
+        depth: usize,
+    ) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Print(expr) => out.push_str(&Self::eval(expr, context, scopes)?.as_text()?),
+                Node::If(condition, then, otherwise) => {
+                    let branch = match Self::eval(condition, context, scopes)?.is_truthy() {
+                        true => then,
+                        false => otherwise,
+                    };
+                    Self::render_nodes(branch, context, scopes, templates, out, depth)?;
+                },
+                Node::For(var, expr, body) => {
+                    let Value::List(items) = Self::eval(expr, context, scopes)? else {
+                        return Err(format!("`{}` cannot be iterated", var));
+                    };
+
+                    let count = items.len();
+                    for (i, item) in items.into_iter().enumerate() {
+                        let state = Value::object(vec![
+                            ("index", Value::String((i + 1).to_string())),
+                            ("first", Value::Bool(i == 0)),
+                            ("last", Value::Bool(i + 1 == count)),
+                        ]);
+
+                        scopes.push(("loop".to_string(), state));
+                        scopes.push((var.clone(), item));
+                        let result = Self::render_nodes(body, context, scopes, templates, out, depth);
+                        scopes.truncate(scopes.len() - 2);
+                        result?;
+                    }
+                },
+                Node::Include(name) => {
+                    if depth >= 32 {
+                        return Err(format!("includes of `{}` nest too deeply", name));
+                    }
+                    let template = templates.templates.get(name)
+                        .ok_or_else(|| format!("unknown template `{}`", name))?;
+                    Self::render_nodes(&template.nodes, context, scopes, templates, out, depth + 1)?;
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Named templates that can include each other.
+#[derive(Debug, Default)]
+pub struct Templates {
+    templates: HashMap<String, Template>,
+}
+
+impl Templates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Templates of the files in `dir`, each named after its file without the extension,
+    /// e.g. `model.ts.tmpl` is the `model` template.
+    pub fn load<P: AsRef<Path>>(dir: P) -> crate::Result<'static, Self> {
+        let mut paths = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        paths.sort();
+
+        let mut templates = Self::new();
+        for path in paths.iter().filter(|path| path.is_file()) {
+            let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            let name = file_name.split('.').next().unwrap_or_default();
+            if name.is_empty() {
+                continue;
+            }
+            if templates.contains(name) {
+                let error = format!("{}: another file defines the `{}` template", file_name, name);
+                return Err(Error::Transform(vec![TransformError::Template(error)]));
+            }
+
+            let source = std::fs::read_to_string(path)?;
+            templates.add(name, &source).map_err(|error| Error::Transform(vec![error]))?;
+        }
+        Ok(templates)
+    }
+
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), TransformError> {
+        let template = Template::parse(source).map_err(|error| Self::named(name, error))?;
+        self.templates.insert(name.to_string(), template);
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.templates.contains_key(name)
+    }
+
+    pub fn render(&self, name: &str, context: &Value) -> Result<String, TransformError> {
+        let template = self.templates.get(name)
+            .ok_or_else(|| TransformError::Template(format!("unknown template `{}`", name)))?;
+        template.render(context, self).map_err(|error| Self::named(name, error))
+    }
+
+    fn named(name: &str, error: TransformError) -> TransformError {
+        match error {
+            TransformError::Template(error) => TransformError::Template(format!("{}: {}", name, error)),
+            error => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("Hello, {{ name }}!", "Hello, order_item!"; "print")]
+    #[test_case("{{ name | pascal }} {{ name | screaming }}", "OrderItem ORDER_ITEM"; "filters")]
+    #[test_case("{{ tags | join(\" | \") }}", "a | b"; "join")]
+    #[test_case("{% for tag in tags %}{{ loop.index }}.{{ tag }}{% if not loop.last %}, {% endif %}{% endfor %}", "1.a, 2.b"; "for")]
+    #[test_case("{% if name == \"order_item\" and tags %}yes{% else %}no{% endif %}", "yes"; "if")]
+    #[test_case("{% if meta.missing %}yes{% else %}no{% endif %}", "no"; "missing key")]
+    #[test_case("items {\n    {% for tag in tags %}\n    {{ tag }}\n    {% endfor %}\n}{# done #}", "items {\n    a\n    b\n}"; "standalone tags")]
+    #[test_case("{% include \"item\" %}", "<order_item>"; "include")]
+    fn check(source: &str, expected: &str) {
+        let context = Value::object(vec![
+            ("name", "order_item".into()),
+            ("tags", Value::List(vec!["a".into(), "b".into()])),
+            ("meta", Value::object(vec![])),
+        ]);
+
+        let mut templates = Templates::new();
+        templates.add("item", "<{{ name }}>").unwrap();
+        templates.add("main", source).unwrap();
+
+        assert_eq!(templates.render("main", &context).unwrap(), expected)
+    }
+
+    #[test_case("{% if name %}", "main: `{% if %}` is not closed by `{% endif %}`"; "unclosed if")]
+    #[test_case("{{ name | reverse }}", "main: unknown filter `reverse`"; "unknown filter")]
+    #[test_case("{{ other }}", "main: unknown variable `other`"; "unknown variable")]
+    fn check_errors(source: &str, expected: &str) {
+        let context = Value::object(vec![("name", "order".into())]);
+
+        let mut templates = Templates::new();
+        let result = templates.add("main", source).and_then(|_| templates.render("main", &context));
+
+        assert_eq!(result, Err(TransformError::Template(expected.to_string())))
+    }
+
+    #[test_case("{{ name | pascal }}", Ok("OrderItem".to_string()); "expanded")]
+    #[test_case("{{ name", Err(TransformError::Template("`{{` is not closed".to_string())); "parse error")]
+    #[test_case("{{ other }}", Err(TransformError::Template("unknown variable `other`".to_string())); "render error")]
+    fn check_expand(source: &str, expected: Result<String, TransformError>) {
+        let vars = HashMap::from([("name".to_string(), "order_item".to_string())]);
+        assert_eq!(Template::expand(source, &vars), expected)
+    }
+
+    #[test]
+    fn check_load() {
+        let dir = std::env::temp_dir().join(format!("mex-templates-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("model.ts.tmpl"), "<{% include \"name\" %}>").unwrap();
+        std::fs::write(dir.join("name.tmpl"), "{{ name }}").unwrap();
+        std::fs::write(dir.join(".hidden"), "{{").unwrap();
+
+        let loaded = Templates::load(&dir).map(|templates| templates.render("model", &Value::object(vec![("name", "order".into())])));
+        std::fs::write(dir.join("name.txt"), "").unwrap();
+        let clash = Templates::load(&dir).err().map(|error| format!("{:?}", error));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.unwrap(), Ok("<order>".to_string()));
+        assert_eq!(clash, Some("Transform([Template(\"name.txt: another file defines the `name` template\")])".to_string()));
+    }
+}
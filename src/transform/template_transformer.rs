@@ -0,0 +1,571 @@
+use std::cell::RefCell;
+use lalrpop_util::ErrorRecovery;
+use crate::ast::*;
+use crate::lexer::{LexicalError, Token};
+use crate::transform::{Target, TextToken, TokenWriter, TransformError, Transformer};
+use crate::transform::case::to_pascal_case;
+use crate::transform::model_index::{generic_names, model_params_def, Bindings, ModelIndex, ModelKind};
+use crate::transform::template::{Template, Templates, Value};
+
+/// Renders user supplied templates over a lowered form of the models.
+///
+/// Every package (the global one included) with models is rendered with the `package` template,
+/// or with the `model` template once per model when there is no `package` template. Templates see:
+/// - `package`: `name`, `path` (list) and `models`;
+/// - `model`: `name`, `kind` (`record`, `fragment`, `tuple`, `enum` or `alias`), `generics`,
+///   `metadata` (`name`, `type`, `default`), `fields`, `variants` and, for aliases, `target`;
+/// - `field`: `name`, `type`, `type_kind` (a model kind, `scalar` or `generic`), `optional` and `metadata`;
+/// - `variant`: `name`, `kind` (`unit`, `record`, `tuple` or `enum`) and `fields`.
+///
+/// Spreads are expanded into fields, and inline types are lowered to models named after their parent and field.
+pub struct TemplateTransformer<'input> {
+    index: RefCell<ModelIndex<'input>>,
+    templates: Templates,
+    file_name: Option<String>,
+
+    path: RefCell<Vec<String>>,
+    models: RefCell<Vec<String>>,
+    generics: RefCell<Vec<String>>,
+
+    lowered: RefCell<Vec<Value>>,
+    pending: RefCell<Vec<Value>>,
+    packages: RefCell<Vec<Value>>,
+    writer: TokenWriter,
+    errors: RefCell<Vec<TransformError>>,
+}
+
+impl<'input> TemplateTransformer<'input> {
+    pub fn new(templates: Templates) -> Self {
+        TemplateTransformer {
+            index: RefCell::new(ModelIndex::default()),
+            templates,
+            file_name: None,
+
+            path: RefCell::new(Vec::new()),
+            models: RefCell::new(Vec::new()),
+            generics: RefCell::new(Vec::new()),
+
+            lowered: RefCell::new(Vec::new()),
+            pending: RefCell::new(Vec::new()),
+            packages: RefCell::new(Vec::new()),
+            writer: TokenWriter::new(),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Template of the file each package is written to, e.g. `{{ package.path | join("/") }}/models.ts`.
+    pub fn with_file_name(mut self, file_name: &str) -> Self {
+        self.file_name = Some(file_name.to_string());
+        self
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, scope: &RefScope<'input>, render: &R) -> crate::Result<'static, ()> {
+        *self.index.borrow_mut() = ModelIndex::new(scope);
+        self.visit_scope(scope, true);
+
+        let errors = self.errors.take();
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+
+        if let Err(error) = self.render_packages() {
+            return Err(vec![error].into());
+        }
+
+        self.writer.apply(render);
+        Ok(())
+    }
+
+    fn error(&self, error: TransformError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn render_packages(&self) -> Result<(), TransformError> {
+        let file_name = self.file_name.as_deref().map(Template::parse).transpose()?;
+
+        for package in self.packages.take() {
+            let context = Value::object(vec![("package", package.clone())]);
+
+            let text = match self.templates.contains("package") {
+                true => self.templates.render("package", &context)?,
+                false => {
+                    let Some(Value::List(models)) = package.get("models") else { continue };
+                    models.iter()
+                        .map(|model| {
+                            let context = Value::object(vec![("package", package.clone()), ("model", model.clone())]);
+                            self.templates.render("model", &context)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?
+                        .join("\n")
+                },
+            };
+
+            let tokens = self.writer.capture(|| {
+                for line in text.trim_end_matches('\n').split('\n') {
+                    match line.is_empty() {
+                        true => self.writer.empty_line(),
+                        false => self.writer.line(line),
+                    }
+                }
+            });
+
+            match &file_name {
+                Some(file_name) => {
+                    // The global package has an empty path, names stay relative to the output directory.
+                    let name = file_name.render(&context, &self.templates)?;
+                    self.writer.render(TextToken::File(name.trim_start_matches('/').to_string()));
+                    self.writer.extend(tokens);
+                },
+                None => self.writer.block(|| self.writer.extend(tokens)),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn package(&self, items: &[RefScope]) {
+        let outer = self.lowered.replace(Vec::new());
+
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                continue;
+            }
+            self.visit_scope(item, false);
+        }
+
+        let models = self.lowered.replace(outer);
+        if !models.is_empty() {
+            let path = self.path.borrow();
+            self.packages.borrow_mut().push(Value::object(vec![
+                ("name", path.last().cloned().unwrap_or_default().into()),
+                ("path", Value::List(path.iter().map(|p| p.clone().into()).collect())),
+                ("models", Value::List(models)),
+            ]));
+        }
+
+        for item in items {
+            if let Scope::Package(_, _) = **item.borrow() {
+                self.visit_scope(item, false);
+            }
+        }
+    }
+
+    /// Lowers a top-level model followed by the models lowered from its inline types.
+    fn top_level(&self, id: &Id, params: &[ModelParamDefinition], f: impl FnOnce(&str) -> Value) {
+        let name = id.as_name().unwrap_or_default();
+
+        *self.generics.borrow_mut() = generic_names(params);
+        let model = f(name);
+        self.generics.borrow_mut().clear();
+
+        self.lowered.borrow_mut().push(model);
+        let pending = self.pending.take();
+        self.lowered.borrow_mut().extend(pending);
+    }
+
+    fn model(&self, name: &str, kind: &str, params: &[ModelParamDefinition], mut extra: Vec<(&str, Value)>) -> Value {
+        let metadata = params.iter()
+            .filter_map(|param| match param {
+                ModelParamDefinition::Metadata { id, type_id, def_value } => Some(Value::object(vec![
+                    ("name", id.as_name().unwrap_or_default().into()),
+                    ("type", self.type_text("", type_id, &Bindings::root()).0.into()),
+                    ("default", def_value.as_ref().map(Self::literal).unwrap_or_default().into()),
+                ])),
+                _ => None,
+            })
+            .collect();
+
+        let mut fields = vec![
+            ("name", name.into()),
+            ("kind", kind.into()),
+            ("generics", Value::List(self.generics.borrow().iter().map(|g| g.clone().into()).collect())),
+            ("metadata", Value::List(metadata)),
+        ];
+        for key in ["fields", "variants"] {
+            if !extra.iter().any(|(k, _)| *k == key) {
+                extra.push((key, Value::List(vec![])));
+            }
+        }
+        fields.extend(extra);
+        Value::object(fields)
+    }
+
+    fn literal(literal: &Literal) -> String {
+        match literal {
+            Literal::String(value) | Literal::Number(value) => value.to_string(),
+        }
+    }
+
+    /// Returns the Mex spelling of a type and its kind, lowering inline types to models.
+    fn type_text(&self, hint: &str, item_type: &ItemType, bindings: &Bindings) -> (String, String) {
+        let (item_type, bindings) = bindings.resolve(item_type);
+
+        match item_type {
+            ItemType::Model(id, params) => {
+                let name = id.as_name().unwrap_or_default();
+
+                let kind = match self.generics.borrow().iter().any(|g| g == name) {
+                    true => "generic",
+                    false => {
+                        let index = self.index.borrow();
+                        match index.find(name, &self.path.borrow()).map(|entry| entry.kind) {
+                            Some(ModelKind::Scalar) => "scalar",
+                            Some(ModelKind::Record) => "record",
+                            Some(ModelKind::Fragment) => "fragment",
+                            Some(ModelKind::Tuple) => "tuple",
+                            Some(ModelKind::Enum) => "enum",
+                            Some(ModelKind::Alias) => "alias",
+                            None => {
+                                self.error(TransformError::UnknownType(name.to_string()));
+                                ""
+                            },
+                        }
+                    },
+                };
+
+                let args = params.iter()
+                    .filter_map(|param| match param {
+                        ModelParam::Generic(item_type) => Some(self.type_text(hint, item_type, bindings).0),
+                        ModelParam::Metadata(_, _) => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                match args.is_empty() {
+                    true => (name.to_string(), kind.to_string()),
+                    false => (format!("{}<{}>", name, args.join(", ")), kind.to_string()),
+                }
+            },
+            ItemType::Inline(def) => {
+                let parent = self.models.borrow().last().cloned().unwrap_or_default();
+                let name = parent + &to_pascal_case(hint);
+
+                let model = self.declaration(&name, def, bindings);
+                self.pending.borrow_mut().push(model);
+
+                let kind = match def {
+                    ModelDefinition::Tuple(_, _, _) => "tuple",
+                    ModelDefinition::Enum(_, _, _) => "enum",
+                    _ => "record",
+                };
+                let generics = self.generics.borrow();
+                match generics.is_empty() {
+                    true => (name, kind.to_string()),
+                    false => (format!("{}<{}>", name, generics.join(", ")), kind.to_string()),
+                }
+            },
+            ItemType::Optional(item_type) => {
+                let (text, kind) = self.type_text(hint, item_type, bindings);
+                (format!("{}?", text), kind)
+            },
+        }
+    }
+
+    fn field(&self, name: &str, item_type: &ItemType, bindings: &Bindings) -> Value {
+        let (resolved, _) = bindings.resolve(item_type);
+        let (optional, inner) = match resolved {
+            ItemType::Optional(inner) => (true, inner.as_ref()),
+            item_type => (false, item_type),
+        };
+
+        let metadata = match inner {
+            ItemType::Model(_, params) => params.iter()
+                .filter_map(|param| match param {
+                    ModelParam::Metadata(id, value) => {
+                        Some((id.as_name().unwrap_or_default().to_string(), Self::literal(value).into()))
+                    },
+                    ModelParam::Generic(_) => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        let (type_text, type_kind) = self.type_text(name, item_type, bindings);
+        Value::object(vec![
+            ("name", name.into()),
+            ("type", type_text.trim_end_matches('?').into()),
+            ("type_kind", type_kind.into()),
+            ("optional", optional.into()),
+            ("metadata", Value::Object(metadata)),
+        ])
+    }
+
+    fn record_fields(&self, items: &[RecordItem], bindings: &Bindings) -> Vec<Value> {
+        let mut fields = vec![];
+
+        let path = self.path.borrow().clone();
+        let result = self.index.borrow().walk_record(items, bindings, &path, &mut |id, item_type, bindings| {
+            fields.push(self.field(id.as_name().unwrap_or_default(), item_type, bindings));
+        });
+
+        if let Err(error) = result {
+            self.error(error);
+        }
+
+        fields
+    }
+
+    fn tuple_fields(&self, items: &[TupleItem], bindings: &Bindings) -> Vec<Value> {
+        items.iter()
+            .enumerate()
+            .map(|(i, item)| match item {
+                TupleItem::Item(item_type) => self.field(&i.to_string(), item_type, bindings),
+                TupleItem::NamedItem(id, item_type) => self.field(id.as_name().unwrap_or_default(), item_type, bindings),
+            })
+            .collect()
+    }
+
+    fn variants(&self, items: &[EnumItem], bindings: &Bindings) -> Vec<Value> {
+        items.iter()
+            .map(|item| {
+                let (id, kind, fields) = match item {
+                    EnumItem::Item(id) => (id, "unit", vec![]),
+                    EnumItem::Record(id, ItemType::Inline(ModelDefinition::Record(_, items, _))) => {
+                        (id, "record", self.record_fields(items, bindings))
+                    },
+                    EnumItem::Tuple(id, ItemType::Inline(ModelDefinition::Tuple(_, items, _))) => {
+                        (id, "tuple", self.tuple_fields(items, bindings))
+                    },
+                    EnumItem::Record(id, item_type) | EnumItem::Tuple(id, item_type) | EnumItem::Enum(id, item_type) => {
+                        let name = id.as_name().unwrap_or_default();
+                        (id, "enum", vec![self.field(name, item_type, bindings)])
+                    },
+                };
+
+                Value::object(vec![
+                    ("name", id.as_name().unwrap_or_default().into()),
+                    ("kind", kind.into()),
+                    ("fields", Value::List(fields)),
+                ])
+            })
+            .collect()
+    }
+
+    fn declaration(&self, name: &str, def: &ModelDefinition, bindings: &Bindings) -> Value {
+        self.models.borrow_mut().push(name.to_string());
+
+        let params = model_params_def(def);
+        let model = match def {
+            ModelDefinition::Record(_, items, _) => {
+                self.model(name, "record", params, vec![("fields", Value::List(self.record_fields(items, bindings)))])
+            },
+            ModelDefinition::Fragment(_, items, _) => {
+                self.model(name, "fragment", params, vec![("fields", Value::List(self.record_fields(items, bindings)))])
+            },
+            ModelDefinition::Tuple(_, items, _) => {
+                self.model(name, "tuple", params, vec![("fields", Value::List(self.tuple_fields(items, bindings)))])
+            },
+            ModelDefinition::Enum(_, items, _) => {
+                self.model(name, "enum", params, vec![("variants", Value::List(self.variants(items, bindings)))])
+            },
+            ModelDefinition::Alias(_, _, item_type) => {
+                let (target, _) = self.type_text(name, item_type, bindings);
+                self.model(name, "alias", params, vec![("target", target.into())])
+            },
+            ModelDefinition::Scalar(_) => self.model(name, "scalar", params, vec![]),
+        };
+
+        self.models.borrow_mut().pop();
+        model
+    }
+}
+
+impl Transformer<'_> for TemplateTransformer<'_> {
+
+    fn visit_id(&self, id: &Id) {
+        if let Some(name) = id.as_name() {
+            self.writer.text(name);
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) {
+        self.writer.text(Self::literal(literal));
+    }
+
+    fn visit_item_type(&self, item_type: &ItemType) {
+        self.writer.text(self.type_text("value", item_type, &Bindings::root()).0);
+    }
+
+    fn visit_model_params(&self, _params: &[ModelParam]) {
+
+    }
+
+    fn visit_model_params_def(&self, _params: &[ModelParamDefinition]) {
+
+    }
+
+    fn visit_scope(&self, item: &RefScope, is_root: bool) {
+        let item = item.borrow();
+
+        match **item {
+            Scope::Global(ref items) => {
+                self.visit_global(items);
+            }
+            Scope::Package(ref id, ref items) => {
+                self.visit_package(id, items, is_root);
+            }
+            Scope::Model(ref def) => {
+                self.visit_model(def);
+            }
+            Scope::Error(ref error) => {
+                self.visit_error(error);
+            }
+        }
+    }
+
+    fn visit_global(&self, items: &[RefScope]) {
+        self.package(items);
+    }
+
+    fn visit_package(&self, id: &Id, items: &[RefScope], _is_root: bool) {
+        self.path.borrow_mut().push(id.as_name().unwrap_or_default().to_string());
+        self.package(items);
+        self.path.borrow_mut().pop();
+    }
+
+    fn visit_model(&self, def: &ModelDefinition) {
+        self.visit_model_params_def(model_params_def(def));
+
+        match def {
+            ModelDefinition::Scalar(ref id) => {
+                self.visit_scalar(id);
+            }
+            ModelDefinition::Record(ref id, ref items, ref params) => {
+                self.visit_record_model(id, items, params);
+            },
+            ModelDefinition::Enum(ref id, ref items, ref params) => {
+                self.visit_enum_model(id, items, params);
+            },
+            ModelDefinition::Tuple(ref id, ref items, ref params) => {
+                self.visit_tuple_model(id, items, params);
+            },
+            ModelDefinition::Alias(ref id, ref params, _) | ModelDefinition::Fragment(ref id, _, ref params) => {
+                self.top_level(id, params, |name| self.declaration(name, def, &Bindings::root()));
+            },
+        }
+    }
+
+    fn visit_header_model(&self, keyword: &str) {
+        self.writer.text(keyword);
+        self.writer.render(TextToken::Space);
+    }
+
+    fn visit_record_model(&self, id: &Id, items: &[RecordItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| {
+            self.models.borrow_mut().push(name.to_string());
+            let fields = self.record_fields(items, &Bindings::root());
+            self.models.borrow_mut().pop();
+            self.model(name, "record", params, vec![("fields", Value::List(fields))])
+        });
+    }
+
+    fn visit_tuple_model(&self, id: &Id, items: &[TupleItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| {
+            self.models.borrow_mut().push(name.to_string());
+            let fields = self.tuple_fields(items, &Bindings::root());
+            self.models.borrow_mut().pop();
+            self.model(name, "tuple", params, vec![("fields", Value::List(fields))])
+        });
+    }
+
+    fn visit_enum_model(&self, id: &Id, items: &[EnumItem], params: &[ModelParamDefinition]) {
+        self.top_level(id, params, |name| {
+            self.models.borrow_mut().push(name.to_string());
+            let variants = self.variants(items, &Bindings::root());
+            self.models.borrow_mut().pop();
+            self.model(name, "enum", params, vec![("variants", Value::List(variants))])
+        });
+    }
+
+    fn visit_model_item(&self, item: &RecordItem) {
+        if let RecordItem::Item(ref id, ref item_type) = item {
+            self.writer.text(self.type_text(id.as_name().unwrap_or_default(), item_type, &Bindings::root()).0);
+        }
+    }
+
+    fn visit_enum_item(&self, item: &EnumItem) {
+        if let EnumItem::Item(ref id) = item {
+            self.visit_id(id);
+        }
+    }
+
+    fn visit_scalar(&self, _id: &Id) {
+
+    }
+
+    fn visit_error(&self, error: &ErrorRecovery<usize, Token, LexicalError>) {
+        self.error(TransformError::Parsing(format!("{:?}", error.error)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compiler;
+    use crate::transform::FilesRender;
+
+    const PACKAGE: &str = "// {{ package.path | join(\".\") }}\n{% for model in package.models %}\n\n{% include \"model\" %}{% endfor %}";
+    const MODEL: &str = "{% if model.kind == \"enum\" %}\n\
+        export type {{ model.name }} = {% for variant in model.variants %}\"{{ variant.name }}\"{% if not loop.last %} | {% endif %}{% endfor %};\n\
+        {% else %}\n\
+        export interface {{ model.name }} {\n\
+        {% for field in model.fields %}\n    {% include \"field\" %}\n{% endfor %}\n\
+        }\n\
+        {% endif %}";
+    const FIELD: &str = "{{ field.name | camel }}{% if field.optional %}?{% endif %}: {{ field.type }};{% if field.metadata.len %} // max {{ field.metadata.len }}{% endif %}";
+
+    #[test]
+    fn check() {
+        let code = "package Shop {\n    fragment Entity {\n        id: Int\n    }\n\
+            model Order {\n        ... Entity\n        created_at: String[len=32]\n        meta: {\n            note: String?\n        }\n        status: Status\n    }\n\
+            model Status enum {\n        Open\n        Closed\n    }\n}\nscalar Int;\nscalar String;";
+
+        let mut templates = Templates::new();
+        templates.add("package", PACKAGE).unwrap();
+        templates.add("model", MODEL).unwrap();
+        templates.add("field", FIELD).unwrap();
+
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        TemplateTransformer::new(templates)
+            .with_file_name("{{ package.path | join(\"/\") | lower }}/models.ts")
+            .apply(&ast, &render)
+            .unwrap();
+        let files = render.as_files(4);
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "shop/models.ts");
+        assert_eq!(
+            files[0].1,
+            "// Shop\n\n\
+            export interface Entity {\n    id: Int;\n}\n\n\
+            export interface Order {\n    id: Int;\n    createdAt: String; // max 32\n    meta: OrderMeta;\n    status: Status;\n}\n\n\
+            export interface OrderMeta {\n    note?: String;\n}\n\n\
+            export type Status = \"Open\" | \"Closed\";\n"
+        );
+    }
+
+    #[test]
+    fn check_global_file() {
+        let code = "model Item {\n    id: Int\n}\nscalar Int;";
+
+        let mut templates = Templates::new();
+        templates.add("model", "{{ model.name }}").unwrap();
+
+        let global = Scope::Global(vec![]).into();
+        let source = Source::from_str(code);
+        let compiler = Compiler::new(&source, &global).unwrap();
+        let ast = compiler.make_ast().unwrap();
+
+        let render = FilesRender::new();
+        TemplateTransformer::new(templates)
+            .with_file_name("{{ package.path | join(\"/\") }}/models.ts")
+            .apply(&ast, &render)
+            .unwrap();
+
+        assert_eq!(render.as_files(4), vec![("models.ts".to_string(), "Item\n".to_string())]);
+    }
+}
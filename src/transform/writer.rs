@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use crate::transform::{Target, TextToken};
+
+/// Token buffer the code generators write into before handing the tokens to a `Target`.
+#[derive(Default)]
+pub struct TokenWriter {
+    tokens: RefCell<Vec<TextToken>>,
+}
+
+impl TokenWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn render(&self, token: TextToken) {
+        self.tokens.borrow_mut().push(token);
+    }
+
+    pub fn text<S: Into<String>>(&self, text: S) {
+        self.render(TextToken::Text(text.into()));
+    }
+
+    pub fn line<S: Into<String>>(&self, text: S) {
+        self.text(text);
+        self.render(TextToken::NewLine);
+    }
+
+    pub fn empty_line(&self) {
+        self.render(TextToken::EmptyLine);
+    }
+
+    pub fn indent(&self) {
+        self.render(TextToken::IncIndent);
+    }
+
+    pub fn dedent(&self) {
+        self.render(TextToken::DecIndent);
+    }
+
+    pub fn extend(&self, tokens: Vec<TextToken>) {
+        self.tokens.borrow_mut().extend(tokens);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.borrow().is_empty()
+    }
+
+    /// Runs `f` and returns the tokens it wrote instead of keeping them in the buffer.
+    pub fn capture<F: FnOnce()>(&self, f: F) -> Vec<TextToken> {
+        let outer = self.tokens.replace(Vec::new());
+        f();
+        self.tokens.replace(outer)
+    }
+
+    /// Writes a block separated from the previous one by an empty line, skipping empty blocks.
+    pub fn block<F: FnOnce()>(&self, f: F) {
+        let tokens = self.capture(f);
+        if !tokens.is_empty() {
+            if !self.is_empty() {
+                self.empty_line();
+            }
+            self.extend(tokens);
+        }
+    }
+
+    pub fn into_tokens(self) -> Vec<TextToken> {
+        self.tokens.into_inner()
+    }
+
+    pub fn apply<R: Target<TextToken>>(self, render: &R) {
+        self.into_tokens()
+            .into_iter()
+            .for_each(|t| render.render(t));
+    }
+}